@@ -3,14 +3,20 @@ use std::env::current_exe;
 use anyhow::Result;
 use clap::Parser;
 
-use crate::native;
 #[cfg(feature = "browser-node")]
 use crate::server;
+use crate::{config, keys, native, store};
 
 #[derive(Debug, Parser)]
 pub(crate) enum CliArgs {
     /// Run native node locally
-    Node(native::Params),
+    Node(Box<native::Params>),
+    /// Manage the identity of the native node
+    Keys(keys::Params),
+    /// Manage the config file of the native node
+    Config(config::Params),
+    /// Export or import the header store of the native node
+    Store(store::Params),
     /// Serve compiled wasm node to be run in the browser
     #[cfg(feature = "browser-node")]
     Browser(server::Params),
@@ -26,7 +32,7 @@ pub async fn run() -> Result<()> {
         .to_string_lossy()
         == "lumina-node"
     {
-        CliArgs::Node(native::Params::parse())
+        CliArgs::Node(Box::new(native::Params::parse()))
     } else {
         CliArgs::parse()
     };
@@ -34,7 +40,10 @@ pub async fn run() -> Result<()> {
     let _guard = init_tracing();
 
     match args {
-        CliArgs::Node(args) => native::run(args).await,
+        CliArgs::Node(args) => native::run(*args).await,
+        CliArgs::Keys(args) => keys::run(args),
+        CliArgs::Config(args) => config::run(args),
+        CliArgs::Store(args) => store::run(args).await,
         #[cfg(feature = "browser-node")]
         CliArgs::Browser(args) => server::run(args).await,
     }
@@ -0,0 +1,260 @@
+//! Configuration file of the native node.
+//!
+//! Configuration is written in TOML. Settings in the `[node]` section apply to
+//! every network and can be overridden for a single network in a
+//! `[networks.<network id>]` section. Command line flags take precedence over both.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use directories::ProjectDirs;
+use lumina_node::network::Network;
+use serde::{Deserialize, Deserializer};
+use tracing::info;
+
+use crate::native::NodeOptions;
+
+/// Name of the config file in the config directory.
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Config written by `lumina config init`.
+const DEFAULT_CONFIG: &str = r#"# Lumina node configuration.
+#
+# Settings in the [node] section apply to all networks. They can be overridden
+# for a single network in a [networks.<network id>] section. Command line flags
+# take precedence over both.
+
+# Network to connect to: "mainnet", "arabica", "mocha" or an id of a custom network.
+network = "mainnet"
+
+[node]
+# Addresses to listen on for incoming connections.
+listen = []
+
+# Bootnode multiaddrs, including peer id.
+# If empty, canonical bootnodes of the network are used.
+bootnodes = []
+
+# Peer IDs to trust in addition to the bootnodes.
+trusted_peers = []
+
+# Discover peers in the local network with mDNS.
+# Can only be enabled for custom networks.
+# mdns = false
+
+# Trust the peers discovered with mDNS while they are announced.
+# trust_mdns_peers = false
+
+# Persistent header store path.
+# By default the store is kept in the cache directory of the network.
+# store = "/path/to/store/db"
+
+# Use in-memory store.
+in_memory_store = false
+
+# Path of the keypair file used as node's identity.
+# By default the keypair is stored next to the persistent store.
+# keypair_file = "/path/to/keypair"
+
+# Maximum age of a block considered for syncing and sampling.
+# sampling_window = "30days"
+
+# How long the pruner waits after sampling window before pruning the block.
+# pruning_delay = "1h"
+
+# Keep all the headers back to genesis and never prune them.
+archival = false
+
+# In archival mode, sample blocks outside of the sampling window at most once per interval.
+# historical_sampling_interval = "10s"
+
+# Maximum number of headers in batch while syncing.
+# sync_batch_size = 512
+
+# Serve shwap samples, rows and namespace data to other peers.
+serve_shwap = false
+
+# Trusted header from which the network head is verified.
+# trusted_hash = "<hex encoded header hash>"
+# trusted_height = 1
+
+# Address to serve JSON-RPC over HTTP and WebSocket on.
+# rpc_listen = "127.0.0.1:26658"
+
+# Token required in the `Authorization: Bearer <token>` header of JSON-RPC requests.
+# Must be set if JSON-RPC server listens on a non-loopback address.
+# rpc_auth_token = "<secret token>"
+
+# Address to serve Prometheus metrics on.
+# metrics_listen = "127.0.0.1:9090"
+
+# PEM encoded TLS key and certificate chain, used for `/wss` listening addresses.
+# tls_key_file = "/path/to/key.pem"
+# tls_cert_file = "/path/to/cert.pem"
+
+# Overrides for a single network.
+# [networks.mocha]
+# store = "/path/to/mocha/db"
+"#;
+
+#[derive(Debug, Parser)]
+pub(crate) struct Params {
+    #[command(subcommand)]
+    cmd: ConfigCmd,
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCmd {
+    /// Write the default configuration file
+    Init {
+        /// Path of the config file.
+        #[arg(long)]
+        path: Option<PathBuf>,
+
+        /// Overwrite the existing config file.
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// Content of the configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ConfigFile {
+    /// Network to connect to, if not given on the command line.
+    pub(crate) network: Option<Network>,
+    #[serde(default)]
+    node: NodeOptions,
+    #[serde(default)]
+    networks: HashMap<String, NodeOptions>,
+}
+
+impl ConfigFile {
+    /// Load the config file.
+    ///
+    /// If path is not set, the default config file is loaded if it exists.
+    pub(crate) fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_owned(),
+            None => {
+                let path = default_config_path()?;
+
+                if !path.exists() {
+                    return Ok(ConfigFile::default());
+                }
+
+                path
+            }
+        };
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config {}", path.display()))?;
+        let config = toml::from_str(&content)
+            .with_context(|| format!("Invalid config {}", path.display()))?;
+
+        info!("Loaded config from {}", path.display());
+
+        Ok(config)
+    }
+
+    /// Options of the node for the network, with the network section applied.
+    pub(crate) fn node_options(&self, network: &Network) -> NodeOptions {
+        let options = self.node.clone();
+
+        let network_options = self
+            .networks
+            .iter()
+            .find(|(id, _)| id.parse::<Network>().ok().as_ref() == Some(network));
+
+        match network_options {
+            Some((_, overrides)) => options.merge(overrides.clone()),
+            None => options,
+        }
+    }
+}
+
+pub(crate) fn run(args: Params) -> Result<()> {
+    match args.cmd {
+        ConfigCmd::Init { path, force } => {
+            let path = match path {
+                Some(path) => path,
+                None => default_config_path()?,
+            };
+
+            if path.exists() && !force {
+                bail!(
+                    "Config {} already exists, use --force to overwrite it",
+                    path.display()
+                );
+            }
+
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+
+            fs::write(&path, DEFAULT_CONFIG)
+                .with_context(|| format!("Failed to write config {}", path.display()))?;
+
+            println!("Config: {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Path of the config file used if none is given.
+pub(crate) fn default_config_path() -> Result<PathBuf> {
+    Ok(ProjectDirs::from("co", "eiger", "lumina")
+        .context("failed to construct project path")?
+        .config_dir()
+        .join(CONFIG_FILE_NAME))
+}
+
+/// Deserialize a human readable duration, e.g. `30days` or `1h`.
+pub(crate) fn deserialize_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(s) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+
+    parse_duration::parse(&s)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        let config: ConfigFile = toml::from_str(DEFAULT_CONFIG).unwrap();
+
+        assert_eq!(config.network, Some(Network::Mainnet));
+        assert!(config.networks.is_empty());
+    }
+
+    #[test]
+    fn trusted_checkpoint_from_config_and_cli() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [node]
+            trusted_hash = "7E3C1F1F5E5F4E1F5D0E2CCBB4F4B4A2D6B7B0B1F6E1E3A7F7D1D6C5C1B2A3E4"
+            "#,
+        )
+        .unwrap();
+        let args =
+            crate::native::Params::try_parse_from(["lumina", "--trusted-height", "5"]).unwrap();
+
+        let options = config.node_options(&Network::Mainnet).merge(args.options);
+
+        assert!(options.trusted_hash.is_some());
+        assert_eq!(options.trusted_height, Some(5));
+    }
+}
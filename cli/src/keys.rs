@@ -0,0 +1,223 @@
+//! Management of the persistent node identity.
+//!
+//! Keypair is stored in libp2p protobuf encoding, by default next to the
+//! database of the node, so that the node keeps the same peer ID across restarts.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::{value_parser, Args, Parser, Subcommand};
+use libp2p::identity::Keypair;
+use libp2p::PeerId;
+use lumina_node::network::Network;
+use tracing::info;
+
+use crate::native::default_store_dir;
+
+/// Name of the keypair file in the store directory.
+const KEYPAIR_FILE_NAME: &str = "keypair";
+
+#[derive(Debug, Parser)]
+pub(crate) struct Params {
+    #[command(subcommand)]
+    cmd: KeysCmd,
+}
+
+#[derive(Debug, Subcommand)]
+enum KeysCmd {
+    /// Generate a new ed25519 keypair
+    Generate {
+        #[command(flatten)]
+        location: KeypairLocation,
+
+        /// Overwrite the existing keypair.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Import an ed25519 secret key
+    Import {
+        #[command(flatten)]
+        location: KeypairLocation,
+
+        /// Hex encoded 32 bytes of the ed25519 secret key.
+        secret_key: String,
+
+        /// Overwrite the existing keypair.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Show the peer ID of the keypair
+    Show {
+        #[command(flatten)]
+        location: KeypairLocation,
+    },
+}
+
+#[derive(Debug, Args)]
+struct KeypairLocation {
+    /// Network the keypair is used for.
+    #[arg(short, long)]
+    #[clap(value_parser = value_parser!(Network))]
+    network: Network,
+
+    /// Persistent header store path. Keypair is stored next to it.
+    #[arg(short, long)]
+    store: Option<PathBuf>,
+
+    /// Path of the keypair file. Overrides the location next to the store.
+    #[arg(long)]
+    keypair_file: Option<PathBuf>,
+}
+
+impl KeypairLocation {
+    fn path(&self) -> Result<PathBuf> {
+        match self.keypair_file {
+            Some(ref path) => Ok(path.to_owned()),
+            None => default_keypair_path(self.store.as_deref(), self.network.id()),
+        }
+    }
+}
+
+pub(crate) fn run(args: Params) -> Result<()> {
+    match args.cmd {
+        KeysCmd::Generate { location, force } => {
+            let path = location.path()?;
+            let keypair = Keypair::generate_ed25519();
+
+            save_keypair(&path, &keypair, force)?;
+            print_peer_id(&path, &keypair);
+        }
+        KeysCmd::Import {
+            location,
+            secret_key,
+            force,
+        } => {
+            let path = location.path()?;
+            let secret_key = hex::decode(secret_key.trim()).context("Invalid hex string")?;
+            let keypair =
+                Keypair::ed25519_from_bytes(secret_key).context("Invalid ed25519 secret key")?;
+
+            save_keypair(&path, &keypair, force)?;
+            print_peer_id(&path, &keypair);
+        }
+        KeysCmd::Show { location } => {
+            let path = location.path()?;
+            let keypair = load_keypair(&path)?;
+
+            print_peer_id(&path, &keypair);
+        }
+    }
+
+    Ok(())
+}
+
+/// Path of the keypair file for the given store path.
+///
+/// If store path is not set, the default store directory of the network is used.
+pub(crate) fn default_keypair_path(store: Option<&Path>, network_id: &str) -> Result<PathBuf> {
+    let dir = match store {
+        Some(path) => path
+            .parent()
+            .map(Path::to_owned)
+            .context("Store path has no parent directory")?,
+        None => default_store_dir(network_id)?,
+    };
+
+    Ok(dir.join(KEYPAIR_FILE_NAME))
+}
+
+/// Load the keypair from the file, or generate and save a new one if it doesn't exist.
+pub(crate) fn load_or_generate_keypair(path: &Path) -> Result<Keypair> {
+    if path.exists() {
+        let keypair = load_keypair(path)?;
+        info!("Loaded keypair from {}", path.display());
+        return Ok(keypair);
+    }
+
+    let keypair = Keypair::generate_ed25519();
+    save_keypair(path, &keypair, false)?;
+    info!("Generated new keypair in {}", path.display());
+
+    Ok(keypair)
+}
+
+fn load_keypair(path: &Path) -> Result<Keypair> {
+    let bytes =
+        fs::read(path).with_context(|| format!("Failed to read keypair {}", path.display()))?;
+
+    Keypair::from_protobuf_encoding(&bytes)
+        .with_context(|| format!("Invalid keypair in {}", path.display()))
+}
+
+fn save_keypair(path: &Path, keypair: &Keypair, overwrite: bool) -> Result<()> {
+    if path.exists() && !overwrite {
+        bail!(
+            "Keypair {} already exists, use --force to overwrite it",
+            path.display()
+        );
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let bytes = keypair
+        .to_protobuf_encoding()
+        .context("Failed to encode keypair")?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    // Keypair is a secret, so make it readable only by the owner
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options
+        .open(path)
+        .and_then(|mut file| {
+            // Mode of `options` applies only to new files, so tighten an overwritten one too
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                file.set_permissions(fs::Permissions::from_mode(0o600))?;
+            }
+
+            file.write_all(&bytes)
+        })
+        .with_context(|| format!("Failed to write keypair {}", path.display()))
+}
+
+fn print_peer_id(path: &Path, keypair: &Keypair) {
+    println!("Keypair: {}", path.display());
+    println!("Peer ID: {}", PeerId::from(keypair.public()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn overwritten_keypair_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(KEYPAIR_FILE_NAME);
+
+        fs::write(&path, b"old keypair").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let keypair = Keypair::generate_ed25519();
+        save_keypair(&path, &keypair, false).unwrap_err();
+        save_keypair(&path, &keypair, true).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(load_keypair(&path).unwrap().public(), keypair.public());
+    }
+}
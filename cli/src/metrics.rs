@@ -0,0 +1,49 @@
+//! HTTP endpoint exposing [`Node`] metrics to Prometheus.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use blockstore::Blockstore;
+use lumina_node::node::Node;
+use lumina_node::store::Store;
+use tokio::net::TcpListener;
+use tracing::info;
+
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+pub(crate) async fn serve<B, S>(listen_addr: SocketAddr, node: Arc<Node<B, S>>) -> Result<()>
+where
+    B: Blockstore + 'static,
+    S: Store + 'static,
+{
+    let app = Router::new()
+        .route("/metrics", get(handle_metrics::<B, S>))
+        .with_state(node);
+
+    let listener = TcpListener::bind(listen_addr)
+        .await
+        .with_context(|| format!("Failed to bind metrics server to {listen_addr}"))?;
+
+    info!("Metrics server listening on {listen_addr}");
+
+    axum::serve(listener, app)
+        .await
+        .context("Metrics server failed")
+}
+
+async fn handle_metrics<B, S>(State(node): State<Arc<Node<B, S>>>) -> impl IntoResponse
+where
+    B: Blockstore + 'static,
+    S: Store + 'static,
+{
+    (
+        [(header::CONTENT_TYPE, OPENMETRICS_CONTENT_TYPE)],
+        node.encode_metrics(),
+    )
+}
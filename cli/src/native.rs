@@ -1,4 +1,5 @@
 use std::env;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
@@ -7,17 +8,22 @@ use anyhow::{bail, Context, Result};
 use blockstore::EitherBlockstore;
 use celestia_rpc::prelude::*;
 use celestia_rpc::Client;
-use clap::{value_parser, Parser};
+use celestia_types::hash::Hash;
+use clap::{value_parser, Args, Parser};
 use directories::ProjectDirs;
 use libp2p::multiaddr::{Multiaddr, Protocol};
+use libp2p::PeerId;
 use lumina_node::blockstore::{InMemoryBlockstore, RedbBlockstore};
 use lumina_node::events::NodeEvent;
 use lumina_node::network::Network;
-use lumina_node::node::{Node, MIN_PRUNING_DELAY, MIN_SAMPLING_WINDOW};
-use lumina_node::store::{EitherStore, InMemoryStore, RedbStore, Store as _};
+use lumina_node::node::{Node, TrustedCheckpoint, MIN_PRUNING_DELAY, MIN_SAMPLING_WINDOW};
+use lumina_node::store::{EitherStore, InMemoryStore, MigrationMode, RedbStore, Store as _};
+use serde::Deserialize;
 use tokio::task::spawn_blocking;
-use tracing::info;
-use tracing::warn;
+use tracing::{error, info, warn};
+
+use crate::config::{self, ConfigFile};
+use crate::{keys, metrics, rpc};
 
 const CELESTIA_LOCAL_BRIDGE_RPC_ADDR: &str = "ws://localhost:36658";
 
@@ -27,81 +33,301 @@ type Store = EitherStore<InMemoryStore, RedbStore>;
 #[derive(Debug, Parser)]
 pub(crate) struct Params {
     /// Network to connect.
+    ///
+    /// **Default:** network from the config file, or mainnet.
     #[arg(short, long)]
     #[clap(value_parser = value_parser!(Network))]
-    pub(crate) network: Network,
+    pub(crate) network: Option<Network>,
+
+    /// Path of the config file.
+    ///
+    /// If not set, the default config file is used if it exists.
+    #[arg(short, long)]
+    pub(crate) config: Option<PathBuf>,
 
+    #[command(flatten)]
+    pub(crate) options: NodeOptions,
+}
+
+/// Settings of the node, from the command line or the config file.
+#[derive(Debug, Clone, Default, Args, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct NodeOptions {
     /// Listening addresses. Can be used multiple times.
     #[arg(short, long = "listen")]
+    #[serde(default, rename = "listen")]
     pub(crate) listen_addrs: Vec<Multiaddr>,
 
     /// Bootnode multiaddr, including peer id. Can be used multiple times.
     #[arg(short, long = "bootnode")]
+    #[serde(default)]
     pub(crate) bootnodes: Vec<Multiaddr>,
 
+    /// Peer ID to trust in addition to the bootnodes. Can be used multiple times.
+    #[arg(long = "trusted-peer")]
+    #[serde(default)]
+    pub(crate) trusted_peers: Vec<PeerId>,
+
+    /// Discover peers in the local network with mDNS. Only for custom networks.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub(crate) mdns: Option<bool>,
+
+    /// Trust the peers discovered with mDNS while they are announced.
+    ///
+    /// If no bootnodes are set, the local bridge node is then found with mDNS
+    /// instead of its JSON-RPC.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub(crate) trust_mdns_peers: Option<bool>,
+
     /// Persistent header store path.
     #[arg(short, long)]
     pub(crate) store: Option<PathBuf>,
 
     /// Use in-memory store.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub(crate) in_memory_store: Option<bool>,
+
+    /// Path of the keypair file used as node's identity.
+    ///
+    /// By default, keypair is stored next to the persistent store and generated
+    /// if missing. If in-memory store is used, a random identity is used instead.
     #[arg(long)]
-    pub(crate) in_memory_store: bool,
+    pub(crate) keypair_file: Option<PathBuf>,
 
     /// Sampling window defines maximum age of a block considered for syncing and sampling.
     #[arg(long)]
     #[clap(value_parser = parse_duration::parse)]
+    #[serde(default, deserialize_with = "config::deserialize_duration")]
     pub(crate) sampling_window: Option<Duration>,
 
     /// Pruning delay defines how much time the pruner should wait after sampling window in
     /// order to prune the block.
     #[arg(long)]
     #[clap(value_parser = parse_duration::parse)]
+    #[serde(default, deserialize_with = "config::deserialize_duration")]
     pub(crate) pruning_delay: Option<Duration>,
+
+    /// Keep all the headers back to genesis and never prune them.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub(crate) archival: Option<bool>,
+
+    /// In archival mode, sample blocks outside of the sampling window at most once per interval.
+    ///
+    /// If not set, such blocks are not sampled.
+    #[arg(long)]
+    #[clap(value_parser = parse_duration::parse)]
+    #[serde(default, deserialize_with = "config::deserialize_duration")]
+    pub(crate) historical_sampling_interval: Option<Duration>,
+
+    /// Maximum number of headers in batch while syncing.
+    #[arg(long)]
+    pub(crate) sync_batch_size: Option<u64>,
+
+    /// Serve shwap samples, rows and namespace data to other peers.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub(crate) serve_shwap: Option<bool>,
+
+    /// Hash of the trusted header from which the network head is verified.
+    #[arg(long)]
+    pub(crate) trusted_hash: Option<Hash>,
+
+    /// Height of the trusted header from which the network head is verified.
+    #[arg(long)]
+    pub(crate) trusted_height: Option<u64>,
+
+    /// Address to serve JSON-RPC over HTTP and WebSocket on, e.g. 127.0.0.1:26658.
+    ///
+    /// If not set, JSON-RPC server is disabled.
+    #[arg(long)]
+    pub(crate) rpc_listen: Option<SocketAddr>,
+
+    /// Token required in the `Authorization: Bearer <token>` header of JSON-RPC requests.
+    ///
+    /// Must be set if JSON-RPC server listens on a non-loopback address.
+    #[arg(long, env = "LUMINA_RPC_AUTH_TOKEN")]
+    pub(crate) rpc_auth_token: Option<String>,
+
+    /// Address to serve Prometheus metrics on, e.g. 127.0.0.1:9090.
+    ///
+    /// Metrics are available under `/metrics` path. If not set, metrics server is disabled.
+    #[arg(long)]
+    pub(crate) metrics_listen: Option<SocketAddr>,
+
+    /// Path of the PEM encoded TLS private key, used for secure websocket listening addresses.
+    #[arg(long, env = "LUMINA_TLS_KEY_FILE")]
+    pub(crate) tls_key_file: Option<PathBuf>,
+
+    /// Path of the PEM encoded TLS certificate chain, used for secure websocket listening addresses.
+    #[arg(long, env = "LUMINA_TLS_CERT_FILE")]
+    pub(crate) tls_cert_file: Option<PathBuf>,
+}
+
+impl NodeOptions {
+    /// Merge two sets of options. Options that are set in `overrides` take precedence.
+    pub(crate) fn merge(self, overrides: NodeOptions) -> NodeOptions {
+        fn merge_vec<T>(base: Vec<T>, overrides: Vec<T>) -> Vec<T> {
+            if overrides.is_empty() {
+                base
+            } else {
+                overrides
+            }
+        }
+
+        NodeOptions {
+            listen_addrs: merge_vec(self.listen_addrs, overrides.listen_addrs),
+            bootnodes: merge_vec(self.bootnodes, overrides.bootnodes),
+            trusted_peers: merge_vec(self.trusted_peers, overrides.trusted_peers),
+            mdns: overrides.mdns.or(self.mdns),
+            trust_mdns_peers: overrides.trust_mdns_peers.or(self.trust_mdns_peers),
+            store: overrides.store.or(self.store),
+            in_memory_store: overrides.in_memory_store.or(self.in_memory_store),
+            keypair_file: overrides.keypair_file.or(self.keypair_file),
+            sampling_window: overrides.sampling_window.or(self.sampling_window),
+            pruning_delay: overrides.pruning_delay.or(self.pruning_delay),
+            archival: overrides.archival.or(self.archival),
+            historical_sampling_interval: overrides
+                .historical_sampling_interval
+                .or(self.historical_sampling_interval),
+            sync_batch_size: overrides.sync_batch_size.or(self.sync_batch_size),
+            serve_shwap: overrides.serve_shwap.or(self.serve_shwap),
+            trusted_hash: overrides.trusted_hash.or(self.trusted_hash),
+            trusted_height: overrides.trusted_height.or(self.trusted_height),
+            rpc_listen: overrides.rpc_listen.or(self.rpc_listen),
+            rpc_auth_token: overrides.rpc_auth_token.or(self.rpc_auth_token),
+            metrics_listen: overrides.metrics_listen.or(self.metrics_listen),
+            tls_key_file: overrides.tls_key_file.or(self.tls_key_file),
+            tls_cert_file: overrides.tls_cert_file.or(self.tls_cert_file),
+        }
+    }
 }
 
 pub(crate) async fn run(args: Params) -> Result<()> {
-    let (blockstore, store) = if args.in_memory_store {
+    let config = ConfigFile::load(args.config.as_deref())?;
+    let network = args.network.or(config.network.clone()).unwrap_or_default();
+    let options = config.node_options(&network).merge(args.options);
+    let in_memory_store = options.in_memory_store.unwrap_or(false);
+    let mdns = options.mdns.unwrap_or(false);
+    let trust_mdns_peers = options.trust_mdns_peers.unwrap_or(false);
+
+    let keypair_path = match options.keypair_file {
+        Some(path) => Some(path),
+        None if in_memory_store => None,
+        None => Some(keys::default_keypair_path(
+            options.store.as_deref(),
+            network.id(),
+        )?),
+    };
+
+    let (blockstore, store) = if in_memory_store {
         open_in_memory_stores()
     } else {
-        open_db_stores(args.store, args.network.id()).await?
+        open_db_stores(options.store, network.id()).await?
     };
 
     let mut node_builder = Node::builder()
         .store(store)
         .blockstore(blockstore)
-        .network(args.network.clone());
+        .network(network.clone())
+        .serve_shwap(options.serve_shwap.unwrap_or(false))
+        .mdns(mdns)
+        .trust_mdns_peers(trust_mdns_peers);
+
+    if let Some(path) = keypair_path {
+        let keypair = keys::load_or_generate_keypair(&path)?;
+        node_builder = node_builder.keypair(keypair);
+    }
 
-    if let Some(sampling_window) = args.sampling_window {
+    if let Some(sampling_window) = options.sampling_window {
         node_builder = node_builder.sampling_window(sampling_window);
-    } else if args.in_memory_store {
+    } else if in_memory_store {
         // In-memory stores are memory hungry, so we lower sampling window.
         node_builder = node_builder.sampling_window(MIN_SAMPLING_WINDOW);
     }
 
-    if let Some(pruning_delay) = args.pruning_delay {
+    if let Some(pruning_delay) = options.pruning_delay {
         node_builder = node_builder.pruning_delay(pruning_delay);
-    } else if args.in_memory_store {
+    } else if in_memory_store {
         // In-memory stores are memory hungry, so we lower pruning window.
         node_builder = node_builder.pruning_delay(MIN_PRUNING_DELAY);
     }
 
-    if args.bootnodes.is_empty() {
-        if args.network.is_custom() {
+    if let Some(archival) = options.archival {
+        node_builder = node_builder.archival(archival);
+    }
+
+    if let Some(interval) = options.historical_sampling_interval {
+        node_builder = node_builder.historical_sampling_interval(interval);
+    }
+
+    if let Some(batch_size) = options.sync_batch_size {
+        node_builder = node_builder.sync_batch_size(batch_size);
+    }
+
+    match (options.trusted_hash, options.trusted_height) {
+        (Some(hash), Some(height)) => {
+            node_builder =
+                node_builder.trusted_checkpoint(TrustedCheckpoint::Hash { height, hash });
+        }
+        (None, None) => {}
+        _ => bail!("Both trusted hash and trusted height must be set"),
+    }
+
+    match (options.tls_key_file, options.tls_cert_file) {
+        (Some(key_file), Some(cert_file)) => {
+            node_builder = node_builder.tls_files(key_file, cert_file);
+        }
+        (None, None) => {}
+        _ => bail!("Both TLS key and certificate files must be set"),
+    }
+
+    if let Some(addr) = options.rpc_listen {
+        if options.rpc_auth_token.is_none() && !addr.ip().is_loopback() {
+            bail!("JSON-RPC server listening on {addr} requires an auth token");
+        }
+    }
+
+    if options.bootnodes.is_empty() {
+        // With trusted mDNS peers the local bridge is discovered without asking its JSON-RPC.
+        if network.is_custom() && !(mdns && trust_mdns_peers) {
             let bootnodes = fetch_bridge_multiaddrs(CELESTIA_LOCAL_BRIDGE_RPC_ADDR).await?;
             node_builder = node_builder.bootnodes(bootnodes);
         }
     } else {
-        node_builder = node_builder.bootnodes(args.bootnodes);
+        node_builder = node_builder.bootnodes(options.bootnodes);
     }
 
-    if !args.listen_addrs.is_empty() {
-        node_builder = node_builder.listen(args.listen_addrs);
+    if !options.listen_addrs.is_empty() {
+        node_builder = node_builder.listen(options.listen_addrs);
     }
 
-    let (_node, mut events) = node_builder
+    let (node, mut events) = node_builder
         .start_subscribed()
         .await
         .context("Failed to start node")?;
+    let node = Arc::new(node);
+
+    for peer_id in options.trusted_peers {
+        node.set_peer_trust(peer_id, true).await?;
+    }
+
+    if let Some(addr) = options.rpc_listen {
+        let auth_token = options.rpc_auth_token;
+        let node = node.clone();
+        tokio::spawn(async move {
+            if let Err(e) = rpc::serve(addr, auth_token, node).await {
+                error!("{e:#}");
+            }
+        });
+    }
+
+    if let Some(addr) = options.metrics_listen {
+        let node = node.clone();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(addr, node).await {
+                error!("{e:#}");
+            }
+        });
+    }
 
     while let Ok(ev) = events.recv().await {
         match ev.event {
@@ -124,9 +350,8 @@ fn open_in_memory_stores() -> (Blockstore, Store) {
 
 async fn open_db_stores(path: Option<PathBuf>, network_id: &str) -> Result<(Blockstore, Store)> {
     info!("Initializing store");
-    let db = open_db(path, network_id).await?;
-    let store = RedbStore::new(db.clone()).await?;
-    let blockstore = RedbBlockstore::new(db);
+    let store = open_store(path, network_id).await?;
+    let blockstore = RedbBlockstore::new(store.raw_db());
 
     let stored_ranges = store.get_stored_header_ranges().await?;
     if stored_ranges.is_empty() {
@@ -141,22 +366,27 @@ async fn open_db_stores(path: Option<PathBuf>, network_id: &str) -> Result<(Bloc
     ))
 }
 
-async fn open_db(path: Option<PathBuf>, network_id: &str) -> Result<Arc<redb::Database>> {
+/// Open the persistent store, backing it up before any schema migrations.
+pub(crate) async fn open_store(path: Option<PathBuf>, network_id: &str) -> Result<RedbStore> {
+    let path = store_path(path, network_id).await?;
+    let store = RedbStore::open_with_migrations(&path, MigrationMode::Backup)
+        .await
+        .with_context(|| format!("Failed to open store {}", path.display()))?;
+
+    Ok(store)
+}
+
+async fn store_path(path: Option<PathBuf>, network_id: &str) -> Result<PathBuf> {
+    if let Some(path) = path {
+        return Ok(path);
+    }
+
     let network_id = network_id.to_owned();
 
     spawn_blocking(move || {
         use std::fs;
 
-        if let Some(path) = path {
-            let db = redb::Database::create(path)?;
-            return Ok(Arc::new(db));
-        }
-
-        let cache_dir = ProjectDirs::from("co", "eiger", "lumina")
-            .context("failed to construct project path")?
-            .cache_dir()
-            .join(&network_id)
-            .to_owned();
+        let cache_dir = default_store_dir(&network_id)?;
 
         let old_cache_dir = ProjectDirs::from("co", "eiger", "celestia")
             .context("failed to construct project path")?
@@ -164,27 +394,45 @@ async fn open_db(path: Option<PathBuf>, network_id: &str) -> Result<Arc<redb::Da
             .join(&network_id)
             .to_owned();
 
-        if is_sled_db(&old_cache_dir) {
-            warn!("Removing deprecated store {}", old_cache_dir.display());
-            fs::remove_dir_all(&old_cache_dir)?;
-        }
-
-        if is_sled_db(&cache_dir) {
-            warn!("Removing deprecated store {}", cache_dir.display());
-            fs::remove_dir_all(&cache_dir)?;
-        }
+        set_aside_sled_db(&old_cache_dir)?;
+        set_aside_sled_db(&cache_dir)?;
 
         // Directories need to pre-exist
         fs::create_dir_all(&cache_dir)?;
 
-        let path = cache_dir.join("db");
-        let db = redb::Database::create(path)?;
-
-        Ok(Arc::new(db))
+        Ok(cache_dir.join("db"))
     })
     .await?
 }
 
+/// Default directory of the persistent store for the network.
+pub(crate) fn default_store_dir(network_id: &str) -> Result<PathBuf> {
+    Ok(ProjectDirs::from("co", "eiger", "lumina")
+        .context("failed to construct project path")?
+        .cache_dir()
+        .join(network_id))
+}
+
+/// Move a deprecated sled store out of the way, keeping its data.
+fn set_aside_sled_db(path: &Path) -> Result<()> {
+    if !is_sled_db(path) {
+        return Ok(());
+    }
+
+    let mut backup = path.to_owned().into_os_string();
+    backup.push(".sled-backup");
+    let backup = PathBuf::from(backup);
+
+    warn!(
+        "Moving deprecated store {} to {}",
+        path.display(),
+        backup.display()
+    );
+    std::fs::rename(path, &backup)?;
+
+    Ok(())
+}
+
 fn is_sled_db(path: impl AsRef<Path>) -> bool {
     let path = path.as_ref();
     path.join("blobs").is_dir() && path.join("conf").is_file() && path.join("db").is_file()
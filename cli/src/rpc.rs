@@ -0,0 +1,871 @@
+//! JSON-RPC server exposing [`Node`] with the Celestia node API.
+//!
+//! Methods and types follow the ones used by `celestia-rpc`, so the same clients can
+//! talk to both. Server is reachable over HTTP and WebSocket on the same address,
+//! with subscriptions available only over WebSocket.
+//!
+//! If an auth token is set, every request must carry it in the
+//! `Authorization: Bearer <token>` header.
+
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use blockstore::Blockstore;
+use celestia_rpc::blob::BlobsAtHeight;
+use celestia_rpc::share::GetRangeResponse;
+use celestia_types::hash::Hash;
+use celestia_types::nmt::{Namespace, NamespacedSha2Hasher};
+use celestia_types::row_namespace_data::NamespaceData;
+use celestia_types::{Blob, Commitment, ExtendedDataSquare, ExtendedHeader, Share, SyncState};
+use futures::future::try_join_all;
+use futures::StreamExt;
+use hyper::upgrade::Upgraded;
+use hyper_util::rt::TokioIo;
+use jsonrpsee::core::{RpcResult, SubscriptionResult};
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee::types::{ErrorCode, ErrorObject, ErrorObjectOwned};
+use jsonrpsee::{Methods, PendingSubscriptionSink, RpcModule, SubscriptionMessage};
+use lumina_node::events::{EventSubscriber, NodeEvent};
+use lumina_node::node::{Node, NodeError, SamplingInfo, SyncingInfo};
+use lumina_node::store::{SamplingStatus, Store, StoreError};
+use serde::{Deserialize, Serialize};
+use soketto::handshake::http::{is_upgrade_request, Server as WsServer};
+use tokio::net::TcpListener;
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+use tracing::{debug, info, warn};
+
+/// Maximum number of pending messages per WebSocket connection.
+const WS_BUFFER_SIZE: usize = 256;
+
+/// Statistics of the data availability sampling.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SamplingStats {
+    /// All headers up to this height are sampled.
+    pub head_of_sampled_chain: u64,
+    /// The latest height known to the node.
+    pub network_head_height: u64,
+    /// Whether sampling caught up with the network head.
+    pub catch_up_done: bool,
+    /// Whether sampling is running.
+    pub is_running: bool,
+}
+
+#[rpc(server)]
+pub trait Header {
+    /// GetByHash returns the header of the given hash from the node's header store.
+    #[method(name = "header.GetByHash")]
+    async fn header_get_by_hash(&self, hash: Hash) -> RpcResult<ExtendedHeader>;
+
+    /// GetByHeight returns the ExtendedHeader at the given height if it is currently available.
+    #[method(name = "header.GetByHeight")]
+    async fn header_get_by_height(&self, height: u64) -> RpcResult<ExtendedHeader>;
+
+    /// GetRangeByHeight returns the given range (from:to) of ExtendedHeaders, verified against `from`.
+    #[method(name = "header.GetRangeByHeight")]
+    async fn header_get_range_by_height(
+        &self,
+        from: ExtendedHeader,
+        to: u64,
+    ) -> RpcResult<Vec<ExtendedHeader>>;
+
+    /// LocalHead returns the ExtendedHeader of the chain head.
+    #[method(name = "header.LocalHead")]
+    async fn header_local_head(&self) -> RpcResult<ExtendedHeader>;
+
+    /// NetworkHead provides the Syncer's view of the current network head.
+    #[method(name = "header.NetworkHead")]
+    async fn header_network_head(&self) -> RpcResult<ExtendedHeader>;
+
+    /// Subscribe to recent ExtendedHeaders from the network.
+    #[subscription(name = "header.Subscribe", unsubscribe = "header.Unsubscribe", item = ExtendedHeader)]
+    async fn header_subscribe(&self) -> SubscriptionResult;
+
+    /// SyncState returns the current state of the header Syncer.
+    #[method(name = "header.SyncState")]
+    async fn header_sync_state(&self) -> RpcResult<SyncState>;
+
+    /// SyncWait blocks until the header Syncer is synced to network head.
+    #[method(name = "header.SyncWait")]
+    async fn header_sync_wait(&self) -> RpcResult<()>;
+
+    /// WaitForHeight blocks until the header at the given height has been processed by the store.
+    #[method(name = "header.WaitForHeight")]
+    async fn header_wait_for_height(&self, height: u64) -> RpcResult<ExtendedHeader>;
+}
+
+#[rpc(server)]
+pub trait Share {
+    /// GetEDS gets the full EDS identified by the given height.
+    #[method(name = "share.GetEDS")]
+    async fn share_get_eds(&self, height: u64) -> RpcResult<ExtendedDataSquare>;
+
+    /// GetRange gets a list of shares and their corresponding proof.
+    ///
+    /// The start and end index ignores parity shares and corresponds to ODS.
+    #[method(name = "share.GetRange")]
+    async fn share_get_range(
+        &self,
+        height: u64,
+        start: u64,
+        end: u64,
+    ) -> RpcResult<GetRangeResponse>;
+
+    /// GetShare gets a Share by coordinates in EDS.
+    #[method(name = "share.GetShare")]
+    async fn share_get_share(&self, height: u64, row: u64, col: u64) -> RpcResult<Share>;
+
+    /// GetNamespaceData gets all shares from an EDS within the given namespace.
+    #[method(name = "share.GetNamespaceData")]
+    async fn share_get_namespace_data(
+        &self,
+        height: u64,
+        namespace: Namespace,
+    ) -> RpcResult<NamespaceData>;
+
+    /// SharesAvailable returns successfully if the block at given height was sampled and accepted.
+    #[method(name = "share.SharesAvailable")]
+    async fn share_shares_available(&self, height: u64) -> RpcResult<()>;
+}
+
+#[rpc(server)]
+pub trait Blob {
+    /// Get retrieves the blob by commitment under the given namespace and height.
+    #[method(name = "blob.Get")]
+    async fn blob_get(
+        &self,
+        height: u64,
+        namespace: Namespace,
+        commitment: Commitment,
+    ) -> RpcResult<Blob>;
+
+    /// GetAll returns all blobs under the given namespaces and height.
+    #[method(name = "blob.GetAll")]
+    async fn blob_get_all(
+        &self,
+        height: u64,
+        namespaces: Vec<Namespace>,
+    ) -> RpcResult<Option<Vec<Blob>>>;
+
+    /// Subscribe to published blobs from the given namespace as they are included.
+    #[subscription(name = "blob.Subscribe", unsubscribe = "blob.Unsubscribe", item = BlobsAtHeight)]
+    async fn blob_subscribe(&self, namespace: Namespace) -> SubscriptionResult;
+}
+
+#[rpc(server)]
+pub trait Das {
+    /// SamplingStats returns the current statistics over the DA sampling process.
+    #[method(name = "das.SamplingStats")]
+    async fn das_sampling_stats(&self) -> RpcResult<SamplingStats>;
+
+    /// WaitCatchUp blocks until DASer finishes catching up to the network head.
+    #[method(name = "das.WaitCatchUp")]
+    async fn das_wait_catch_up(&self) -> RpcResult<()>;
+}
+
+struct RpcHandler<B, S>
+where
+    B: Blockstore + 'static,
+    S: Store + 'static,
+{
+    node: Arc<Node<B, S>>,
+}
+
+impl<B, S> Clone for RpcHandler<B, S>
+where
+    B: Blockstore + 'static,
+    S: Store + 'static,
+{
+    fn clone(&self) -> Self {
+        RpcHandler {
+            node: self.node.clone(),
+        }
+    }
+}
+
+/// Start the JSON-RPC server on the given address.
+///
+/// If `auth_token` is set, requests without it are rejected. Server runs until
+/// the task it is spawned in gets dropped.
+pub(crate) async fn serve<B, S>(
+    listen_addr: SocketAddr,
+    auth_token: Option<String>,
+    node: Arc<Node<B, S>>,
+) -> Result<()>
+where
+    B: Blockstore + 'static,
+    S: Store + 'static,
+{
+    let handler = RpcHandler { node };
+    let mut module = RpcModule::new(());
+
+    module.merge(HeaderServer::into_rpc(handler.clone()))?;
+    module.merge(ShareServer::into_rpc(handler.clone()))?;
+    module.merge(BlobServer::into_rpc(handler.clone()))?;
+    module.merge(DasServer::into_rpc(handler))?;
+
+    let methods: Methods = module.into();
+    let auth_token: Option<Arc<str>> = auth_token.map(Into::into);
+    let app = Router::new()
+        .route("/", post(handle_http).get(handle_ws))
+        .with_state(methods)
+        .layer(middleware::from_fn_with_state(auth_token, check_auth));
+
+    let listener = TcpListener::bind(listen_addr)
+        .await
+        .with_context(|| format!("Failed to bind RPC server to {listen_addr}"))?;
+
+    info!("RPC server listening on {listen_addr}");
+
+    axum::serve(listener, app)
+        .await
+        .context("RPC server failed")
+}
+
+async fn check_auth(
+    State(auth_token): State<Option<Arc<str>>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !is_authorized(req.headers(), auth_token.as_deref()) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    next.run(req).await
+}
+
+/// Returns `true` if no token is required or the request carries the required one.
+fn is_authorized(headers: &HeaderMap, auth_token: Option<&str>) -> bool {
+    let Some(auth_token) = auth_token else {
+        return true;
+    };
+
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| constant_time_eq(token.as_bytes(), auth_token.as_bytes()))
+}
+
+/// Compare the tokens without leaking the length of their common prefix through timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn handle_http(State(methods): State<Methods>, body: String) -> Response {
+    let response = call_method(&methods, &body).await.0;
+
+    ([(header::CONTENT_TYPE, "application/json")], response).into_response()
+}
+
+async fn handle_ws(State(methods): State<Methods>, mut req: Request) -> Response {
+    if !is_upgrade_request(&req) {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+
+    let mut server = WsServer::new();
+
+    let response = match server.receive_request(&req) {
+        Ok(response) => response,
+        Err(e) => {
+            debug!("Invalid WebSocket handshake: {e}");
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    };
+
+    let on_upgrade = hyper::upgrade::on(&mut req);
+
+    tokio::spawn(async move {
+        match on_upgrade.await {
+            Ok(upgraded) => handle_ws_connection(methods, server, upgraded).await,
+            Err(e) => debug!("WebSocket upgrade failed: {e}"),
+        }
+    });
+
+    response.map(|_| Body::empty())
+}
+
+async fn handle_ws_connection(methods: Methods, server: WsServer, upgraded: Upgraded) {
+    let stream = TokioIo::new(upgraded).compat();
+    let (mut ws_sender, mut ws_receiver) = server.into_builder(stream).finish();
+    let (tx, mut rx) = mpsc::channel::<String>(WS_BUFFER_SIZE);
+
+    let writer = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if ws_sender.send_text_owned(msg).await.is_err() || ws_sender.flush().await.is_err() {
+                break;
+            }
+        }
+        let _ = ws_sender.close().await;
+    });
+
+    let mut message = Vec::new();
+
+    loop {
+        message.clear();
+
+        if let Err(e) = ws_receiver.receive_data(&mut message).await {
+            debug!("WebSocket connection closed: {e}");
+            break;
+        }
+
+        let request = String::from_utf8_lossy(&message).into_owned();
+        let methods = methods.clone();
+        let tx = tx.clone();
+
+        // Each request is handled in its own task, so that long running calls
+        // like `header.WaitForHeight` do not block the whole connection.
+        tokio::spawn(async move {
+            let (response, notifications) = call_method(&methods, &request).await;
+
+            if tx.send(response).await.is_err() {
+                return;
+            }
+
+            if let Some(mut notifications) = notifications {
+                while let Some(notification) = notifications.recv().await {
+                    if tx.send(notification).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    // Dropping the writer closes all the pending subscriptions of this connection.
+    writer.abort();
+}
+
+async fn call_method(methods: &Methods, request: &str) -> (String, Option<mpsc::Receiver<String>>) {
+    match methods.raw_json_request(request, WS_BUFFER_SIZE).await {
+        Ok((response, notifications)) => (response, Some(notifications)),
+        Err(e) => {
+            debug!("Invalid JSON-RPC request: {e}");
+            let error = ErrorObject::from(ErrorCode::ParseError);
+            let response = serde_json::json!({
+                "jsonrpc": "2.0",
+                "error": error,
+                "id": null,
+            });
+            (response.to_string(), None)
+        }
+    }
+}
+
+fn rpc_error(e: impl Display) -> ErrorObjectOwned {
+    ErrorObject::owned(ErrorCode::InternalError.code(), e.to_string(), None::<()>)
+}
+
+fn sampling_stats(syncing_info: &SyncingInfo, sampling_info: &SamplingInfo) -> SamplingStats {
+    let network_head_height = syncing_info.subjective_head;
+    let stored_head_height = syncing_info.stored_headers.head().unwrap_or(0);
+
+    // Daser samples from the top, so all the stored headers below the lowest
+    // pending one were already sampled.
+    let head_of_sampled_chain = match sampling_info.pending_headers.tail() {
+        Some(height) => height - 1,
+        None => stored_head_height,
+    };
+
+    SamplingStats {
+        head_of_sampled_chain,
+        network_head_height,
+        catch_up_done: network_head_height > 0 && head_of_sampled_chain >= network_head_height,
+        is_running: sampling_info.is_running,
+    }
+}
+
+/// Get the ODS shares in `range`, together with the proof of their inclusion.
+fn share_range(
+    header: &ExtendedHeader,
+    eds: &ExtendedDataSquare,
+    range: Range<usize>,
+) -> RpcResult<GetRangeResponse> {
+    let proof = eds.get_share_range(range, &header.dah).map_err(rpc_error)?;
+    let shares = proof
+        .shares()
+        .iter()
+        .map(|share| Share::from_raw(share))
+        .collect::<Result<_, _>>()
+        .map_err(rpc_error)?;
+
+    Ok(GetRangeResponse { shares, proof })
+}
+
+impl<B, S> RpcHandler<B, S>
+where
+    B: Blockstore + 'static,
+    S: Store + 'static,
+{
+    /// Get the header from the store or request it from the network if it wasn't synced.
+    async fn header_by_height(&self, height: u64) -> RpcResult<ExtendedHeader> {
+        match self.node.get_header_by_height(height).await {
+            Ok(header) => Ok(header),
+            Err(NodeError::Store(StoreError::NotFound)) => self
+                .node
+                .request_header_by_height(height)
+                .await
+                .map_err(rpc_error),
+            Err(e) => Err(rpc_error(e)),
+        }
+    }
+
+    async fn is_synced(&self) -> RpcResult<bool> {
+        let info = self.node.syncer_info().await.map_err(rpc_error)?;
+        Ok(info.stored_headers.contains(info.subjective_head))
+    }
+
+    async fn sampling_stats(&self) -> RpcResult<SamplingStats> {
+        let syncing_info = self.node.syncer_info().await.map_err(rpc_error)?;
+        let sampling_info = self.node.sampling_info();
+
+        Ok(sampling_stats(&syncing_info, &sampling_info))
+    }
+
+    /// Re-evaluate `check` on every node event until it returns `true`.
+    async fn wait_until<F, Fut>(&self, mut events: EventSubscriber, mut check: F) -> RpcResult<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = RpcResult<bool>>,
+    {
+        while !check().await? {
+            events.recv().await.map_err(|_| rpc_error("Node stopped"))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[jsonrpsee::core::async_trait]
+impl<B, S> HeaderServer for RpcHandler<B, S>
+where
+    B: Blockstore + 'static,
+    S: Store + 'static,
+{
+    async fn header_get_by_hash(&self, hash: Hash) -> RpcResult<ExtendedHeader> {
+        match self.node.get_header_by_hash(&hash).await {
+            Ok(header) => Ok(header),
+            Err(NodeError::Store(StoreError::NotFound)) => self
+                .node
+                .request_header_by_hash(&hash)
+                .await
+                .map_err(rpc_error),
+            Err(e) => Err(rpc_error(e)),
+        }
+    }
+
+    async fn header_get_by_height(&self, height: u64) -> RpcResult<ExtendedHeader> {
+        self.header_by_height(height).await
+    }
+
+    async fn header_get_range_by_height(
+        &self,
+        from: ExtendedHeader,
+        to: u64,
+    ) -> RpcResult<Vec<ExtendedHeader>> {
+        // Range is (from, to), same as in celestia-node.
+        let amount = to.saturating_sub(from.height().value()).saturating_sub(1);
+
+        if amount == 0 {
+            return Ok(Vec::new());
+        }
+
+        self.node
+            .request_verified_headers(&from, amount)
+            .await
+            .map_err(rpc_error)
+    }
+
+    async fn header_local_head(&self) -> RpcResult<ExtendedHeader> {
+        self.node.get_local_head_header().await.map_err(rpc_error)
+    }
+
+    async fn header_network_head(&self) -> RpcResult<ExtendedHeader> {
+        self.node
+            .get_network_head_header()
+            .await
+            .map_err(rpc_error)?
+            .ok_or_else(|| rpc_error("Network head is not known yet"))
+    }
+
+    async fn header_subscribe(&self, pending: PendingSubscriptionSink) -> SubscriptionResult {
+        let mut events = self.node.event_subscriber();
+        let sink = pending.accept().await?;
+
+        loop {
+            let event = select! {
+                _ = sink.closed() => break,
+                event = events.recv() => event,
+            };
+
+            let Ok(event) = event else {
+                break;
+            };
+
+            let NodeEvent::AddedHeaderFromHeaderSub { height } = event.event else {
+                continue;
+            };
+
+            let header = match self.node.get_header_by_height(height).await {
+                Ok(header) => header,
+                Err(e) => {
+                    warn!("Failed to get header {height} for subscription: {e}");
+                    continue;
+                }
+            };
+
+            if sink
+                .send(SubscriptionMessage::from_json(&header)?)
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn header_sync_state(&self) -> RpcResult<SyncState> {
+        let info = self.node.syncer_info().await.map_err(rpc_error)?;
+        let local_head = self.node.get_local_head_header().await.map_err(rpc_error)?;
+
+        let from_height = info.stored_headers.tail().unwrap_or_default();
+        let from_hash = self
+            .node
+            .get_header_by_height(from_height)
+            .await
+            .map(|header| header.hash())
+            .unwrap_or_default();
+
+        Ok(SyncState {
+            id: 0,
+            height: local_head.height().value(),
+            from_height,
+            to_height: info.subjective_head,
+            from_hash,
+            to_hash: local_head.hash(),
+            start: local_head.time(),
+            end: local_head.time(),
+            error: None,
+        })
+    }
+
+    async fn header_sync_wait(&self) -> RpcResult<()> {
+        let events = self.node.event_subscriber();
+        self.wait_until(events, || self.is_synced()).await
+    }
+
+    async fn header_wait_for_height(&self, height: u64) -> RpcResult<ExtendedHeader> {
+        let events = self.node.event_subscriber();
+
+        self.wait_until(events, || async {
+            let info = self.node.syncer_info().await.map_err(rpc_error)?;
+            Ok(info.stored_headers.contains(height))
+        })
+        .await?;
+
+        self.node
+            .get_header_by_height(height)
+            .await
+            .map_err(rpc_error)
+    }
+}
+
+#[jsonrpsee::core::async_trait]
+impl<B, S> ShareServer for RpcHandler<B, S>
+where
+    B: Blockstore + 'static,
+    S: Store + 'static,
+{
+    async fn share_get_eds(&self, height: u64) -> RpcResult<ExtendedDataSquare> {
+        self.node.request_eds(height, None).await.map_err(rpc_error)
+    }
+
+    async fn share_get_range(
+        &self,
+        height: u64,
+        start: u64,
+        end: u64,
+    ) -> RpcResult<GetRangeResponse> {
+        let start = usize::try_from(start).map_err(rpc_error)?;
+        let end = usize::try_from(end).map_err(rpc_error)?;
+
+        let header = self.header_by_height(height).await?;
+        let eds = self
+            .node
+            .request_eds(height, None)
+            .await
+            .map_err(rpc_error)?;
+
+        share_range(&header, &eds, start..end)
+    }
+
+    async fn share_get_share(&self, height: u64, row: u64, col: u64) -> RpcResult<Share> {
+        let row = u16::try_from(row).map_err(rpc_error)?;
+        let col = u16::try_from(col).map_err(rpc_error)?;
+
+        let sample = self
+            .node
+            .request_sample(row, col, height, None)
+            .await
+            .map_err(rpc_error)?;
+
+        Ok(sample.share)
+    }
+
+    async fn share_get_namespace_data(
+        &self,
+        height: u64,
+        namespace: Namespace,
+    ) -> RpcResult<NamespaceData> {
+        let header = self.header_by_height(height).await?;
+
+        let rows = header
+            .dah
+            .row_roots()
+            .iter()
+            .enumerate()
+            .filter(|(_, root)| root.contains::<NamespacedSha2Hasher>(*namespace))
+            .map(|(row, _)| {
+                self.node
+                    .request_row_namespace_data(namespace, row as u16, height, None)
+            });
+
+        let rows = try_join_all(rows).await.map_err(rpc_error)?;
+
+        Ok(NamespaceData { rows })
+    }
+
+    async fn share_shares_available(&self, height: u64) -> RpcResult<()> {
+        let metadata = self
+            .node
+            .get_sampling_metadata(height)
+            .await
+            .map_err(rpc_error)?;
+
+        match metadata.map(|m| m.status) {
+            Some(SamplingStatus::Accepted) => Ok(()),
+            Some(SamplingStatus::Rejected) => Err(rpc_error("Data is not available")),
+            _ => Err(rpc_error("Block was not sampled yet")),
+        }
+    }
+}
+
+#[jsonrpsee::core::async_trait]
+impl<B, S> BlobServer for RpcHandler<B, S>
+where
+    B: Blockstore + 'static,
+    S: Store + 'static,
+{
+    async fn blob_get(
+        &self,
+        height: u64,
+        namespace: Namespace,
+        commitment: Commitment,
+    ) -> RpcResult<Blob> {
+        let header = self.header_by_height(height).await?;
+
+        self.node
+            .request_all_blobs(&header, namespace, None)
+            .await
+            .map_err(rpc_error)?
+            .into_iter()
+            .find(|blob| blob.commitment == commitment)
+            .ok_or_else(|| rpc_error("Blob not found"))
+    }
+
+    async fn blob_get_all(
+        &self,
+        height: u64,
+        namespaces: Vec<Namespace>,
+    ) -> RpcResult<Option<Vec<Blob>>> {
+        let header = self.header_by_height(height).await?;
+
+        let blobs = namespaces
+            .into_iter()
+            .map(|namespace| self.node.request_all_blobs(&header, namespace, None));
+
+        let blobs: Vec<_> = try_join_all(blobs)
+            .await
+            .map_err(rpc_error)?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        // celestia-node returns `null` when there are no blobs.
+        Ok((!blobs.is_empty()).then_some(blobs))
+    }
+
+    async fn blob_subscribe(
+        &self,
+        pending: PendingSubscriptionSink,
+        namespace: Namespace,
+    ) -> SubscriptionResult {
+        let mut blobs_stream = match self.node.subscribe_blobs(namespace, None).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                pending.reject(rpc_error(e)).await;
+                return Ok(());
+            }
+        };
+        let sink = pending.accept().await?;
+
+        loop {
+            let item = select! {
+                _ = sink.closed() => break,
+                item = blobs_stream.next() => item,
+            };
+
+            let (height, blobs) = match item {
+                Some(Ok(item)) => item,
+                Some(Err(e)) => {
+                    warn!("Failed to get blobs for subscription: {e}");
+                    break;
+                }
+                None => break,
+            };
+
+            let blobs = BlobsAtHeight {
+                height,
+                // celestia-node returns `null` when there are no blobs.
+                blobs: (!blobs.is_empty()).then_some(blobs),
+            };
+
+            if sink
+                .send(SubscriptionMessage::from_json(&blobs)?)
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[jsonrpsee::core::async_trait]
+impl<B, S> DasServer for RpcHandler<B, S>
+where
+    B: Blockstore + 'static,
+    S: Store + 'static,
+{
+    async fn das_sampling_stats(&self) -> RpcResult<SamplingStats> {
+        self.sampling_stats().await
+    }
+
+    async fn das_wait_catch_up(&self) -> RpcResult<()> {
+        let events = self.node.event_subscriber();
+
+        self.wait_until(events, || async {
+            Ok(self.sampling_stats().await?.catch_up_done)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use celestia_types::test_utils::{generate_dummy_eds, ExtendedHeaderGenerator};
+    use celestia_types::{AppVersion, DataAvailabilityHeader};
+    use lumina_node::block_ranges::BlockRanges;
+    use lumina_node::test_utils::new_block_ranges;
+
+    #[test]
+    fn auth_token() {
+        let mut headers = HeaderMap::new();
+        assert!(is_authorized(&headers, None));
+        assert!(!is_authorized(&headers, Some("secret")));
+
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer secret"),
+        );
+        assert!(is_authorized(&headers, None));
+        assert!(is_authorized(&headers, Some("secret")));
+        assert!(!is_authorized(&headers, Some("secret2")));
+        assert!(!is_authorized(&headers, Some("secre")));
+
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("secret"));
+        assert!(!is_authorized(&headers, Some("secret")));
+    }
+
+    #[test]
+    fn get_range() {
+        let eds = generate_dummy_eds(8, AppVersion::V2);
+        let dah = DataAvailabilityHeader::from_eds(&eds);
+        let header = ExtendedHeaderGenerator::new().next_with_dah(dah);
+
+        let response = share_range(&header, &eds, 3..10).unwrap();
+        response.proof.verify(header.dah.hash()).unwrap();
+        assert_eq!(response.shares.len(), 7);
+
+        // Indexes are of the ODS, which is 4 shares wide
+        for (share, idx) in response.shares.iter().zip(3u16..10) {
+            assert_eq!(share, eds.share(idx / 4, idx % 4).unwrap());
+        }
+
+        let start = 5;
+        share_range(&header, &eds, start..start).unwrap_err();
+        share_range(&header, &eds, 0..17).unwrap_err();
+    }
+
+    #[test]
+    fn sampling_stats_while_sampling() {
+        let syncing_info = SyncingInfo {
+            stored_headers: new_block_ranges([1..=10]),
+            subjective_head: 12,
+        };
+        let sampling_info = SamplingInfo {
+            is_running: true,
+            pending_headers: new_block_ranges([4..=6, 9..=10]),
+        };
+
+        let stats = sampling_stats(&syncing_info, &sampling_info);
+        assert_eq!(stats.head_of_sampled_chain, 3);
+        assert_eq!(stats.network_head_height, 12);
+        assert!(!stats.catch_up_done);
+        assert!(stats.is_running);
+    }
+
+    #[test]
+    fn sampling_stats_caught_up() {
+        let syncing_info = SyncingInfo {
+            stored_headers: new_block_ranges([1..=12]),
+            subjective_head: 12,
+        };
+        let sampling_info = SamplingInfo {
+            is_running: true,
+            pending_headers: BlockRanges::new(),
+        };
+
+        let stats = sampling_stats(&syncing_info, &sampling_info);
+        assert_eq!(stats.head_of_sampled_chain, 12);
+        assert!(stats.catch_up_done);
+
+        // Nothing is caught up before the network head is known
+        let syncing_info = SyncingInfo {
+            stored_headers: BlockRanges::new(),
+            subjective_head: 0,
+        };
+
+        let stats = sampling_stats(&syncing_info, &SamplingInfo::default());
+        assert_eq!(stats.head_of_sampled_chain, 0);
+        assert!(!stats.catch_up_done);
+        assert!(!stats.is_running);
+    }
+}
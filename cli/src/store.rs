@@ -0,0 +1,236 @@
+//! Maintenance of the persistent header store.
+//!
+//! Snapshots allow pre-seeding a new node with headers instead of syncing
+//! them over `header-ex`, `fsck` checks the store after a crash and `compact`
+//! reclaims space left after pruning.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::{value_parser, Args, Parser, Subcommand};
+use lumina_node::blockstore::RedbBlockstore;
+use lumina_node::network::Network;
+use lumina_node::store::{export_snapshot, import_snapshot, RedbStore, Store};
+use tokio::fs::File;
+use tokio::io::{BufReader, BufWriter};
+use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
+
+use crate::native::open_store;
+
+#[derive(Debug, Parser)]
+pub(crate) struct Params {
+    #[command(subcommand)]
+    cmd: StoreCmd,
+}
+
+#[derive(Debug, Subcommand)]
+enum StoreCmd {
+    /// Write headers and sampling metadata of the store to a snapshot file
+    Export {
+        #[command(flatten)]
+        location: StoreLocation,
+
+        /// Path of the snapshot file.
+        file: PathBuf,
+
+        /// Overwrite the existing snapshot file.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Read a snapshot file into an empty store, or resume a failed import
+    Import {
+        #[command(flatten)]
+        location: StoreLocation,
+
+        /// Path of the snapshot file.
+        file: PathBuf,
+    },
+    /// Check consistency of the headers and indexes in the store and find orphaned blocks
+    Fsck {
+        #[command(flatten)]
+        location: StoreLocation,
+
+        /// Truncate the store to the last consistent height and remove orphaned blocks
+        /// if any issues are found.
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Show the number of headers and blocks, and the size of each table of the store
+    Stats {
+        #[command(flatten)]
+        location: StoreLocation,
+    },
+    /// Reclaim the space of the pruned data
+    Compact {
+        #[command(flatten)]
+        location: StoreLocation,
+    },
+}
+
+#[derive(Debug, Args)]
+struct StoreLocation {
+    /// Network the store is used for.
+    #[arg(short, long)]
+    #[clap(value_parser = value_parser!(Network))]
+    network: Network,
+
+    /// Persistent header store path.
+    #[arg(short, long)]
+    store: Option<PathBuf>,
+}
+
+impl StoreLocation {
+    async fn open(self) -> Result<RedbStore> {
+        open_store(self.store, self.network.id()).await
+    }
+}
+
+pub(crate) async fn run(args: Params) -> Result<()> {
+    match args.cmd {
+        StoreCmd::Export {
+            location,
+            file,
+            force,
+        } => {
+            if file.exists() && !force {
+                bail!(
+                    "Snapshot {} already exists, use --force to overwrite it",
+                    file.display()
+                );
+            }
+
+            let store = location.open().await?;
+            // Snapshot is written to a temporary file first, so that a failed export
+            // doesn't leave a truncated snapshot behind.
+            let tmp_file = tmp_path(&file);
+            let exported = match export_to_file(&store, &tmp_file).await {
+                Ok(exported) => exported,
+                Err(e) => {
+                    let _ = tokio::fs::remove_file(&tmp_file).await;
+                    return Err(e);
+                }
+            };
+            store.close().await?;
+
+            tokio::fs::rename(&tmp_file, &file)
+                .await
+                .with_context(|| format!("Failed to create snapshot {}", file.display()))?;
+
+            println!("Exported {exported} headers to {}", file.display());
+        }
+        StoreCmd::Import { location, file } => {
+            let store = location.open().await?;
+            let input = File::open(&file)
+                .await
+                .with_context(|| format!("Failed to open snapshot {}", file.display()))?;
+            let mut reader = BufReader::new(input).compat();
+
+            let imported = import_snapshot(&store, &mut reader).await?;
+            let stored_ranges = store.get_stored_header_ranges().await?;
+            store.close().await?;
+
+            println!("Imported {imported} headers from {}", file.display());
+            println!("Stored headers: {stored_ranges}");
+        }
+        StoreCmd::Fsck { location, repair } => {
+            let store = location.open().await?;
+            let blockstore = RedbBlockstore::new(store.raw_db());
+            let report = store.check_integrity().await?;
+            let orphaned = store.check_blockstore(&blockstore).await?;
+
+            println!("Stored headers: {}", report.stored_ranges);
+            for issue in report.issues.iter().chain(&orphaned) {
+                println!("{issue}");
+            }
+
+            if report.is_consistent() && orphaned.is_empty() {
+                drop(blockstore);
+                store.close().await?;
+                println!("No issues found");
+                return Ok(());
+            }
+
+            let height = report.last_consistent_height.unwrap_or(0);
+
+            if !repair {
+                drop(blockstore);
+                store.close().await?;
+                let issues = report.issues.len() + orphaned.len();
+                if report.is_consistent() {
+                    bail!("Found {issues} issues, use --repair to remove the orphaned blocks");
+                }
+                bail!(
+                    "Found {issues} issues, use --repair to truncate the store to height {height}"
+                );
+            }
+
+            if !report.is_consistent() {
+                store.truncate(height).await?;
+                println!("Store truncated to height {height}");
+            }
+            store.remove_orphaned_blocks(&blockstore).await?;
+
+            let report = store.check_integrity().await?;
+            let orphaned = store.check_blockstore(&blockstore).await?;
+            drop(blockstore);
+            store.close().await?;
+
+            if !report.is_consistent() || !orphaned.is_empty() {
+                bail!(
+                    "Found {} issues after the repair",
+                    report.issues.len() + orphaned.len()
+                );
+            }
+
+            println!("Stored headers: {}", report.stored_ranges);
+        }
+        StoreCmd::Stats { location } => {
+            let store = location.open().await?;
+            let stats = store.stats().await?;
+            store.close().await?;
+
+            println!("Headers: {}", stats.headers);
+            println!("Blocks: {}", stats.cids);
+            for table in &stats.tables {
+                let bytes = table.bytes.unwrap_or_default();
+                println!("{}: {} entries, {bytes} bytes", table.name, table.entries);
+            }
+            if let Some(bytes) = stats.fragmented_bytes {
+                println!("Reclaimable: {bytes} bytes");
+            }
+        }
+        StoreCmd::Compact { location } => {
+            let mut store = location.open().await?;
+            let compacted = store.compact().await?;
+            store.close().await?;
+
+            if compacted {
+                println!("Store compacted");
+            } else {
+                println!("Store is already compact");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn export_to_file(store: &RedbStore, path: &Path) -> Result<u64> {
+    let out = File::create(path)
+        .await
+        .with_context(|| format!("Failed to create snapshot {}", path.display()))?;
+    let mut writer = BufWriter::new(out).compat_write();
+
+    let exported = export_snapshot(store, &mut writer).await?;
+    writer.into_inner().into_inner().sync_all().await?;
+
+    Ok(exported)
+}
+
+/// Path of the temporary file written next to the `path`.
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
@@ -43,6 +43,27 @@ pub enum LuminaError {
         msg: String,
     },
 
+    /// Error returned when a namespace is invalid or malformed
+    #[error("Invalid namespace: {msg}")]
+    InvalidNamespace {
+        /// Description of why the namespace is invalid
+        msg: String,
+    },
+
+    /// Error returned when a time is out of the supported range
+    #[error("Invalid time: {msg}")]
+    InvalidTime {
+        /// Description of why the time is invalid
+        msg: String,
+    },
+
+    /// Error returned when the node configuration is incomplete or contradictory
+    #[error("Invalid configuration: {msg}")]
+    InvalidConfig {
+        /// Description of the problem, naming the affected field
+        msg: String,
+    },
+
     /// Error returned when storage initialization fails
     #[error("Storage initialization failed: {msg}")]
     StorageInit {
@@ -68,6 +89,18 @@ impl LuminaError {
         Self::InvalidHeader { msg: msg.into() }
     }
 
+    pub fn invalid_namespace(msg: impl Into<String>) -> Self {
+        Self::InvalidNamespace { msg: msg.into() }
+    }
+
+    pub fn invalid_time(msg: impl Into<String>) -> Self {
+        Self::InvalidTime { msg: msg.into() }
+    }
+
+    pub fn invalid_config(msg: impl Into<String>) -> Self {
+        Self::InvalidConfig { msg: msg.into() }
+    }
+
     pub fn storage_init(msg: impl Into<String>) -> Self {
         Self::StorageInit { msg: msg.into() }
     }
@@ -7,16 +7,20 @@
 mod error;
 mod types;
 
+use celestia_types::nmt::Namespace;
 use celestia_types::ExtendedHeader;
 use error::{LuminaError, Result};
-use lumina_node::{
-    blockstore::RedbBlockstore, events::EventSubscriber, node::PeerTrackerInfo, store::RedbStore,
-    Node,
-};
+use lumina_node::{events::EventSubscriber, node::PeerTrackerInfo, Node};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tendermint::hash::Hash;
+use tendermint::Time;
 use tokio::sync::{Mutex, RwLock};
-use types::{NetworkInfo, NodeConfig, NodeEvent, PeerId, SyncingInfo};
+use types::{
+    BandwidthStats, BlobSubscription, Blockstore, NetworkInfo, NodeConfig, NodeEvent, PeerId,
+    PeerInfo, Store, StoreStats, SyncingInfo,
+};
 use uniffi::Object;
 
 uniffi::setup_scaffolding!();
@@ -26,7 +30,7 @@ lumina_node::uniffi_reexport_scaffolding!();
 /// The main Lumina node that manages the connection to the Celestia network.
 #[derive(Object)]
 pub struct LuminaNode {
-    node: RwLock<Option<Node<RedbBlockstore, RedbStore>>>,
+    node: RwLock<Option<Node<Blockstore, Store>>>,
     events_subscriber: Mutex<Option<EventSubscriber>>,
     config: NodeConfig,
 }
@@ -111,6 +115,28 @@ impl LuminaNode {
         Ok(info.into())
     }
 
+    /// Gets the bandwidth used by the node, summed over all peers and protocols.
+    pub async fn bandwidth_stats(&self) -> Result<BandwidthStats> {
+        let node = self.node.read().await;
+        let node = node.as_ref().ok_or(LuminaError::NodeNotRunning)?;
+        Ok(node.bandwidth_stats().into())
+    }
+
+    /// Gets the bandwidth used with the peer with given ID.
+    pub async fn bandwidth_for_peer(&self, peer_id: PeerId) -> Result<BandwidthStats> {
+        let node = self.node.read().await;
+        let node = node.as_ref().ok_or(LuminaError::NodeNotRunning)?;
+        let peer_id = peer_id.to_libp2p().map_err(LuminaError::network)?;
+        Ok(node.bandwidth_for_peer(&peer_id).into())
+    }
+
+    /// Gets the bandwidth used by the protocol with given ID, e.g. `/ipfs/id/1.0.0`.
+    pub async fn bandwidth_for_protocol(&self, protocol_id: String) -> Result<BandwidthStats> {
+        let node = self.node.read().await;
+        let node = node.as_ref().ok_or(LuminaError::NodeNotRunning)?;
+        Ok(node.bandwidth_for_protocol(&protocol_id).into())
+    }
+
     /// Gets list of addresses the node is listening to.
     pub async fn listeners(&self) -> Result<Vec<String>> {
         let node = self.node.read().await;
@@ -135,6 +161,58 @@ impl LuminaNode {
         Ok(node.set_peer_trust(peer_id, is_trusted).await?)
     }
 
+    /// Connects to a peer on the given multiaddress, waiting until the connection is established.
+    pub async fn connect(&self, addr: String) -> Result<()> {
+        let node = self.node.read().await;
+        let node = node.as_ref().ok_or(LuminaError::NodeNotRunning)?;
+        let addr = addr
+            .parse()
+            .map_err(|e| LuminaError::network(format!("Invalid multiaddr: {e}")))?;
+        Ok(node.connect(addr).await?)
+    }
+
+    /// Closes all connections to the peer with given ID.
+    pub async fn disconnect(&self, peer_id: PeerId) -> Result<()> {
+        let node = self.node.read().await;
+        let node = node.as_ref().ok_or(LuminaError::NodeNotRunning)?;
+        let peer_id = peer_id.to_libp2p().map_err(LuminaError::network)?;
+        Ok(node.disconnect(peer_id).await?)
+    }
+
+    /// Blocks the peer with given ID, disconnecting it and refusing any further connections.
+    ///
+    /// The blocklist is persisted across restarts.
+    pub async fn block_peer(&self, peer_id: PeerId) -> Result<()> {
+        let node = self.node.read().await;
+        let node = node.as_ref().ok_or(LuminaError::NodeNotRunning)?;
+        let peer_id = peer_id.to_libp2p().map_err(LuminaError::network)?;
+        Ok(node.block_peer(peer_id).await?)
+    }
+
+    /// Unblocks the peer with given ID.
+    pub async fn unblock_peer(&self, peer_id: PeerId) -> Result<()> {
+        let node = self.node.read().await;
+        let node = node.as_ref().ok_or(LuminaError::NodeNotRunning)?;
+        let peer_id = peer_id.to_libp2p().map_err(LuminaError::network)?;
+        Ok(node.unblock_peer(peer_id).await?)
+    }
+
+    /// Gets list of blocked peer IDs.
+    pub async fn list_blocked_peers(&self) -> Result<Vec<PeerId>> {
+        let node = self.node.read().await;
+        let node = node.as_ref().ok_or(LuminaError::NodeNotRunning)?;
+        let peers = node.list_blocked_peers().await?;
+        Ok(peers.into_iter().map(PeerId::from).collect())
+    }
+
+    /// Gets information about the peer with given ID, `None` if the peer is unknown.
+    pub async fn peer_info(&self, peer_id: PeerId) -> Result<Option<PeerInfo>> {
+        let node = self.node.read().await;
+        let node = node.as_ref().ok_or(LuminaError::NodeNotRunning)?;
+        let peer_id = peer_id.to_libp2p().map_err(LuminaError::network)?;
+        Ok(node.peer_info(peer_id).await?.map(PeerInfo::from))
+    }
+
     /// Request the head header from the network.
     ///
     /// Returns a serialized ExtendedHeader string.
@@ -223,6 +301,21 @@ impl LuminaNode {
         Ok(header.to_string())
     }
 
+    /// Get the last synced header with the time at or before the given `time`.
+    pub async fn get_header_by_time(&self, time: SystemTime) -> Result<String> {
+        let node = self.node.read().await;
+        let node = node.as_ref().ok_or(LuminaError::NodeNotRunning)?;
+        let since_epoch = time
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| LuminaError::invalid_time(e.to_string()))?;
+        let secs = i64::try_from(since_epoch.as_secs())
+            .map_err(|e| LuminaError::invalid_time(e.to_string()))?;
+        let time = Time::from_unix_timestamp(secs, since_epoch.subsec_nanos())
+            .map_err(|e| LuminaError::invalid_time(e.to_string()))?;
+        let header = node.get_header_by_time(time).await?;
+        Ok(header.to_string())
+    }
+
     /// Gets headers from the given heights range.
     ///
     /// If start of the range is undefined (None), the first returned header will be of height 1.
@@ -259,6 +352,34 @@ impl LuminaNode {
         Ok(metadata.map(|m| serde_json::to_string(&m).unwrap()))
     }
 
+    /// Gets storage usage of the node.
+    pub async fn storage_stats(&self) -> Result<StoreStats> {
+        let node = self.node.read().await;
+        let node = node.as_ref().ok_or(LuminaError::NodeNotRunning)?;
+        let stats = node.storage_stats().await?;
+        Ok(stats.into())
+    }
+
+    /// Subscribes to the blobs of the namespace in the new blocks.
+    ///
+    /// `namespace` is the raw 29 bytes of the namespace. Blocks are processed in order,
+    /// starting from `start_height`, or from the block after the current head if it is not
+    /// set. Heights without any blobs of the namespace are returned with an empty list.
+    pub async fn subscribe_blobs(
+        &self,
+        namespace: Vec<u8>,
+        start_height: Option<u64>,
+    ) -> Result<Arc<BlobSubscription>> {
+        let namespace = Namespace::from_raw(&namespace)
+            .map_err(|e| LuminaError::invalid_namespace(e.to_string()))?;
+
+        let node = self.node.read().await;
+        let node = node.as_ref().ok_or(LuminaError::NodeNotRunning)?;
+
+        let stream = node.subscribe_blobs(namespace, start_height).await?;
+        Ok(Arc::new(BlobSubscription::new(stream)))
+    }
+
     /// Returns the next event from the node's event channel.
     pub async fn next_event(&self) -> Result<NodeEvent> {
         let mut events_subscriber = self.events_subscriber.lock().await;
@@ -0,0 +1,50 @@
+use futures::StreamExt;
+use lumina_node::node::BlobStream;
+use tokio::sync::Mutex;
+use uniffi::{Object, Record};
+
+use crate::error::Result;
+
+/// Blobs of a namespace in the block at a given height.
+#[derive(Record)]
+pub struct BlobsAtHeight {
+    /// Height of the block.
+    pub height: u64,
+    /// JSON serialized blobs of the namespace. Empty if block has no blobs of the namespace.
+    pub blobs: Vec<String>,
+}
+
+/// Subscription to the blobs of a namespace, created with `LuminaNode::subscribe_blobs`.
+#[derive(Object)]
+pub struct BlobSubscription {
+    stream: Mutex<BlobStream>,
+}
+
+impl BlobSubscription {
+    pub(crate) fn new(stream: BlobStream) -> Self {
+        BlobSubscription {
+            stream: Mutex::new(stream),
+        }
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl BlobSubscription {
+    /// Waits for the blobs of the next height.
+    ///
+    /// Returns `None` when the subscription ended, either because the node was stopped
+    /// or because the previous call returned an error.
+    pub async fn next(&self) -> Result<Option<BlobsAtHeight>> {
+        let Some(res) = self.stream.lock().await.next().await else {
+            return Ok(None);
+        };
+
+        let (height, blobs) = res?;
+        let blobs = blobs
+            .iter()
+            .map(|blob| serde_json::to_string(blob).unwrap())
+            .collect();
+
+        Ok(Some(BlobsAtHeight { height, blobs }))
+    }
+}
@@ -1,12 +1,26 @@
-use std::{path::PathBuf, sync::Arc, time::Duration};
+use std::{path::PathBuf, str::FromStr, sync::Arc, time::Duration};
 
+use blockstore::EitherBlockstore;
+use celestia_types::{hash::Hash, ExtendedHeader};
 use libp2p::identity::Keypair;
-use lumina_node::{blockstore::RedbBlockstore, network, store::RedbStore, NodeBuilder};
+use lumina_node::{
+    blockstore::{EncryptedBlockstore, RedbBlockstore},
+    encryption::EncryptionKey,
+    network,
+    node::TrustedCheckpoint,
+    store::{sampled_cids, RedbStore},
+    NodeBuilder,
+};
 use tokio::task::spawn_blocking;
 use uniffi::Record;
 
 use crate::error::{LuminaError, Result};
 
+/// Blockstore of the node, encrypted if `encryption_key` is set.
+pub(crate) type Blockstore = EitherBlockstore<RedbBlockstore, EncryptedBlockstore<RedbBlockstore>>;
+/// Store of the node, encrypted if `encryption_key` is set.
+pub(crate) type Store = RedbStore;
+
 /// Configuration options for the Lumina node
 #[derive(Debug, Clone, Record)]
 pub struct NodeConfig {
@@ -25,11 +39,27 @@ pub struct NodeConfig {
     pub batch_size: Option<u64>,
     /// Optional Set the keypair to be used as Node's identity. If None, generates a new Ed25519 keypair.
     pub ed25519_secret_key_bytes: Option<Vec<u8>>,
+    /// Hash of the trusted header from which the network head is verified.
+    /// Must be set together with `trusted_height`.
+    pub trusted_hash: Option<String>,
+    /// Height of the trusted header from which the network head is verified.
+    /// Must be set together with `trusted_hash`.
+    pub trusted_height: Option<u64>,
+    /// JSON serialized trusted header from which the network head is verified.
+    /// Takes precedence over `trusted_hash` and `trusted_height`.
+    pub trusted_header: Option<String>,
+    /// 32 bytes key used to encrypt the stored data at rest. If None, data is stored unencrypted.
+    /// Encryption can be enabled only on a new store, existing unencrypted store must be removed.
+    /// Both the store and the blockstore are kept in redb, which supports encryption.
+    pub encryption_key: Option<Vec<u8>>,
+    /// Key which the stored data was previously encrypted with. If set, data is re-encrypted
+    /// with `encryption_key` on start. Must be set together with `encryption_key`.
+    pub previous_encryption_key: Option<Vec<u8>>,
 }
 
 impl NodeConfig {
     /// Convert into NodeBuilder for the implementation
-    pub(crate) async fn into_node_builder(self) -> Result<NodeBuilder<RedbBlockstore, RedbStore>> {
+    pub(crate) async fn into_node_builder(self) -> Result<NodeBuilder<Blockstore, Store>> {
         let network_id = self.network.id();
         let base_path = PathBuf::from(self.base_path);
         let store_path = base_path.join(format!("store-{}", network_id));
@@ -52,11 +82,56 @@ impl NodeConfig {
         .await
         .map_err(|e| LuminaError::storage(format!("Failed to create base directory: {}", e)))??;
 
-        let store = RedbStore::new(db.clone())
-            .await
-            .map_err(|e| LuminaError::storage_init(format!("Failed to initialize store: {}", e)))?;
+        let encryption_key = self
+            .encryption_key
+            .map(|key| parse_encryption_key(&key))
+            .transpose()?;
+        let previous_encryption_key = self
+            .previous_encryption_key
+            .map(|key| parse_encryption_key(&key))
+            .transpose()?;
 
-        let blockstore = RedbBlockstore::new(db);
+        let (store, blockstore) = match (encryption_key, previous_encryption_key) {
+            (None, None) => {
+                let store = RedbStore::new(db.clone()).await;
+                let blockstore = RedbBlockstore::new(db);
+                (store, EitherBlockstore::Left(blockstore))
+            }
+            (Some(key), None) => {
+                // Store refuses to be encrypted if the database, including the blocks, isn't empty
+                let store = RedbStore::new_encrypted(db.clone(), &key).await;
+                let blockstore = EncryptedBlockstore::new(RedbBlockstore::new(db), &key);
+                (store, EitherBlockstore::Right(blockstore))
+            }
+            (Some(key), Some(previous_key)) => {
+                let store =
+                    RedbStore::new_encrypted_with_previous_key(db.clone(), &key, &previous_key)
+                        .await;
+                let blockstore = EncryptedBlockstore::new_with_previous_key(
+                    RedbBlockstore::new(db),
+                    &key,
+                    &previous_key,
+                );
+                // Store is re-encrypted when opened, blocks are re-encrypted separately.
+                if let Ok(ref store) = store {
+                    let cids = sampled_cids(store).await.map_err(|e| {
+                        LuminaError::storage_init(format!("Failed to read sampled blocks: {}", e))
+                    })?;
+                    blockstore.reencrypt(cids).await.map_err(|e| {
+                        LuminaError::storage_init(format!("Failed to re-encrypt blockstore: {}", e))
+                    })?;
+                }
+                (store, EitherBlockstore::Right(blockstore))
+            }
+            (None, Some(_)) => {
+                return Err(LuminaError::storage_init(
+                    "Previous encryption key must be set together with encryption key",
+                ));
+            }
+        };
+
+        let store = store
+            .map_err(|e| LuminaError::storage_init(format!("Failed to initialize store: {}", e)))?;
 
         let bootnodes = if let Some(bootnodes) = self.bootnodes {
             let mut resolved = Vec::with_capacity(bootnodes.len());
@@ -95,6 +170,31 @@ impl NodeConfig {
             builder = builder.pruning_delay(Duration::from_secs(secs.into()));
         }
 
+        if let Some(header) = self.trusted_header {
+            let header: ExtendedHeader = serde_json::from_str(&header).map_err(|e| {
+                LuminaError::invalid_header(format!("Invalid trusted header JSON: {}", e))
+            })?;
+            builder = builder.trusted_checkpoint(header);
+        } else if let Some(hash) = self.trusted_hash {
+            let hash =
+                Hash::from_str(&hash).map_err(|e| LuminaError::invalid_hash(e.to_string()))?;
+            let height = self.trusted_height.ok_or_else(|| {
+                LuminaError::invalid_config(
+                    "`trusted_height` is missing, it must be set together with `trusted_hash`",
+                )
+            })?;
+            builder = builder.trusted_checkpoint(TrustedCheckpoint::Hash { height, hash });
+        } else if self.trusted_height.is_some() {
+            return Err(LuminaError::invalid_config(
+                "`trusted_hash` is missing, it must be set together with `trusted_height`",
+            ));
+        }
+
         Ok(builder)
     }
 }
+
+fn parse_encryption_key(bytes: &[u8]) -> Result<EncryptionKey> {
+    EncryptionKey::try_from(bytes)
+        .map_err(|e| LuminaError::storage_init(format!("Invalid encryption key: {}", e)))
+}
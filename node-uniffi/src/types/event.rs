@@ -145,6 +145,20 @@ pub enum NodeEvent {
         /// A human readable error.
         error: String,
     },
+    /// Store was copied aside before its schema was migrated.
+    StoreBackedUp {
+        /// Location of the backup.
+        location: String,
+    },
+    /// Store schema was migrated when the store was opened.
+    StoreMigrated {
+        /// Schema version before the migration.
+        from_version: u64,
+        /// Schema version after the migration.
+        to_version: u64,
+        /// A human readable description of the migration.
+        description: String,
+    },
     /// Network was compromised.
     ///
     /// This happens when a valid bad encoding fraud proof is received.
@@ -243,6 +257,16 @@ impl From<LuminaNodeEvent> for NodeEvent {
             LuminaNodeEvent::FatalSyncerError { error } => NodeEvent::FatalSyncerError { error },
             LuminaNodeEvent::PrunedHeaders { to_height } => NodeEvent::PrunedHeaders { to_height },
             LuminaNodeEvent::FatalPrunerError { error } => NodeEvent::FatalPrunerError { error },
+            LuminaNodeEvent::StoreBackedUp { location } => NodeEvent::StoreBackedUp { location },
+            LuminaNodeEvent::StoreMigrated {
+                from_version,
+                to_version,
+                description,
+            } => NodeEvent::StoreMigrated {
+                from_version,
+                to_version,
+                description,
+            },
             LuminaNodeEvent::NetworkCompromised => NodeEvent::NetworkCompromised,
             LuminaNodeEvent::NodeStopped => NodeEvent::NodeStopped,
             _ => panic!("Unknown event: {:?}", event),
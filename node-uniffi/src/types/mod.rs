@@ -1,9 +1,14 @@
+mod blob;
 mod config;
 mod event;
 mod network;
+mod storage;
 mod sync;
 
+pub use blob::BlobSubscription;
 pub use config::NodeConfig;
+pub(crate) use config::{Blockstore, Store};
 pub use event::{NodeEvent, PeerId};
-pub use network::NetworkInfo;
+pub use network::{BandwidthStats, NetworkInfo, PeerInfo};
+pub use storage::StoreStats;
 pub use sync::SyncingInfo;
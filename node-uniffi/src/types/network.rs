@@ -1,7 +1,11 @@
+use celestia_types::p2p::BandwidthStats as CelestiaBandwidthStats;
 use libp2p::swarm::ConnectionCounters as Libp2pConnectionCounters;
 use libp2p::swarm::NetworkInfo as Libp2pNetworkInfo;
+use lumina_node::node::PeerInfo as LuminaPeerInfo;
 use uniffi::Record;
 
+use crate::types::PeerId;
+
 #[derive(Record)]
 pub struct NetworkInfo {
     /// The total number of connected peers.
@@ -51,3 +55,66 @@ impl From<&Libp2pConnectionCounters> for ConnectionCounters {
         }
     }
 }
+
+/// Information about a peer.
+#[derive(Record)]
+pub struct PeerInfo {
+    /// Id of the peer.
+    pub peer_id: PeerId,
+    /// Known addresses of the peer.
+    pub addrs: Vec<String>,
+    /// Protocols supported by the peer, as reported by identify.
+    pub protocols: Vec<String>,
+    /// Agent version of the peer, as reported by identify.
+    pub agent_version: Option<String>,
+    /// Whether the peer is currently connected.
+    pub connected: bool,
+    /// Whether the peer is trusted.
+    pub trusted: bool,
+    /// Whether the peer is currently banned for misbehaving.
+    pub banned: bool,
+    /// Whether the peer is blocked.
+    pub blocked: bool,
+    /// Current score of the peer, decaying towards zero over time.
+    pub score: f64,
+}
+
+impl From<LuminaPeerInfo> for PeerInfo {
+    fn from(info: LuminaPeerInfo) -> Self {
+        Self {
+            peer_id: info.peer_id.into(),
+            addrs: info.addrs.iter().map(|addr| addr.to_string()).collect(),
+            protocols: info.protocols,
+            agent_version: info.agent_version,
+            connected: info.connected,
+            trusted: info.trusted,
+            banned: info.banned,
+            blocked: info.blocked,
+            score: info.score,
+        }
+    }
+}
+
+/// Bandwidth used by the node.
+#[derive(Record)]
+pub struct BandwidthStats {
+    /// Total bytes received.
+    pub total_in: f32,
+    /// Total bytes sent.
+    pub total_out: f32,
+    /// Rate of receiving, in bytes per second.
+    pub rate_in: f32,
+    /// Rate of sending, in bytes per second.
+    pub rate_out: f32,
+}
+
+impl From<CelestiaBandwidthStats> for BandwidthStats {
+    fn from(stats: CelestiaBandwidthStats) -> Self {
+        Self {
+            total_in: stats.total_in,
+            total_out: stats.total_out,
+            rate_in: stats.rate_in,
+            rate_out: stats.rate_out,
+        }
+    }
+}
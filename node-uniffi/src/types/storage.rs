@@ -0,0 +1,47 @@
+use lumina_node::store::{StoreStats as LuminaStoreStats, TableStats as LuminaTableStats};
+use uniffi::Record;
+
+/// Storage usage of a single table of the database.
+#[derive(Record)]
+struct TableStats {
+    /// Name of the table.
+    name: String,
+    /// Number of entries in the table.
+    entries: u64,
+    /// Bytes used by the table.
+    bytes: Option<u64>,
+}
+
+impl From<LuminaTableStats> for TableStats {
+    fn from(table: LuminaTableStats) -> Self {
+        Self {
+            name: table.name,
+            entries: table.entries,
+            bytes: table.bytes,
+        }
+    }
+}
+
+/// Storage usage of the node.
+#[derive(Record)]
+pub struct StoreStats {
+    /// Number of stored headers.
+    headers: u64,
+    /// Number of CIDs of the blocks kept in the blockstore.
+    cids: u64,
+    /// Tables of the database.
+    tables: Vec<TableStats>,
+    /// Bytes of the database which can be reclaimed by compaction.
+    fragmented_bytes: Option<u64>,
+}
+
+impl From<LuminaStoreStats> for StoreStats {
+    fn from(stats: LuminaStoreStats) -> Self {
+        Self {
+            headers: stats.headers,
+            cids: stats.cids,
+            tables: stats.tables.into_iter().map(TableStats::from).collect(),
+            fragmented_bytes: stats.fragmented_bytes,
+        }
+    }
+}
@@ -3,28 +3,36 @@
 use std::time::Duration;
 
 use blockstore::EitherBlockstore;
+use celestia_types::hash::Hash;
 use celestia_types::nmt::Namespace;
 use celestia_types::{Blob, ExtendedHeader};
-use js_sys::Array;
-use lumina_node::blockstore::{InMemoryBlockstore, IndexedDbBlockstore};
+use js_sys::{Array, Date};
+use lumina_node::blockstore::{EncryptedBlockstore, InMemoryBlockstore, IndexedDbBlockstore};
+use lumina_node::encryption::EncryptionKey;
 use lumina_node::network;
-use lumina_node::node::{NodeBuilder, MIN_PRUNING_DELAY, MIN_SAMPLING_WINDOW};
-use lumina_node::store::{EitherStore, InMemoryStore, IndexedDbStore, SamplingMetadata};
+use lumina_node::node::{NodeBuilder, TrustedCheckpoint, MIN_PRUNING_DELAY, MIN_SAMPLING_WINDOW};
+use lumina_node::store::{
+    sampled_cids, EitherStore, InMemoryStore, IndexedDbStore, SamplingMetadata,
+};
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error};
+use tendermint::Time;
+use tracing::{debug, error, warn};
 use wasm_bindgen::prelude::*;
 use web_sys::BroadcastChannel;
 
 use crate::commands::{CheckableResponseExt, NodeCommand, SingleHeaderQuery};
-use crate::error::{Context, Result};
+use crate::error::{Context, Error, Result};
 use crate::ports::WorkerClient;
 use crate::utils::{
     is_safari, js_value_from_display, request_storage_persistence, resolve_dnsaddr_multiaddress,
-    timeout, Network,
+    storage_estimate, timeout, Network,
+};
+use crate::worker::{
+    PersistentBlockstore, WasmBlockstore, WasmStore, BLOBS_CHANNEL_CLOSE_MESSAGE,
+    BLOBS_CHANNEL_READY_MESSAGE,
 };
-use crate::worker::{WasmBlockstore, WasmStore};
-use crate::wrapper::libp2p::NetworkInfoSnapshot;
-use crate::wrapper::node::{PeerTrackerInfoSnapshot, SyncingInfoSnapshot};
+use crate::wrapper::libp2p::{NetworkInfoSnapshot, PeerInfoSnapshot};
+use crate::wrapper::node::{PeerTrackerInfoSnapshot, StorageStatsSnapshot, SyncingInfoSnapshot};
 
 /// Config for the lumina wasm node.
 #[wasm_bindgen(inspectable, js_name = NodeConfig)]
@@ -58,6 +66,35 @@ pub struct WasmNodeConfig {
     ///
     /// The minimum value that can be set is 60 seconds.
     pub custom_pruning_delay_secs: Option<u32>,
+    /// Hash of the trusted header from which the network head is verified.
+    ///
+    /// Must be set together with `trusted_height`.
+    #[wasm_bindgen(getter_with_clone)]
+    pub trusted_hash: Option<String>,
+    /// Height of the trusted header from which the network head is verified.
+    ///
+    /// Must be set together with `trusted_hash`.
+    pub trusted_height: Option<u32>,
+    /// JSON serialized trusted header from which the network head is verified.
+    ///
+    /// Takes precedence over `trusted_hash` and `trusted_height`.
+    #[wasm_bindgen(getter_with_clone)]
+    pub trusted_header: Option<String>,
+    /// 32 bytes key used to encrypt the data stored in persistent memory.
+    ///
+    /// If this is not set, data is stored unencrypted. Encryption can be enabled only
+    /// on a new store, existing unencrypted data must be removed first.
+    ///
+    /// Only the persistent memory can be encrypted, so this must not be set if
+    /// `use_persistent_memory` is false.
+    #[wasm_bindgen(getter_with_clone)]
+    pub encryption_key: Option<Vec<u8>>,
+    /// Key which the persistent data was previously encrypted with.
+    ///
+    /// If this is set, data is re-encrypted with `encryption_key` when the node starts.
+    /// Must be set together with `encryption_key`.
+    #[wasm_bindgen(getter_with_clone)]
+    pub previous_encryption_key: Option<Vec<u8>>,
 }
 
 /// `NodeClient` is responsible for steering [`NodeWorker`] by sending it commands and receiving
@@ -217,6 +254,64 @@ impl NodeClient {
         response.into_set_peer_trust().check_variant()?
     }
 
+    /// Connect to a peer on the given multiaddress.
+    ///
+    /// Resolves once the connection is established, rejects if the dial fails.
+    pub async fn connect(&self, addr: &str) -> Result<()> {
+        let command = NodeCommand::Connect(addr.parse()?);
+        let response = self.worker.exec(command).await?;
+        response.into_connected_to_peer().check_variant()?
+    }
+
+    /// Close all connections to the peer with a given ID.
+    pub async fn disconnect(&self, peer_id: &str) -> Result<()> {
+        let command = NodeCommand::Disconnect(peer_id.parse()?);
+        let response = self.worker.exec(command).await?;
+        response.into_disconnected().check_variant()?
+    }
+
+    /// Block the peer with a given ID, disconnecting it and refusing any further
+    /// connections. The blocklist is persisted across restarts.
+    #[wasm_bindgen(js_name = blockPeer)]
+    pub async fn block_peer(&self, peer_id: &str) -> Result<()> {
+        let command = NodeCommand::SetPeerBlocked {
+            peer_id: peer_id.parse()?,
+            is_blocked: true,
+        };
+        let response = self.worker.exec(command).await?;
+        response.into_set_peer_blocked().check_variant()?
+    }
+
+    /// Unblock the peer with a given ID.
+    #[wasm_bindgen(js_name = unblockPeer)]
+    pub async fn unblock_peer(&self, peer_id: &str) -> Result<()> {
+        let command = NodeCommand::SetPeerBlocked {
+            peer_id: peer_id.parse()?,
+            is_blocked: false,
+        };
+        let response = self.worker.exec(command).await?;
+        response.into_set_peer_blocked().check_variant()?
+    }
+
+    /// Get all the blocked peers.
+    #[wasm_bindgen(js_name = listBlockedPeers)]
+    pub async fn list_blocked_peers(&self) -> Result<Array> {
+        let command = NodeCommand::GetBlockedPeers;
+        let response = self.worker.exec(command).await?;
+        let peers = response.into_blocked_peers().check_variant()?;
+        let result = peers?.iter().map(js_value_from_display).collect();
+
+        Ok(result)
+    }
+
+    /// Get the information about the peer with a given ID, `undefined` if the peer is unknown.
+    #[wasm_bindgen(js_name = peerInfo)]
+    pub async fn peer_info(&self, peer_id: &str) -> Result<Option<PeerInfoSnapshot>> {
+        let command = NodeCommand::GetPeerInfo(peer_id.parse()?);
+        let response = self.worker.exec(command).await?;
+        response.into_peer_info().check_variant()?
+    }
+
     /// Request the head header from the network.
     #[wasm_bindgen(js_name = requestHeadHeader)]
     pub async fn request_head_header(&self) -> Result<ExtendedHeader> {
@@ -276,6 +371,42 @@ impl NodeClient {
         response.into_blobs().check_variant()?
     }
 
+    /// Subscribe to the blobs of the namespace in the new blocks.
+    ///
+    /// Returns a [`BlobSubscription`] with a [`BroadcastChannel`] on which an object with
+    /// `height` and `blobs` is posted for each block, starting from `start_height` or from
+    /// the block after the current head. Heights without any blobs of the namespace are
+    /// posted with an empty `blobs` list. If blobs of a height cannot be retrieved, an object
+    /// with `error` is posted and the subscription ends. Blobs are held until the
+    /// subscription is returned, so none are missed if the channel is listened on right away.
+    ///
+    /// Subscription also ends when the [`BlobSubscription`] is closed or freed, or when
+    /// `"close"` is posted on its channel.
+    #[wasm_bindgen(js_name = subscribeBlobs)]
+    pub async fn subscribe_blobs(
+        &self,
+        namespace: &Namespace,
+        start_height: Option<u64>,
+    ) -> Result<BlobSubscription> {
+        let command = NodeCommand::SubscribeBlobs {
+            namespace: *namespace,
+            start_height,
+        };
+        let response = self.worker.exec(command).await?;
+        let name = response.into_blobs_channel_name().check_variant()??;
+        let channel =
+            BroadcastChannel::new(&name).context("Failed to allocate BroadcastChannel")?;
+        let subscription = BlobSubscription { channel };
+
+        // Worker holds the blobs until we listen on the channel
+        subscription
+            .channel
+            .post_message(&BLOBS_CHANNEL_READY_MESSAGE.into())
+            .context("Failed to start blob subscription")?;
+
+        Ok(subscription)
+    }
+
     /// Get current header syncing info.
     #[wasm_bindgen(js_name = syncerInfo)]
     pub async fn syncer_info(&self) -> Result<SyncingInfoSnapshot> {
@@ -318,6 +449,23 @@ impl NodeClient {
         response.into_header().check_variant()?
     }
 
+    /// Get the last synced header with the time at or before the given `time`.
+    #[wasm_bindgen(js_name = getHeaderByTime)]
+    pub async fn get_header_by_time(&self, time: &Date) -> Result<ExtendedHeader> {
+        let millis = time.get_time();
+        if !millis.is_finite() {
+            return Err(Error::new("Invalid date"));
+        }
+
+        let secs = (millis / 1000.0).floor();
+        let nanos = ((millis - secs * 1000.0) * 1_000_000.0) as u32;
+        let time = Time::from_unix_timestamp(secs as i64, nanos).context("Invalid date")?;
+
+        let command = NodeCommand::GetHeader(SingleHeaderQuery::ByTime(time));
+        let response = self.worker.exec(command).await?;
+        response.into_header().check_variant()?
+    }
+
     /// Get synced headers from the given heights range.
     ///
     /// If start of the range is undefined (None), the first returned header will be of height 1.
@@ -349,6 +497,26 @@ impl NodeClient {
         response.into_sampling_metadata().check_variant()?
     }
 
+    /// Get storage usage of the node.
+    ///
+    /// Usage and quota of the whole origin are estimated with `navigator.storage.estimate()`.
+    #[wasm_bindgen(js_name = storageStats)]
+    pub async fn storage_stats(&self) -> Result<StorageStatsSnapshot> {
+        let command = NodeCommand::GetStorageStats;
+        let response = self.worker.exec(command).await?;
+        let stats = response.into_storage_stats().check_variant()??;
+
+        let (usage, quota) = match storage_estimate().await {
+            Ok(estimate) => estimate,
+            Err(e) => {
+                warn!("Error estimating storage usage: {e}");
+                (None, None)
+            }
+        };
+
+        Ok(StorageStatsSnapshot::new(stats, usage, quota))
+    }
+
     /// Returns a [`BroadcastChannel`] for events generated by [`Node`].
     #[wasm_bindgen(js_name = eventsChannel)]
     pub async fn events_channel(&self) -> Result<BroadcastChannel> {
@@ -360,6 +528,36 @@ impl NodeClient {
     }
 }
 
+/// Subscription to the blobs of a namespace, created with [`NodeClient::subscribe_blobs`].
+#[wasm_bindgen]
+pub struct BlobSubscription {
+    channel: BroadcastChannel,
+}
+
+#[wasm_bindgen]
+impl BlobSubscription {
+    /// [`BroadcastChannel`] on which the blobs are posted.
+    #[wasm_bindgen(getter)]
+    pub fn channel(&self) -> BroadcastChannel {
+        self.channel.clone()
+    }
+
+    /// End the subscription and close the channel.
+    pub fn close(&self) {
+        // Posting fails if the channel is already closed, which is fine
+        let _ = self
+            .channel
+            .post_message(&BLOBS_CHANNEL_CLOSE_MESSAGE.into());
+        self.channel.close();
+    }
+}
+
+impl Drop for BlobSubscription {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
 #[wasm_bindgen(js_class = NodeConfig)]
 impl WasmNodeConfig {
     /// Get the configuration with default bootnodes for provided network
@@ -375,6 +573,11 @@ impl WasmNodeConfig {
             use_persistent_memory: true,
             custom_sampling_window_secs: None,
             custom_pruning_delay_secs: None,
+            trusted_hash: None,
+            trusted_height: None,
+            trusted_header: None,
+            encryption_key: None,
+            previous_encryption_key: None,
         }
     }
 
@@ -383,17 +586,23 @@ impl WasmNodeConfig {
         let network_id = network.id();
 
         let mut builder = if self.use_persistent_memory {
-            let store = IndexedDbStore::new(network_id)
-                .await
-                .context("Failed to open the store")?;
-            let blockstore = IndexedDbBlockstore::new(&format!("{network_id}-blockstore"))
-                .await
-                .context("Failed to open the blockstore")?;
+            let (store, blockstore) = open_persistent_stores(
+                network_id,
+                self.encryption_key.as_deref(),
+                self.previous_encryption_key.as_deref(),
+            )
+            .await?;
 
             NodeBuilder::new()
                 .store(EitherStore::Right(store))
                 .blockstore(EitherBlockstore::Right(blockstore))
         } else {
+            if self.encryption_key.is_some() || self.previous_encryption_key.is_some() {
+                return Err(Error::new(
+                    "encryption key can be set only when using persistent memory",
+                ));
+            }
+
             NodeBuilder::new()
                 .store(EitherStore::Left(InMemoryStore::new()))
                 .blockstore(EitherBlockstore::Left(InMemoryBlockstore::new()))
@@ -426,10 +635,86 @@ impl WasmNodeConfig {
             builder = builder.pruning_delay(dur);
         }
 
+        if let Some(header) = self.trusted_header {
+            let header: ExtendedHeader =
+                serde_json::from_str(&header).context("invalid trusted header")?;
+            builder = builder.trusted_checkpoint(header);
+        } else if let Some(hash) = self.trusted_hash {
+            let hash: Hash = hash.parse().context("invalid trusted hash")?;
+            let height = self
+                .trusted_height
+                .context("trusted height must be set together with trusted hash")?
+                .into();
+            builder = builder.trusted_checkpoint(TrustedCheckpoint::Hash { height, hash });
+        } else if self.trusted_height.is_some() {
+            return Err(Error::new(
+                "trusted hash must be set together with trusted height",
+            ));
+        }
+
         Ok(builder)
     }
 }
 
+async fn open_persistent_stores(
+    network_id: &str,
+    encryption_key: Option<&[u8]>,
+    previous_encryption_key: Option<&[u8]>,
+) -> Result<(IndexedDbStore, PersistentBlockstore)> {
+    let blockstore = IndexedDbBlockstore::new(&format!("{network_id}-blockstore"))
+        .await
+        .context("Failed to open the blockstore")?;
+
+    let key = encryption_key
+        .map(EncryptionKey::try_from)
+        .transpose()
+        .context("invalid encryption key")?;
+    let previous_key = previous_encryption_key
+        .map(EncryptionKey::try_from)
+        .transpose()
+        .context("invalid previous encryption key")?;
+
+    match (key, previous_key) {
+        (None, None) => {
+            let store = IndexedDbStore::new(network_id)
+                .await
+                .context("Failed to open the store")?;
+
+            Ok((store, EitherBlockstore::Left(blockstore)))
+        }
+        (Some(key), None) => {
+            let store = IndexedDbStore::new_encrypted(network_id, &key)
+                .await
+                .context("Failed to open the store")?;
+            let blockstore = EncryptedBlockstore::new(blockstore, &key);
+
+            Ok((store, EitherBlockstore::Right(blockstore)))
+        }
+        (Some(key), Some(previous_key)) => {
+            let store =
+                IndexedDbStore::new_encrypted_with_previous_key(network_id, &key, &previous_key)
+                    .await
+                    .context("Failed to open the store")?;
+            let blockstore =
+                EncryptedBlockstore::new_with_previous_key(blockstore, &key, &previous_key);
+
+            // Store is re-encrypted when opened, blocks are re-encrypted separately.
+            let cids = sampled_cids(&store)
+                .await
+                .context("Failed to read sampled blocks")?;
+            blockstore
+                .reencrypt(cids)
+                .await
+                .context("Failed to re-encrypt the blockstore")?;
+
+            Ok((store, EitherBlockstore::Right(blockstore)))
+        }
+        (None, Some(_)) => Err(Error::new(
+            "previous encryption key must be set together with encryption key",
+        )),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -444,7 +729,7 @@ mod tests {
     use rexie::Rexie;
     use wasm_bindgen_futures::spawn_local;
     use wasm_bindgen_test::wasm_bindgen_test;
-    use web_sys::MessageChannel;
+    use web_sys::{MessageChannel, MessageEvent};
 
     use crate::worker::NodeWorker;
 
@@ -530,6 +815,56 @@ mod tests {
         assert_eq!(blob.namespace, namespace);
     }
 
+    #[wasm_bindgen_test]
+    async fn subscribe_blobs_from_stored_height() {
+        #[derive(Deserialize)]
+        struct BlobsMessage {
+            height: u64,
+            blobs: Vec<Blob>,
+        }
+
+        remove_database().await.expect("failed to clear db");
+        let rpc_client = Client::new(WS_URL).await.unwrap();
+        let namespace = Namespace::new_v0(&[0xCD, 0xDC, 0xCD, 0xDC, 0xCD, 0xDD]).unwrap();
+        let data = b"Hello, Subscriber";
+        let blobs = vec![Blob::new(namespace, data.to_vec(), AppVersion::V3).unwrap()];
+
+        let submitted_height = rpc_client
+            .blob_submit(&blobs, TxConfig::default())
+            .await
+            .expect("successful submission");
+
+        // Node is started after the submission, so the height is already stored
+        let bridge_ma = fetch_bridge_webtransport_multiaddr(&rpc_client).await;
+        let client = spawn_connected_node(vec![bridge_ma.to_string()]).await;
+
+        let subscription = client
+            .subscribe_blobs(&namespace, Some(submitted_height))
+            .await
+            .unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let onmessage = Closure::<dyn Fn(MessageEvent)>::new(move |ev: MessageEvent| {
+            let _ = tx.send(ev.data());
+        });
+        subscription
+            .channel()
+            .set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+
+        let msg: BlobsMessage = serde_wasm_bindgen::from_value(rx.recv().await.unwrap()).unwrap();
+        assert_eq!(msg.height, submitted_height);
+        assert_eq!(msg.blobs.len(), 1);
+        assert_eq!(msg.blobs[0].data, data);
+
+        subscription.close();
+        rpc_client
+            .p2p_close_peer(&PeerId(
+                client.local_peer_id().await.unwrap().parse().unwrap(),
+            ))
+            .await
+            .unwrap();
+    }
+
     async fn spawn_connected_node(bootnodes: Vec<String>) -> NodeClient {
         let message_channel = MessageChannel::new().unwrap();
         let mut worker = NodeWorker::new(message_channel.port1().into());
@@ -550,6 +885,11 @@ mod tests {
                 use_persistent_memory: false,
                 custom_sampling_window_secs: None,
                 custom_pruning_delay_secs: None,
+                trusted_hash: None,
+                trusted_height: None,
+                trusted_header: None,
+                encryption_key: None,
+                previous_encryption_key: None,
             })
             .await
             .unwrap();
@@ -6,17 +6,18 @@ use enum_as_inner::EnumAsInner;
 use libp2p::Multiaddr;
 use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
+use tendermint::Time;
 use tracing::error;
 use wasm_bindgen::JsError;
 
 use celestia_types::{hash::Hash, ExtendedHeader};
 use lumina_node::node::{PeerTrackerInfo, SyncingInfo};
-use lumina_node::store::SamplingMetadata;
+use lumina_node::store::{SamplingMetadata, StoreStats};
 
 use crate::client::WasmNodeConfig;
 use crate::error::Error;
 use crate::error::Result;
-use crate::wrapper::libp2p::NetworkInfoSnapshot;
+use crate::wrapper::libp2p::{NetworkInfoSnapshot, PeerInfoSnapshot};
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Serialize, Deserialize)]
@@ -35,6 +36,14 @@ pub(crate) enum NodeCommand {
         peer_id: PeerId,
         is_trusted: bool,
     },
+    Connect(Multiaddr),
+    Disconnect(PeerId),
+    SetPeerBlocked {
+        peer_id: PeerId,
+        is_blocked: bool,
+    },
+    GetBlockedPeers,
+    GetPeerInfo(PeerId),
     WaitConnected {
         trusted: bool,
     },
@@ -53,11 +62,16 @@ pub(crate) enum NodeCommand {
     GetSamplingMetadata {
         height: u64,
     },
+    GetStorageStats,
     RequestAllBlobs {
         header: ExtendedHeader,
         namespace: Namespace,
         timeout_secs: Option<f64>,
     },
+    SubscribeBlobs {
+        namespace: Namespace,
+        start_height: Option<u64>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -65,6 +79,7 @@ pub(crate) enum SingleHeaderQuery {
     Head,
     ByHash(Hash),
     ByHeight(u64),
+    ByTime(Time),
 }
 
 #[derive(Serialize, Deserialize, Debug, EnumAsInner)]
@@ -81,13 +96,20 @@ pub(crate) enum WorkerResponse {
     NetworkInfo(Result<NetworkInfoSnapshot>),
     ConnectedPeers(Result<Vec<String>>),
     SetPeerTrust(Result<()>),
+    ConnectedToPeer(Result<()>),
+    Disconnected(Result<()>),
+    SetPeerBlocked(Result<()>),
+    BlockedPeers(Result<Vec<String>>),
+    PeerInfo(Result<Option<PeerInfoSnapshot>>),
     Connected(Result<()>),
     Listeners(Result<Vec<Multiaddr>>),
     Header(Result<ExtendedHeader, Error>),
     Headers(Result<Vec<ExtendedHeader>, Error>),
     LastSeenNetworkHead(Result<Option<ExtendedHeader>, Error>),
     SamplingMetadata(Result<Option<SamplingMetadata>>),
+    StorageStats(Result<StoreStats>),
     Blobs(Result<Vec<Blob>>),
+    BlobsChannelName(Result<String>),
 }
 
 pub(crate) trait CheckableResponseExt {
@@ -136,7 +136,9 @@ from_display! {
     tendermint::error::Error,
     libp2p::identity::ParseError,
     libp2p::multiaddr::Error,
+    serde_json::Error,
     celestia_types::Error,
+    lumina_node::encryption::EncryptionError,
     lumina_node::node::NodeError,
     lumina_node::store::StoreError,
     crate::worker::WorkerError,
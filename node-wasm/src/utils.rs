@@ -5,7 +5,7 @@ use std::future::Future;
 use std::net::{IpAddr, Ipv4Addr};
 
 use gloo_timers::future::TimeoutFuture;
-use js_sys::{Math, Promise};
+use js_sys::{Math, Promise, Reflect};
 use libp2p::multiaddr::Protocol;
 use libp2p::{Multiaddr, PeerId};
 use serde::Deserialize;
@@ -19,7 +19,8 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
     DedicatedWorkerGlobalScope, MessageEvent, Request, RequestInit, RequestMode, Response,
-    ServiceWorker, ServiceWorkerGlobalScope, SharedWorker, SharedWorkerGlobalScope, Worker,
+    ServiceWorker, ServiceWorkerGlobalScope, SharedWorker, SharedWorkerGlobalScope, StorageManager,
+    Worker,
 };
 
 use lumina_node::network;
@@ -127,21 +128,28 @@ impl MessageEventExt for MessageEvent {
     }
 }
 
-/// Request persistent storage from user for us, which has side effect of increasing the quota we
-/// have. This function doesn't `await` on JavaScript promise, as that would block until user
-/// either allows or blocks our request in a prompt (and we cannot do much with the result anyway).
-pub(crate) async fn request_storage_persistence() -> Result<(), Error> {
-    let storage_manager = if let Some(window) = web_sys::window() {
-        window.navigator().storage()
+/// Returns `navigator.storage` of the current global scope, `None` if it has no access to it.
+fn storage_manager() -> Result<Option<StorageManager>, Error> {
+    if let Some(window) = web_sys::window() {
+        Ok(Some(window.navigator().storage()))
     } else if Worker::is_worker_type() {
-        Worker::worker_self().navigator().storage()
+        Ok(Some(Worker::worker_self().navigator().storage()))
     } else if SharedWorker::is_worker_type() {
-        SharedWorker::worker_self().navigator().storage()
+        Ok(Some(SharedWorker::worker_self().navigator().storage()))
     } else if ServiceWorker::is_worker_type() {
         warn!("ServiceWorker doesn't have access to StorageManager");
-        return Ok(());
+        Ok(None)
     } else {
-        return Err(Error::new("`navigator.storage` not found in global scope"));
+        Err(Error::new("`navigator.storage` not found in global scope"))
+    }
+}
+
+/// Request persistent storage from user for us, which has side effect of increasing the quota we
+/// have. This function doesn't `await` on JavaScript promise, as that would block until user
+/// either allows or blocks our request in a prompt (and we cannot do much with the result anyway).
+pub(crate) async fn request_storage_persistence() -> Result<(), Error> {
+    let Some(storage_manager) = storage_manager()? else {
+        return Ok(());
     };
 
     let fullfiled = Closure::once(move |granted: JsValue| {
@@ -165,6 +173,24 @@ pub(crate) async fn request_storage_persistence() -> Result<(), Error> {
     Ok(())
 }
 
+/// Usage and quota of the origin's storage in bytes, as estimated by `navigator.storage.estimate()`.
+///
+/// Estimates include all the storage of the origin, not only the IndexedDB of the node.
+pub(crate) async fn storage_estimate() -> Result<(Option<f64>, Option<f64>), Error> {
+    let Some(storage_manager) = storage_manager()? else {
+        return Ok((None, None));
+    };
+
+    let estimate = JsFuture::from(storage_manager.estimate()?).await?;
+    let get = |key: &str| {
+        Reflect::get(&estimate, &key.into())
+            .ok()
+            .and_then(|value| value.as_f64())
+    };
+
+    Ok((get("usage"), get("quota")))
+}
+
 const CHROME_USER_AGENT_DETECTION_STR: &str = "Chrome/";
 const FIREFOX_USER_AGENT_DETECTION_STR: &str = "Firefox/";
 const SAFARI_USER_AGENT_DETECTION_STR: &str = "Safari/";
@@ -4,31 +4,45 @@ use std::time::Duration;
 use blockstore::EitherBlockstore;
 use celestia_types::nmt::Namespace;
 use celestia_types::Blob;
+use futures::{select, FutureExt, StreamExt};
 use libp2p::{Multiaddr, PeerId};
 use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen::to_value;
 use thiserror::Error;
 use tokio::sync::mpsc;
 use tracing::{error, info, warn};
+use wasm_bindgen::closure::Closure;
 use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::spawn_local;
-use web_sys::BroadcastChannel;
+use web_sys::{BroadcastChannel, MessageEvent};
 
 use celestia_types::ExtendedHeader;
-use lumina_node::blockstore::{InMemoryBlockstore, IndexedDbBlockstore};
+use lumina_node::blockstore::{EncryptedBlockstore, InMemoryBlockstore, IndexedDbBlockstore};
 use lumina_node::events::{EventSubscriber, NodeEventInfo};
-use lumina_node::node::{Node, SyncingInfo};
-use lumina_node::store::{EitherStore, InMemoryStore, IndexedDbStore, SamplingMetadata};
+use lumina_node::node::{BlobStream, Node, SyncingInfo};
+use lumina_node::store::{
+    EitherStore, InMemoryStore, IndexedDbStore, SamplingMetadata, StoreStats,
+};
 
 use crate::client::WasmNodeConfig;
 use crate::commands::{NodeCommand, SingleHeaderQuery, WorkerResponse};
 use crate::error::{Context, Error, Result};
 use crate::ports::{ClientMessage, WorkerServer};
 use crate::utils::random_id;
-use crate::wrapper::libp2p::NetworkInfoSnapshot;
+use crate::wrapper::libp2p::{NetworkInfoSnapshot, PeerInfoSnapshot};
 
-pub(crate) type WasmBlockstore = EitherBlockstore<InMemoryBlockstore, IndexedDbBlockstore>;
+/// Message which ends the blob subscription when posted on its [`BroadcastChannel`].
+pub(crate) const BLOBS_CHANNEL_CLOSE_MESSAGE: &str = "close";
+/// Message posted by the subscriber once it listens on the [`BroadcastChannel`].
+///
+/// [`BroadcastChannel`] doesn't buffer messages, so blobs are forwarded only after it.
+pub(crate) const BLOBS_CHANNEL_READY_MESSAGE: &str = "ready";
+
+pub(crate) type WasmBlockstore = EitherBlockstore<InMemoryBlockstore, PersistentBlockstore>;
 pub(crate) type WasmStore = EitherStore<InMemoryStore, IndexedDbStore>;
+pub(crate) type PersistentBlockstore =
+    EitherBlockstore<IndexedDbBlockstore, EncryptedBlockstore<IndexedDbBlockstore>>;
 
 #[derive(Debug, Serialize, Deserialize, Error)]
 pub enum WorkerError {
@@ -159,6 +173,36 @@ impl NodeWorkerInstance {
         Ok(self.node.set_peer_trust(peer_id, is_trusted).await?)
     }
 
+    async fn connect(&mut self, addr: Multiaddr) -> Result<()> {
+        Ok(self.node.connect(addr).await?)
+    }
+
+    async fn disconnect(&mut self, peer_id: PeerId) -> Result<()> {
+        Ok(self.node.disconnect(peer_id).await?)
+    }
+
+    async fn set_peer_blocked(&mut self, peer_id: PeerId, is_blocked: bool) -> Result<()> {
+        if is_blocked {
+            Ok(self.node.block_peer(peer_id).await?)
+        } else {
+            Ok(self.node.unblock_peer(peer_id).await?)
+        }
+    }
+
+    async fn get_blocked_peers(&mut self) -> Result<Vec<String>> {
+        Ok(self
+            .node
+            .list_blocked_peers()
+            .await?
+            .iter()
+            .map(|id| id.to_string())
+            .collect())
+    }
+
+    async fn get_peer_info(&mut self, peer_id: PeerId) -> Result<Option<PeerInfoSnapshot>> {
+        Ok(self.node.peer_info(peer_id).await?.map(Into::into))
+    }
+
     async fn get_connected_peers(&mut self) -> Result<Vec<String>> {
         Ok(self
             .node
@@ -195,6 +239,7 @@ impl NodeWorkerInstance {
             SingleHeaderQuery::Head => self.node.get_local_head_header().await,
             SingleHeaderQuery::ByHash(hash) => self.node.get_header_by_hash(&hash).await,
             SingleHeaderQuery::ByHeight(height) => self.node.get_header_by_height(height).await,
+            SingleHeaderQuery::ByTime(time) => self.node.get_header_by_time(time).await,
         }?)
     }
 
@@ -227,6 +272,10 @@ impl NodeWorkerInstance {
         Ok(self.node.get_sampling_metadata(height).await?)
     }
 
+    async fn get_storage_stats(&mut self) -> Result<StoreStats> {
+        Ok(self.node.storage_stats().await?)
+    }
+
     async fn request_all_blobs(
         &mut self,
         header: ExtendedHeader,
@@ -240,6 +289,21 @@ impl NodeWorkerInstance {
             .await?)
     }
 
+    async fn subscribe_blobs(
+        &mut self,
+        namespace: Namespace,
+        start_height: Option<u64>,
+    ) -> Result<String> {
+        let name = format!("BlobsChannel-{}", random_id());
+        let stream = self.node.subscribe_blobs(namespace, start_height).await?;
+        let channel =
+            BroadcastChannel::new(&name).context("Failed to allocate BroadcastChannel")?;
+
+        spawn_local(blob_forwarder_task(stream, channel));
+
+        Ok(name)
+    }
+
     async fn process_command(&mut self, command: NodeCommand) -> WorkerResponse {
         match command {
             NodeCommand::IsRunning => WorkerResponse::IsRunning(true),
@@ -268,6 +332,20 @@ impl NodeWorkerInstance {
                 peer_id,
                 is_trusted,
             } => WorkerResponse::SetPeerTrust(self.set_peer_trust(peer_id, is_trusted).await),
+            NodeCommand::Connect(addr) => WorkerResponse::ConnectedToPeer(self.connect(addr).await),
+            NodeCommand::Disconnect(peer_id) => {
+                WorkerResponse::Disconnected(self.disconnect(peer_id).await)
+            }
+            NodeCommand::SetPeerBlocked {
+                peer_id,
+                is_blocked,
+            } => WorkerResponse::SetPeerBlocked(self.set_peer_blocked(peer_id, is_blocked).await),
+            NodeCommand::GetBlockedPeers => {
+                WorkerResponse::BlockedPeers(self.get_blocked_peers().await)
+            }
+            NodeCommand::GetPeerInfo(peer_id) => {
+                WorkerResponse::PeerInfo(self.get_peer_info(peer_id).await)
+            }
             NodeCommand::WaitConnected { trusted } => {
                 WorkerResponse::Connected(self.wait_connected(trusted).await)
             }
@@ -289,6 +367,9 @@ impl NodeWorkerInstance {
             NodeCommand::GetSamplingMetadata { height } => {
                 WorkerResponse::SamplingMetadata(self.get_sampling_metadata(height).await)
             }
+            NodeCommand::GetStorageStats => {
+                WorkerResponse::StorageStats(self.get_storage_stats().await)
+            }
             NodeCommand::RequestAllBlobs {
                 header,
                 namespace,
@@ -297,6 +378,12 @@ impl NodeWorkerInstance {
                 self.request_all_blobs(header, namespace, timeout_secs)
                     .await,
             ),
+            NodeCommand::SubscribeBlobs {
+                namespace,
+                start_height,
+            } => WorkerResponse::BlobsChannelName(
+                self.subscribe_blobs(namespace, start_height).await,
+            ),
             NodeCommand::InternalPing => WorkerResponse::InternalPong,
         }
     }
@@ -325,3 +412,70 @@ async fn event_forwarder_task(mut events_sub: EventSubscriber, events_channel: B
         }
     }
 }
+
+async fn blob_forwarder_task(mut stream: BlobStream, blobs_channel: BroadcastChannel) {
+    // Subscriber posts a ready message on the channel once it listens on it,
+    // and a close message once it's not interested anymore
+    let (ready_tx, mut ready_rx) = mpsc::unbounded_channel();
+    let (close_tx, mut close_rx) = mpsc::unbounded_channel();
+    let onmessage = Closure::<dyn Fn(MessageEvent)>::new(move |ev: MessageEvent| {
+        match ev.data().as_string().as_deref() {
+            Some(BLOBS_CHANNEL_READY_MESSAGE) => {
+                let _ = ready_tx.send(());
+            }
+            Some(BLOBS_CHANNEL_CLOSE_MESSAGE) => {
+                let _ = close_tx.send(());
+            }
+            _ => {}
+        }
+    });
+    blobs_channel.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+
+    let ready = select! {
+        _ = ready_rx.recv().fuse() => true,
+        _ = close_rx.recv().fuse() => false,
+    };
+
+    if ready {
+        forward_blobs(&mut stream, &blobs_channel, &mut close_rx).await;
+    }
+
+    blobs_channel.set_onmessage(None);
+    blobs_channel.close();
+}
+
+async fn forward_blobs(
+    stream: &mut BlobStream,
+    blobs_channel: &BroadcastChannel,
+    close_rx: &mut mpsc::UnboundedReceiver<()>,
+) {
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum BlobsMessage {
+        Blobs { height: u64, blobs: Vec<Blob> },
+        Error { error: String },
+    }
+
+    loop {
+        let res = select! {
+            _ = close_rx.recv().fuse() => break,
+            res = stream.next().fuse() => match res {
+                Some(res) => res,
+                None => break,
+            },
+        };
+
+        let msg = match res {
+            Ok((height, blobs)) => BlobsMessage::Blobs { height, blobs },
+            Err(e) => BlobsMessage::Error {
+                error: e.to_string(),
+            },
+        };
+
+        if let Ok(val) = to_value(&msg) {
+            if blobs_channel.post_message(&val).is_err() {
+                break;
+            }
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use libp2p::swarm::{
     ConnectionCounters as SwarmConnectionCounters, NetworkInfo as SwarmNetworkInfo,
 };
+use lumina_node::node::PeerInfo;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
@@ -54,3 +55,47 @@ impl From<&SwarmConnectionCounters> for ConnectionCountersSnapshot {
         }
     }
 }
+
+/// Information about a peer.
+#[wasm_bindgen(inspectable)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerInfoSnapshot {
+    /// Id of the peer.
+    #[wasm_bindgen(getter_with_clone)]
+    pub peer_id: String,
+    /// Known addresses of the peer.
+    #[wasm_bindgen(getter_with_clone)]
+    pub addrs: Vec<String>,
+    /// Protocols supported by the peer, as reported by identify.
+    #[wasm_bindgen(getter_with_clone)]
+    pub protocols: Vec<String>,
+    /// Agent version of the peer, as reported by identify.
+    #[wasm_bindgen(getter_with_clone)]
+    pub agent_version: Option<String>,
+    /// Whether the peer is currently connected.
+    pub connected: bool,
+    /// Whether the peer is trusted.
+    pub trusted: bool,
+    /// Whether the peer is currently banned for misbehaving.
+    pub banned: bool,
+    /// Whether the peer is blocked.
+    pub blocked: bool,
+    /// Current score of the peer, decaying towards zero over time.
+    pub score: f64,
+}
+
+impl From<PeerInfo> for PeerInfoSnapshot {
+    fn from(info: PeerInfo) -> Self {
+        Self {
+            peer_id: info.peer_id.to_string(),
+            addrs: info.addrs.iter().map(|addr| addr.to_string()).collect(),
+            protocols: info.protocols,
+            agent_version: info.agent_version,
+            connected: info.connected,
+            trusted: info.trusted,
+            banned: info.banned,
+            blocked: info.blocked,
+            score: info.score,
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use lumina_node::node::{PeerTrackerInfo, SyncingInfo};
+use lumina_node::store::{StoreStats, TableStats};
 use wasm_bindgen::prelude::*;
 
 /// Statistics of the connected peers
@@ -9,6 +10,10 @@ pub struct PeerTrackerInfoSnapshot {
     pub num_connected_peers: u64,
     /// Number of the connected trusted peers.
     pub num_connected_trusted_peers: u64,
+    /// Number of the currently banned peers.
+    pub num_banned_peers: u64,
+    /// Average score of the connected peers.
+    pub average_peer_score: f64,
 }
 
 /// A range of blocks between `start` and `end` height, inclusive
@@ -32,11 +37,62 @@ pub struct SyncingInfoSnapshot {
     pub subjective_head: u64,
 }
 
+/// Storage usage of a single table of the store.
+#[wasm_bindgen(inspectable)]
+#[derive(Debug, Clone)]
+pub struct TableStatsSnapshot {
+    /// Name of the table.
+    #[wasm_bindgen(getter_with_clone)]
+    pub name: String,
+    /// Number of entries in the table.
+    pub entries: u64,
+}
+
+/// Storage usage of the node.
+#[wasm_bindgen(inspectable)]
+#[derive(Debug)]
+pub struct StorageStatsSnapshot {
+    /// Number of stored headers.
+    pub headers: u64,
+    /// Number of CIDs of the blocks kept in the blockstore.
+    pub cids: u64,
+    /// Object stores of the IndexedDB.
+    #[wasm_bindgen(getter_with_clone)]
+    pub tables: Vec<TableStatsSnapshot>,
+    /// Bytes used by the origin, as estimated by the browser.
+    pub usage: Option<f64>,
+    /// Bytes available to the origin, as estimated by the browser.
+    pub quota: Option<f64>,
+}
+
+impl StorageStatsSnapshot {
+    pub(crate) fn new(stats: StoreStats, usage: Option<f64>, quota: Option<f64>) -> Self {
+        Self {
+            headers: stats.headers,
+            cids: stats.cids,
+            tables: stats.tables.into_iter().map(Into::into).collect(),
+            usage,
+            quota,
+        }
+    }
+}
+
+impl From<TableStats> for TableStatsSnapshot {
+    fn from(value: TableStats) -> Self {
+        Self {
+            name: value.name,
+            entries: value.entries,
+        }
+    }
+}
+
 impl From<PeerTrackerInfo> for PeerTrackerInfoSnapshot {
     fn from(value: PeerTrackerInfo) -> Self {
         Self {
             num_connected_peers: value.num_connected_peers,
             num_connected_trusted_peers: value.num_connected_trusted_peers,
+            num_banned_peers: value.num_banned_peers,
+            average_peer_score: value.average_peer_score,
         }
     }
 }
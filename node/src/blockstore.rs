@@ -2,6 +2,14 @@
 
 use crate::p2p::MAX_MH_SIZE;
 
+pub use self::encrypted_blockstore::EncryptedBlockstore;
+#[cfg(all(feature = "sqlite", not(target_arch = "wasm32")))]
+pub use self::sqlite_blockstore::SqliteBlockstore;
+
+mod encrypted_blockstore;
+#[cfg(all(feature = "sqlite", not(target_arch = "wasm32")))]
+mod sqlite_blockstore;
+
 /// An [`InMemoryBlockstore`] with maximum multihash size used by lumina.
 ///
 /// [`InMemoryBlockstore`]: blockstore::InMemoryBlockstore
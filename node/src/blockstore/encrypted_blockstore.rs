@@ -0,0 +1,325 @@
+use std::sync::{Arc, PoisonError, RwLock};
+
+use blockstore::cond_send::CondSync;
+use blockstore::{Blockstore, Result};
+use cid::CidGeneric;
+use tracing::debug;
+
+use crate::encryption::{Cipher, EncryptionError, EncryptionKey};
+
+const BLOCKS_DOMAIN: &str = "lumina.blockstore.blocks";
+
+/// A [`Blockstore`] wrapper keeping the blocks encrypted at rest.
+///
+/// Blocks are encrypted with the provided [`EncryptionKey`], while their CIDs are kept
+/// in plain. Blocks which can't be decrypted are treated as missing and replaced when
+/// they are put again, since they can be retrieved from the network.
+///
+/// Blocks stored before the blockstore was encrypted are not encrypted, so encryption
+/// should be enabled only on an empty blockstore, e.g. together with the store, which
+/// refuses to be encrypted if it isn't empty.
+///
+/// After the key is changed, blocks encrypted with the previous keys should be
+/// re-encrypted with [`EncryptedBlockstore::reencrypt`], so that they are no longer
+/// readable with the retired keys.
+#[derive(Debug)]
+pub struct EncryptedBlockstore<B> {
+    blockstore: B,
+    cipher: RwLock<Arc<Cipher>>,
+}
+
+impl<B> EncryptedBlockstore<B>
+where
+    B: Blockstore,
+{
+    /// Create a new `EncryptedBlockstore` encrypting the blocks of the `blockstore`.
+    pub fn new(blockstore: B, key: &EncryptionKey) -> Self {
+        EncryptedBlockstore {
+            blockstore,
+            cipher: RwLock::new(Arc::new(Cipher::new(key))),
+        }
+    }
+
+    /// Create a new `EncryptedBlockstore`, which can still read blocks encrypted with
+    /// the `previous_key`.
+    pub fn new_with_previous_key(
+        blockstore: B,
+        key: &EncryptionKey,
+        previous_key: &EncryptionKey,
+    ) -> Self {
+        EncryptedBlockstore {
+            blockstore,
+            cipher: RwLock::new(Arc::new(Cipher::new(key).with_previous_key(previous_key))),
+        }
+    }
+
+    /// Encrypt new blocks with the `key`.
+    ///
+    /// Blocks encrypted with the current or any previous key can still be read, until
+    /// they are re-encrypted with [`EncryptedBlockstore::reencrypt`].
+    pub fn rotate_key(&self, key: &EncryptionKey) {
+        let mut cipher = self.cipher.write().unwrap_or_else(PoisonError::into_inner);
+        *cipher = Arc::new(cipher.rotate(key));
+    }
+
+    /// Re-encrypt the blocks with the `cids` with the current key and retire the previous keys.
+    ///
+    /// Blocks which can't be decrypted are removed. Blocks missing from `cids` which are
+    /// still encrypted with a previous key can't be read afterwards and are treated as
+    /// missing. CIDs of the blocks kept for the stored headers are returned by
+    /// [`sampled_cids`].
+    ///
+    /// [`sampled_cids`]: crate::store::sampled_cids
+    pub async fn reencrypt<I, const S: usize>(&self, cids: I) -> Result<()>
+    where
+        I: IntoIterator<Item = CidGeneric<S>>,
+    {
+        let cipher = self.cipher();
+        let current = cipher.current();
+
+        for cid in cids {
+            let Some(data) = self.blockstore.get(&cid).await? else {
+                continue;
+            };
+            let cid_bytes = cid.to_bytes();
+
+            match cipher.decrypt(BLOCKS_DOMAIN, &cid_bytes, &data) {
+                // Already encrypted with the current key
+                Ok((_, 0)) => {}
+                Ok((data, _)) => {
+                    let encrypted = current.encrypt(BLOCKS_DOMAIN, &cid_bytes, &data);
+                    self.blockstore.remove(&cid).await?;
+                    self.blockstore.put_keyed(&cid, &encrypted).await?;
+                }
+                // Block could be put with a key rotated meanwhile
+                Err(_)
+                    if self
+                        .get_decrypted(&cid)
+                        .await?
+                        .is_some_and(|res| res.is_ok()) => {}
+                Err(e) => {
+                    debug!("Removing block {cid} which can't be decrypted: {e}");
+                    self.blockstore.remove(&cid).await?;
+                }
+            }
+        }
+
+        let mut cipher_guard = self.cipher.write().unwrap_or_else(PoisonError::into_inner);
+
+        // If the key was rotated meanwhile, blocks were re-encrypted with what is now one
+        // of the previous keys, which must be kept.
+        if Arc::ptr_eq(&cipher_guard, &cipher) {
+            *cipher_guard = Arc::new(current);
+        } else {
+            debug!("Key rotated during re-encryption, previous keys are kept");
+        }
+
+        Ok(())
+    }
+
+    /// Returns the underlying blockstore, holding the encrypted blocks.
+    pub fn inner(&self) -> &B {
+        &self.blockstore
+    }
+
+    fn cipher(&self) -> Arc<Cipher> {
+        self.cipher
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    async fn get_decrypted<const S: usize>(
+        &self,
+        cid: &CidGeneric<S>,
+    ) -> Result<Option<Result<Vec<u8>, EncryptionError>>> {
+        let Some(data) = self.blockstore.get(cid).await? else {
+            return Ok(None);
+        };
+
+        let decrypted = self
+            .cipher()
+            .decrypt(BLOCKS_DOMAIN, &cid.to_bytes(), &data)
+            .map(|(data, _)| data);
+
+        if let Err(ref e) = decrypted {
+            debug!("Block {cid} can't be decrypted: {e}");
+        }
+
+        Ok(Some(decrypted))
+    }
+}
+
+impl<B> Blockstore for EncryptedBlockstore<B>
+where
+    B: Blockstore,
+    Self: CondSync,
+{
+    async fn get<const S: usize>(&self, cid: &CidGeneric<S>) -> Result<Option<Vec<u8>>> {
+        Ok(self.get_decrypted(cid).await?.and_then(Result::ok))
+    }
+
+    async fn put_keyed<const S: usize>(&self, cid: &CidGeneric<S>, data: &[u8]) -> Result<()> {
+        match self.get_decrypted(cid).await? {
+            // Existing blocks are not overwritten
+            Some(Ok(_)) => return Ok(()),
+            Some(Err(_)) => self.blockstore.remove(cid).await?,
+            None => {}
+        }
+
+        let encrypted = self.cipher().encrypt(BLOCKS_DOMAIN, &cid.to_bytes(), data);
+        self.blockstore.put_keyed(cid, &encrypted).await
+    }
+
+    async fn remove<const S: usize>(&self, cid: &CidGeneric<S>) -> Result<()> {
+        self.blockstore.remove(cid).await
+    }
+
+    async fn close(self) -> Result<()> {
+        self.blockstore.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockstore::InMemoryBlockstore;
+    use crate::p2p::shwap::sample_cid;
+    use crate::test_utils::async_test;
+
+    #[async_test]
+    async fn put_get_remove() {
+        let key = EncryptionKey::generate();
+        let blockstore = EncryptedBlockstore::new(InMemoryBlockstore::new(), &key);
+        let cid = sample_cid(0, 0, 1).unwrap();
+
+        assert!(!blockstore.has(&cid).await.unwrap());
+
+        blockstore.put_keyed(&cid, b"data").await.unwrap();
+        // Existing blocks are not overwritten
+        blockstore.put_keyed(&cid, b"other").await.unwrap();
+
+        assert!(blockstore.has(&cid).await.unwrap());
+        assert_eq!(blockstore.get(&cid).await.unwrap(), Some(b"data".to_vec()));
+
+        let stored = blockstore.inner().get(&cid).await.unwrap().unwrap();
+        assert!(!stored.windows(4).any(|w| w == b"data"));
+
+        blockstore.remove(&cid).await.unwrap();
+        assert_eq!(blockstore.get(&cid).await.unwrap(), None);
+    }
+
+    #[async_test]
+    async fn key_rotation() {
+        let old_key = EncryptionKey::generate();
+        let new_key = EncryptionKey::generate();
+        let blockstore = EncryptedBlockstore::new(InMemoryBlockstore::new(), &old_key);
+        let old_cid = sample_cid(0, 0, 1).unwrap();
+        let new_cid = sample_cid(0, 0, 2).unwrap();
+
+        blockstore.put_keyed(&old_cid, b"old").await.unwrap();
+        blockstore.rotate_key(&new_key);
+        blockstore.put_keyed(&new_cid, b"new").await.unwrap();
+
+        assert_eq!(
+            blockstore.get(&old_cid).await.unwrap(),
+            Some(b"old".to_vec())
+        );
+        assert_eq!(
+            blockstore.get(&new_cid).await.unwrap(),
+            Some(b"new".to_vec())
+        );
+
+        // blocks encrypted with an unknown key are treated as missing and replaced
+        let blockstore = EncryptedBlockstore::new(blockstore.blockstore, &new_key);
+        assert_eq!(blockstore.get(&old_cid).await.unwrap(), None);
+        assert_eq!(
+            blockstore.get(&new_cid).await.unwrap(),
+            Some(b"new".to_vec())
+        );
+
+        blockstore.put_keyed(&old_cid, b"old").await.unwrap();
+        assert_eq!(
+            blockstore.get(&old_cid).await.unwrap(),
+            Some(b"old".to_vec())
+        );
+
+        // previous key can be provided when opening the blockstore
+        let blockstore =
+            EncryptedBlockstore::new_with_previous_key(blockstore.blockstore, &old_key, &new_key);
+        assert_eq!(
+            blockstore.get(&new_cid).await.unwrap(),
+            Some(b"new".to_vec())
+        );
+    }
+
+    #[async_test]
+    async fn rotate_twice_and_reencrypt() {
+        let key1 = EncryptionKey::generate();
+        let key2 = EncryptionKey::generate();
+        let key3 = EncryptionKey::generate();
+        let blockstore = EncryptedBlockstore::new(InMemoryBlockstore::new(), &key1);
+        let cid1 = sample_cid(0, 0, 1).unwrap();
+        let cid2 = sample_cid(0, 0, 2).unwrap();
+        let cid3 = sample_cid(0, 0, 3).unwrap();
+
+        blockstore.put_keyed(&cid1, b"data1").await.unwrap();
+        blockstore.rotate_key(&key2);
+        blockstore.put_keyed(&cid2, b"data2").await.unwrap();
+        blockstore.rotate_key(&key3);
+        blockstore.put_keyed(&cid3, b"data3").await.unwrap();
+
+        // blocks of all the previous keys are readable
+        for (cid, data) in [(cid1, b"data1"), (cid2, b"data2"), (cid3, b"data3")] {
+            assert_eq!(blockstore.get(&cid).await.unwrap(), Some(data.to_vec()));
+        }
+
+        blockstore.reencrypt([cid1, cid2, cid3]).await.unwrap();
+
+        // blocks are readable with only the current key
+        let blockstore = EncryptedBlockstore::new(blockstore.blockstore, &key3);
+        for (cid, data) in [(cid1, b"data1"), (cid2, b"data2"), (cid3, b"data3")] {
+            assert_eq!(blockstore.get(&cid).await.unwrap(), Some(data.to_vec()));
+        }
+
+        // and no longer with the retired keys
+        let blockstore = EncryptedBlockstore::new(blockstore.blockstore, &key1);
+        assert_eq!(blockstore.get(&cid1).await.unwrap(), None);
+    }
+
+    #[async_test]
+    async fn reencrypt_retires_previous_keys() {
+        let old_key = EncryptionKey::generate();
+        let new_key = EncryptionKey::generate();
+        let blockstore = EncryptedBlockstore::new(InMemoryBlockstore::new(), &old_key);
+        let listed = sample_cid(0, 0, 1).unwrap();
+        let unlisted = sample_cid(0, 0, 2).unwrap();
+        let corrupted = sample_cid(0, 0, 3).unwrap();
+
+        blockstore.put_keyed(&listed, b"listed").await.unwrap();
+        blockstore.put_keyed(&unlisted, b"unlisted").await.unwrap();
+        blockstore
+            .inner()
+            .put_keyed(&corrupted, b"corrupted")
+            .await
+            .unwrap();
+
+        let blockstore =
+            EncryptedBlockstore::new_with_previous_key(blockstore.blockstore, &new_key, &old_key);
+        blockstore
+            .reencrypt(
+                [listed, unlisted, corrupted]
+                    .into_iter()
+                    .filter(|cid| *cid != unlisted),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            blockstore.get(&listed).await.unwrap(),
+            Some(b"listed".to_vec())
+        );
+        assert_eq!(blockstore.get(&unlisted).await.unwrap(), None);
+        assert!(!blockstore.inner().has(&corrupted).await.unwrap());
+    }
+}
@@ -0,0 +1,255 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use blockstore::{Blockstore, Error, Result};
+use cid::CidGeneric;
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use tokio::task::spawn_blocking;
+
+use crate::utils::Counter;
+
+const CREATE_BLOCKS_TABLE: &str = "CREATE TABLE IF NOT EXISTS blockstore_blocks (
+    cid BLOB PRIMARY KEY,
+    data BLOB NOT NULL
+)";
+
+/// A [`Blockstore`] implementation backed by a [`SQLite`] database.
+///
+/// [`SQLite`]: https://www.sqlite.org
+#[derive(Debug)]
+pub struct SqliteBlockstore {
+    conn: Arc<Mutex<Connection>>,
+    task_counter: Counter,
+}
+
+impl SqliteBlockstore {
+    /// Open a persistent [`SQLite`] blockstore.
+    ///
+    /// [`SQLite`]: https://www.sqlite.org
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+
+        let conn = spawn_blocking(|| Connection::open(path))
+            .await?
+            .map_err(db_error)?;
+
+        SqliteBlockstore::new(Arc::new(Mutex::new(conn))).await
+    }
+
+    /// Open an in memory [`SQLite`] blockstore.
+    ///
+    /// [`SQLite`]: https://www.sqlite.org
+    pub async fn in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory().map_err(db_error)?;
+
+        SqliteBlockstore::new(Arc::new(Mutex::new(conn))).await
+    }
+
+    /// Create a new `SqliteBlockstore` with an already opened [`rusqlite::Connection`].
+    ///
+    /// Connection can be shared with the [`SqliteStore`].
+    ///
+    /// [`SqliteStore`]: crate::store::SqliteStore
+    pub async fn new(conn: Arc<Mutex<Connection>>) -> Result<Self> {
+        let blockstore = SqliteBlockstore {
+            conn,
+            task_counter: Counter::new(),
+        };
+
+        blockstore
+            .write_tx(|tx| {
+                tx.execute(CREATE_BLOCKS_TABLE, []).map_err(db_error)?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(blockstore)
+    }
+
+    /// Returns the raw [`rusqlite::Connection`].
+    pub fn raw_db(&self) -> Arc<Mutex<Connection>> {
+        self.conn.clone()
+    }
+
+    /// Execute a read transaction.
+    async fn read_tx<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Transaction) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.conn.clone();
+        let guard = self.task_counter.guard();
+
+        spawn_blocking(move || {
+            let _guard = guard;
+
+            {
+                let mut conn = lock_conn(&conn)?;
+                let tx = conn
+                    .transaction_with_behavior(TransactionBehavior::Deferred)
+                    .map_err(db_error)?;
+                f(&tx)
+            }
+        })
+        .await?
+    }
+
+    /// Execute a write transaction.
+    ///
+    /// If closure returns an error the store state is not changed, otherwise transaction is commited.
+    async fn write_tx<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Transaction) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.conn.clone();
+        let guard = self.task_counter.guard();
+
+        spawn_blocking(move || {
+            let _guard = guard;
+
+            {
+                let mut conn = lock_conn(&conn)?;
+                let tx = conn
+                    .transaction_with_behavior(TransactionBehavior::Immediate)
+                    .map_err(db_error)?;
+                let res = f(&tx);
+
+                if res.is_ok() {
+                    tx.commit().map_err(db_error)?;
+                } else {
+                    tx.rollback().map_err(db_error)?;
+                }
+
+                res
+            }
+        })
+        .await?
+    }
+}
+
+impl Blockstore for SqliteBlockstore {
+    async fn get<const S: usize>(&self, cid: &CidGeneric<S>) -> Result<Option<Vec<u8>>> {
+        let cid = cid.to_bytes();
+
+        self.read_tx(move |tx| {
+            tx.query_row(
+                "SELECT data FROM blockstore_blocks WHERE cid = ?1",
+                params![cid],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_error)
+        })
+        .await
+    }
+
+    async fn put_keyed<const S: usize>(&self, cid: &CidGeneric<S>, data: &[u8]) -> Result<()> {
+        let cid = cid.to_bytes();
+        let data = data.to_vec();
+
+        self.write_tx(move |tx| {
+            tx.execute(
+                "INSERT OR IGNORE INTO blockstore_blocks (cid, data) VALUES (?1, ?2)",
+                params![cid, data],
+            )
+            .map_err(db_error)?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove<const S: usize>(&self, cid: &CidGeneric<S>) -> Result<()> {
+        let cid = cid.to_bytes();
+
+        self.write_tx(move |tx| {
+            tx.execute("DELETE FROM blockstore_blocks WHERE cid = ?1", params![cid])
+                .map_err(db_error)?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn has<const S: usize>(&self, cid: &CidGeneric<S>) -> Result<bool> {
+        let cid = cid.to_bytes();
+
+        self.read_tx(move |tx| {
+            let found = tx
+                .query_row(
+                    "SELECT 1 FROM blockstore_blocks WHERE cid = ?1",
+                    params![cid],
+                    |_| Ok(()),
+                )
+                .optional()
+                .map_err(db_error)?;
+
+            Ok(found.is_some())
+        })
+        .await
+    }
+
+    async fn close(mut self) -> Result<()> {
+        // Wait all ongoing `spawn_blocking` tasks to finish.
+        self.task_counter.wait_guards().await;
+        Ok(())
+    }
+}
+
+fn lock_conn(conn: &Mutex<Connection>) -> Result<MutexGuard<'_, Connection>> {
+    conn.lock()
+        .map_err(|_| Error::FatalDatabaseError("SQLite connection lock poisoned".into()))
+}
+
+fn db_error(e: rusqlite::Error) -> Error {
+    match e {
+        rusqlite::Error::SqliteFailure(err, _) if err.code == rusqlite::ErrorCode::TooBig => {
+            Error::ValueTooLarge
+        }
+        e => Error::FatalDatabaseError(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::p2p::shwap::sample_cid;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn put_get_remove() {
+        let blockstore = SqliteBlockstore::in_memory().await.unwrap();
+        let cid = sample_cid(0, 0, 1).unwrap();
+
+        assert!(!blockstore.has(&cid).await.unwrap());
+
+        blockstore.put_keyed(&cid, b"data").await.unwrap();
+        // Existing blocks are not overwritten
+        blockstore.put_keyed(&cid, b"other").await.unwrap();
+
+        assert!(blockstore.has(&cid).await.unwrap());
+        assert_eq!(blockstore.get(&cid).await.unwrap(), Some(b"data".to_vec()));
+
+        blockstore.remove(&cid).await.unwrap();
+        assert_eq!(blockstore.get(&cid).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn shares_connection_with_store() {
+        use crate::store::{SqliteStore, Store};
+
+        let db_dir = TempDir::with_prefix("lumina.blockstore.test").unwrap();
+        let db = db_dir.path().join("db.sqlite");
+        let cid = sample_cid(0, 0, 1).unwrap();
+
+        let store = SqliteStore::open(&db).await.unwrap();
+        let blockstore = SqliteBlockstore::new(store.raw_db()).await.unwrap();
+        blockstore.put_keyed(&cid, b"data").await.unwrap();
+        blockstore.close().await.unwrap();
+        store.close().await.unwrap();
+
+        let blockstore = SqliteBlockstore::open(&db).await.unwrap();
+        assert_eq!(blockstore.get(&cid).await.unwrap(), Some(b"data".to_vec()));
+    }
+}
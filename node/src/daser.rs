@@ -8,7 +8,8 @@
 //!     - If a block has not been sampled or it was rejected, Daser will queue it for sampling.
 //!       Rejected blocks are resampled because their rejection could be caused by
 //!       edge-cases unrelated to data availability, such as network issues.
-//!     - If a block is not within the sampling window, it is not queued.
+//!     - If a block is not within the sampling window, it is not queued, unless historical
+//!       sampling is enabled in archival mode.
 //!     - Queue is always sorted in descending order to give priority to latest blocks.
 //! 3. As new headers become available in the [`Store`], Daser adds them to the queue if
 //!    they are within the sampling window.
@@ -28,6 +29,10 @@
 //!     - [`Store`] is updated with the sampling result.
 //! 5. Steps 3 and 4 are repeated concurently, unless we detect that all peers have disconnected.
 //!    At that point Daser cleans the queue and moves back to step 1.
+//!
+//! If historical sampling is enabled, blocks outside of the sampling window are sampled too,
+//! but only after all the blocks within the window, one at a time and at most once per
+//! historical sampling interval.
 
 use std::collections::HashSet;
 use std::sync::Arc;
@@ -36,17 +41,21 @@ use futures::future::BoxFuture;
 use futures::stream::FuturesUnordered;
 use futures::{FutureExt, StreamExt};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use tendermint::Time;
 use tokio::select;
+use tokio::sync::watch;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, warn};
 use web_time::{Duration, Instant};
 
 use crate::events::{EventPublisher, NodeEvent};
-use crate::executor::{spawn, JoinHandle};
+use crate::executor::{sleep, spawn, JoinHandle};
+use crate::metrics::Metrics;
 use crate::p2p::shwap::sample_cid;
 use crate::p2p::{P2p, P2pError};
 use crate::store::{BlockRanges, SamplingStatus, Store, StoreError};
+use crate::utils::FusedReusableFuture;
 
 const MAX_SAMPLES_NEEDED: usize = 16;
 const GET_SAMPLE_TIMEOUT: Duration = Duration::from_secs(10);
@@ -65,10 +74,20 @@ pub enum DaserError {
     Store(#[from] StoreError),
 }
 
+/// Status of the data availability sampling.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SamplingInfo {
+    /// Whether the sampling is running.
+    pub is_running: bool,
+    /// Ranges of stored headers which are queued for sampling or are being sampled.
+    pub pending_headers: BlockRanges,
+}
+
 /// Component responsible for data availability sampling of blocks from the network.
 pub(crate) struct Daser {
     cancellation_token: CancellationToken,
     join_handle: JoinHandle,
+    info_rx: watch::Receiver<SamplingInfo>,
 }
 
 /// Arguments used to configure the [`Daser`].
@@ -84,6 +103,12 @@ where
     pub(crate) event_pub: EventPublisher,
     /// Size of the sampling window.
     pub(crate) sampling_window: Duration,
+    /// Minimum interval between samplings of blocks outside of the sampling window.
+    ///
+    /// `None` if such blocks are not sampled.
+    pub(crate) historical_sampling_interval: Option<Duration>,
+    /// Metrics of the node.
+    pub(crate) metrics: Metrics,
 }
 
 impl Daser {
@@ -94,7 +119,11 @@ impl Daser {
     {
         let cancellation_token = CancellationToken::new();
         let event_pub = args.event_pub.clone();
-        let mut worker = Worker::new(args, cancellation_token.child_token())?;
+        let (info_tx, info_rx) = watch::channel(SamplingInfo {
+            is_running: true,
+            ..Default::default()
+        });
+        let mut worker = Worker::new(args, cancellation_token.child_token(), info_tx)?;
 
         let join_handle = spawn(async move {
             if let Err(e) = worker.run().await {
@@ -104,14 +133,22 @@ impl Daser {
                     error: e.to_string(),
                 });
             }
+
+            worker.info_tx.send_modify(|info| info.is_running = false);
         });
 
         Ok(Daser {
             cancellation_token,
             join_handle,
+            info_rx,
         })
     }
 
+    /// Get the current status of the sampling.
+    pub(crate) fn info(&self) -> SamplingInfo {
+        self.info_rx.borrow().clone()
+    }
+
     /// Stop the worker.
     pub(crate) fn stop(&self) {
         // Singal the Worker to stop.
@@ -145,13 +182,21 @@ where
     ongoing: BlockRanges,
     prev_head: Option<u64>,
     sampling_window: Duration,
+    historical_sampling_interval: Option<Duration>,
+    historical_sampling_delay: FusedReusableFuture<()>,
+    metrics: Metrics,
+    info_tx: watch::Sender<SamplingInfo>,
 }
 
 impl<S> Worker<S>
 where
     S: Store,
 {
-    fn new(args: DaserArgs<S>, cancellation_token: CancellationToken) -> Result<Worker<S>> {
+    fn new(
+        args: DaserArgs<S>,
+        cancellation_token: CancellationToken,
+        info_tx: watch::Sender<SamplingInfo>,
+    ) -> Result<Worker<S>> {
         Ok(Worker {
             cancellation_token,
             event_pub: args.event_pub,
@@ -164,10 +209,17 @@ where
             ongoing: BlockRanges::default(),
             prev_head: None,
             sampling_window: args.sampling_window,
+            historical_sampling_interval: args.historical_sampling_interval,
+            historical_sampling_delay: FusedReusableFuture::terminated(),
+            metrics: args.metrics,
+            info_tx,
         })
     }
 
     async fn run(&mut self) -> Result<()> {
+        // Queue is populated before connecting, so that pending headers are known.
+        self.populate_queue().await?;
+
         loop {
             if self.cancellation_token.is_cancelled() {
                 break;
@@ -244,6 +296,8 @@ where
                 self.schedule_next_sample_block().await?;
             }
 
+            self.update_info();
+
             select! {
                 _ = self.cancellation_token.cancelled() => {
                     break;
@@ -276,6 +330,9 @@ where
                     wait_new_head = store.wait_new_head();
                     self.populate_queue().await?;
                 }
+                _ = &mut self.historical_sampling_delay => {
+                    // Next historical block can be scheduled.
+                }
             }
         }
 
@@ -311,15 +368,28 @@ where
 
         // Make sure that the block is still in the sampling window.
         if !self.in_sampling_window(header.time()) {
-            // As soon as we reach a block that is not in the sampling
-            // window, it means the rest wouldn't be either.
-            self.queue
-                .remove_relaxed(1..=height)
-                .expect("invalid height");
-            self.done
-                .insert_relaxed(1..=height)
-                .expect("invalid height");
-            return Ok(());
+            let Some(interval) = self.historical_sampling_interval else {
+                // As soon as we reach a block that is not in the sampling
+                // window, it means the rest wouldn't be either.
+                self.queue
+                    .remove_relaxed(1..=height)
+                    .expect("invalid height");
+                self.done
+                    .insert_relaxed(1..=height)
+                    .expect("invalid height");
+                return Ok(());
+            };
+
+            if !self.historical_sampling_delay.is_terminated() {
+                // Historical blocks are sampled at a reduced rate. Block is queued
+                // again and scheduled after the delay.
+                self.queue
+                    .insert_relaxed(height..=height)
+                    .expect("invalid height");
+                return Ok(());
+            }
+
+            self.historical_sampling_delay.set(sleep(interval));
         }
 
         // Select random shares to be sampled
@@ -341,6 +411,7 @@ where
 
         let p2p = self.p2p.clone();
         let event_pub = self.event_pub.clone();
+        let metrics = self.metrics.clone();
 
         // Schedule retrival of the CIDs. This will be run later on in the `select!` loop.
         let fut = async move {
@@ -392,10 +463,17 @@ where
                 });
             }
 
+            if !block_accepted {
+                p2p.report_sampling_failure().await?;
+            }
+
+            let took = now.elapsed();
+            metrics.observe_sampling(block_accepted, took);
+
             event_pub.send(NodeEvent::SamplingFinished {
                 height,
                 accepted: block_accepted,
-                took: now.elapsed(),
+                took,
             });
 
             Ok((height, block_accepted))
@@ -421,10 +499,25 @@ where
         let accepted = self.store.get_accepted_sampling_ranges().await?;
 
         self.queue = stored - accepted - &self.done - &self.ongoing;
+        self.update_info();
 
         Ok(())
     }
 
+    /// Publish the headers pending to be sampled in [`SamplingInfo`].
+    fn update_info(&self) {
+        let pending_headers = self.queue.clone() + &self.ongoing;
+
+        self.info_tx.send_if_modified(|info| {
+            if info.pending_headers != pending_headers {
+                info.pending_headers = pending_headers;
+                true
+            } else {
+                false
+            }
+        });
+    }
+
     /// Returns true if `time` is within the sampling window.
     fn in_sampling_window(&self, time: Time) -> bool {
         let now = Time::now();
@@ -475,7 +568,7 @@ mod tests {
     use crate::p2p::shwap::convert_cid;
     use crate::p2p::P2pCmd;
     use crate::store::InMemoryStore;
-    use crate::test_utils::{async_test, MockP2pHandle};
+    use crate::test_utils::{async_test, new_block_ranges, MockP2pHandle};
     use bytes::BytesMut;
     use celestia_proto::bitswap::Block;
     use celestia_types::consts::appconsts::AppVersion;
@@ -504,6 +597,8 @@ mod tests {
             p2p: Arc::new(mock),
             store: store.clone(),
             sampling_window: DEFAULT_SAMPLING_WINDOW,
+            historical_sampling_interval: None,
+            metrics: Metrics::default(),
         })
         .unwrap();
 
@@ -531,6 +626,8 @@ mod tests {
             p2p: Arc::new(mock),
             store: store.clone(),
             sampling_window: DEFAULT_SAMPLING_WINDOW,
+            historical_sampling_interval: None,
+            metrics: Metrics::default(),
         })
         .unwrap();
 
@@ -545,6 +642,52 @@ mod tests {
         gen_and_sample_block(&mut handle, &mut gen, &store, &mut event_sub, 8, false).await;
     }
 
+    #[async_test]
+    async fn sampling_info() {
+        let (mock, mut handle) = P2p::mocked();
+        let store = Arc::new(InMemoryStore::new());
+        let events = EventChannel::new();
+
+        let mut gen = ExtendedHeaderGenerator::new();
+        let edses: Vec<_> = (0..2)
+            .map(|_| generate_dummy_eds(2, AppVersion::V2))
+            .collect();
+        let headers = edses
+            .iter()
+            .map(|eds| gen.next_with_dah(DataAvailabilityHeader::from_eds(eds)))
+            .collect::<Vec<_>>();
+        store.insert(headers).await.unwrap();
+
+        let daser = Daser::start(DaserArgs {
+            event_pub: events.publisher(),
+            p2p: Arc::new(mock),
+            store: store.clone(),
+            sampling_window: DEFAULT_SAMPLING_WINDOW,
+            historical_sampling_interval: None,
+            metrics: Metrics::default(),
+        })
+        .unwrap();
+
+        handle.expect_no_cmd().await;
+        let info = daser.info();
+        assert!(info.is_running);
+        assert_eq!(info.pending_headers, new_block_ranges([1..=2]));
+
+        handle.announce_peer_connected();
+
+        handle_get_shwap_cid(&mut handle, 2, &edses[1], false).await;
+        handle_get_shwap_cid(&mut handle, 1, &edses[0], false).await;
+        handle.expect_no_cmd().await;
+
+        let info = daser.info();
+        assert!(info.is_running);
+        assert!(info.pending_headers.is_empty());
+
+        daser.stop();
+        daser.join().await;
+        assert!(!daser.info().is_running);
+    }
+
     #[async_test]
     async fn backward_dasing() {
         let (mock, mut handle) = P2p::mocked();
@@ -556,6 +699,8 @@ mod tests {
             p2p: Arc::new(mock),
             store: store.clone(),
             sampling_window: DEFAULT_SAMPLING_WINDOW,
+            historical_sampling_interval: None,
+            metrics: Metrics::default(),
         })
         .unwrap();
 
@@ -615,6 +760,7 @@ mod tests {
                 P2pCmd::GetShwapCid { respond_to, .. } => {
                     let _ = respond_to.send(Err(P2pError::BitswapQueryTimeout));
                 }
+                P2pCmd::ReportSamplingFailure => {}
                 cmd => panic!("Unexpected command: {cmd:?}"),
             }
         }
@@ -654,6 +800,66 @@ mod tests {
         handle.expect_no_cmd().await;
     }
 
+    #[async_test]
+    async fn historical_sampling() {
+        let (mock, mut handle) = P2p::mocked();
+        let store = Arc::new(InMemoryStore::new());
+        let events = EventChannel::new();
+
+        let _daser = Daser::start(DaserArgs {
+            event_pub: events.publisher(),
+            p2p: Arc::new(mock),
+            store: store.clone(),
+            sampling_window: DEFAULT_SAMPLING_WINDOW,
+            historical_sampling_interval: Some(Duration::from_millis(500)),
+            metrics: Metrics::default(),
+        })
+        .unwrap();
+
+        let mut gen = ExtendedHeaderGenerator::new();
+        let old_time = Time::now()
+            .checked_sub(DEFAULT_SAMPLING_WINDOW * 2)
+            .unwrap();
+        gen.set_time(old_time, Duration::from_secs(1));
+
+        handle.announce_peer_connected();
+        handle.expect_no_cmd().await;
+
+        let mut edses = Vec::new();
+        let mut headers = Vec::new();
+
+        for height in 1..=4 {
+            if height == 4 {
+                gen.reset_time();
+            }
+
+            let eds = generate_dummy_eds(2, AppVersion::V2);
+            let dah = DataAvailabilityHeader::from_eds(&eds);
+            headers.push(gen.next_with_dah(dah));
+            edses.push(eds);
+        }
+
+        store.insert(headers).await.unwrap();
+
+        // Block within the sampling window is sampled first
+        handle_get_shwap_cid(&mut handle, 4, &edses[3], false).await;
+
+        // Then historical blocks, one at a time with a delay in between
+        handle_get_shwap_cid(&mut handle, 3, &edses[2], false).await;
+        handle.expect_no_cmd().await;
+        sleep(Duration::from_millis(300)).await;
+
+        handle_get_shwap_cid(&mut handle, 2, &edses[1], false).await;
+        handle.expect_no_cmd().await;
+        sleep(Duration::from_millis(300)).await;
+
+        handle_get_shwap_cid(&mut handle, 1, &edses[0], false).await;
+        handle.expect_no_cmd().await;
+
+        let accepted = store.get_accepted_sampling_ranges().await.unwrap();
+        assert_eq!(accepted, new_block_ranges([1..=4]));
+    }
+
     async fn gen_and_sample_block(
         handle: &mut MockP2pHandle,
         gen: &mut ExtendedHeaderGenerator,
@@ -670,6 +876,9 @@ mod tests {
         store.insert(header).await.unwrap();
 
         let cids = handle_get_shwap_cid(handle, height, &eds, simulate_invalid_sampling).await;
+        if simulate_invalid_sampling {
+            handle.expect_report_sampling_failure().await;
+        }
         handle.expect_no_cmd().await;
 
         let mut sampling_metadata = store.get_sampling_metadata(height).await.unwrap().unwrap();
@@ -0,0 +1,291 @@
+//! Encryption of the stored data at rest.
+//!
+//! Values are encrypted with XChaCha20-Poly1305, using a random nonce for each value.
+//! Keys of the values (e.g. heights or CIDs) are left in plain, so that they can still
+//! be queried, but they are authenticated together with the value, so an encrypted value
+//! can't be moved under a different key.
+//!
+//! Encryption is supported only by the `RedbStore` and the `IndexedDbStore`, which encode
+//! their values themselves, and by any blockstore wrapped in an `EncryptedBlockstore`.
+//! Other stores don't accept an [`EncryptionKey`] and keep the data unencrypted.
+
+use std::fmt;
+
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use thiserror::Error;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Length of the [`EncryptionKey`] in bytes.
+pub const ENCRYPTION_KEY_LEN: usize = 32;
+
+/// Version of the format of the encrypted values.
+const FORMAT_VERSION: u8 = 1;
+const NONCE_LEN: usize = 24;
+
+/// Representation of all the errors that can occur when encrypting the stored data.
+#[derive(Debug, Error)]
+pub enum EncryptionError {
+    /// Provided key has invalid length.
+    #[error("Encryption key must be {ENCRYPTION_KEY_LEN} bytes long, got {0}")]
+    InvalidKeyLength(usize),
+
+    /// Value couldn't be decrypted with any of the keys.
+    #[error("Decryption failed, data is corrupted or encrypted with another key")]
+    DecryptionFailed,
+}
+
+/// A secret key used to encrypt the stored data.
+///
+/// Key is zeroized when dropped.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct EncryptionKey([u8; ENCRYPTION_KEY_LEN]);
+
+impl EncryptionKey {
+    /// Create a key from its bytes.
+    pub fn new(bytes: [u8; ENCRYPTION_KEY_LEN]) -> Self {
+        EncryptionKey(bytes)
+    }
+
+    /// Generate a new random key.
+    pub fn generate() -> Self {
+        EncryptionKey(XChaCha20Poly1305::generate_key(&mut OsRng).into())
+    }
+
+    /// Returns the bytes of the key.
+    pub fn as_bytes(&self) -> &[u8; ENCRYPTION_KEY_LEN] {
+        &self.0
+    }
+}
+
+impl TryFrom<&[u8]> for EncryptionKey {
+    type Error = EncryptionError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let bytes = bytes
+            .try_into()
+            .map_err(|_| EncryptionError::InvalidKeyLength(bytes.len()))?;
+        Ok(EncryptionKey(bytes))
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// AEAD cipher of the stored values.
+///
+/// Values are always encrypted with the current key, while any of the previous keys
+/// can be used to decrypt them, e.g. until the data is re-encrypted after the key rotation.
+#[derive(Clone)]
+pub(crate) struct Cipher {
+    keys: Vec<XChaCha20Poly1305>,
+}
+
+impl Cipher {
+    pub(crate) fn new(key: &EncryptionKey) -> Self {
+        Cipher {
+            keys: vec![XChaCha20Poly1305::new(key.as_bytes().into())],
+        }
+    }
+
+    /// Add a key which is used only to decrypt the values.
+    pub(crate) fn with_previous_key(mut self, key: &EncryptionKey) -> Self {
+        self.keys
+            .push(XChaCha20Poly1305::new(key.as_bytes().into()));
+        self
+    }
+
+    /// Returns the cipher encrypting with the new `key`, while all the current keys are
+    /// kept for decryption.
+    pub(crate) fn rotate(&self, key: &EncryptionKey) -> Cipher {
+        let mut keys = Vec::with_capacity(self.keys.len() + 1);
+        keys.push(XChaCha20Poly1305::new(key.as_bytes().into()));
+        keys.extend(self.keys.iter().cloned());
+
+        Cipher { keys }
+    }
+
+    /// Returns the cipher with only the current key.
+    pub(crate) fn current(&self) -> Cipher {
+        Cipher {
+            keys: self.keys[..1].to_vec(),
+        }
+    }
+
+    /// Encrypt the `value` stored in the `domain` under the `key`.
+    pub(crate) fn encrypt(&self, domain: &str, key: &[u8], value: &[u8]) -> Vec<u8> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = aad(domain, key);
+        let ciphertext = self.keys[0]
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: value,
+                    aad: &aad,
+                },
+            )
+            .expect("encryption is infallible for in-memory buffers");
+
+        let mut encrypted = Vec::with_capacity(1 + NONCE_LEN + ciphertext.len());
+        encrypted.push(FORMAT_VERSION);
+        encrypted.extend_from_slice(&nonce);
+        encrypted.extend_from_slice(&ciphertext);
+        encrypted
+    }
+
+    /// Decrypt the `value` stored in the `domain` under the `key`.
+    ///
+    /// Returns the index of the key which decrypted it too, `0` being the current key.
+    pub(crate) fn decrypt(
+        &self,
+        domain: &str,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<(Vec<u8>, usize), EncryptionError> {
+        let Some((&FORMAT_VERSION, rest)) = value.split_first() else {
+            return Err(EncryptionError::DecryptionFailed);
+        };
+        if rest.len() < NONCE_LEN {
+            return Err(EncryptionError::DecryptionFailed);
+        }
+
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let nonce = XNonce::from_slice(nonce);
+        let aad = aad(domain, key);
+
+        self.keys
+            .iter()
+            .enumerate()
+            .find_map(|(idx, cipher)| {
+                let payload = Payload {
+                    msg: ciphertext,
+                    aad: &aad,
+                };
+                cipher
+                    .decrypt(nonce, payload)
+                    .ok()
+                    .map(|value| (value, idx))
+            })
+            .ok_or(EncryptionError::DecryptionFailed)
+    }
+}
+
+impl fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cipher")
+            .field("keys", &self.keys.len())
+            .finish()
+    }
+}
+
+fn aad(domain: &str, key: &[u8]) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(domain.len() + 1 + key.len());
+    aad.extend_from_slice(domain.as_bytes());
+    aad.push(0);
+    aad.extend_from_slice(key);
+    aad
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_decrypt() {
+        let cipher = Cipher::new(&EncryptionKey::generate());
+        let encrypted = cipher.encrypt("headers", &1u64.to_be_bytes(), b"header");
+
+        assert!(!encrypted.windows(6).any(|w| w == b"header"));
+        assert_eq!(
+            cipher
+                .decrypt("headers", &1u64.to_be_bytes(), &encrypted)
+                .unwrap(),
+            (b"header".to_vec(), 0)
+        );
+
+        // value is bound to its key and domain
+        cipher
+            .decrypt("headers", &2u64.to_be_bytes(), &encrypted)
+            .unwrap_err();
+        cipher
+            .decrypt("sampling", &1u64.to_be_bytes(), &encrypted)
+            .unwrap_err();
+        cipher
+            .decrypt("headers", &1u64.to_be_bytes(), &encrypted[..10])
+            .unwrap_err();
+    }
+
+    #[test]
+    fn previous_keys() {
+        let old_key = EncryptionKey::generate();
+        let new_key = EncryptionKey::generate();
+
+        let old_cipher = Cipher::new(&old_key);
+        let encrypted = old_cipher.encrypt("blocks", b"cid", b"data");
+
+        let new_cipher = Cipher::new(&new_key);
+        new_cipher
+            .decrypt("blocks", b"cid", &encrypted)
+            .unwrap_err();
+
+        let rotating_cipher = Cipher::new(&new_key).with_previous_key(&old_key);
+        assert_eq!(
+            rotating_cipher
+                .decrypt("blocks", b"cid", &encrypted)
+                .unwrap(),
+            (b"data".to_vec(), 1)
+        );
+
+        let encrypted = rotating_cipher.encrypt("blocks", b"cid", b"data");
+        old_cipher
+            .decrypt("blocks", b"cid", &encrypted)
+            .unwrap_err();
+        assert_eq!(
+            rotating_cipher
+                .current()
+                .decrypt("blocks", b"cid", &encrypted)
+                .unwrap(),
+            (b"data".to_vec(), 0)
+        );
+    }
+
+    #[test]
+    fn rotate_keeps_all_keys() {
+        let key1 = EncryptionKey::generate();
+        let key2 = EncryptionKey::generate();
+        let key3 = EncryptionKey::generate();
+
+        let cipher1 = Cipher::new(&key1);
+        let encrypted1 = cipher1.encrypt("blocks", b"cid", b"data1");
+        let cipher2 = cipher1.rotate(&key2);
+        let encrypted2 = cipher2.encrypt("blocks", b"cid", b"data2");
+        let cipher3 = cipher2.rotate(&key3);
+
+        assert_eq!(
+            cipher3.decrypt("blocks", b"cid", &encrypted1).unwrap(),
+            (b"data1".to_vec(), 2)
+        );
+        assert_eq!(
+            cipher3.decrypt("blocks", b"cid", &encrypted2).unwrap(),
+            (b"data2".to_vec(), 1)
+        );
+        cipher3
+            .current()
+            .decrypt("blocks", b"cid", &encrypted1)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn key_from_slice() {
+        let key = EncryptionKey::try_from(&[1; 32][..]).unwrap();
+        assert_eq!(key.as_bytes(), &[1; 32]);
+        assert!(matches!(
+            EncryptionKey::try_from(&[1; 16][..]),
+            Err(EncryptionError::InvalidKeyLength(16))
+        ));
+        assert_eq!(format!("{key:?}"), "EncryptionKey(..)");
+    }
+}
@@ -288,6 +288,22 @@ pub enum NodeEvent {
         error: String,
     },
 
+    /// Store was copied aside before its schema was migrated.
+    StoreBackedUp {
+        /// Location of the backup.
+        location: String,
+    },
+
+    /// Store schema was migrated when the store was opened.
+    StoreMigrated {
+        /// Schema version before the migration.
+        from_version: u64,
+        /// Schema version after the migration.
+        to_version: u64,
+        /// A human readable description of the migration.
+        description: String,
+    },
+
     /// Network was compromised.
     ///
     /// This happens when a valid bad encoding fraud proof is received.
@@ -321,6 +337,8 @@ impl NodeEvent {
             | NodeEvent::FetchingHeadersStarted { .. }
             | NodeEvent::FetchingHeadersFinished { .. }
             | NodeEvent::PrunedHeaders { .. }
+            | NodeEvent::StoreBackedUp { .. }
+            | NodeEvent::StoreMigrated { .. }
             | NodeEvent::NodeStopped => false,
         }
     }
@@ -432,6 +450,19 @@ impl fmt::Display for NodeEvent {
             NodeEvent::FatalPrunerError { error } => {
                 write!(f, "Pruner stopped because of a fatal error: {error}")
             }
+            NodeEvent::StoreBackedUp { location } => {
+                write!(f, "Store backed up to {location}")
+            }
+            NodeEvent::StoreMigrated {
+                from_version,
+                to_version,
+                description,
+            } => {
+                write!(
+                    f,
+                    "Store migrated from v{from_version} to v{to_version}: {description}"
+                )
+            }
             NodeEvent::NetworkCompromised => {
                 write!(f, "The network is compromised and should not be trusted. ")?;
                 write!(f, "Node stopped synchronizing and sampling, but you can still make some queries to the network.")
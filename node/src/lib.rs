@@ -4,8 +4,10 @@
 pub mod block_ranges;
 pub mod blockstore;
 mod daser;
+pub mod encryption;
 pub mod events;
 mod executor;
+mod metrics;
 pub mod network;
 pub mod node;
 mod p2p;
@@ -0,0 +1,279 @@
+//! Prometheus metrics of the [`Node`].
+//!
+//! [`Node`]: crate::node::Node
+
+use std::time::Duration;
+
+use libp2p::PeerId;
+use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::{Registry, Unit};
+
+use crate::block_ranges::{BlockRangeExt, BlockRanges};
+use crate::peer_tracker::PeerTrackerInfo;
+
+/// Prefix of all the metrics.
+const METRICS_PREFIX: &str = "lumina";
+
+type HistogramFamily<S> = Family<S, Histogram, fn() -> Histogram>;
+
+/// Handles for updating the metrics from the components of the [`Node`].
+///
+/// Metrics are cheap to clone and all the clones update the same values.
+///
+/// [`Node`]: crate::node::Node
+#[derive(Debug, Clone)]
+pub(crate) struct Metrics {
+    sampling_duration: Histogram,
+    sampled_blocks: Family<SamplingLabels, Counter>,
+    header_ex_request_duration: HistogramFamily<PeerLabels>,
+    stored_headers: Gauge,
+    stored_header_ranges: Gauge,
+    pruned_height: Gauge,
+    pruned_headers: Counter,
+    connected_peers: Gauge,
+    connected_trusted_peers: Gauge,
+    bitswap_query_timeouts: Counter,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct SamplingLabels {
+    result: SamplingResult,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelValue)]
+enum SamplingResult {
+    Accepted,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct PeerLabels {
+    peer: String,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            // 50ms to ~25s
+            sampling_duration: Histogram::new(exponential_buckets(0.05, 2.0, 10)),
+            sampled_blocks: Family::default(),
+            header_ex_request_duration: Family::new_with_constructor(|| {
+                // 10ms to ~20s
+                Histogram::new(exponential_buckets(0.01, 2.0, 12))
+            }),
+            stored_headers: Gauge::default(),
+            stored_header_ranges: Gauge::default(),
+            pruned_height: Gauge::default(),
+            pruned_headers: Counter::default(),
+            connected_peers: Gauge::default(),
+            connected_trusted_peers: Gauge::default(),
+            bitswap_query_timeouts: Counter::default(),
+        }
+    }
+}
+
+impl Metrics {
+    /// Create new metrics and register them in a new [`Registry`].
+    pub(crate) fn new() -> (Self, Registry) {
+        let metrics = Metrics::default();
+        let mut registry = Registry::with_prefix(METRICS_PREFIX);
+
+        registry.register_with_unit(
+            "sampling_duration",
+            "Time it took to sample a block",
+            Unit::Seconds,
+            metrics.sampling_duration.clone(),
+        );
+        registry.register(
+            "sampled_blocks",
+            "Number of sampled blocks by the result of sampling",
+            metrics.sampled_blocks.clone(),
+        );
+        registry.register_with_unit(
+            "header_ex_request_duration",
+            "Time it took a peer to respond to a header-ex request",
+            Unit::Seconds,
+            metrics.header_ex_request_duration.clone(),
+        );
+        registry.register(
+            "stored_headers",
+            "Number of headers in the store",
+            metrics.stored_headers.clone(),
+        );
+        registry.register(
+            "stored_header_ranges",
+            "Number of disjoint ranges of headers in the store",
+            metrics.stored_header_ranges.clone(),
+        );
+        registry.register(
+            "pruned_height",
+            "Height of the most recently pruned header",
+            metrics.pruned_height.clone(),
+        );
+        registry.register(
+            "pruned_headers",
+            "Number of headers removed by the pruner",
+            metrics.pruned_headers.clone(),
+        );
+        registry.register(
+            "connected_peers",
+            "Number of connected peers",
+            metrics.connected_peers.clone(),
+        );
+        registry.register(
+            "connected_trusted_peers",
+            "Number of connected trusted peers",
+            metrics.connected_trusted_peers.clone(),
+        );
+        registry.register(
+            "bitswap_query_timeouts",
+            "Number of bitswap queries that timed out",
+            metrics.bitswap_query_timeouts.clone(),
+        );
+
+        (metrics, registry)
+    }
+
+    /// Record the result of sampling a block.
+    pub(crate) fn observe_sampling(&self, accepted: bool, took: Duration) {
+        let result = if accepted {
+            SamplingResult::Accepted
+        } else {
+            SamplingResult::Rejected
+        };
+
+        self.sampling_duration.observe(took.as_secs_f64());
+        self.sampled_blocks
+            .get_or_create(&SamplingLabels { result })
+            .inc();
+    }
+
+    /// Record the response time of a `header-ex` request.
+    pub(crate) fn observe_header_ex_request(&self, peer: &PeerId, took: Duration) {
+        self.header_ex_request_duration
+            .get_or_create(&PeerLabels {
+                peer: peer.to_string(),
+            })
+            .observe(took.as_secs_f64());
+    }
+
+    /// Remove the per-peer metrics of a peer, once it's disconnected.
+    pub(crate) fn remove_peer(&self, peer: &PeerId) {
+        self.header_ex_request_duration.remove(&PeerLabels {
+            peer: peer.to_string(),
+        });
+    }
+
+    /// Update the metrics of the headers in the store.
+    pub(crate) fn set_stored_ranges(&self, ranges: &BlockRanges) {
+        let headers: u64 = ranges.as_ref().iter().map(|range| range.len()).sum();
+
+        self.stored_headers.set(to_i64(headers));
+        self.stored_header_ranges
+            .set(to_i64(ranges.as_ref().len() as u64));
+    }
+
+    /// Record a header removed by the pruner.
+    pub(crate) fn observe_pruned(&self, height: u64) {
+        self.pruned_height.set(to_i64(height));
+        self.pruned_headers.inc();
+    }
+
+    /// Returns the number of headers removed by the pruner.
+    pub(crate) fn pruned_headers(&self) -> u64 {
+        self.pruned_headers.get()
+    }
+
+    /// Update the metrics of the connected peers.
+    pub(crate) fn set_peers(&self, info: &PeerTrackerInfo) {
+        self.connected_peers.set(to_i64(info.num_connected_peers));
+        self.connected_trusted_peers
+            .set(to_i64(info.num_connected_trusted_peers));
+    }
+
+    /// Record a bitswap query that timed out.
+    pub(crate) fn observe_bitswap_query_timeout(&self) {
+        self.bitswap_query_timeouts.inc();
+    }
+}
+
+fn to_i64(val: u64) -> i64 {
+    i64::try_from(val).unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus_client::encoding::text::encode;
+
+    fn encoded(registry: &Registry) -> String {
+        let mut buf = String::new();
+        encode(&mut buf, registry).unwrap();
+        buf
+    }
+
+    #[test]
+    fn sampling_metrics() {
+        let (metrics, registry) = Metrics::new();
+
+        metrics.observe_sampling(true, Duration::from_millis(100));
+        metrics.observe_sampling(true, Duration::from_millis(200));
+        metrics.observe_sampling(false, Duration::from_secs(10));
+
+        let text = encoded(&registry);
+        assert!(text.contains("lumina_sampled_blocks_total{result=\"Accepted\"} 2"));
+        assert!(text.contains("lumina_sampled_blocks_total{result=\"Rejected\"} 1"));
+        assert!(text.contains("lumina_sampling_duration_seconds_count 3"));
+    }
+
+    #[test]
+    fn store_and_peer_metrics() {
+        let (metrics, registry) = Metrics::new();
+        let ranges = BlockRanges::from_vec([1..=10, 21..=25].into_iter().collect()).unwrap();
+
+        metrics.set_stored_ranges(&ranges);
+        metrics.observe_pruned(3);
+        metrics.set_peers(&PeerTrackerInfo {
+            num_connected_peers: 5,
+            num_connected_trusted_peers: 2,
+            ..Default::default()
+        });
+
+        let text = encoded(&registry);
+        assert!(text.contains("lumina_stored_headers 15"));
+        assert!(text.contains("lumina_stored_header_ranges 2"));
+        assert!(text.contains("lumina_pruned_height 3"));
+        assert!(text.contains("lumina_pruned_headers_total 1"));
+        assert!(text.contains("lumina_connected_peers 5"));
+        assert!(text.contains("lumina_connected_trusted_peers 2"));
+    }
+
+    #[test]
+    fn header_ex_metrics_per_peer() {
+        let (metrics, registry) = Metrics::new();
+        let peer1 = PeerId::random();
+        let peer2 = PeerId::random();
+
+        metrics.observe_header_ex_request(&peer1, Duration::from_millis(50));
+        metrics.observe_header_ex_request(&peer1, Duration::from_millis(70));
+        metrics.observe_header_ex_request(&peer2, Duration::from_millis(50));
+
+        let text = encoded(&registry);
+        assert!(text.contains(&format!(
+            "lumina_header_ex_request_duration_seconds_count{{peer=\"{peer1}\"}} 2"
+        )));
+        assert!(text.contains(&format!(
+            "lumina_header_ex_request_duration_seconds_count{{peer=\"{peer2}\"}} 1"
+        )));
+
+        metrics.remove_peer(&peer1);
+
+        let text = encoded(&registry);
+        assert!(!text.contains(&peer1.to_string()));
+        assert!(text.contains(&peer2.to_string()));
+    }
+}
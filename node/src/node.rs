@@ -4,42 +4,58 @@
 //! and then proceed with synchronization and data sampling of the blocks.
 
 use std::ops::RangeBounds;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
 use blockstore::Blockstore;
 use celestia_types::hash::Hash;
 use celestia_types::nmt::Namespace;
+use celestia_types::p2p::BandwidthStats;
 use celestia_types::row::Row;
 use celestia_types::row_namespace_data::RowNamespaceData;
 use celestia_types::sample::Sample;
-use celestia_types::{Blob, ExtendedHeader};
+use celestia_types::{Blob, ExtendedDataSquare, ExtendedHeader};
 use libp2p::identity::Keypair;
 use libp2p::swarm::NetworkInfo;
 use libp2p::{Multiaddr, PeerId};
-use tokio::sync::watch;
+use prometheus_client::registry::Registry;
+use tendermint::Time;
+use tokio::select;
+use tokio::sync::{mpsc, watch};
 use tokio_util::sync::CancellationToken;
-use tracing::warn;
+use tracing::{info, warn};
 
 use crate::blockstore::InMemoryBlockstore;
 use crate::daser::{Daser, DaserArgs};
 use crate::events::{EventChannel, EventSubscriber, NodeEvent};
-use crate::executor::{spawn_cancellable, JoinHandle};
+use crate::executor::{spawn_cancellable, Interval, JoinHandle};
+use crate::metrics::Metrics;
 use crate::p2p::{P2p, P2pArgs};
 use crate::pruner::{Pruner, PrunerArgs, DEFAULT_PRUNING_INTERVAL};
-use crate::store::{InMemoryStore, SamplingMetadata, Store, StoreError};
+use crate::store::{InMemoryStore, SamplingMetadata, Store, StoreError, StoreStats};
 use crate::syncer::{Syncer, SyncerArgs};
 
+mod blob_stream;
 mod builder;
+mod namespace_data;
 
+use self::blob_stream::BlobStreamArgs;
+
+pub use self::blob_stream::BlobStream;
 pub use self::builder::{
     NodeBuilder, NodeBuilderError, DEFAULT_PRUNING_DELAY, DEFAULT_SAMPLING_WINDOW,
     MIN_PRUNING_DELAY, MIN_SAMPLING_WINDOW,
 };
-pub use crate::daser::DaserError;
-pub use crate::p2p::{HeaderExError, P2pError};
-pub use crate::peer_tracker::PeerTrackerInfo;
-pub use crate::syncer::{SyncerError, SyncingInfo};
+pub use crate::daser::{DaserError, SamplingInfo};
+pub use crate::p2p::{HeaderExError, P2pError, ShrexError};
+pub use crate::peer_tracker::{PeerInfo, PeerTrackerInfo, DEFAULT_PEER_BAN_DURATION};
+pub use crate::syncer::{SyncerError, SyncingInfo, TrustedCheckpoint};
+
+/// How often the store metrics are refreshed.
+const METRICS_UPDATE_INTERVAL: Duration = Duration::from_secs(10);
+/// Number of headers pruned while running, after which the store is compacted on stop.
+const COMPACTION_PRUNED_HEADERS: u64 = 10_000;
 
 /// Alias of [`Result`] with [`NodeError`] error type
 ///
@@ -68,6 +84,10 @@ pub enum NodeError {
     /// An error propagated from the `Daser` component.
     #[error("Daser: {0}")]
     Daser(#[from] DaserError),
+
+    /// Header at the height is older than the syncing window and will never be synchronized.
+    #[error("Header at height {0} is outside of the syncing window")]
+    OutsideSyncingWindow(u64),
 }
 
 struct NodeConfig<B, S>
@@ -81,9 +101,18 @@ where
     pub(crate) p2p_local_keypair: Keypair,
     pub(crate) p2p_bootnodes: Vec<Multiaddr>,
     pub(crate) p2p_listen_on: Vec<Multiaddr>,
+    pub(crate) p2p_serve_shwap: bool,
+    pub(crate) p2p_tls_key_file: Option<PathBuf>,
+    pub(crate) p2p_tls_cert_file: Option<PathBuf>,
+    pub(crate) p2p_peer_ban_duration: Duration,
+    pub(crate) p2p_mdns: bool,
+    pub(crate) p2p_mdns_trust: bool,
     pub(crate) sync_batch_size: u64,
     pub(crate) sampling_window: Duration,
     pub(crate) pruning_window: Duration,
+    pub(crate) archival: bool,
+    pub(crate) historical_sampling_interval: Option<Duration>,
+    pub(crate) trusted_checkpoint: Option<TrustedCheckpoint>,
 }
 
 /// Celestia node.
@@ -101,6 +130,13 @@ where
     pruner: Option<Arc<Pruner>>,
     tasks_cancellation_token: CancellationToken,
     network_compromised_task: JoinHandle,
+    metrics_task: JoinHandle,
+    metrics: Metrics,
+    metrics_registry: Registry,
+    blob_streams_done_tx: Option<mpsc::Sender<()>>,
+    blob_streams_done_rx: mpsc::Receiver<()>,
+    /// Age of the oldest synchronized headers, `None` in archival mode.
+    syncing_window: Option<Duration>,
 }
 
 impl Node<InMemoryBlockstore, InMemoryStore> {
@@ -137,8 +173,14 @@ where
     async fn start(config: NodeConfig<B, S>) -> Result<(Self, EventSubscriber)> {
         let event_channel = EventChannel::new();
         let event_sub = event_channel.subscribe();
+
+        if let Some(report) = config.store.migration_report() {
+            report.publish(&event_channel.publisher());
+        }
+
         let store = Arc::new(config.store);
         let blockstore = Arc::new(config.blockstore);
+        let (metrics, metrics_registry) = Metrics::new();
 
         let p2p = Arc::new(
             P2p::start(P2pArgs {
@@ -146,9 +188,16 @@ where
                 local_keypair: config.p2p_local_keypair,
                 bootnodes: config.p2p_bootnodes,
                 listen_on: config.p2p_listen_on,
+                serve_shwap: config.p2p_serve_shwap,
+                tls_key_file: config.p2p_tls_key_file,
+                tls_cert_file: config.p2p_tls_cert_file,
+                peer_ban_duration: config.p2p_peer_ban_duration,
+                mdns: config.p2p_mdns,
+                mdns_trust: config.p2p_mdns_trust,
                 blockstore: blockstore.clone(),
                 store: store.clone(),
                 event_pub: event_channel.publisher(),
+                metrics: metrics.clone(),
             })
             .await?,
         );
@@ -161,6 +210,8 @@ where
             // We sync only what we need to sample. So syncing_window is
             // the same as sampling_window.
             syncing_window: config.sampling_window,
+            archival: config.archival,
+            trusted_checkpoint: config.trusted_checkpoint,
         })?);
 
         let daser = Arc::new(Daser::start(DaserArgs {
@@ -168,15 +219,21 @@ where
             store: store.clone(),
             event_pub: event_channel.publisher(),
             sampling_window: config.sampling_window,
+            historical_sampling_interval: config.historical_sampling_interval,
+            metrics: metrics.clone(),
         })?);
 
-        let pruner = Arc::new(Pruner::start(PrunerArgs {
-            store: store.clone(),
-            blockstore: blockstore.clone(),
-            event_pub: event_channel.publisher(),
-            pruning_interval: DEFAULT_PRUNING_INTERVAL,
-            pruning_window: config.pruning_window,
-        }));
+        // Nothing is pruned in archival mode.
+        let pruner = (!config.archival).then(|| {
+            Arc::new(Pruner::start(PrunerArgs {
+                store: store.clone(),
+                blockstore: blockstore.clone(),
+                event_pub: event_channel.publisher(),
+                pruning_interval: DEFAULT_PRUNING_INTERVAL,
+                pruning_window: config.pruning_window,
+                metrics: metrics.clone(),
+            }))
+        });
 
         let tasks_cancellation_token = CancellationToken::new();
 
@@ -194,7 +251,9 @@ where
                 // Network compromised! Stop workers.
                 syncer.stop();
                 daser.stop();
-                pruner.stop();
+                if let Some(pruner) = pruner {
+                    pruner.stop();
+                }
 
                 event_pub.send(NodeEvent::NetworkCompromised);
                 // This is a very important message and we want to log it even
@@ -203,6 +262,37 @@ where
             }
         });
 
+        // spawn the task that will keep the store and peers metrics up to date
+        let metrics_task = spawn_cancellable(tasks_cancellation_token.child_token(), {
+            let metrics = metrics.clone();
+            let store = store.clone();
+            let mut peer_tracker_info_watcher = p2p.peer_tracker_info_watcher();
+
+            async move {
+                let mut interval = Interval::new(METRICS_UPDATE_INTERVAL).await;
+
+                loop {
+                    if let Ok(ranges) = store.get_stored_header_ranges().await {
+                        metrics.set_stored_ranges(&ranges);
+                    }
+
+                    metrics.set_peers(&peer_tracker_info_watcher.borrow_and_update());
+
+                    select! {
+                        _ = interval.tick() => {}
+                        res = peer_tracker_info_watcher.changed() => {
+                            if res.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        });
+
+        let (blob_streams_done_tx, blob_streams_done_rx) = mpsc::channel(1);
+        let syncing_window = (!config.archival).then_some(config.sampling_window);
+
         let node = Node {
             event_channel,
             p2p: Some(p2p),
@@ -210,9 +300,15 @@ where
             store: Some(store),
             syncer: Some(syncer),
             daser: Some(daser),
-            pruner: Some(pruner),
+            pruner,
             tasks_cancellation_token,
             network_compromised_task,
+            metrics_task,
+            metrics,
+            metrics_registry,
+            blob_streams_done_tx: Some(blob_streams_done_tx),
+            blob_streams_done_rx,
+            syncing_window,
         };
 
         Ok((node, event_sub))
@@ -223,21 +319,31 @@ where
         {
             let daser = self.daser.take().expect("Daser not initialized");
             let syncer = self.syncer.take().expect("Syncer not initialized");
-            let pruner = self.pruner.take().expect("Pruner not initialized");
+            let pruner = self.pruner.take();
             let p2p = self.p2p.take().expect("P2p not initialized");
 
             // Cancel Node's tasks
             self.tasks_cancellation_token.cancel();
             self.network_compromised_task.join().await;
+            self.metrics_task.join().await;
+
+            // Wait for all blob streams to finish. Each of them holds a sender, so
+            // the channel is closed after the last one is dropped.
+            self.blob_streams_done_tx.take();
+            while self.blob_streams_done_rx.recv().await.is_some() {}
 
             // Stop all components that use P2p.
             daser.stop();
             syncer.stop();
-            pruner.stop();
+            if let Some(ref pruner) = pruner {
+                pruner.stop();
+            }
 
             daser.join().await;
             syncer.join().await;
-            pruner.join().await;
+            if let Some(pruner) = pruner {
+                pruner.join().await;
+            }
 
             // Now stop P2p component.
             p2p.stop();
@@ -253,7 +359,18 @@ where
 
         // Everything that was holding Store is now dropped, so we can close it.
         let store = self.store.take().expect("Store not initialized");
-        let store = Arc::into_inner(store).expect("Not all Arc<Store> were dropped");
+        let mut store = Arc::into_inner(store).expect("Not all Arc<Store> were dropped");
+
+        // Space freed by pruning is not returned to the system until the store is
+        // compacted, which needs exclusive access to the database.
+        if self.metrics.pruned_headers() >= COMPACTION_PRUNED_HEADERS {
+            match store.compact().await {
+                Ok(true) => info!("Store compacted"),
+                Ok(false) => {}
+                Err(e) => warn!("Store failed to compact: {e}"),
+            }
+        }
+
         if let Err(e) = store.close().await {
             warn!("Store failed to close: {e}");
         }
@@ -265,10 +382,20 @@ where
         self.syncer.as_ref().expect("Syncer not initialized")
     }
 
+    fn daser(&self) -> &Daser {
+        self.daser.as_ref().expect("Daser not initialized")
+    }
+
     fn p2p(&self) -> &P2p {
         self.p2p.as_ref().expect("P2p not initialized")
     }
 
+    fn blockstore(&self) -> &B {
+        self.blockstore
+            .as_ref()
+            .expect("Blockstore not initialized")
+    }
+
     fn store(&self) -> &S {
         self.store.as_ref().expect("Store not initialized")
     }
@@ -288,6 +415,36 @@ where
         self.p2p().peer_tracker_info().clone()
     }
 
+    /// Get the bandwidth used by the node, summed over all peers and protocols.
+    ///
+    /// Only the data of the streams is counted, without the overhead of the
+    /// transports, encryption and multiplexing.
+    pub fn bandwidth_stats(&self) -> BandwidthStats {
+        self.p2p().bandwidth_stats()
+    }
+
+    /// Get the bandwidth used with the peer with a given ID.
+    pub fn bandwidth_for_peer(&self, peer_id: &PeerId) -> BandwidthStats {
+        self.p2p().bandwidth_for_peer(peer_id)
+    }
+
+    /// Get the bandwidth used by the protocol with a given ID, e.g. `/ipfs/id/1.0.0`.
+    pub fn bandwidth_for_protocol(&self, protocol_id: &str) -> BandwidthStats {
+        self.p2p().bandwidth_for_protocol(protocol_id)
+    }
+
+    /// Get the metrics of the node encoded in the [OpenMetrics text format].
+    ///
+    /// This can be served directly to Prometheus.
+    ///
+    /// [OpenMetrics text format]: https://github.com/OpenObservability/OpenMetrics/blob/main/specification/OpenMetrics.md
+    pub fn encode_metrics(&self) -> String {
+        let mut buf = String::new();
+        prometheus_client::encoding::text::encode(&mut buf, &self.metrics_registry)
+            .expect("writing to a String never fails");
+        buf
+    }
+
     /// Get [`PeerTrackerInfo`] watcher.
     pub fn peer_tracker_info_watcher(&self) -> watch::Receiver<PeerTrackerInfo> {
         self.p2p().peer_tracker_info_watcher()
@@ -323,6 +480,45 @@ where
         Ok(self.p2p().set_peer_trust(peer_id, is_trusted).await?)
     }
 
+    /// Connect to a peer on the given address.
+    ///
+    /// Waits until the connection is established or the dial fails.
+    pub async fn connect(&self, addr: Multiaddr) -> Result<()> {
+        Ok(self.p2p().connect(addr).await?)
+    }
+
+    /// Close all connections to the peer with a given ID.
+    ///
+    /// Peer may be connected again later, use [`Node::block_peer`] to prevent that.
+    pub async fn disconnect(&self, peer_id: PeerId) -> Result<()> {
+        Ok(self.p2p().disconnect(peer_id).await?)
+    }
+
+    /// Block the peer with a given ID.
+    ///
+    /// Peer is disconnected and no connections to or from it are allowed until it's
+    /// unblocked. The blocklist is persisted in the store and survives restarts.
+    pub async fn block_peer(&self, peer_id: PeerId) -> Result<()> {
+        Ok(self.p2p().set_peer_blocked(peer_id, true).await?)
+    }
+
+    /// Unblock the peer with a given ID.
+    pub async fn unblock_peer(&self, peer_id: PeerId) -> Result<()> {
+        Ok(self.p2p().set_peer_blocked(peer_id, false).await?)
+    }
+
+    /// Get all the blocked peers.
+    pub async fn list_blocked_peers(&self) -> Result<Vec<PeerId>> {
+        Ok(self.p2p().blocked_peers().await?)
+    }
+
+    /// Get the information about the peer with a given ID, `None` if the peer is unknown.
+    ///
+    /// Protocols and agent version are the ones reported by the peer via identify.
+    pub async fn peer_info(&self, peer_id: PeerId) -> Result<Option<PeerInfo>> {
+        Ok(self.p2p().peer_info(peer_id).await?)
+    }
+
     /// Request the head header from the network.
     pub async fn request_head_header(&self) -> Result<ExtendedHeader> {
         Ok(self.p2p().get_head_header().await?)
@@ -385,6 +581,9 @@ where
 
     /// Request a verified [`RowNamespaceData`] from the network.
     ///
+    /// Retrieved data is cached in the blockstore until the block is pruned, so
+    /// requests for it are served without going to the network again.
+    ///
     /// # Errors
     ///
     /// On failure to receive a verified [`RowNamespaceData`] within a certain time, the
@@ -396,21 +595,99 @@ where
         block_height: u64,
         timeout: Option<Duration>,
     ) -> Result<RowNamespaceData> {
-        Ok(self
-            .p2p()
-            .get_row_namespace_data(namespace, row_index, block_height, timeout)
-            .await?)
+        Ok(namespace_data::get_row_namespace_data(
+            self.p2p(),
+            self.blockstore(),
+            self.store(),
+            namespace,
+            row_index,
+            block_height,
+            timeout,
+        )
+        .await?)
     }
 
     /// Request all blobs with provided namespace in the block corresponding to this header
     /// using bitswap protocol.
+    ///
+    /// Like in [`Node::request_row_namespace_data`], retrieved data is cached in
+    /// the blockstore until the block is pruned.
     pub async fn request_all_blobs(
         &self,
         header: &ExtendedHeader,
         namespace: Namespace,
         timeout: Option<Duration>,
     ) -> Result<Vec<Blob>> {
-        Ok(self.p2p().get_all_blobs(header, namespace, timeout).await?)
+        Ok(namespace_data::get_all_blobs(
+            self.p2p(),
+            self.blockstore(),
+            self.store(),
+            header,
+            namespace,
+            timeout,
+        )
+        .await?)
+    }
+
+    /// Subscribe to the blobs of the namespace in the new blocks.
+    ///
+    /// Blocks are processed in order of height as their headers get synchronized,
+    /// starting from `start_height`, or from the block after the current head if it
+    /// is `None`. Heights without any blobs of the namespace are yielded with an
+    /// empty list, so consumers can keep track of the processed heights and resume
+    /// from the next one after a restart.
+    ///
+    /// `start_height` should be within the sampling window, since older headers
+    /// are never synchronized. If it is not, the stream yields
+    /// [`NodeError::OutsideSyncingWindow`] and ends.
+    pub async fn subscribe_blobs(
+        &self,
+        namespace: Namespace,
+        start_height: Option<u64>,
+    ) -> Result<BlobStream> {
+        let start_height = match start_height {
+            Some(height) => Some(height),
+            None => match self.store().head_height().await {
+                Ok(height) => Some(height + 1),
+                Err(StoreError::NotFound) => None,
+                Err(e) => return Err(e.into()),
+            },
+        };
+
+        Ok(BlobStream::start(BlobStreamArgs {
+            p2p: self.p2p.clone().expect("P2p not initialized"),
+            blockstore: self.blockstore.clone().expect("Blockstore not initialized"),
+            store: self.store.clone().expect("Store not initialized"),
+            namespace,
+            start_height,
+            syncing_window: self.syncing_window,
+            cancellation_token: self.tasks_cancellation_token.child_token(),
+            done_tx: self.blob_streams_done_tx.clone().expect("Node not running"),
+        }))
+    }
+
+    /// Request the [`ExtendedDataSquare`] of the block at the given height.
+    ///
+    /// Rows of the square are requested in parallel using shwap protocol, then the square
+    /// is repaired and verified against the data availability header. If that fails,
+    /// the square is requested using `shrex-eds` protocol.
+    ///
+    /// The header of the block must already be synchronized.
+    pub async fn request_eds(
+        &self,
+        height: u64,
+        timeout: Option<Duration>,
+    ) -> Result<ExtendedDataSquare> {
+        let header = self.store().get_by_height(height).await?;
+        Ok(self.p2p().get_eds(&header, timeout).await?)
+    }
+
+    /// Get storage usage statistics of the store.
+    ///
+    /// If the blockstore shares the database with the store, its tables are
+    /// included as well.
+    pub async fn storage_stats(&self) -> Result<StoreStats> {
+        Ok(self.store().stats().await?)
     }
 
     /// Get current header syncing info.
@@ -418,6 +695,11 @@ where
         Ok(self.syncer().info().await?)
     }
 
+    /// Get current data availability sampling info.
+    pub fn sampling_info(&self) -> SamplingInfo {
+        self.daser().info()
+    }
+
     /// Get the latest header announced in the network.
     pub async fn get_network_head_header(&self) -> Result<Option<ExtendedHeader>> {
         Ok(self.p2p().get_network_head().await?)
@@ -438,6 +720,11 @@ where
         Ok(self.store().get_by_height(height).await?)
     }
 
+    /// Get the last synced header with the time at or before the given `time`.
+    pub async fn get_header_by_time(&self, time: Time) -> Result<ExtendedHeader> {
+        Ok(self.store().get_by_time(time).await?)
+    }
+
     /// Get synced headers from the given heights range.
     ///
     /// If start of the range is unbounded, the first returned header will be of height 1.
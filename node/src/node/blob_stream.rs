@@ -0,0 +1,300 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use blockstore::Blockstore;
+use celestia_types::nmt::Namespace;
+use celestia_types::Blob;
+use futures::Stream;
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::executor::spawn_cancellable;
+use crate::node::namespace_data::get_all_blobs;
+use crate::node::{NodeError, Result};
+use crate::p2p::P2p;
+use crate::store::Store;
+use crate::syncer::in_syncing_window;
+
+/// Maximum time to wait for the blobs of a single block.
+const BLOBS_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Number of heights buffered when consumer is slower than the node.
+const BLOB_STREAM_CAPACITY: usize = 16;
+
+/// Stream of blobs of a namespace, created with [`Node::subscribe_blobs`].
+///
+/// Each item holds a height and all the blobs of the namespace in the block at that
+/// height. Heights are yielded in order and without gaps, so heights without any
+/// blobs of the namespace are yielded with an empty list.
+///
+/// If blobs of a height cannot be retrieved, the error is yielded and the stream ends.
+/// This is also the case for heights whose headers will never be synchronized, because
+/// they are older than the syncing window.
+/// Subscription can be resumed by subscribing again from the failed height.
+///
+/// [`Node::subscribe_blobs`]: crate::node::Node::subscribe_blobs
+#[derive(Debug)]
+pub struct BlobStream {
+    rx: mpsc::Receiver<Result<(u64, Vec<Blob>)>>,
+}
+
+impl Stream for BlobStream {
+    type Item = Result<(u64, Vec<Blob>)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+pub(crate) struct BlobStreamArgs<B, S>
+where
+    B: Blockstore,
+    S: Store,
+{
+    pub(crate) p2p: Arc<P2p>,
+    pub(crate) blockstore: Arc<B>,
+    pub(crate) store: Arc<S>,
+    pub(crate) namespace: Namespace,
+    /// If `None`, subscription starts from the first head inserted into the store.
+    pub(crate) start_height: Option<u64>,
+    /// Age of the oldest synchronized headers, `None` in archival mode.
+    pub(crate) syncing_window: Option<Duration>,
+    pub(crate) cancellation_token: CancellationToken,
+    /// Dropped when the task finishes, so that `Node` can wait for the subscriptions
+    /// to release the store.
+    pub(crate) done_tx: mpsc::Sender<()>,
+}
+
+impl BlobStream {
+    pub(crate) fn start<B, S>(args: BlobStreamArgs<B, S>) -> BlobStream
+    where
+        B: Blockstore + 'static,
+        S: Store + 'static,
+    {
+        let (tx, rx) = mpsc::channel(BLOB_STREAM_CAPACITY);
+
+        spawn_cancellable(args.cancellation_token, async move {
+            let _done_tx = args.done_tx;
+
+            let mut height = match args.start_height {
+                Some(height) => height,
+                // Store was empty when subscribing, so start from the first head.
+                None => select! {
+                    _ = tx.closed() => return,
+                    height = args.store.wait_new_head() => height,
+                },
+            };
+
+            loop {
+                let blobs = get_blobs(
+                    &args.p2p,
+                    &*args.blockstore,
+                    &*args.store,
+                    height,
+                    args.namespace,
+                    args.syncing_window,
+                );
+
+                let res = select! {
+                    _ = tx.closed() => break,
+                    res = blobs => res,
+                };
+
+                let failed = res.is_err();
+
+                if tx.send(res.map(|blobs| (height, blobs))).await.is_err() || failed {
+                    break;
+                }
+
+                height += 1;
+            }
+        });
+
+        BlobStream { rx }
+    }
+}
+
+async fn get_blobs<B, S>(
+    p2p: &P2p,
+    blockstore: &B,
+    store: &S,
+    height: u64,
+    namespace: Namespace,
+    syncing_window: Option<Duration>,
+) -> Result<Vec<Blob>>
+where
+    B: Blockstore,
+    S: Store,
+{
+    ensure_syncable(store, height, syncing_window).await?;
+    store.wait_height(height).await?;
+    let header = store.get_by_height(height).await?;
+
+    Ok(get_all_blobs(
+        p2p,
+        blockstore,
+        store,
+        &header,
+        namespace,
+        Some(BLOBS_REQUEST_TIMEOUT),
+    )
+    .await?)
+}
+
+/// Returns an error if the header at `height` is missing and will never be synchronized.
+async fn ensure_syncable<S>(store: &S, height: u64, syncing_window: Option<Duration>) -> Result<()>
+where
+    S: Store,
+{
+    // Headers are synchronized back to genesis in archival mode.
+    let Some(syncing_window) = syncing_window else {
+        return Ok(());
+    };
+
+    let ranges = store.get_stored_header_ranges().await?;
+
+    if ranges.contains(height) {
+        return Ok(());
+    }
+
+    // Missing headers are fetched backwards from the lowest stored header above
+    // them, but only while that header is within the syncing window.
+    let Some(anchor_height) = ranges
+        .as_ref()
+        .iter()
+        .map(|range| *range.start())
+        .find(|start| *start > height)
+    else {
+        // Header above the head is synchronized once it is produced.
+        return Ok(());
+    };
+
+    let anchor = store.get_by_height(anchor_height).await?;
+
+    if in_syncing_window(&anchor, syncing_window) {
+        Ok(())
+    } else {
+        Err(NodeError::OutsideSyncingWindow(height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockstore::InMemoryBlockstore;
+    use crate::executor::sleep;
+    use crate::node::DEFAULT_SAMPLING_WINDOW;
+    use crate::store::InMemoryStore;
+    use crate::test_utils::{async_test, gen_filled_store};
+    use celestia_types::test_utils::ExtendedHeaderGenerator;
+    use futures::StreamExt;
+    use tendermint::Time;
+
+    fn start_stream<S>(
+        p2p: Arc<P2p>,
+        store: Arc<S>,
+        start_height: Option<u64>,
+    ) -> (BlobStream, mpsc::Receiver<()>)
+    where
+        S: Store + 'static,
+    {
+        let (done_tx, done_rx) = mpsc::channel(1);
+        let stream = BlobStream::start(BlobStreamArgs {
+            p2p,
+            blockstore: Arc::new(InMemoryBlockstore::new()),
+            store,
+            namespace: Namespace::new_v0(&[1, 2, 3]).unwrap(),
+            start_height,
+            syncing_window: Some(DEFAULT_SAMPLING_WINDOW),
+            cancellation_token: CancellationToken::new(),
+            done_tx,
+        });
+
+        (stream, done_rx)
+    }
+
+    #[async_test]
+    async fn empty_heights_from_start_height() {
+        let (store, mut gen) = gen_filled_store(3).await;
+        let store = Arc::new(store);
+        let (p2p, _handle) = P2p::mocked();
+
+        let (mut stream, _done_rx) = start_stream(Arc::new(p2p), store.clone(), Some(2));
+
+        assert_eq!(stream.next().await.unwrap().unwrap(), (2, vec![]));
+        assert_eq!(stream.next().await.unwrap().unwrap(), (3, vec![]));
+
+        store.insert(gen.next()).await.unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), (4, vec![]));
+    }
+
+    #[async_test]
+    async fn starts_from_first_head_of_empty_store() {
+        let store = Arc::new(InMemoryStore::new());
+        let mut gen = ExtendedHeaderGenerator::new_from_height(10);
+        let (p2p, _handle) = P2p::mocked();
+
+        let (mut stream, _done_rx) = start_stream(Arc::new(p2p), store.clone(), None);
+
+        // Let the subscription start waiting for the head
+        sleep(Duration::from_millis(10)).await;
+
+        store.insert(gen.next()).await.unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), (10, vec![]));
+    }
+
+    #[async_test]
+    async fn height_outside_syncing_window() {
+        let month_and_day_ago = Duration::from_secs(31 * 24 * 60 * 60);
+        let mut gen = ExtendedHeaderGenerator::new();
+        gen.set_time(
+            (Time::now() - month_and_day_ago).expect("to not underflow"),
+            Duration::from_secs(1),
+        );
+        let headers = gen.next_many(10);
+        gen.reset_time();
+
+        // Only the headers out of the syncing window are stored, from height 6
+        let store = Arc::new(InMemoryStore::new());
+        store.insert(headers[5..].to_vec()).await.unwrap();
+        let (p2p, _handle) = P2p::mocked();
+        let p2p = Arc::new(p2p);
+
+        let (mut stream, _done_rx) = start_stream(p2p.clone(), store.clone(), Some(3));
+        assert!(matches!(
+            stream.next().await.unwrap(),
+            Err(NodeError::OutsideSyncingWindow(3))
+        ));
+        assert!(stream.next().await.is_none());
+
+        // Stored headers are served even if they are old
+        let (mut stream, _done_rx) = start_stream(p2p.clone(), store.clone(), Some(9));
+        assert_eq!(stream.next().await.unwrap().unwrap(), (9, vec![]));
+        assert_eq!(stream.next().await.unwrap().unwrap(), (10, vec![]));
+
+        // Headers above the head are waited for
+        store.insert(gen.next()).await.unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), (11, vec![]));
+    }
+
+    #[async_test]
+    async fn task_finishes_when_stream_dropped() {
+        let (store, _gen) = gen_filled_store(3).await;
+        let store = Arc::new(store);
+        let (p2p, _handle) = P2p::mocked();
+        let p2p = Arc::new(p2p);
+
+        // Waiting for the next height
+        let (stream, mut done_rx) = start_stream(p2p.clone(), store.clone(), Some(4));
+        drop(stream);
+        assert!(done_rx.recv().await.is_none());
+
+        // Waiting for the first head
+        let (stream, mut done_rx) = start_stream(p2p, Arc::new(InMemoryStore::new()), None);
+        drop(stream);
+        assert!(done_rx.recv().await.is_none());
+    }
+}
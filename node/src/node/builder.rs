@@ -1,4 +1,5 @@
 use std::any::TypeId;
+use std::path::PathBuf;
 use std::time::Duration;
 
 use blockstore::Blockstore;
@@ -10,7 +11,9 @@ use crate::blockstore::InMemoryBlockstore;
 use crate::events::EventSubscriber;
 use crate::network::Network;
 use crate::node::{Node, NodeConfig, Result};
+use crate::peer_tracker::DEFAULT_PEER_BAN_DURATION;
 use crate::store::{InMemoryStore, Store};
+use crate::syncer::TrustedCheckpoint;
 
 const HOUR: u64 = 60 * 60;
 const DAY: u64 = 24 * HOUR;
@@ -37,9 +40,18 @@ where
     network: Option<Network>,
     bootnodes: Vec<Multiaddr>,
     listen: Vec<Multiaddr>,
+    serve_shwap: bool,
+    tls_key_file: Option<PathBuf>,
+    tls_cert_file: Option<PathBuf>,
     sync_batch_size: Option<u64>,
     sampling_window: Option<Duration>,
     pruning_delay: Option<Duration>,
+    archival: bool,
+    historical_sampling_interval: Option<Duration>,
+    trusted_checkpoint: Option<TrustedCheckpoint>,
+    peer_ban_duration: Option<Duration>,
+    mdns: bool,
+    trust_mdns_peers: bool,
 }
 
 /// Representation of all the errors that can occur when interacting with the [`NodeBuilder`].
@@ -56,6 +68,18 @@ pub enum NodeBuilderError {
     /// Pruning delay is smaller than [`MIN_PRUNING_DELAY`].
     #[error("Pruning delay is {0:?} but cannot be smaller than {MIN_PRUNING_DELAY:?}")]
     PruningDelayTooSmall(Duration),
+
+    /// Historical sampling is enabled without the archival mode.
+    #[error("Historical sampling can only be enabled in archival mode")]
+    HistoricalSamplingWithoutArchival,
+
+    /// Trusted checkpoint is invalid.
+    #[error("Invalid trusted checkpoint: {0}")]
+    InvalidTrustedCheckpoint(String),
+
+    /// mDNS is enabled for a public network.
+    #[error("mDNS can only be enabled for custom networks")]
+    MdnsOnPublicNetwork,
 }
 
 impl NodeBuilder<InMemoryBlockstore, InMemoryStore> {
@@ -86,9 +110,18 @@ impl NodeBuilder<InMemoryBlockstore, InMemoryStore> {
             network: None,
             bootnodes: Vec::new(),
             listen: Vec::new(),
+            serve_shwap: false,
+            tls_key_file: None,
+            tls_cert_file: None,
             sync_batch_size: None,
             sampling_window: None,
             pruning_delay: None,
+            archival: false,
+            historical_sampling_interval: None,
+            trusted_checkpoint: None,
+            peer_ban_duration: None,
+            mdns: false,
+            trust_mdns_peers: false,
         }
     }
 }
@@ -121,7 +154,12 @@ where
 
     /// Set the [`Blockstore`] for Bitswap.
     ///
+    /// To keep the blocks encrypted at rest, wrap the blockstore in an
+    /// [`EncryptedBlockstore`] using the same key as the store.
+    ///
     /// **Default:** [`InMemoryBlockstore`]
+    ///
+    /// [`EncryptedBlockstore`]: crate::blockstore::EncryptedBlockstore
     pub fn blockstore<B2>(self, blockstore: B2) -> NodeBuilder<B2, S>
     where
         B2: Blockstore + 'static,
@@ -133,14 +171,27 @@ where
             network: self.network,
             bootnodes: self.bootnodes,
             listen: self.listen,
+            serve_shwap: self.serve_shwap,
+            tls_key_file: self.tls_key_file,
+            tls_cert_file: self.tls_cert_file,
             sync_batch_size: self.sync_batch_size,
             sampling_window: self.sampling_window,
             pruning_delay: self.pruning_delay,
+            archival: self.archival,
+            historical_sampling_interval: self.historical_sampling_interval,
+            trusted_checkpoint: self.trusted_checkpoint,
+            peer_ban_duration: self.peer_ban_duration,
+            mdns: self.mdns,
+            trust_mdns_peers: self.trust_mdns_peers,
         }
     }
 
     /// Set the [`Store`] for headers.
     ///
+    /// Only the `RedbStore` and the `IndexedDbStore` can be encrypted at rest, using their
+    /// `new_encrypted` constructors. Other stores, like the [`InMemoryStore`] or the
+    /// `SqliteStore`, always keep the data unencrypted.
+    ///
     /// **Default:** [`InMemoryStore`]
     pub fn store<S2>(self, store: S2) -> NodeBuilder<B, S2>
     where
@@ -153,9 +204,18 @@ where
             network: self.network,
             bootnodes: self.bootnodes,
             listen: self.listen,
+            serve_shwap: self.serve_shwap,
+            tls_key_file: self.tls_key_file,
+            tls_cert_file: self.tls_cert_file,
             sync_batch_size: self.sync_batch_size,
             sampling_window: self.sampling_window,
             pruning_delay: self.pruning_delay,
+            archival: self.archival,
+            historical_sampling_interval: self.historical_sampling_interval,
+            trusted_checkpoint: self.trusted_checkpoint,
+            peer_ban_duration: self.peer_ban_duration,
+            mdns: self.mdns,
+            trust_mdns_peers: self.trust_mdns_peers,
         }
     }
 
@@ -201,6 +261,32 @@ where
         }
     }
 
+    /// Serve shwap samples, rows and namespace data from the blockstore to other peers.
+    ///
+    /// Each peer is served up to a fixed rate, to avoid being overloaded by a single peer.
+    ///
+    /// **Default:** false
+    pub fn serve_shwap(self, enable: bool) -> Self {
+        NodeBuilder {
+            serve_shwap: enable,
+            ..self
+        }
+    }
+
+    /// Set the TLS private key and certificate chain files, both PEM encoded.
+    ///
+    /// They are used to accept secure websocket connections on `/tls/ws` or `/wss`
+    /// listening addresses. Has no effect in the browser.
+    ///
+    /// **Default:** None
+    pub fn tls_files(self, key_file: impl Into<PathBuf>, cert_file: impl Into<PathBuf>) -> Self {
+        NodeBuilder {
+            tls_key_file: Some(key_file.into()),
+            tls_cert_file: Some(cert_file.into()),
+            ..self
+        }
+    }
+
     /// Maximum number of headers in batch while syncing.
     ///
     /// **Default:** 512
@@ -240,6 +326,94 @@ where
         }
     }
 
+    /// Enable archival mode.
+    ///
+    /// In archival mode headers are synchronized and kept all the way back to genesis, and
+    /// nothing is pruned. Sampling window still applies to sampling, unless historical
+    /// sampling is enabled with [`NodeBuilder::historical_sampling_interval`].
+    ///
+    /// Backfilling continues from the stored headers after a restart.
+    ///
+    /// **Default:** false
+    pub fn archival(self, enable: bool) -> Self {
+        NodeBuilder {
+            archival: enable,
+            ..self
+        }
+    }
+
+    /// Sample blocks outside of the sampling window in archival mode.
+    ///
+    /// Such blocks are sampled after all the blocks within the sampling window, one at a time
+    /// and at most once per `interval`.
+    ///
+    /// **Default:** None, blocks outside of the sampling window are not sampled.
+    pub fn historical_sampling_interval(self, interval: Duration) -> Self {
+        NodeBuilder {
+            historical_sampling_interval: Some(interval),
+            ..self
+        }
+    }
+
+    /// Set the trusted checkpoint from which the chain is verified.
+    ///
+    /// Network head is accepted only if it can be verified from the checkpoint,
+    /// instead of trusting the first head returned by the trusted peers. Checkpoint
+    /// should be recent enough, so that the validator set didn't change since then.
+    /// A new checkpoint older than the 14 days trusting period is rejected.
+    ///
+    /// **Default:** None
+    pub fn trusted_checkpoint(self, checkpoint: impl Into<TrustedCheckpoint>) -> Self {
+        NodeBuilder {
+            trusted_checkpoint: Some(checkpoint.into()),
+            ..self
+        }
+    }
+
+    /// Set the time for which misbehaving peers are banned.
+    ///
+    /// Peers are scored based on the correctness and latency of their responses and
+    /// messages. Peers whose score drops too low are disconnected and can't reconnect
+    /// until the ban expires. Trusted peers are never banned.
+    ///
+    /// **Default:** 1 hour
+    pub fn peer_ban_duration(self, dur: Duration) -> Self {
+        NodeBuilder {
+            peer_ban_duration: Some(dur),
+            ..self
+        }
+    }
+
+    /// Enable discovery of peers in the local network with mDNS.
+    ///
+    /// Peers discovered this way are dialed, which allows nodes of a local devnet to
+    /// find each other and a local bridge without any configuration. It can only be
+    /// used with custom networks. mDNS is not available in browsers, where this
+    /// setting is ignored.
+    ///
+    /// **Default:** false
+    pub fn mdns(self, enable: bool) -> Self {
+        NodeBuilder {
+            mdns: enable,
+            ..self
+        }
+    }
+
+    /// Trust the peers discovered with mDNS, while they are announced.
+    ///
+    /// Trusted peers are never banned and the network head is requested only from them,
+    /// so this lets a node of a local devnet start without any bootnodes. Any host in
+    /// the local network can announce itself, so enable it only in a network you control.
+    /// Requires [`NodeBuilder::mdns`] to be enabled.
+    ///
+    /// **Default:** false
+    pub fn trust_mdns_peers(self, trust: bool) -> Self {
+        NodeBuilder {
+            trust_mdns_peers: trust,
+            ..self
+        }
+    }
+
     fn build_config(self) -> Result<NodeConfig<B, S>, NodeBuilderError> {
         let network = self.network.ok_or(NodeBuilderError::NetworkNotSpecified)?;
 
@@ -249,7 +423,15 @@ where
             self.bootnodes
         };
 
-        if bootnodes.is_empty() && self.listen.is_empty() {
+        if self.mdns && !network.is_custom() {
+            return Err(NodeBuilderError::MdnsOnPublicNetwork);
+        }
+
+        if self.trust_mdns_peers && !self.mdns {
+            warn!("Trusting mDNS peers has no effect, because mDNS is disabled.");
+        }
+
+        if bootnodes.is_empty() && self.listen.is_empty() && !self.mdns {
             // It is a valid scenario for user to create a node without any bootnodes
             // and listening addresses. However it may not be what they wanted. Because
             // of that we display a warning.
@@ -291,7 +473,33 @@ where
 
         let pruning_window = sampling_window.saturating_add(pruning_delay);
 
-        info!("Sampling window: {sampling_window:?}, Pruning window: {pruning_window:?}",);
+        if self.historical_sampling_interval.is_some() && !self.archival {
+            return Err(NodeBuilderError::HistoricalSamplingWithoutArchival);
+        }
+
+        if self.archival && in_memory_stores_used {
+            warn!("Archival mode is used with in-memory stores. All the headers will be kept in memory.");
+        }
+
+        match self.trusted_checkpoint {
+            Some(TrustedCheckpoint::Hash { height: 0, .. }) => {
+                return Err(NodeBuilderError::InvalidTrustedCheckpoint(
+                    "height must be greater than 0".to_owned(),
+                ));
+            }
+            Some(TrustedCheckpoint::Header(ref header)) => {
+                header
+                    .validate()
+                    .map_err(|e| NodeBuilderError::InvalidTrustedCheckpoint(e.to_string()))?;
+            }
+            _ => {}
+        }
+
+        if self.archival {
+            info!("Sampling window: {sampling_window:?}, Archival mode, pruning disabled");
+        } else {
+            info!("Sampling window: {sampling_window:?}, Pruning window: {pruning_window:?}",);
+        }
 
         Ok(NodeConfig {
             blockstore: self.blockstore,
@@ -300,9 +508,18 @@ where
             p2p_local_keypair: self.keypair.unwrap_or_else(Keypair::generate_ed25519),
             p2p_bootnodes: bootnodes,
             p2p_listen_on: self.listen,
+            p2p_serve_shwap: self.serve_shwap,
+            p2p_tls_key_file: self.tls_key_file,
+            p2p_tls_cert_file: self.tls_cert_file,
+            p2p_peer_ban_duration: self.peer_ban_duration.unwrap_or(DEFAULT_PEER_BAN_DURATION),
+            p2p_mdns: self.mdns,
+            p2p_mdns_trust: self.trust_mdns_peers,
             sync_batch_size: self.sync_batch_size.unwrap_or(512),
             sampling_window,
             pruning_window,
+            archival: self.archival,
+            historical_sampling_interval: self.historical_sampling_interval,
+            trusted_checkpoint: self.trusted_checkpoint,
         })
     }
 }
@@ -0,0 +1,301 @@
+//! Retrieval of namespaced data, cached in the blockstore.
+//!
+//! Verified [`RowNamespaceData`] is kept in the blockstore under its Shwap CID and
+//! its CID is tracked in the [`SamplingMetadata`] of the header, so that the pruner
+//! removes it together with the header.
+//!
+//! [`SamplingMetadata`]: crate::store::SamplingMetadata
+
+use std::time::Duration;
+
+use blockstore::Blockstore;
+use celestia_proto::bitswap::Block;
+use celestia_proto::shwap::RowNamespaceData as RawRowNamespaceData;
+use celestia_types::nmt::Namespace;
+use celestia_types::row_namespace_data::{RowNamespaceData, RowNamespaceDataId};
+use celestia_types::{Blob, ExtendedHeader};
+use cid::Cid;
+use futures::stream::FuturesOrdered;
+use futures::TryStreamExt;
+use prost::Message;
+use tracing::{debug, warn};
+
+use crate::p2p::shwap::{convert_cid, get_block_container};
+use crate::p2p::{rows_containing_namespace, P2p, P2pError, Result};
+use crate::store::Store;
+
+/// Get a verified [`RowNamespaceData`], from the blockstore if it was already retrieved,
+/// or from the network otherwise.
+pub(crate) async fn get_row_namespace_data<B, S>(
+    p2p: &P2p,
+    blockstore: &B,
+    store: &S,
+    namespace: Namespace,
+    row_index: u16,
+    block_height: u64,
+    timeout: Option<Duration>,
+) -> Result<RowNamespaceData>
+where
+    B: Blockstore,
+    S: Store,
+{
+    let id = RowNamespaceDataId::new(namespace, row_index, block_height).map_err(P2pError::Cid)?;
+    let cid = convert_cid(&id.into())?;
+
+    if let Some(row) = get_cached(blockstore, id, &cid).await {
+        return Ok(row);
+    }
+
+    let row = p2p
+        .get_row_namespace_data(namespace, row_index, block_height, timeout)
+        .await?;
+
+    // Bitswap puts the verified block into the blockstore, so it only needs to be tracked.
+    if let Err(e) = store.add_sampling_cids(block_height, vec![cid]).await {
+        warn!("Failed to track namespace data of block {block_height}: {e}");
+    }
+
+    Ok(row)
+}
+
+/// Get all blobs with provided namespace in the block corresponding to this header.
+///
+/// Rows are retrieved with [`get_row_namespace_data`]. If bitswap fails, the data is
+/// requested with `shrex-nd` protocol instead and put into the blockstore.
+pub(crate) async fn get_all_blobs<B, S>(
+    p2p: &P2p,
+    blockstore: &B,
+    store: &S,
+    header: &ExtendedHeader,
+    namespace: Namespace,
+    timeout: Option<Duration>,
+) -> Result<Vec<Blob>>
+where
+    B: Blockstore,
+    S: Store,
+{
+    let height = header.height().value();
+    let app_version = header.app_version()?;
+    let rows_to_fetch = rows_containing_namespace(&header.dah, namespace);
+
+    let futs = rows_to_fetch
+        .iter()
+        .copied()
+        .map(|row_idx| {
+            get_row_namespace_data(p2p, blockstore, store, namespace, row_idx, height, timeout)
+        })
+        .collect::<FuturesOrdered<_>>();
+
+    let rows: Vec<_> = match futs.try_collect().await {
+        Ok(rows) => rows,
+        Err(e) if e.is_fatal() => return Err(e),
+        Err(e) => {
+            debug!("Bitswap failed to fetch namespace data ({e}), falling back to shrex-nd");
+            let rows = p2p
+                .get_shrex_namespace_data(header, namespace, timeout)
+                .await?;
+
+            put_rows(blockstore, store, namespace, height, &rows_to_fetch, &rows).await;
+            rows
+        }
+    };
+    let shares = rows.iter().flat_map(|row| row.shares.iter());
+
+    Ok(Blob::reconstruct_all(shares, app_version)?)
+}
+
+/// Get a [`RowNamespaceData`] from the blockstore.
+///
+/// Only verified data is put into the blockstore, so it is not verified again.
+async fn get_cached<B>(
+    blockstore: &B,
+    id: RowNamespaceDataId,
+    cid: &Cid,
+) -> Option<RowNamespaceData>
+where
+    B: Blockstore,
+{
+    let block = match blockstore.get(cid).await {
+        Ok(Some(block)) => block,
+        Ok(None) => return None,
+        Err(e) => {
+            warn!("Failed to read namespace data from blockstore: {e}");
+            return None;
+        }
+    };
+
+    let decoded = get_block_container(cid, &block)
+        .and_then(|container| Ok(RowNamespaceData::decode(id, &container[..])?));
+
+    match decoded {
+        Ok(row) => Some(row),
+        Err(e) => {
+            warn!("Invalid namespace data in blockstore ({cid}): {e}");
+            None
+        }
+    }
+}
+
+/// Put verified rows into the blockstore.
+///
+/// Rows are tracked in the store first, so that they are never left behind when the
+/// header is pruned.
+async fn put_rows<B, S>(
+    blockstore: &B,
+    store: &S,
+    namespace: Namespace,
+    height: u64,
+    row_indexes: &[u16],
+    rows: &[RowNamespaceData],
+) where
+    B: Blockstore,
+    S: Store,
+{
+    let blocks = row_indexes
+        .iter()
+        .zip(rows)
+        .map(|(row_idx, row)| {
+            let id = RowNamespaceDataId::new(namespace, *row_idx, height).map_err(P2pError::Cid)?;
+            let cid = convert_cid(&id.into())?;
+            let block = Block {
+                cid: cid.to_bytes(),
+                container: RawRowNamespaceData::from(row.clone()).encode_to_vec(),
+            };
+            Ok((cid, block.encode_to_vec()))
+        })
+        .collect::<Result<Vec<_>>>();
+
+    let blocks = match blocks {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            warn!("Failed to encode namespace data of block {height}: {e}");
+            return;
+        }
+    };
+
+    let cids = blocks.iter().map(|(cid, _)| *cid).collect();
+
+    if let Err(e) = store.add_sampling_cids(height, cids).await {
+        warn!("Failed to track namespace data of block {height}: {e}");
+        return;
+    }
+
+    if let Err(e) = blockstore.put_many_keyed(blocks).await {
+        warn!("Failed to cache namespace data of block {height}: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockstore::InMemoryBlockstore;
+    use crate::p2p::P2pCmd;
+    use crate::store::{InMemoryStore, SamplingStatus};
+    use crate::test_utils::async_test;
+    use celestia_types::consts::appconsts::AppVersion;
+    use celestia_types::test_utils::{generate_dummy_eds, ExtendedHeaderGenerator};
+    use celestia_types::{DataAvailabilityHeader, ExtendedDataSquare};
+
+    async fn store_with_header(eds: &ExtendedDataSquare) -> (InMemoryStore, ExtendedHeader) {
+        let store = InMemoryStore::new();
+        let header =
+            ExtendedHeaderGenerator::new().next_with_dah(DataAvailabilityHeader::from_eds(eds));
+        store.insert(header.clone()).await.unwrap();
+        (store, header)
+    }
+
+    fn encode_block(id: RowNamespaceDataId, row: &RowNamespaceData) -> Vec<u8> {
+        Block {
+            cid: convert_cid(&id.into()).unwrap().to_bytes(),
+            container: RawRowNamespaceData::from(row.clone()).encode_to_vec(),
+        }
+        .encode_to_vec()
+    }
+
+    #[async_test]
+    async fn cached_row_served_without_network() {
+        let eds = generate_dummy_eds(4, AppVersion::V2);
+        let namespace = eds.row(0).unwrap()[0].namespace();
+        let (store, header) = store_with_header(&eds).await;
+        let blockstore = InMemoryBlockstore::new();
+        let (p2p, mut handle) = P2p::mocked();
+
+        let (id, row) = eds
+            .get_namespace_data(namespace, &header.dah, 1)
+            .unwrap()
+            .remove(0);
+
+        let (res, _) = tokio::join!(
+            get_row_namespace_data(&p2p, &blockstore, &store, namespace, 0, 1, None),
+            async {
+                let (cid, respond_to) = handle.expect_get_shwap_cid().await;
+                let block = encode_block(id, &row);
+                // Simulate bitswap putting the received block into the blockstore.
+                blockstore.put_keyed(&cid, &block).await.unwrap();
+                respond_to.send(Ok(block)).unwrap();
+            }
+        );
+        assert_eq!(res.unwrap(), row);
+
+        let cid = convert_cid(&id.into()).unwrap();
+        let metadata = store.get_sampling_metadata(1).await.unwrap().unwrap();
+        assert_eq!(metadata.status, SamplingStatus::Unknown);
+        assert_eq!(metadata.cids, vec![cid]);
+
+        let cached = get_row_namespace_data(&p2p, &blockstore, &store, namespace, 0, 1, None)
+            .await
+            .unwrap();
+        assert_eq!(cached, row);
+        handle.expect_no_cmd().await;
+    }
+
+    #[async_test]
+    async fn shrex_rows_cached() {
+        let eds = generate_dummy_eds(4, AppVersion::V2);
+        let namespace = eds.row(0).unwrap()[0].namespace();
+        let (store, header) = store_with_header(&eds).await;
+        let blockstore = InMemoryBlockstore::new();
+        let (p2p, mut handle) = P2p::mocked();
+
+        let rows = eds.get_namespace_data(namespace, &header.dah, 1).unwrap();
+        let blobs = Blob::reconstruct_all(
+            rows.iter().flat_map(|(_, row)| row.shares.iter()),
+            AppVersion::V2,
+        )
+        .unwrap();
+
+        let (res, _) = tokio::join!(
+            get_all_blobs(&p2p, &blockstore, &store, &header, namespace, None),
+            async {
+                for _ in &rows {
+                    let (_, respond_to) = handle.expect_get_shwap_cid().await;
+                    respond_to.send(Err(P2pError::BitswapQueryTimeout)).unwrap();
+                }
+
+                match handle.try_recv_cmd().await {
+                    Some(P2pCmd::ShrexRequest { respond_to, .. }) => {
+                        let payload = rows
+                            .iter()
+                            .flat_map(|(_, row)| {
+                                RawRowNamespaceData::from(row.clone())
+                                    .encode_length_delimited_to_vec()
+                            })
+                            .collect();
+                        respond_to.send(Ok(payload)).unwrap();
+                    }
+                    cmd => panic!("Expecting ShrexRequest, but received: {cmd:?}"),
+                }
+            }
+        );
+        assert_eq!(res.unwrap(), blobs);
+
+        let metadata = store.get_sampling_metadata(1).await.unwrap().unwrap();
+        assert_eq!(metadata.cids.len(), rows.len());
+
+        let cached = get_all_blobs(&p2p, &blockstore, &store, &header, namespace, None)
+            .await
+            .unwrap();
+        assert_eq!(cached, blobs);
+        handle.expect_no_cmd().await;
+    }
+}
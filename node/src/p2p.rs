@@ -6,31 +6,44 @@
 //! - libp2p-kad
 //! - libp2p-autonat
 //! - libp2p-ping
+//! - libp2p-mdns (opt-in, native only)
 //! - header-sub topic on libp2p-gossipsub
 //! - fraud-sub topic on libp2p-gossipsub
 //! - header-ex client
 //! - header-ex server
 //! - bitswap 1.2.0
 //! - shwap - celestia's data availability protocol on top of bitswap
+//! - shwap server (opt-in)
+//! - shrex-nd client
+//! - shrex-eds client
+//! - shrex-sub topic on libp2p-gossipsub
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::future::poll_fn;
+use std::mem;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::task::Poll;
 use std::time::Duration;
 
 use blockstore::Blockstore;
 use celestia_proto::p2p::pb::{header_request, HeaderRequest};
+use celestia_proto::share::p2p::shrex::sub::RecentEdsNotification;
+use celestia_proto::shwap::RowNamespaceData as RawRowNamespaceData;
+use celestia_types::consts::appconsts::SHARE_SIZE;
 use celestia_types::fraud_proof::BadEncodingFraudProof;
 use celestia_types::hash::Hash;
 use celestia_types::nmt::{Namespace, NamespacedSha2Hasher};
+use celestia_types::p2p::BandwidthStats;
 use celestia_types::row::{Row, RowId};
 use celestia_types::row_namespace_data::{RowNamespaceData, RowNamespaceDataId};
 use celestia_types::sample::{Sample, SampleId};
-use celestia_types::{Blob, ExtendedHeader, FraudProof};
+use celestia_types::{
+    DataAvailabilityHeader, ExtendedDataSquare, ExtendedHeader, FraudProof, Share,
+};
 use cid::Cid;
-use futures::stream::FuturesOrdered;
-use futures::{StreamExt, TryStreamExt};
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
 use libp2p::core::transport::ListenerId;
 use libp2p::{
     autonat,
@@ -40,42 +53,56 @@ use libp2p::{
     identity::Keypair,
     kad,
     multiaddr::Protocol,
-    ping,
+    ping, request_response,
     swarm::{
         dial_opts::{DialOpts, PeerCondition},
         ConnectionId, DialError, NetworkBehaviour, NetworkInfo, Swarm, SwarmEvent,
     },
     Multiaddr, PeerId,
 };
+use prost::Message;
+use rand::seq::SliceRandom;
 use smallvec::SmallVec;
 use tendermint_proto::Protobuf;
 use tokio::select;
 use tokio::sync::{mpsc, oneshot, watch};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, instrument, trace, warn};
+use web_time::Instant;
 
+mod bandwidth;
+mod bitswap;
 mod connection_control;
 mod header_ex;
 pub(crate) mod header_session;
+mod mdns;
+mod shrex;
 pub(crate) mod shwap;
 mod swarm;
 
 use crate::block_ranges::BlockRange;
 use crate::events::{EventPublisher, NodeEvent};
 use crate::executor::{self, spawn, Interval, JoinHandle};
+use crate::metrics::Metrics;
+use crate::p2p::bandwidth::BandwidthMeter;
 use crate::p2p::header_ex::{HeaderExBehaviour, HeaderExConfig};
 use crate::p2p::header_session::HeaderSession;
+use crate::p2p::mdns::MdnsChange;
+use crate::p2p::shrex::{
+    new_shrex_behaviour, ShrexBehaviour, ShrexEvent, ShrexProtocol, ShrexRequest, SHREX_SUB_TOPIC,
+};
 use crate::p2p::shwap::{convert_cid, get_block_container, ShwapMultihasher};
 use crate::p2p::swarm::new_swarm;
 use crate::peer_tracker::PeerTracker;
-use crate::peer_tracker::PeerTrackerInfo;
-use crate::store::Store;
+use crate::peer_tracker::{PeerInfo, PeerReport, PeerTrackerInfo};
+use crate::store::{Store, StoreError};
 use crate::utils::{
     celestia_protocol_id, fraudsub_ident_topic, gossipsub_ident_topic, MultiaddrExt,
     OneshotResultSender, OneshotResultSenderExt, OneshotSenderExt, Token,
 };
 
 pub use crate::p2p::header_ex::HeaderExError;
+pub use crate::p2p::shrex::ShrexError;
 
 // Minimal number of peers that we want to maintain connection to.
 // If we have fewer peers than that, we will try to reconnect / discover
@@ -85,6 +112,21 @@ const MIN_CONNECTED_PEERS: u64 = 4;
 // Maximum size of a [`Multihash`].
 pub(crate) const MAX_MH_SIZE: usize = 64;
 
+// Maximum number of peers tried for a single shrex request.
+const SHREX_MAX_ATTEMPTS: usize = 3;
+
+// Maximum number of columns requested at once when an EDS can't be reconstructed from rows.
+const COLUMNS_IN_FLIGHT: usize = 8;
+
+// Maximum number of peers saved in the store, to be dialed on the next start.
+const MAX_KNOWN_PEERS: usize = 64;
+
+// Saved peers which were not seen for longer than this are forgotten.
+const KNOWN_PEER_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+// How often the known peers are saved in the store.
+const KNOWN_PEERS_SAVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
 // all fraud proofs for height bigger than head height by this threshold
 // will be ignored
 const FRAUD_PROOF_HEAD_HEIGHT_THRESHOLD: u64 = 20;
@@ -106,6 +148,10 @@ pub enum P2pError {
     #[error("Failed to initialize noise: {0}")]
     NoiseInit(String),
 
+    /// Failed to initialize mDNS behaviour.
+    #[error("Failed to initialize mDNS: {0}")]
+    MdnsInit(String),
+
     /// The worker has died.
     #[error("Worker died")]
     WorkerDied,
@@ -122,6 +168,10 @@ pub enum P2pError {
     #[error("HeaderEx: {0}")]
     HeaderEx(#[from] HeaderExError),
 
+    /// An error propagated from the `shrex` protocols.
+    #[error("Shrex: {0}")]
+    Shrex(#[from] ShrexError),
+
     /// Bootnode address is missing its peer ID.
     #[error("Bootnode multiaddrs without peer ID: {0:?}")]
     BootnodeAddrsWithoutPeerId(Vec<Multiaddr>),
@@ -146,9 +196,21 @@ pub enum P2pError {
     #[error("Shwap: {0}")]
     Shwap(String),
 
+    /// Block was incorrectly encoded by its producer.
+    #[error("Bad encoding detected at height {}", .0.height())]
+    BadEncoding(Box<BadEncodingFraudProof>),
+
     /// An error propagated from [`celestia_types`].
     #[error(transparent)]
     CelestiaTypes(#[from] celestia_types::Error),
+
+    /// Dialing a peer failed.
+    #[error("Dial failed: {0}")]
+    Dial(String),
+
+    /// An error propagated from the [`Store`].
+    #[error("Store: {0}")]
+    Store(#[from] StoreError),
 }
 
 impl P2pError {
@@ -159,18 +221,23 @@ impl P2pError {
         match self {
             P2pError::GossipsubInit(_)
             | P2pError::NoiseInit(_)
+            | P2pError::MdnsInit(_)
             | P2pError::TlsInit(_)
             | P2pError::WorkerDied
             | P2pError::ChannelClosedUnexpectedly
             | P2pError::BootnodeAddrsWithoutPeerId(_) => true,
             P2pError::NoConnectedPeers
             | P2pError::HeaderEx(_)
+            | P2pError::Shrex(_)
             | P2pError::Bitswap(_)
             | P2pError::ProtoDecodeFailed(_)
             | P2pError::Cid(_)
             | P2pError::BitswapQueryTimeout
             | P2pError::Shwap(_)
-            | P2pError::CelestiaTypes(_) => false,
+            | P2pError::BadEncoding(_)
+            | P2pError::CelestiaTypes(_)
+            | P2pError::Dial(_) => false,
+            P2pError::Store(e) => e.is_fatal(),
         }
     }
 }
@@ -203,6 +270,8 @@ pub(crate) struct P2p {
     join_handle: JoinHandle,
     peer_tracker_info_watcher: watch::Receiver<PeerTrackerInfo>,
     local_peer_id: PeerId,
+    metrics: Metrics,
+    bandwidth_meter: BandwidthMeter,
 }
 
 /// Arguments used to configure the [`P2p`].
@@ -219,12 +288,26 @@ where
     pub bootnodes: Vec<Multiaddr>,
     /// List of the addresses on which to listen for incoming connections.
     pub listen_on: Vec<Multiaddr>,
+    /// Whether to serve shwap data from the blockstore to other peers.
+    pub serve_shwap: bool,
+    /// Path of the PEM encoded TLS private key used for secure websocket listeners.
+    pub tls_key_file: Option<PathBuf>,
+    /// Path of the PEM encoded TLS certificate chain used for secure websocket listeners.
+    pub tls_cert_file: Option<PathBuf>,
     /// The store for headers.
     pub blockstore: Arc<B>,
     /// The store for headers.
     pub store: Arc<S>,
     /// Event publisher.
     pub event_pub: EventPublisher,
+    /// Metrics of the node.
+    pub metrics: Metrics,
+    /// Time for which misbehaving peers are banned.
+    pub peer_ban_duration: Duration,
+    /// Whether to discover peers in the local network with mDNS.
+    pub mdns: bool,
+    /// Whether to trust the peers discovered with mDNS while they are announced.
+    pub mdns_trust: bool,
 }
 
 #[derive(Debug)]
@@ -235,6 +318,8 @@ pub(crate) enum P2pCmd {
     HeaderExRequest {
         request: HeaderRequest,
         respond_to: OneshotResultSender<Vec<ExtendedHeader>, P2pError>,
+        /// Receives the peer the request was sent to, unless it was sent to multiple peers.
+        peer_tx: Option<oneshot::Sender<PeerId>>,
     },
     Listeners {
         respond_to: oneshot::Sender<Vec<Multiaddr>>,
@@ -251,16 +336,50 @@ pub(crate) enum P2pCmd {
         peer_id: PeerId,
         is_trusted: bool,
     },
+    Connect {
+        addr: Multiaddr,
+        respond_to: OneshotResultSender<(), P2pError>,
+    },
+    Disconnect {
+        peer_id: PeerId,
+    },
+    SetPeerBlocked {
+        peer_id: PeerId,
+        is_blocked: bool,
+        respond_to: OneshotResultSender<(), P2pError>,
+    },
+    BlockedPeers {
+        respond_to: oneshot::Sender<Vec<PeerId>>,
+    },
+    PeerInfo {
+        peer_id: PeerId,
+        respond_to: oneshot::Sender<Option<PeerInfo>>,
+    },
     GetShwapCid {
         cid: Cid,
         respond_to: OneshotResultSender<Vec<u8>, P2pError>,
     },
+    ShrexRequest {
+        protocol: ShrexProtocol,
+        request: ShrexRequest,
+        respond_to: OneshotResultSender<Vec<u8>, P2pError>,
+        /// Receives the peer the request was sent to.
+        peer_tx: oneshot::Sender<PeerId>,
+    },
+    PublishBadEncodingFraudProof {
+        befp: Box<BadEncodingFraudProof>,
+    },
     GetNetworkCompromisedToken {
         respond_to: oneshot::Sender<Token>,
     },
     GetNetworkHead {
         respond_to: oneshot::Sender<Option<ExtendedHeader>>,
     },
+    ReportPeer {
+        peer: PeerId,
+        report: PeerReport,
+    },
+    ReportSamplingFailure,
 }
 
 impl P2p {
@@ -273,15 +392,26 @@ impl P2p {
         validate_bootnode_addrs(&args.bootnodes)?;
 
         let local_peer_id = PeerId::from(args.local_keypair.public());
+        let metrics = args.metrics.clone();
 
-        let peer_tracker = Arc::new(PeerTracker::new(args.event_pub.clone()));
+        let peer_tracker = Arc::new(
+            PeerTracker::new(args.event_pub.clone()).with_ban_duration(args.peer_ban_duration),
+        );
         let peer_tracker_info_watcher = peer_tracker.info_watcher();
 
         let cancellation_token = CancellationToken::new();
         let (cmd_tx, cmd_rx) = mpsc::channel(16);
 
-        let mut worker =
-            Worker::new(args, cancellation_token.child_token(), cmd_rx, peer_tracker).await?;
+        let bandwidth_meter = BandwidthMeter::new();
+
+        let mut worker = Worker::new(
+            args,
+            cancellation_token.child_token(),
+            cmd_rx,
+            peer_tracker,
+            bandwidth_meter.clone(),
+        )
+        .await?;
 
         let join_handle = spawn(async move {
             worker.run().await;
@@ -293,6 +423,8 @@ impl P2p {
             join_handle,
             peer_tracker_info_watcher,
             local_peer_id,
+            metrics,
+            bandwidth_meter,
         })
     }
 
@@ -312,6 +444,8 @@ impl P2p {
             join_handle,
             peer_tracker_info_watcher: peer_tracker_rx,
             local_peer_id: PeerId::random(),
+            metrics: Metrics::default(),
+            bandwidth_meter: BandwidthMeter::new(),
         };
 
         let handle = crate::test_utils::MockP2pHandle {
@@ -352,6 +486,21 @@ impl P2p {
         self.peer_tracker_info_watcher.clone()
     }
 
+    /// Get the bandwidth used by all the peers.
+    pub fn bandwidth_stats(&self) -> BandwidthStats {
+        self.bandwidth_meter.stats()
+    }
+
+    /// Get the bandwidth used by the peer.
+    pub fn bandwidth_for_peer(&self, peer_id: &PeerId) -> BandwidthStats {
+        self.bandwidth_meter.stats_for_peer(peer_id)
+    }
+
+    /// Get the bandwidth used by the protocol.
+    pub fn bandwidth_for_protocol(&self, protocol: &str) -> BandwidthStats {
+        self.bandwidth_meter.stats_for_protocol(protocol)
+    }
+
     /// A reference to the current [`PeerTrackerInfo`].
     pub fn peer_tracker_info(&self) -> watch::Ref<PeerTrackerInfo> {
         self.peer_tracker_info_watcher.borrow()
@@ -405,6 +554,7 @@ impl P2p {
         self.send_command(P2pCmd::HeaderExRequest {
             request,
             respond_to: tx,
+            peer_tx: None,
         })
         .await?;
 
@@ -456,32 +606,41 @@ impl P2p {
 
         let range = height..=height + amount - 1;
 
-        let mut session = HeaderSession::new(range, self.cmd_tx.clone());
-        let headers = session.run().await?;
-
-        // `.validate()` is called on each header separately by `HeaderExClientHandler`.
-        //
-        // The last step is to verify that all headers are from the same chain
+        // `.validate()` is called on each header separately by `HeaderExClientHandler`,
+        // while the session verifies that all headers are from the same chain as `from`
         // and indeed connected with the next one.
-        from.verify_adjacent_range(&headers)
-            .map_err(|_| HeaderExError::InvalidResponse)?;
+        let mut session =
+            HeaderSession::new(range, self.cmd_tx.clone()).with_anchor(from.to_owned());
 
-        Ok(headers)
+        session.run().await
     }
 
-    /// Request a list of ranges with the `header-ex` protocol
+    /// Request a range of headers with the `header-ex` protocol
     ///
-    /// For each of the ranges, headers are verified against each other, but it's the caller
-    /// responsibility to verify range edges against headers existing in the store.
+    /// Headers are verified against each other and against the known headers adjacent
+    /// to the range, `prev` preceding it and `next` following it, if they are given.
+    /// Peers which sent headers not connected with them are reported. It's still the
+    /// caller responsibility to verify range edges against headers existing in the store.
     pub(crate) async fn get_unverified_header_range(
         &self,
         range: BlockRange,
+        prev: Option<ExtendedHeader>,
+        next: Option<ExtendedHeader>,
     ) -> Result<Vec<ExtendedHeader>> {
         if range.is_empty() {
             return Err(HeaderExError::InvalidRequest.into());
         }
 
         let mut session = HeaderSession::new(range, self.cmd_tx.clone());
+
+        if let Some(prev) = prev {
+            session = session.with_anchor(prev);
+        }
+
+        if let Some(next) = next {
+            session = session.with_tail_anchor(next);
+        }
+
         let headers = session.run().await?;
 
         let Some(head) = headers.first() else {
@@ -509,9 +668,10 @@ impl P2p {
         .await?;
 
         let data = match timeout {
-            Some(dur) => executor::timeout(dur, rx)
-                .await
-                .map_err(|_| P2pError::BitswapQueryTimeout)???,
+            Some(dur) => executor::timeout(dur, rx).await.map_err(|_| {
+                self.metrics.observe_bitswap_query_timeout();
+                P2pError::BitswapQueryTimeout
+            })???,
             None => rx.await??,
         };
 
@@ -549,6 +709,14 @@ impl P2p {
         Ok(sample)
     }
 
+    /// Report that a sample couldn't be retrieved on bitswap protocol.
+    ///
+    /// Bitswap doesn't expose which peer failed to send the block, so all the connected
+    /// peers expected to hold the block data get a mild penalty.
+    pub(crate) async fn report_sampling_failure(&self) -> Result<()> {
+        self.send_command(P2pCmd::ReportSamplingFailure).await
+    }
+
     /// Request a [`RowNamespaceData`] on bitswap protocol.
     pub async fn get_row_namespace_data(
         &self,
@@ -567,33 +735,316 @@ impl P2p {
         Ok(row_namespace_data)
     }
 
-    /// Request all blobs with provided namespace in the block corresponding to this header
-    /// using bitswap protocol.
-    pub async fn get_all_blobs(
+    /// Send a request on one of the `shrex` protocols.
+    ///
+    /// Each attempt is sent to a different random peer that serves `shrex`.
+    /// Peers are reported based on whether their response passes `decode`.
+    async fn shrex_request<T, F>(
+        &self,
+        protocol: ShrexProtocol,
+        request: ShrexRequest,
+        timeout: Option<Duration>,
+        mut decode: F,
+    ) -> Result<T>
+    where
+        F: FnMut(Vec<u8>) -> Result<T>,
+    {
+        let mut last_err = P2pError::Shrex(ShrexError::NoPeers);
+
+        for _ in 0..SHREX_MAX_ATTEMPTS {
+            let (tx, rx) = oneshot::channel();
+            let (peer_tx, peer_rx) = oneshot::channel();
+            let sent_at = Instant::now();
+
+            self.send_command(P2pCmd::ShrexRequest {
+                protocol,
+                request: request.clone(),
+                respond_to: tx,
+                peer_tx,
+            })
+            .await?;
+
+            let res = match timeout {
+                Some(dur) => match executor::timeout(dur, rx).await {
+                    Ok(res) => res?,
+                    Err(_) => Err(ShrexError::OutboundFailure(
+                        request_response::OutboundFailure::Timeout,
+                    )
+                    .into()),
+                },
+                None => rx.await?,
+            };
+
+            let res = res.and_then(&mut decode);
+            let latency = sent_at.elapsed();
+
+            // Peer is sent when the request is sent, so it's known by now
+            if let Ok(peer) = peer_rx.await {
+                let report = match res {
+                    Ok(_) => Some(PeerReport::Success { latency }),
+                    Err(P2pError::Shrex(ShrexError::InvalidResponse)) => {
+                        Some(PeerReport::InvalidResponse)
+                    }
+                    // Failures of the request itself are reported by the worker
+                    Err(_) => None,
+                };
+
+                if let Some(report) = report {
+                    self.send_command(P2pCmd::ReportPeer { peer, report })
+                        .await?;
+                }
+            }
+
+            match res {
+                Ok(val) => return Ok(val),
+                Err(e @ P2pError::Shrex(ShrexError::NoPeers)) => return Err(e),
+                Err(e) if e.is_fatal() => return Err(e),
+                Err(e) => {
+                    debug!("shrex request failed: {e}");
+                    last_err = e;
+                }
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Request all [`RowNamespaceData`] with provided namespace in the block
+    /// corresponding to this header using `shrex-nd` protocol.
+    pub async fn get_shrex_namespace_data(
         &self,
         header: &ExtendedHeader,
         namespace: Namespace,
         timeout: Option<Duration>,
-    ) -> Result<Vec<Blob>> {
+    ) -> Result<Vec<RowNamespaceData>> {
         let height = header.height().value();
+        let rows_to_fetch = rows_containing_namespace(&header.dah, namespace);
+
+        if rows_to_fetch.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let request = ShrexRequest::namespace_data(height, namespace);
+
+        self.shrex_request(ShrexProtocol::NamespaceData, request, timeout, |payload| {
+            let mut buf = &payload[..];
+            let mut rows = Vec::with_capacity(rows_to_fetch.len());
+
+            for row_idx in &rows_to_fetch {
+                let raw = RawRowNamespaceData::decode_length_delimited(&mut buf)
+                    .map_err(|_| ShrexError::InvalidResponse)?;
+                let id =
+                    RowNamespaceDataId::new(namespace, *row_idx, height).map_err(P2pError::Cid)?;
+                let row =
+                    RowNamespaceData::from_raw(id, raw).map_err(|_| ShrexError::InvalidResponse)?;
+
+                row.verify(id, &header.dah)
+                    .map_err(|_| ShrexError::InvalidResponse)?;
+
+                rows.push(row);
+            }
+
+            if !buf.is_empty() {
+                return Err(ShrexError::InvalidResponse.into());
+            }
+
+            Ok(rows)
+        })
+        .await
+    }
+
+    /// Request [`ExtendedDataSquare`] of the block corresponding to this header.
+    ///
+    /// Rows are requested in parallel with shwap and the square is repaired from
+    /// them. If that fails, the square is requested with `shrex-eds` protocol instead.
+    pub async fn get_eds(
+        &self,
+        header: &ExtendedHeader,
+        timeout: Option<Duration>,
+    ) -> Result<ExtendedDataSquare> {
+        match self.get_shwap_eds(header, timeout).await {
+            Ok(eds) => Ok(eds),
+            Err(e) if e.is_fatal() => Err(e),
+            // Block is incorrectly encoded, fetching it again won't help
+            Err(e @ P2pError::BadEncoding(_)) => Err(e),
+            Err(e) => {
+                debug!("Failed to reconstruct EDS with shwap ({e}), falling back to shrex-eds");
+                self.get_shrex_eds(header, timeout).await
+            }
+        }
+    }
+
+    /// Request [`ExtendedDataSquare`] of the block corresponding to this header
+    /// using shwap [`Row`]s and [`Sample`]s.
+    ///
+    /// Original rows are requested first. For each row that fails, one of the parity
+    /// rows is requested instead. If less than half of the rows is received, the
+    /// original columns are completed with samples of the missing rows. Once each
+    /// original column can be repaired, the square is repaired and verified against
+    /// the [`DataAvailabilityHeader`] of the header.
+    ///
+    /// If the square turns out to be incorrectly encoded, a [`BadEncodingFraudProof`]
+    /// is published to the network and [`P2pError::BadEncoding`] is returned.
+    pub async fn get_shwap_eds(
+        &self,
+        header: &ExtendedHeader,
+        timeout: Option<Duration>,
+    ) -> Result<ExtendedDataSquare> {
+        let height = header.height().value();
+        let square_width = header.dah.square_width();
+        let ods_width = usize::from(square_width / 2);
+
+        let mut rows = vec![None; usize::from(square_width)];
+        let mut received = 0;
+        let mut next_rows = 0..square_width;
+
+        let get_row =
+            |row_idx| async move { (row_idx, self.get_row(row_idx, height, timeout).await) };
+
+        let mut futs = next_rows
+            .by_ref()
+            .take(ods_width)
+            .map(get_row)
+            .collect::<FuturesUnordered<_>>();
+
+        while let Some((row_idx, res)) = futs.next().await {
+            match res {
+                Ok(row) => {
+                    rows[usize::from(row_idx)] = Some(row);
+                    received += 1;
+
+                    if received == ods_width {
+                        break;
+                    }
+                }
+                Err(e) if e.is_fatal() => return Err(e),
+                Err(e) => {
+                    debug!("Failed to get row {row_idx} of block {height}: {e}");
+
+                    if let Some(row_idx) = next_rows.next() {
+                        futs.push(get_row(row_idx));
+                    }
+                }
+            }
+        }
+
+        let column_shares = if received < ods_width {
+            debug!("Got only {received} rows of block {height}, falling back to columns");
+
+            let missing_rows: Vec<_> = (0..square_width)
+                .filter(|row_idx| rows[usize::from(*row_idx)].is_none())
+                .collect();
+            let needed = ods_width - received;
+
+            futures::stream::iter(0..square_width / 2)
+                .map(|col_idx| {
+                    self.get_column_shares(col_idx, &missing_rows, needed, height, timeout)
+                })
+                .buffered(COLUMNS_IN_FLIGHT)
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            Vec::new()
+        };
+
+        match shwap::reconstruct_eds(&rows, &column_shares, header) {
+            Err(P2pError::BadEncoding(befp)) => {
+                self.send_command(P2pCmd::PublishBadEncodingFraudProof { befp: befp.clone() })
+                    .await?;
+                Err(P2pError::BadEncoding(befp))
+            }
+            res => res,
+        }
+    }
+
+    /// Request `needed` shares of the column, out of the given rows, as [`Sample`]s.
+    ///
+    /// Returns the received shares together with the index of their row.
+    async fn get_column_shares(
+        &self,
+        col_idx: u16,
+        row_indexes: &[u16],
+        needed: usize,
+        height: u64,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<(u16, Share)>> {
+        let mut shares = Vec::with_capacity(needed);
+        let mut last_err = None;
+        let mut next_rows = row_indexes.iter().copied();
+
+        let get_sample = |row_idx| async move {
+            let res = self.get_sample(row_idx, col_idx, height, timeout).await;
+            (row_idx, res)
+        };
+
+        let mut futs = next_rows
+            .by_ref()
+            .take(needed)
+            .map(get_sample)
+            .collect::<FuturesUnordered<_>>();
+
+        while let Some((row_idx, res)) = futs.next().await {
+            match res {
+                Ok(sample) => {
+                    shares.push((row_idx, sample.share));
+
+                    if shares.len() == needed {
+                        return Ok(shares);
+                    }
+                }
+                Err(e) if e.is_fatal() => return Err(e),
+                Err(e) => {
+                    debug!("Failed to get sample ({row_idx}, {col_idx}) of block {height}: {e}");
+                    last_err = Some(e);
+
+                    if let Some(row_idx) = next_rows.next() {
+                        futs.push(get_sample(row_idx));
+                    }
+                }
+            }
+        }
+
+        Err(last_err.unwrap_or(P2pError::Shwap(format!(
+            "not enough shares to reconstruct column {col_idx} of block {height}"
+        ))))
+    }
+
+    /// Request [`ExtendedDataSquare`] of the block corresponding to this header
+    /// using `shrex-eds` protocol.
+    ///
+    /// The original data square is received and extended locally, then verified
+    /// against the [`DataAvailabilityHeader`] of the header.
+    pub async fn get_shrex_eds(
+        &self,
+        header: &ExtendedHeader,
+        timeout: Option<Duration>,
+    ) -> Result<ExtendedDataSquare> {
         let app_version = header.app_version()?;
-        let rows_to_fetch: Vec<_> = header
-            .dah
-            .row_roots()
-            .iter()
-            .enumerate()
-            .filter(|(_, row)| row.contains::<NamespacedSha2Hasher>(*namespace))
-            .map(|(n, _)| n as u16)
-            .collect();
+        let ods_width = usize::from(header.dah.square_width() / 2);
+        let request = ShrexRequest::eds(header.height().value());
 
-        let futs = rows_to_fetch
-            .into_iter()
-            .map(|row_idx| self.get_row_namespace_data(namespace, row_idx, height, timeout))
-            .collect::<FuturesOrdered<_>>();
-        let rows: Vec<_> = futs.try_collect().await?;
-        let shares = rows.iter().flat_map(|row| row.shares.iter());
+        self.shrex_request(ShrexProtocol::Eds, request, timeout, |payload| {
+            if payload.len() != ods_width * ods_width * SHARE_SIZE {
+                return Err(ShrexError::InvalidResponse.into());
+            }
+
+            let shares = payload
+                .chunks_exact(SHARE_SIZE)
+                .map(|share| share.to_vec())
+                .collect();
+
+            let eds = ExtendedDataSquare::from_ods(shares, app_version)
+                .map_err(|_| ShrexError::InvalidResponse)?;
 
-        Ok(Blob::reconstruct_all(shares, app_version)?)
+            if DataAvailabilityHeader::from_eds(&eds) != header.dah {
+                return Err(ShrexError::InvalidResponse.into());
+            }
+
+            Ok(eds)
+        })
+        .await
     }
 
     /// Get the addresses where [`P2p`] listens on for incoming connections.
@@ -625,6 +1076,67 @@ impl P2p {
         .await
     }
 
+    /// Connect to a peer on the given address.
+    ///
+    /// If the address contains the peer id and the peer is already connected, this
+    /// returns immediately. Otherwise it waits until the dial finishes.
+    pub async fn connect(&self, addr: Multiaddr) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+
+        self.send_command(P2pCmd::Connect {
+            addr,
+            respond_to: tx,
+        })
+        .await?;
+
+        rx.await?
+    }
+
+    /// Close all connections to the peer.
+    pub async fn disconnect(&self, peer_id: PeerId) -> Result<()> {
+        self.send_command(P2pCmd::Disconnect { peer_id }).await
+    }
+
+    /// Block or unblock the peer.
+    ///
+    /// Blocked peer is disconnected and no connections to or from it are allowed
+    /// until it is unblocked. The list of blocked peers is persisted in the store.
+    pub async fn set_peer_blocked(&self, peer_id: PeerId, is_blocked: bool) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+
+        self.send_command(P2pCmd::SetPeerBlocked {
+            peer_id,
+            is_blocked,
+            respond_to: tx,
+        })
+        .await?;
+
+        rx.await?
+    }
+
+    /// Get the list of blocked peers.
+    pub async fn blocked_peers(&self) -> Result<Vec<PeerId>> {
+        let (tx, rx) = oneshot::channel();
+
+        self.send_command(P2pCmd::BlockedPeers { respond_to: tx })
+            .await?;
+
+        Ok(rx.await?)
+    }
+
+    /// Get the information about the peer, `None` if the peer is not known.
+    pub async fn peer_info(&self, peer_id: PeerId) -> Result<Option<PeerInfo>> {
+        let (tx, rx) = oneshot::channel();
+
+        self.send_command(P2pCmd::PeerInfo {
+            peer_id,
+            respond_to: tx,
+        })
+        .await?;
+
+        Ok(rx.await?)
+    }
+
     /// Get the cancellation token which will be cancelled when the network gets compromised.
     ///
     /// After this token is cancelled, the network should be treated as insincere
@@ -664,12 +1176,15 @@ where
 {
     connection_control: connection_control::Behaviour,
     autonat: autonat::Behaviour,
-    bitswap: beetswap::Behaviour<MAX_MH_SIZE, B>,
+    bitswap: bitswap::Behaviour<B>,
     ping: ping::Behaviour,
     identify: identify::Behaviour,
     header_ex: HeaderExBehaviour<S>,
+    shrex_nd: ShrexBehaviour,
+    shrex_eds: ShrexBehaviour,
     gossipsub: gossipsub::Behaviour,
     kademlia: kad::Behaviour<kad::store::MemoryStore>,
+    mdns: mdns::Behaviour,
 }
 
 struct Worker<B, S>
@@ -682,14 +1197,31 @@ where
     listeners: SmallVec<[ListenerId; 1]>,
     header_sub_topic_hash: TopicHash,
     bad_encoding_fraud_sub_topic: TopicHash,
+    shrex_sub_topic_hash: TopicHash,
     cmd_rx: mpsc::Receiver<P2pCmd>,
     peer_tracker: Arc<PeerTracker>,
     header_sub_state: Option<HeaderSubState>,
     bitswap_queries: HashMap<beetswap::QueryId, OneshotResultSender<Vec<u8>, P2pError>>,
+    shrex_nd_queries:
+        HashMap<request_response::OutboundRequestId, OneshotResultSender<Vec<u8>, P2pError>>,
+    shrex_eds_queries:
+        HashMap<request_response::OutboundRequestId, OneshotResultSender<Vec<u8>, P2pError>>,
+    /// Peers that announced new EDSes on `shrex-sub`.
+    shrex_peers: HashSet<PeerId>,
     network_compromised_token: Token,
     store: Arc<S>,
     event_pub: EventPublisher,
     bootnodes: HashMap<PeerId, Vec<Multiaddr>>,
+    /// Peers saved in the store by the previous run, dialed on the first bootstrap.
+    known_peers: HashMap<PeerId, Vec<Multiaddr>>,
+    metrics: Metrics,
+    bandwidth_meter: BandwidthMeter,
+    /// Whether to trust the peers discovered with mDNS.
+    mdns_trust: bool,
+    /// Peers trusted only because they were discovered with mDNS.
+    mdns_trusted_peers: HashSet<PeerId>,
+    /// Dials requested with [`P2pCmd::Connect`], waiting for the outcome.
+    pending_dials: HashMap<ConnectionId, OneshotResultSender<(), P2pError>>,
 }
 
 struct HeaderSubState {
@@ -707,10 +1239,11 @@ where
         cancellation_token: CancellationToken,
         cmd_rx: mpsc::Receiver<P2pCmd>,
         peer_tracker: Arc<PeerTracker>,
+        bandwidth_meter: BandwidthMeter,
     ) -> Result<Self, P2pError> {
         let local_peer_id = PeerId::from(args.local_keypair.public());
 
-        let connection_control = connection_control::Behaviour::new();
+        let connection_control = connection_control::Behaviour::new(peer_tracker.clone());
         let autonat = autonat::Behaviour::new(local_peer_id, autonat::Config::default());
         let ping = ping::Behaviour::new(ping::Config::default());
 
@@ -723,20 +1256,33 @@ where
         let header_sub_topic = gossipsub_ident_topic(&args.network_id, "/header-sub/v0.0.1");
         let bad_encoding_fraud_sub_topic =
             fraudsub_ident_topic(BadEncodingFraudProof::TYPE, &args.network_id);
-        let gossipsub = init_gossipsub(&args, [&header_sub_topic, &bad_encoding_fraud_sub_topic])?;
+        let shrex_sub_topic = gossipsub_ident_topic(&args.network_id, SHREX_SUB_TOPIC);
+        let gossipsub = init_gossipsub(
+            &args,
+            [
+                &header_sub_topic,
+                &bad_encoding_fraud_sub_topic,
+                &shrex_sub_topic,
+            ],
+        )?;
 
         let kademlia = init_kademlia(&args)?;
+        let mdns = mdns::new_behaviour(args.mdns, local_peer_id)?;
         let bitswap = init_bitswap(
             args.blockstore.clone(),
             args.store.clone(),
             &args.network_id,
+            args.serve_shwap,
         )?;
 
         let header_ex = HeaderExBehaviour::new(HeaderExConfig {
             network_id: &args.network_id,
             peer_tracker: peer_tracker.clone(),
             header_store: args.store.clone(),
+            metrics: args.metrics.clone(),
         });
+        let shrex_nd = new_shrex_behaviour(&args.network_id, ShrexProtocol::NamespaceData);
+        let shrex_eds = new_shrex_behaviour(&args.network_id, ShrexProtocol::Eds);
 
         let behaviour = Behaviour {
             connection_control,
@@ -746,10 +1292,20 @@ where
             identify,
             gossipsub,
             header_ex,
+            shrex_nd,
+            shrex_eds,
             kademlia,
+            mdns,
         };
 
-        let mut swarm = new_swarm(args.local_keypair, behaviour).await?;
+        let mut swarm = new_swarm(
+            args.local_keypair,
+            behaviour,
+            args.tls_key_file.as_deref(),
+            args.tls_cert_file.as_deref(),
+            bandwidth_meter.clone(),
+        )
+        .await?;
         let mut listeners = SmallVec::new();
 
         for addr in args.listen_on {
@@ -775,6 +1331,46 @@ where
             peer_tracker.set_trusted(*peer_id, true);
         }
 
+        match args.store.get_blocked_peers().await {
+            Ok(peers) => {
+                for peer_id in peers {
+                    peer_tracker.set_blocked(peer_id, true);
+                }
+            }
+            Err(e) => warn!("Failed to load blocked peers: {e}"),
+        }
+
+        let mut known_peers = HashMap::new();
+
+        match args.store.get_known_peers().await {
+            Ok(peers) => {
+                for peer in peers {
+                    if peer.is_stale(KNOWN_PEER_MAX_AGE)
+                        || peer.addrs.is_empty()
+                        || peer.peer_id == local_peer_id
+                        || bootnodes.contains_key(&peer.peer_id)
+                        || peer_tracker.is_blocked(&peer.peer_id)
+                    {
+                        continue;
+                    }
+
+                    peer_tracker.add_known_peer(&peer);
+
+                    for addr in &peer.addrs {
+                        swarm
+                            .behaviour_mut()
+                            .kademlia
+                            .add_address(&peer.peer_id, addr.to_owned());
+                    }
+
+                    known_peers.insert(peer.peer_id, peer.addrs);
+                }
+
+                debug!("Loaded {} known peers", known_peers.len());
+            }
+            Err(e) => warn!("Failed to load known peers: {e}"),
+        }
+
         Ok(Worker {
             cancellation_token,
             cmd_rx,
@@ -782,19 +1378,30 @@ where
             listeners,
             bad_encoding_fraud_sub_topic: bad_encoding_fraud_sub_topic.hash(),
             header_sub_topic_hash: header_sub_topic.hash(),
+            shrex_sub_topic_hash: shrex_sub_topic.hash(),
             peer_tracker,
             header_sub_state: None,
             bitswap_queries: HashMap::new(),
+            shrex_nd_queries: HashMap::new(),
+            shrex_eds_queries: HashMap::new(),
+            shrex_peers: HashSet::new(),
             network_compromised_token: Token::new(),
             store: args.store,
             event_pub: args.event_pub,
             bootnodes,
+            known_peers,
+            metrics: args.metrics,
+            bandwidth_meter,
+            mdns_trust: args.mdns_trust,
+            mdns_trusted_peers: HashSet::new(),
+            pending_dials: HashMap::new(),
         })
     }
 
     async fn run(&mut self) {
         let mut report_interval = Interval::new(Duration::from_secs(60)).await;
         let mut kademlia_interval = Interval::new(Duration::from_secs(30)).await;
+        let mut known_peers_interval = Interval::new(KNOWN_PEERS_SAVE_INTERVAL).await;
         let mut peer_tracker_info_watcher = self.peer_tracker.info_watcher();
 
         // Initiate discovery
@@ -808,10 +1415,17 @@ where
                         warn!("All peers disconnected");
                         self.bootstrap();
                     }
+
+                    self.disconnect_banned_peers();
                 }
                 _ = report_interval.tick() => {
+                    // Scores decay and bans expire over time
+                    self.peer_tracker.update_scores_info();
                     self.report();
                 }
+                _ = known_peers_interval.tick() => {
+                    self.save_known_peers().await;
+                }
                 _ = kademlia_interval.tick() => {
                     if self.peer_tracker.info().num_connected_peers < MIN_CONNECTED_PEERS
                     {
@@ -840,7 +1454,11 @@ where
     fn bootstrap(&mut self) {
         self.event_pub.send(NodeEvent::ConnectingToBootnodes);
 
-        for (peer_id, addrs) in &self.bootnodes {
+        // Peers from the previous run are dialed only once, later they are
+        // reached through kademlia like any other peer.
+        let known_peers = mem::take(&mut self.known_peers);
+
+        for (peer_id, addrs) in self.bootnodes.iter().chain(&known_peers) {
             let dial_opts = DialOpts::peer_id(*peer_id)
                 .addresses(addrs.clone())
                 // Tell Swarm not to dial if peer is already connected or there
@@ -861,6 +1479,83 @@ where
         }
     }
 
+    /// Save the best peers in the store, so that they can be dialed on the next start.
+    async fn save_known_peers(&mut self) {
+        let peers = self
+            .peer_tracker
+            .known_peers(MAX_KNOWN_PEERS)
+            .into_iter()
+            .filter(|peer| !peer.is_stale(KNOWN_PEER_MAX_AGE))
+            .collect::<Vec<_>>();
+
+        trace!("Saving {} known peers", peers.len());
+
+        if let Err(e) = self.store.set_known_peers(peers).await {
+            warn!("Failed to save known peers: {e}");
+        }
+    }
+
+    fn on_connect(&mut self, addr: Multiaddr, respond_to: OneshotResultSender<(), P2pError>) {
+        let dial_opts = match addr.peer_id() {
+            Some(peer_id) => DialOpts::peer_id(peer_id)
+                .addresses(vec![addr])
+                // Dial even if there is an ongoing dialing, to learn its outcome.
+                .condition(PeerCondition::Disconnected)
+                .build(),
+            None => DialOpts::unknown_peer_id().address(addr).build(),
+        };
+        let connection_id = dial_opts.connection_id();
+
+        match self.swarm.dial(dial_opts) {
+            Ok(()) => {
+                self.pending_dials.insert(connection_id, respond_to);
+            }
+            // Peer is already connected
+            Err(DialError::DialPeerConditionFalse(_)) => respond_to.maybe_send_ok(()),
+            Err(e) => respond_to.maybe_send_err(P2pError::Dial(e.to_string())),
+        }
+    }
+
+    async fn on_set_peer_blocked(
+        &mut self,
+        peer_id: PeerId,
+        is_blocked: bool,
+    ) -> Result<(), P2pError> {
+        if *self.swarm.local_peer_id() == peer_id
+            || self.peer_tracker.is_blocked(&peer_id) == is_blocked
+        {
+            return Ok(());
+        }
+
+        let mut blocked_peers = self.peer_tracker.blocked_peers();
+
+        if is_blocked {
+            blocked_peers.push(peer_id);
+        } else {
+            blocked_peers.retain(|blocked| *blocked != peer_id);
+        }
+
+        // Persist first, so the tracker is left unchanged if the store fails.
+        self.store.set_blocked_peers(blocked_peers).await?;
+        self.peer_tracker.set_blocked(peer_id, is_blocked);
+
+        if is_blocked {
+            info!("Blocking peer {peer_id}");
+            let _ = self.swarm.disconnect_peer_id(peer_id);
+            self.swarm.behaviour_mut().kademlia.remove_peer(&peer_id);
+        } else {
+            info!("Unblocking peer {peer_id}");
+        }
+
+        Ok(())
+    }
+
+    fn disconnect_banned_peers(&mut self) {
+        for connection_id in self.peer_tracker.banned_connections() {
+            self.swarm.close_connection(connection_id);
+        }
+    }
+
     fn prune_canceled_bitswap_queries(&mut self) {
         let mut cancelled = SmallVec::<[_; 16]>::new();
 
@@ -918,6 +1613,8 @@ where
                 _ => {}
             }
         }
+
+        self.save_known_peers().await;
     }
 
     async fn on_swarm_event(&mut self, ev: SwarmEvent<BehaviourEvent<B, S>>) -> Result<()> {
@@ -928,6 +1625,11 @@ where
                 BehaviourEvent::Kademlia(ev) => self.on_kademlia_event(ev).await?,
                 BehaviourEvent::Bitswap(ev) => self.on_bitswap_event(ev).await,
                 BehaviourEvent::Ping(ev) => self.on_ping_event(ev).await,
+                BehaviourEvent::Mdns(ev) => self.on_mdns_event(ev),
+                BehaviourEvent::ShrexNd(ev) => {
+                    self.on_shrex_event(ShrexProtocol::NamespaceData, ev)
+                }
+                BehaviourEvent::ShrexEds(ev) => self.on_shrex_event(ShrexProtocol::Eds, ev),
                 BehaviourEvent::Autonat(_)
                 | BehaviourEvent::ConnectionControl(_)
                 | BehaviourEvent::HeaderEx(_) => {}
@@ -938,14 +1640,32 @@ where
                 endpoint,
                 ..
             } => {
+                if let Some(respond_to) = self.pending_dials.remove(&connection_id) {
+                    respond_to.maybe_send_ok(());
+                }
+
                 self.on_peer_connected(peer_id, connection_id, endpoint);
             }
+            SwarmEvent::OutgoingConnectionError {
+                connection_id,
+                error,
+                ..
+            } => {
+                if let Some(respond_to) = self.pending_dials.remove(&connection_id) {
+                    respond_to.maybe_send_err(P2pError::Dial(error.to_string()));
+                }
+            }
             SwarmEvent::ConnectionClosed {
                 peer_id,
                 connection_id,
+                num_established,
                 ..
             } => {
                 self.on_peer_disconnected(peer_id, connection_id);
+
+                if num_established == 0 {
+                    self.bandwidth_meter.remove_peer(&peer_id);
+                }
             }
             _ => {}
         }
@@ -961,11 +1681,17 @@ where
             P2pCmd::HeaderExRequest {
                 request,
                 respond_to,
+                peer_tx,
             } => {
-                self.swarm
+                let peer = self
+                    .swarm
                     .behaviour_mut()
                     .header_ex
                     .send_request(request, respond_to);
+
+                if let (Some(peer), Some(peer_tx)) = (peer, peer_tx) {
+                    peer_tx.maybe_send(peer);
+                }
             }
             P2pCmd::Listeners { respond_to } => {
                 let local_peer_id = self.swarm.local_peer_id().to_owned();
@@ -994,12 +1720,48 @@ where
                 is_trusted,
             } => {
                 if *self.swarm.local_peer_id() != peer_id {
+                    // Explicit trust is kept when the peer is no longer announced with mDNS.
+                    self.mdns_trusted_peers.remove(&peer_id);
                     self.peer_tracker.set_trusted(peer_id, is_trusted);
                 }
             }
+            P2pCmd::Connect { addr, respond_to } => {
+                self.on_connect(addr, respond_to);
+            }
+            P2pCmd::Disconnect { peer_id } => {
+                let _ = self.swarm.disconnect_peer_id(peer_id);
+            }
+            P2pCmd::SetPeerBlocked {
+                peer_id,
+                is_blocked,
+                respond_to,
+            } => {
+                let res = self.on_set_peer_blocked(peer_id, is_blocked).await;
+                respond_to.maybe_send(res);
+            }
+            P2pCmd::BlockedPeers { respond_to } => {
+                respond_to.maybe_send(self.peer_tracker.blocked_peers());
+            }
+            P2pCmd::PeerInfo {
+                peer_id,
+                respond_to,
+            } => {
+                respond_to.maybe_send(self.peer_tracker.peer_info(&peer_id));
+            }
             P2pCmd::GetShwapCid { cid, respond_to } => {
                 self.on_get_shwap_cid(cid, respond_to);
             }
+            P2pCmd::ShrexRequest {
+                protocol,
+                request,
+                respond_to,
+                peer_tx,
+            } => {
+                self.on_shrex_request(protocol, request, respond_to, peer_tx);
+            }
+            P2pCmd::PublishBadEncodingFraudProof { befp } => {
+                self.on_publish_bad_encoding_fraud_proof(*befp);
+            }
             P2pCmd::GetNetworkCompromisedToken { respond_to } => {
                 respond_to.maybe_send(self.network_compromised_token.clone())
             }
@@ -1010,6 +1772,14 @@ where
                     .map(|state| state.known_head.clone());
                 respond_to.maybe_send(head);
             }
+            P2pCmd::ReportPeer { peer, report } => {
+                self.peer_tracker.report(peer, report);
+            }
+            P2pCmd::ReportSamplingFailure => {
+                for peer in self.data_peers() {
+                    self.peer_tracker.report(peer, PeerReport::SamplingFailed);
+                }
+            }
         }
 
         Ok(())
@@ -1020,8 +1790,10 @@ where
         let tracker_info = self.peer_tracker.info();
 
         info!(
-            "peers: {}, trusted peers: {}",
-            tracker_info.num_connected_peers, tracker_info.num_connected_trusted_peers,
+            "peers: {}, trusted peers: {}, banned peers: {}",
+            tracker_info.num_connected_peers,
+            tracker_info.num_connected_trusted_peers,
+            tracker_info.num_banned_peers,
         );
     }
 
@@ -1029,6 +1801,8 @@ where
     async fn on_identify_event(&mut self, ev: identify::Event) -> Result<()> {
         match ev {
             identify::Event::Received { peer_id, info, .. } => {
+                self.peer_tracker.set_identify_info(peer_id, &info);
+
                 // Inform Kademlia about the listening addresses
                 // TODO: Remove this when rust-libp2p#5103 is implemented
                 for addr in info.listen_addrs {
@@ -1048,9 +1822,9 @@ where
     async fn on_gossip_sub_event(&mut self, ev: gossipsub::Event) {
         match ev {
             gossipsub::Event::Message {
+                propagation_source,
                 message,
                 message_id,
-                ..
             } => {
                 let Some(peer) = message.source else {
                     // Validation mode is `strict` so this will never happen
@@ -1062,12 +1836,17 @@ where
                 } else if message.topic == self.bad_encoding_fraud_sub_topic {
                     self.on_bad_encoding_fraud_sub_message(&message.data[..], &peer)
                         .await
+                } else if message.topic == self.shrex_sub_topic_hash {
+                    self.on_shrex_sub_message(&message.data[..], peer).await
                 } else {
                     trace!("Unhandled gossipsub message");
                     gossipsub::MessageAcceptance::Ignore
                 };
 
-                if !matches!(acceptance, gossipsub::MessageAcceptance::Reject) {
+                if matches!(acceptance, gossipsub::MessageAcceptance::Reject) {
+                    self.peer_tracker
+                        .report(propagation_source, PeerReport::InvalidMessage);
+                } else {
                     // We may have discovered a new peer
                     self.peer_maybe_discovered(peer);
                 }
@@ -1096,6 +1875,55 @@ where
         Ok(())
     }
 
+    #[instrument(level = "trace", skip(self))]
+    fn on_mdns_event(&mut self, ev: mdns::Event) {
+        match mdns::to_change(&self.swarm.behaviour().mdns, ev) {
+            MdnsChange::Discovered(peers) => {
+                for (peer_id, addr) in peers {
+                    self.on_mdns_discovered(peer_id, addr);
+                }
+            }
+            MdnsChange::Expired(peers) => {
+                for peer_id in peers {
+                    if self.mdns_trusted_peers.remove(&peer_id) {
+                        debug!("{peer_id} is no longer announced with mDNS, removing trust");
+                        self.peer_tracker.set_trusted(peer_id, false);
+                    }
+                }
+            }
+        }
+    }
+
+    fn on_mdns_discovered(&mut self, peer_id: PeerId, addr: Multiaddr) {
+        if peer_id == *self.swarm.local_peer_id() || self.peer_tracker.is_blocked(&peer_id) {
+            return;
+        }
+
+        debug!("Discovered {peer_id} on {addr} with mDNS");
+
+        if self.mdns_trust && !self.peer_tracker.is_trusted(&peer_id) {
+            self.peer_tracker.set_trusted(peer_id, true);
+            self.mdns_trusted_peers.insert(peer_id);
+        }
+
+        self.peer_tracker.add_addresses(peer_id, [&addr]);
+        self.swarm
+            .behaviour_mut()
+            .kademlia
+            .add_address(&peer_id, addr.clone());
+
+        let dial_opts = DialOpts::peer_id(peer_id)
+            .addresses(vec![addr])
+            .condition(PeerCondition::DisconnectedAndNotDialing)
+            .build();
+
+        if let Err(e) = self.swarm.dial(dial_opts) {
+            if !matches!(e, DialError::DialPeerConditionFalse(_)) {
+                warn!("Failed to dial {peer_id} discovered with mDNS: {e}");
+            }
+        }
+    }
+
     #[instrument(level = "trace", skip_all)]
     fn on_get_shwap_cid(&mut self, cid: Cid, respond_to: OneshotResultSender<Vec<u8>, P2pError>) {
         trace!("Requesting CID {cid} from bitswap");
@@ -1120,6 +1948,94 @@ where
         }
     }
 
+    #[instrument(level = "trace", skip(self, respond_to, peer_tx))]
+    fn on_shrex_request(
+        &mut self,
+        protocol: ShrexProtocol,
+        request: ShrexRequest,
+        respond_to: OneshotResultSender<Vec<u8>, P2pError>,
+        peer_tx: oneshot::Sender<PeerId>,
+    ) {
+        let peers = self.data_peers();
+
+        let Some(peer) = peers.choose(&mut rand::thread_rng()) else {
+            respond_to.maybe_send_err(ShrexError::NoPeers);
+            return;
+        };
+
+        trace!("Sending shrex request to {peer}");
+        peer_tx.maybe_send(*peer);
+
+        let behaviour = self.swarm.behaviour_mut();
+
+        match protocol {
+            ShrexProtocol::NamespaceData => {
+                let request_id = behaviour.shrex_nd.send_request(peer, request);
+                self.shrex_nd_queries.insert(request_id, respond_to);
+            }
+            ShrexProtocol::Eds => {
+                let request_id = behaviour.shrex_eds.send_request(peer, request);
+                self.shrex_eds_queries.insert(request_id, respond_to);
+            }
+        }
+    }
+
+    /// Returns the connected peers expected to hold the block data.
+    fn data_peers(&self) -> Vec<PeerId> {
+        // Prefer peers that announced EDSes on shrex-sub, since they are full or
+        // bridge nodes. Fall back to trusted peers otherwise.
+        let peers = self
+            .shrex_peers
+            .iter()
+            .copied()
+            .filter(|peer| self.peer_tracker.is_connected(*peer))
+            .collect::<Vec<_>>();
+
+        if peers.is_empty() {
+            self.peer_tracker.trusted_n_peers(usize::MAX)
+        } else {
+            peers
+        }
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    fn on_shrex_event(&mut self, protocol: ShrexProtocol, ev: ShrexEvent) {
+        let queries = match protocol {
+            ShrexProtocol::NamespaceData => &mut self.shrex_nd_queries,
+            ShrexProtocol::Eds => &mut self.shrex_eds_queries,
+        };
+
+        match ev {
+            request_response::Event::Message {
+                message:
+                    request_response::Message::Response {
+                        request_id,
+                        response,
+                    },
+                ..
+            } => {
+                if let Some(respond_to) = queries.remove(&request_id) {
+                    respond_to.maybe_send(response.into_result().map_err(Into::into));
+                }
+            }
+            request_response::Event::OutboundFailure {
+                peer,
+                request_id,
+                error,
+                ..
+            } => {
+                if let Some(respond_to) = queries.remove(&request_id) {
+                    if let Some(report) = PeerReport::from_outbound_failure(&error) {
+                        self.peer_tracker.report(peer, report);
+                    }
+
+                    respond_to.maybe_send_err(ShrexError::OutboundFailure(error));
+                }
+            }
+            _ => trace!("Unhandled shrex event"),
+        }
+    }
+
     #[instrument(level = "debug", skip_all)]
     async fn on_ping_event(&mut self, ev: ping::Event) {
         match ev.result {
@@ -1180,6 +2096,8 @@ where
             .set_maybe_disconnected(peer_id, connection_id)
         {
             debug!("Peer disconnected");
+            self.shrex_peers.remove(&peer_id);
+            self.metrics.remove_peer(&peer_id);
         }
     }
 
@@ -1221,6 +2139,25 @@ where
         gossipsub::MessageAcceptance::Accept
     }
 
+    #[instrument(skip_all, fields(height = befp.height().value()))]
+    fn on_publish_bad_encoding_fraud_proof(&mut self, befp: BadEncodingFraudProof) {
+        warn!("Detected bad encoding, publishing fraud proof");
+
+        let data = befp.encode_vec();
+
+        if let Err(e) = self
+            .swarm
+            .behaviour_mut()
+            .gossipsub
+            .publish(self.bad_encoding_fraud_sub_topic.clone(), data)
+        {
+            warn!("Failed to publish bad encoding fraud proof: {e}");
+        }
+
+        // trigger cancellation for all services
+        self.network_compromised_token.trigger();
+    }
+
     #[instrument(skip_all)]
     async fn on_bad_encoding_fraud_sub_message(
         &mut self,
@@ -1269,6 +2206,50 @@ where
 
         gossipsub::MessageAcceptance::Accept
     }
+
+    #[instrument(skip_all)]
+    async fn on_shrex_sub_message(
+        &mut self,
+        data: &[u8],
+        peer: PeerId,
+    ) -> gossipsub::MessageAcceptance {
+        let Ok(notification) = RecentEdsNotification::decode(data) else {
+            trace!("Malformed shrex-sub notification from {peer}");
+            return gossipsub::MessageAcceptance::Reject;
+        };
+
+        if notification.height == 0 || notification.data_hash.len() != 32 {
+            trace!("Invalid shrex-sub notification from {peer}");
+            return gossipsub::MessageAcceptance::Reject;
+        }
+
+        // If we already have the header, make sure the announced EDS matches it.
+        if let Ok(header) = self.store.get_by_height(notification.height).await {
+            if header.dah.hash().as_bytes() != notification.data_hash {
+                trace!("shrex-sub notification from {peer} doesn't match the header");
+                return gossipsub::MessageAcceptance::Reject;
+            }
+        }
+
+        if self.shrex_peers.insert(peer) {
+            debug!("Discovered shrex peer {peer}");
+        }
+
+        gossipsub::MessageAcceptance::Accept
+    }
+}
+
+/// Returns indexes of the rows which may contain shares of the namespace.
+pub(crate) fn rows_containing_namespace(
+    dah: &DataAvailabilityHeader,
+    namespace: Namespace,
+) -> Vec<u16> {
+    dah.row_roots()
+        .iter()
+        .enumerate()
+        .filter(|(_, row)| row.contains::<NamespacedSha2Hasher>(*namespace))
+        .map(|(n, _)| n as u16)
+        .collect()
 }
 
 /// Awaits at least one channel from the `bitswap_queries` to close.
@@ -1366,16 +2347,19 @@ fn init_bitswap<B, S>(
     blockstore: Arc<B>,
     store: Arc<S>,
     network_id: &str,
-) -> Result<beetswap::Behaviour<MAX_MH_SIZE, B>>
+    serve: bool,
+) -> Result<bitswap::Behaviour<B>>
 where
     B: Blockstore + 'static,
     S: Store + 'static,
 {
     let protocol_prefix = celestia_protocol_id(network_id, "shwap");
 
-    Ok(beetswap::Behaviour::builder(blockstore)
+    let bitswap = beetswap::Behaviour::builder(blockstore)
         .protocol_prefix(protocol_prefix.as_ref())?
         .register_multihasher(ShwapMultihasher::new(store))
         .client_set_send_dont_have(false)
-        .build())
+        .build();
+
+    Ok(bitswap::Behaviour::new(bitswap, serve))
 }
@@ -0,0 +1,535 @@
+//! Accounting of the bandwidth used by the streams of the peers.
+//!
+//! Every stream opened on a connection is metered. The protocol of a stream
+//! is taken from the multistream-select negotiation, which happens at the
+//! beginning of each stream.
+
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use celestia_types::p2p::BandwidthStats;
+use futures::{AsyncRead, AsyncWrite};
+use libp2p::core::muxing::{StreamMuxer, StreamMuxerEvent};
+use libp2p::PeerId;
+use web_time::Instant;
+
+/// Minimum time over which the rates are calculated.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+/// Maximum number of bytes, in each direction, inspected for the negotiated protocol.
+const MAX_SNIFFED_BYTES: usize = 1024;
+/// Maximum number of multistream-select messages inspected in each direction.
+const MAX_SNIFFED_MESSAGES: usize = 8;
+/// Header of the multistream-select protocol, exchanged before the protocol itself.
+const MULTISTREAM_HEADER: &[u8] = b"/multistream/";
+
+/// Collects the bandwidth used by all the peers and protocols.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BandwidthMeter {
+    state: Arc<Mutex<MeterState>>,
+}
+
+#[derive(Debug, Default)]
+struct MeterState {
+    total: Counter,
+    /// Counters of the connected peers, removed once the last connection closes.
+    peers: HashMap<PeerId, Counter>,
+    protocols: HashMap<String, Counter>,
+}
+
+#[derive(Debug)]
+struct Counter {
+    total_in: u64,
+    total_out: u64,
+    window_start: Instant,
+    window_in: u64,
+    window_out: u64,
+    rate_in: f64,
+    rate_out: f64,
+}
+
+impl BandwidthMeter {
+    /// Creates a new meter.
+    pub(crate) fn new() -> Self {
+        BandwidthMeter::default()
+    }
+
+    /// Wraps the muxer of a connection to the peer, so that its streams are metered.
+    pub(crate) fn wrap<M>(&self, peer_id: PeerId, muxer: M) -> MeteredMuxer<M> {
+        self.state()
+            .peers
+            .entry(peer_id)
+            .or_insert_with(|| Counter::new(Instant::now()));
+
+        MeteredMuxer {
+            inner: muxer,
+            peer_id,
+            meter: self.clone(),
+        }
+    }
+
+    /// Forgets the bandwidth used by the peer, after its last connection was closed.
+    ///
+    /// Bytes still transferred on the streams of the peer are only accounted
+    /// to the totals and protocols.
+    pub(crate) fn remove_peer(&self, peer_id: &PeerId) {
+        self.state().peers.remove(peer_id);
+    }
+
+    /// Returns the bandwidth used by all the streams.
+    pub(crate) fn stats(&self) -> BandwidthStats {
+        self.state().total.stats(Instant::now())
+    }
+
+    /// Returns the bandwidth used by the streams of the peer.
+    pub(crate) fn stats_for_peer(&self, peer_id: &PeerId) -> BandwidthStats {
+        self.state()
+            .peers
+            .get_mut(peer_id)
+            .map(|counter| counter.stats(Instant::now()))
+            .unwrap_or_else(empty_stats)
+    }
+
+    /// Returns the bandwidth used by the streams of the protocol.
+    pub(crate) fn stats_for_protocol(&self, protocol: &str) -> BandwidthStats {
+        self.state()
+            .protocols
+            .get_mut(protocol)
+            .map(|counter| counter.stats(Instant::now()))
+            .unwrap_or_else(empty_stats)
+    }
+
+    fn record(&self, peer_id: &PeerId, protocol: Option<&str>, bytes_in: u64, bytes_out: u64) {
+        let now = Instant::now();
+        let mut state = self.state();
+
+        state.total.add(now, bytes_in, bytes_out);
+
+        if let Some(counter) = state.peers.get_mut(peer_id) {
+            counter.add(now, bytes_in, bytes_out);
+        }
+
+        if let Some(protocol) = protocol {
+            state
+                .protocol_counter(protocol, now)
+                .add(now, bytes_in, bytes_out);
+        }
+    }
+
+    fn record_protocol(&self, protocol: &str, bytes_in: u64, bytes_out: u64) {
+        let now = Instant::now();
+
+        self.state()
+            .protocol_counter(protocol, now)
+            .add(now, bytes_in, bytes_out);
+    }
+
+    fn state(&self) -> MutexGuard<'_, MeterState> {
+        // Counters are always left in a consistent state, so poisoning can be ignored.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl MeterState {
+    fn protocol_counter(&mut self, protocol: &str, now: Instant) -> &mut Counter {
+        if !self.protocols.contains_key(protocol) {
+            self.protocols
+                .insert(protocol.to_owned(), Counter::new(now));
+        }
+
+        self.protocols.get_mut(protocol).expect("inserted above")
+    }
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Counter::new(Instant::now())
+    }
+}
+
+impl Counter {
+    fn new(now: Instant) -> Self {
+        Counter {
+            total_in: 0,
+            total_out: 0,
+            window_start: now,
+            window_in: 0,
+            window_out: 0,
+            rate_in: 0.0,
+            rate_out: 0.0,
+        }
+    }
+
+    fn add(&mut self, now: Instant, bytes_in: u64, bytes_out: u64) {
+        self.update_rates(now);
+
+        self.total_in += bytes_in;
+        self.total_out += bytes_out;
+        self.window_in += bytes_in;
+        self.window_out += bytes_out;
+    }
+
+    /// Recalculates the rates once the current window is long enough.
+    fn update_rates(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.window_start);
+
+        if elapsed < RATE_WINDOW {
+            return;
+        }
+
+        let secs = elapsed.as_secs_f64();
+        self.rate_in = self.window_in as f64 / secs;
+        self.rate_out = self.window_out as f64 / secs;
+        self.window_start = now;
+        self.window_in = 0;
+        self.window_out = 0;
+    }
+
+    fn stats(&mut self, now: Instant) -> BandwidthStats {
+        self.update_rates(now);
+
+        BandwidthStats {
+            total_in: self.total_in as f32,
+            total_out: self.total_out as f32,
+            rate_in: self.rate_in as f32,
+            rate_out: self.rate_out as f32,
+        }
+    }
+}
+
+fn empty_stats() -> BandwidthStats {
+    BandwidthStats {
+        total_in: 0.0,
+        total_out: 0.0,
+        rate_in: 0.0,
+        rate_out: 0.0,
+    }
+}
+
+/// A [`StreamMuxer`] metering all of its streams.
+pub(crate) struct MeteredMuxer<M> {
+    inner: M,
+    peer_id: PeerId,
+    meter: BandwidthMeter,
+}
+
+impl<M> MeteredMuxer<M> {
+    fn meter_stream<S>(&self, stream: S) -> MeteredStream<S> {
+        MeteredStream {
+            inner: stream,
+            peer_id: self.peer_id,
+            meter: self.meter.clone(),
+            protocol: None,
+            sniffer: Some(ProtocolSniffer::default()),
+            pending_in: 0,
+            pending_out: 0,
+        }
+    }
+}
+
+impl<M> StreamMuxer for MeteredMuxer<M>
+where
+    M: StreamMuxer + Unpin,
+    M::Substream: Unpin,
+{
+    type Substream = MeteredStream<M::Substream>;
+    type Error = M::Error;
+
+    fn poll_inbound(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let stream = futures::ready!(Pin::new(&mut self.inner).poll_inbound(cx))?;
+        Poll::Ready(Ok(self.meter_stream(stream)))
+    }
+
+    fn poll_outbound(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let stream = futures::ready!(Pin::new(&mut self.inner).poll_outbound(cx))?;
+        Poll::Ready(Ok(self.meter_stream(stream)))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+        Pin::new(&mut self.inner).poll(cx)
+    }
+}
+
+/// A stream counting the bytes read and written.
+pub(crate) struct MeteredStream<S> {
+    inner: S,
+    peer_id: PeerId,
+    meter: BandwidthMeter,
+    protocol: Option<String>,
+    /// Looks for the negotiated protocol, `None` once it's known or can't be found.
+    sniffer: Option<ProtocolSniffer>,
+    /// Bytes transferred before the protocol was known.
+    pending_in: u64,
+    pending_out: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    In,
+    Out,
+}
+
+impl<S> MeteredStream<S> {
+    fn record(&mut self, direction: Direction, data: &[u8]) {
+        let len = data.len() as u64;
+        let (bytes_in, bytes_out) = match direction {
+            Direction::In => (len, 0),
+            Direction::Out => (0, len),
+        };
+
+        self.meter
+            .record(&self.peer_id, self.protocol.as_deref(), bytes_in, bytes_out);
+
+        let Some(sniffer) = self.sniffer.as_mut() else {
+            return;
+        };
+
+        self.pending_in += bytes_in;
+        self.pending_out += bytes_out;
+
+        match sniffer.feed(direction, data) {
+            Sniffed::Pending => {}
+            Sniffed::Protocol(protocol) => {
+                self.meter
+                    .record_protocol(&protocol, self.pending_in, self.pending_out);
+                self.protocol = Some(protocol);
+                self.sniffer = None;
+            }
+            Sniffed::Unknown => self.sniffer = None,
+        }
+    }
+}
+
+impl<S> AsyncRead for MeteredStream<S>
+where
+    S: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let n = futures::ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        self.record(Direction::In, &buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<S> AsyncWrite for MeteredStream<S>
+where
+    S: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let n = futures::ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+        self.record(Direction::Out, &buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+/// Finds the protocol negotiated with multistream-select in the first bytes
+/// sent and received on a stream.
+///
+/// The protocol is the one proposed by one side and confirmed by the other,
+/// i.e. the one seen in both directions.
+#[derive(Debug, Default)]
+struct ProtocolSniffer {
+    sent: Vec<u8>,
+    received: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Sniffed {
+    Pending,
+    Protocol(String),
+    Unknown,
+}
+
+impl ProtocolSniffer {
+    fn feed(&mut self, direction: Direction, data: &[u8]) -> Sniffed {
+        let buf = match direction {
+            Direction::In => &mut self.received,
+            Direction::Out => &mut self.sent,
+        };
+        let len = data.len().min(MAX_SNIFFED_BYTES - buf.len());
+        buf.extend_from_slice(&data[..len]);
+
+        let received = multistream_messages(&self.received);
+
+        for msg in multistream_messages(&self.sent) {
+            if is_protocol(msg) && received.contains(&msg) {
+                let protocol = &msg[..msg.len() - 1];
+                return Sniffed::Protocol(String::from_utf8_lossy(protocol).into_owned());
+            }
+        }
+
+        if self.sent.len() == MAX_SNIFFED_BYTES && self.received.len() == MAX_SNIFFED_BYTES {
+            Sniffed::Unknown
+        } else {
+            Sniffed::Pending
+        }
+    }
+}
+
+/// Parses the complete, length prefixed, multistream-select messages from the beginning of `buf`.
+fn multistream_messages(mut buf: &[u8]) -> Vec<&[u8]> {
+    let mut messages = Vec::new();
+
+    while messages.len() < MAX_SNIFFED_MESSAGES {
+        let Some((len, rest)) = decode_uvarint(buf) else {
+            break;
+        };
+        let Some(msg) = rest.get(..len) else {
+            break;
+        };
+
+        messages.push(msg);
+        buf = &rest[len..];
+    }
+
+    messages
+}
+
+fn is_protocol(msg: &[u8]) -> bool {
+    msg.starts_with(b"/") && msg.ends_with(b"\n") && !msg.starts_with(MULTISTREAM_HEADER)
+}
+
+fn decode_uvarint(buf: &[u8]) -> Option<(usize, &[u8])> {
+    let mut value = 0usize;
+
+    // Messages are short, so lengths longer than 2 bytes are not expected.
+    for (i, byte) in buf.iter().take(2).enumerate() {
+        value |= usize::from(byte & 0x7f) << (7 * i);
+
+        if byte & 0x80 == 0 {
+            return Some((value, &buf[i + 1..]));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(msg: &str) -> Vec<u8> {
+        let mut buf = vec![msg.len() as u8];
+        buf.extend_from_slice(msg.as_bytes());
+        buf
+    }
+
+    #[test]
+    fn sniff_negotiated_protocol() {
+        let mut sniffer = ProtocolSniffer::default();
+
+        // lazy dialer sends the proposal together with the first data
+        let mut proposal = message("/multistream/1.0.0\n");
+        proposal.extend(message("/foo/1.0.0\n"));
+        proposal.extend(message("/bar/1.0.0\n"));
+        proposal.extend(b"data");
+        assert_eq!(sniffer.feed(Direction::Out, &proposal), Sniffed::Pending);
+
+        assert_eq!(
+            sniffer.feed(Direction::In, &message("/multistream/1.0.0\n")),
+            Sniffed::Pending
+        );
+        assert_eq!(
+            sniffer.feed(Direction::In, &message("na\n")),
+            Sniffed::Pending
+        );
+        assert_eq!(
+            sniffer.feed(Direction::In, &message("/bar/1.0.0\n")),
+            Sniffed::Protocol("/bar/1.0.0".to_string())
+        );
+    }
+
+    #[test]
+    fn sniff_gives_up() {
+        let mut sniffer = ProtocolSniffer::default();
+        let garbage = vec![0xff; MAX_SNIFFED_BYTES * 2];
+
+        assert_eq!(sniffer.feed(Direction::Out, &garbage), Sniffed::Pending);
+        assert_eq!(sniffer.feed(Direction::In, &garbage), Sniffed::Unknown);
+    }
+
+    #[test]
+    fn meter_records_per_peer_and_protocol() {
+        let meter = BandwidthMeter::new();
+        let peer = PeerId::random();
+        let mut stream = meter.wrap(peer, ()).meter_stream(());
+
+        stream.record(Direction::Out, &message("/multistream/1.0.0\n"));
+        stream.record(Direction::Out, &message("/foo/1.0.0\n"));
+        stream.record(Direction::In, &message("/multistream/1.0.0\n"));
+        stream.record(Direction::In, &message("/foo/1.0.0\n"));
+        stream.record(Direction::In, &[0; 100]);
+
+        let total = meter.stats();
+        assert_eq!(total.total_in, 100.0 + 20.0 + 12.0);
+        assert_eq!(total.total_out, 20.0 + 12.0);
+
+        let for_peer = meter.stats_for_peer(&peer);
+        assert_eq!(for_peer.total_in, total.total_in);
+        assert_eq!(for_peer.total_out, total.total_out);
+
+        // bytes of the negotiation are accounted to the protocol too
+        let for_protocol = meter.stats_for_protocol("/foo/1.0.0");
+        assert_eq!(for_protocol.total_in, total.total_in);
+        assert_eq!(for_protocol.total_out, total.total_out);
+
+        assert_eq!(meter.stats_for_peer(&PeerId::random()).total_in, 0.0);
+        assert_eq!(meter.stats_for_protocol("/bar/1.0.0").total_in, 0.0);
+    }
+
+    #[test]
+    fn meter_removes_disconnected_peer() {
+        let meter = BandwidthMeter::new();
+        let peer = PeerId::random();
+        let mut stream = meter.wrap(peer, ()).meter_stream(());
+
+        stream.record(Direction::In, &[0; 100]);
+        assert_eq!(meter.stats_for_peer(&peer).total_in, 100.0);
+
+        meter.remove_peer(&peer);
+        assert!(meter.state().peers.is_empty());
+
+        // late bytes of a closing stream don't bring the peer back
+        stream.record(Direction::In, &[0; 100]);
+        assert!(meter.state().peers.is_empty());
+        assert_eq!(meter.stats_for_peer(&peer).total_in, 0.0);
+        assert_eq!(meter.stats().total_in, 200.0);
+
+        // reconnecting starts from zero
+        let mut stream = meter.wrap(peer, ()).meter_stream(());
+        stream.record(Direction::In, &[0; 10]);
+        assert_eq!(meter.stats_for_peer(&peer).total_in, 10.0);
+    }
+}
@@ -0,0 +1,453 @@
+//! Bitswap behaviour that controls what is served to other peers.
+//!
+//! [`beetswap::Behaviour`] answers any wantlist from the blockstore. This wraps it
+//! and filters the outgoing blocks, so that we serve only Shwap containers, only if
+//! serving was enabled, and only up to a per-peer rate limit. Blocks over the limit
+//! are queued and sent once the quota of the peer refills.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use beetswap::ToHandlerEvent;
+use blockstore::Blockstore;
+use celestia_types::row::ROW_ID_MULTIHASH_CODE;
+use celestia_types::row_namespace_data::ROW_NAMESPACE_DATA_ID_MULTIHASH_CODE;
+use celestia_types::sample::SAMPLE_ID_MULTIHASH_CODE;
+use cid::Cid;
+use libp2p::{
+    core::{transport::PortUse, Endpoint},
+    swarm::{
+        ConnectionClosed, ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour,
+        NotifyHandler, THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+    },
+    Multiaddr, PeerId,
+};
+use tracing::trace;
+use web_time::Instant;
+
+use crate::executor::sleep;
+use crate::p2p::MAX_MH_SIZE;
+
+/// Maximum number of blocks served to a single peer per second.
+const PEER_BLOCKS_PER_SEC: f64 = 128.0;
+/// Maximum number of bytes served to a single peer per second.
+const PEER_BYTES_PER_SEC: f64 = 8.0 * 1024.0 * 1024.0;
+/// Maximum number of seconds worth of quota queued for a single peer.
+///
+/// Blocks over it are not served at all.
+const PEER_QUEUE_SECS: f64 = 4.0;
+/// How often the queued blocks are retried.
+const QUEUE_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+type Inner<B> = beetswap::Behaviour<MAX_MH_SIZE, B>;
+type Block = (Vec<u8>, Vec<u8>);
+
+pub(crate) struct Behaviour<B>
+where
+    B: Blockstore + 'static,
+{
+    inner: Inner<B>,
+    serve: bool,
+    quotas: HashMap<PeerId, PeerQuota>,
+    queues: HashMap<PeerId, BlockQueue>,
+    retry_timer: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+/// Blocks waiting for the quota of a peer to refill.
+struct BlockQueue {
+    handler: NotifyHandler,
+    blocks: VecDeque<Block>,
+    bytes: usize,
+}
+
+impl BlockQueue {
+    fn new(handler: NotifyHandler) -> Self {
+        BlockQueue {
+            handler,
+            blocks: VecDeque::new(),
+            bytes: 0,
+        }
+    }
+
+    fn push(&mut self, block: Block) -> bool {
+        let bytes = self.bytes + block.1.len();
+
+        if (self.blocks.len() + 1) as f64 > PEER_BLOCKS_PER_SEC * PEER_QUEUE_SECS
+            || bytes as f64 > PEER_BYTES_PER_SEC * PEER_QUEUE_SECS
+        {
+            return false;
+        }
+
+        self.blocks.push_back(block);
+        self.bytes = bytes;
+
+        true
+    }
+
+    /// Takes the blocks from the front of the queue, as long as the quota allows.
+    fn take_allowed(&mut self, quota: &mut PeerQuota) -> Vec<Block> {
+        let mut blocks = Vec::new();
+
+        while let Some((_, data)) = self.blocks.front() {
+            if !quota.try_consume(data.len()) {
+                break;
+            }
+
+            let block = self.blocks.pop_front().expect("checked above");
+            self.bytes -= block.1.len();
+            blocks.push(block);
+        }
+
+        blocks
+    }
+}
+
+/// Token bucket limiting how much we serve to a peer.
+struct PeerQuota {
+    blocks: f64,
+    bytes: f64,
+    last_refill: Instant,
+}
+
+impl PeerQuota {
+    fn new() -> Self {
+        PeerQuota {
+            blocks: PEER_BLOCKS_PER_SEC,
+            bytes: PEER_BYTES_PER_SEC,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn try_consume(&mut self, size: usize) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+
+        self.blocks = (self.blocks + elapsed * PEER_BLOCKS_PER_SEC).min(PEER_BLOCKS_PER_SEC);
+        self.bytes = (self.bytes + elapsed * PEER_BYTES_PER_SEC).min(PEER_BYTES_PER_SEC);
+
+        let size = size as f64;
+
+        if self.blocks < 1.0 || self.bytes < size {
+            return false;
+        }
+
+        self.blocks -= 1.0;
+        self.bytes -= size;
+
+        true
+    }
+}
+
+impl<B> Behaviour<B>
+where
+    B: Blockstore + 'static,
+{
+    /// Wrap bitswap behaviour. If `serve` is `false`, nothing is served to other peers.
+    pub(crate) fn new(inner: Inner<B>, serve: bool) -> Self {
+        Behaviour {
+            inner,
+            serve,
+            quotas: HashMap::new(),
+            queues: HashMap::new(),
+            retry_timer: None,
+        }
+    }
+
+    /// Start a query for the [`Cid`].
+    pub(crate) fn get(&mut self, cid: &Cid) -> beetswap::QueryId {
+        self.inner.get(cid)
+    }
+
+    /// Cancel an ongoing query.
+    pub(crate) fn cancel(&mut self, query_id: beetswap::QueryId) {
+        self.inner.cancel(query_id)
+    }
+
+    /// Returns the blocks which can be sent to the peer now, queueing those over its quota.
+    fn filter_outgoing(
+        &mut self,
+        peer_id: PeerId,
+        handler: NotifyHandler,
+        blocks: Vec<Block>,
+    ) -> Vec<Block> {
+        if !self.serve {
+            return Vec::new();
+        }
+
+        let quota = self.quotas.entry(peer_id).or_insert_with(PeerQuota::new);
+        let queue = self
+            .queues
+            .entry(peer_id)
+            .or_insert_with(|| BlockQueue::new(handler.clone()));
+
+        // Queued blocks are answered before the new ones.
+        let mut allowed = queue.take_allowed(quota);
+
+        for block in blocks {
+            if !is_shwap_cid(&block.0) {
+                continue;
+            }
+
+            if queue.blocks.is_empty() && quota.try_consume(block.1.len()) {
+                allowed.push(block);
+            } else if queue.push(block) {
+                trace!("Peer {peer_id} exceeded bitswap serving limit, queueing block");
+            } else {
+                trace!("Peer {peer_id} exceeded bitswap serving queue, dropping block");
+            }
+        }
+
+        queue.handler = handler;
+
+        if queue.blocks.is_empty() {
+            self.queues.remove(&peer_id);
+        }
+
+        allowed
+    }
+
+    /// Returns the queued blocks of a peer whose quota allows sending them.
+    fn take_queued(&mut self) -> Option<(PeerId, NotifyHandler, Vec<Block>)> {
+        let mut ready = None;
+
+        for (peer_id, queue) in self.queues.iter_mut() {
+            let quota = self.quotas.entry(*peer_id).or_insert_with(PeerQuota::new);
+            let blocks = queue.take_allowed(quota);
+
+            if !blocks.is_empty() {
+                ready = Some((*peer_id, queue.handler.clone(), blocks));
+                break;
+            }
+        }
+
+        self.queues.retain(|_, queue| !queue.blocks.is_empty());
+
+        ready
+    }
+}
+
+fn is_shwap_cid(cid: &[u8]) -> bool {
+    let Ok(cid) = Cid::read_bytes(cid) else {
+        return false;
+    };
+
+    matches!(
+        cid.hash().code(),
+        SAMPLE_ID_MULTIHASH_CODE | ROW_ID_MULTIHASH_CODE | ROW_NAMESPACE_DATA_ID_MULTIHASH_CODE
+    )
+}
+
+impl<B> NetworkBehaviour for Behaviour<B>
+where
+    B: Blockstore + 'static,
+{
+    type ConnectionHandler = THandler<Inner<B>>;
+    type ToSwarm = beetswap::Event;
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner.handle_established_inbound_connection(
+            connection_id,
+            peer,
+            local_addr,
+            remote_addr,
+        )
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        role_override: Endpoint,
+        port_use: PortUse,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner.handle_established_outbound_connection(
+            connection_id,
+            peer,
+            addr,
+            role_override,
+            port_use,
+        )
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        self.inner
+            .on_connection_handler_event(peer_id, connection_id, event)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        if let FromSwarm::ConnectionClosed(ConnectionClosed {
+            peer_id,
+            remaining_established: 0,
+            ..
+        }) = event
+        {
+            self.quotas.remove(&peer_id);
+            self.queues.remove(&peer_id);
+        }
+
+        self.inner.on_swarm_event(event)
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        if let Some((peer_id, handler, blocks)) = self.take_queued() {
+            return Poll::Ready(ToSwarm::NotifyHandler {
+                peer_id,
+                handler,
+                event: ToHandlerEvent::QueueOutgoingMessages(blocks),
+            });
+        }
+
+        if self.queues.is_empty() {
+            self.retry_timer = None;
+        } else {
+            let timer = self
+                .retry_timer
+                .get_or_insert_with(|| Box::pin(sleep(QUEUE_RETRY_INTERVAL)));
+
+            if timer.as_mut().poll(cx).is_ready() {
+                self.retry_timer = None;
+                cx.waker().wake_by_ref();
+            }
+        }
+
+        loop {
+            match self.inner.poll(cx) {
+                Poll::Ready(ToSwarm::NotifyHandler {
+                    peer_id,
+                    handler,
+                    event: ToHandlerEvent::QueueOutgoingMessages(blocks),
+                }) => {
+                    let blocks = self.filter_outgoing(peer_id, handler.clone(), blocks);
+
+                    if blocks.is_empty() {
+                        continue;
+                    }
+
+                    return Poll::Ready(ToSwarm::NotifyHandler {
+                        peer_id,
+                        handler,
+                        event: ToHandlerEvent::QueueOutgoingMessages(blocks),
+                    });
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::p2p::shwap::sample_cid;
+    use blockstore::InMemoryBlockstore;
+    use std::sync::Arc;
+
+    fn behaviour(serve: bool) -> Behaviour<InMemoryBlockstore<MAX_MH_SIZE>> {
+        let blockstore = Arc::new(InMemoryBlockstore::new());
+        Behaviour::new(beetswap::Behaviour::new(blockstore), serve)
+    }
+
+    #[test]
+    fn serving_disabled() {
+        let mut bitswap = behaviour(false);
+        let cid = sample_cid(0, 0, 1).unwrap().to_bytes();
+
+        let blocks = bitswap.filter_outgoing(
+            PeerId::random(),
+            NotifyHandler::Any,
+            vec![(cid, vec![0; 100])],
+        );
+        assert!(blocks.is_empty());
+        assert!(bitswap.queues.is_empty());
+    }
+
+    #[test]
+    fn serve_only_shwap_cids() {
+        let mut bitswap = behaviour(true);
+        let shwap_cid = sample_cid(0, 0, 1).unwrap().to_bytes();
+        let other_cid = "bafkreibme22gw2h7y2h7tg2fhqotaqjucnbc24deqo72b6mkl2egezxhvy"
+            .parse::<Cid>()
+            .unwrap()
+            .to_bytes();
+
+        let blocks = bitswap.filter_outgoing(
+            PeerId::random(),
+            NotifyHandler::Any,
+            vec![(shwap_cid.clone(), vec![0; 100]), (other_cid, vec![0; 100])],
+        );
+        assert_eq!(blocks, vec![(shwap_cid, vec![0; 100])]);
+    }
+
+    #[test]
+    fn per_peer_limit() {
+        let mut bitswap = behaviour(true);
+        let peer1 = PeerId::random();
+        let peer2 = PeerId::random();
+
+        let blocks: Vec<_> = (0..PEER_BLOCKS_PER_SEC as u16 * 2)
+            .map(|i| (sample_cid(i, 0, 1).unwrap().to_bytes(), vec![0; 10]))
+            .collect();
+
+        let served = bitswap.filter_outgoing(peer1, NotifyHandler::Any, blocks.clone());
+        assert_eq!(served.len(), PEER_BLOCKS_PER_SEC as usize);
+
+        // Limits are tracked separately for each peer
+        let served = bitswap.filter_outgoing(peer2, NotifyHandler::Any, blocks);
+        assert_eq!(served.len(), PEER_BLOCKS_PER_SEC as usize);
+    }
+
+    #[test]
+    fn blocks_over_limit_are_queued() {
+        let mut bitswap = behaviour(true);
+        let peer = PeerId::random();
+
+        let blocks: Vec<_> = (0..PEER_BLOCKS_PER_SEC as u16 * 2)
+            .map(|i| (sample_cid(i, 0, 1).unwrap().to_bytes(), vec![0; 10]))
+            .collect();
+
+        let served = bitswap.filter_outgoing(peer, NotifyHandler::Any, blocks.clone());
+        assert_eq!(served, blocks[..PEER_BLOCKS_PER_SEC as usize]);
+        assert!(bitswap.take_queued().is_none());
+
+        // refill the quota
+        bitswap.quotas.get_mut(&peer).unwrap().last_refill -= Duration::from_secs(1);
+
+        let (queued_peer, _, served) = bitswap.take_queued().unwrap();
+        assert_eq!(queued_peer, peer);
+        assert_eq!(served, blocks[PEER_BLOCKS_PER_SEC as usize..]);
+        assert!(bitswap.queues.is_empty());
+    }
+
+    #[test]
+    fn queue_is_bounded() {
+        let mut bitswap = behaviour(true);
+        let peer = PeerId::random();
+        let max_queued = (PEER_BLOCKS_PER_SEC * PEER_QUEUE_SECS) as usize;
+
+        let blocks: Vec<_> = (0..PEER_BLOCKS_PER_SEC as u16 * 10)
+            .map(|i| (sample_cid(i, 0, 1).unwrap().to_bytes(), vec![0; 10]))
+            .collect();
+
+        bitswap.filter_outgoing(peer, NotifyHandler::Any, blocks);
+        assert_eq!(bitswap.queues[&peer].blocks.len(), max_queued);
+    }
+}
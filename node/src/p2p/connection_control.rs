@@ -1,3 +1,4 @@
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use libp2p::{
@@ -10,18 +11,48 @@ use libp2p::{
 };
 use void::Void;
 
+use crate::peer_tracker::PeerTracker;
+
 // TODO: Wrap ConnectionLimits in it and exclude limits from trusted peers
 pub(crate) struct Behaviour {
     stopping: bool,
+    peer_tracker: Arc<PeerTracker>,
 }
 
 #[derive(Debug, thiserror::Error)]
 #[error("Swarm is stopping")]
 struct Stopping;
 
+#[derive(Debug, thiserror::Error)]
+#[error("Peer is banned")]
+struct Banned;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Peer is blocked")]
+struct Blocked;
+
 impl Behaviour {
-    pub(crate) fn new() -> Behaviour {
-        Behaviour { stopping: false }
+    pub(crate) fn new(peer_tracker: Arc<PeerTracker>) -> Behaviour {
+        Behaviour {
+            stopping: false,
+            peer_tracker,
+        }
+    }
+
+    fn check_peer(&self, peer: &PeerId) -> Result<(), ConnectionDenied> {
+        if self.stopping {
+            return Err(ConnectionDenied::new(Stopping));
+        }
+
+        if self.peer_tracker.is_blocked(peer) {
+            return Err(ConnectionDenied::new(Blocked));
+        }
+
+        if self.peer_tracker.is_banned(peer) {
+            return Err(ConnectionDenied::new(Banned));
+        }
+
+        Ok(())
     }
 
     pub(crate) fn set_stopping(&mut self, value: bool) {
@@ -49,13 +80,11 @@ impl NetworkBehaviour for Behaviour {
     fn handle_established_inbound_connection(
         &mut self,
         _connection_id: ConnectionId,
-        _peer: PeerId,
+        peer: PeerId,
         _local_addr: &Multiaddr,
         _remote_addr: &Multiaddr,
     ) -> Result<THandler<Self>, ConnectionDenied> {
-        if self.stopping {
-            return Err(ConnectionDenied::new(Stopping));
-        }
+        self.check_peer(&peer)?;
 
         Ok(dummy::ConnectionHandler)
     }
@@ -63,7 +92,7 @@ impl NetworkBehaviour for Behaviour {
     fn handle_pending_outbound_connection(
         &mut self,
         _connection_id: ConnectionId,
-        _maybe_peer: Option<PeerId>,
+        maybe_peer: Option<PeerId>,
         _addresses: &[Multiaddr],
         _effective_role: Endpoint,
     ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
@@ -71,20 +100,22 @@ impl NetworkBehaviour for Behaviour {
             return Err(ConnectionDenied::new(Stopping));
         }
 
+        if let Some(peer) = maybe_peer {
+            self.check_peer(&peer)?;
+        }
+
         Ok(Vec::new())
     }
 
     fn handle_established_outbound_connection(
         &mut self,
         _connection_id: ConnectionId,
-        _peer: PeerId,
+        peer: PeerId,
         _addr: &Multiaddr,
         _role_override: Endpoint,
         _port_use: PortUse,
     ) -> Result<THandler<Self>, ConnectionDenied> {
-        if self.stopping {
-            return Err(ConnectionDenied::new(Stopping));
-        }
+        self.check_peer(&peer)?;
 
         Ok(dummy::ConnectionHandler)
     }
@@ -26,6 +26,7 @@ mod server;
 pub(crate) mod utils;
 
 use crate::executor::timeout;
+use crate::metrics::Metrics;
 use crate::p2p::header_ex::client::HeaderExClientHandler;
 use crate::p2p::header_ex::server::HeaderExServerHandler;
 use crate::p2p::P2pError;
@@ -64,6 +65,7 @@ pub(crate) struct HeaderExConfig<'a, S> {
     pub network_id: &'a str,
     pub peer_tracker: Arc<PeerTracker>,
     pub header_store: Arc<S>,
+    pub metrics: Metrics,
 }
 
 /// Representation of all the errors that can occur in `HeaderEx` component.
@@ -109,19 +111,20 @@ where
                 )],
                 request_response::Config::default(),
             ),
-            client_handler: HeaderExClientHandler::new(config.peer_tracker),
+            client_handler: HeaderExClientHandler::new(config.peer_tracker, config.metrics),
             server_handler: HeaderExServerHandler::new(config.header_store),
         }
     }
 
+    /// Sends the request, returning the peer it was sent to, unless it was sent to multiple peers.
     #[instrument(level = "trace", skip(self, respond_to))]
     pub(crate) fn send_request(
         &mut self,
         request: HeaderRequest,
         respond_to: OneshotResultSender<Vec<ExtendedHeader>, P2pError>,
-    ) {
+    ) -> Option<PeerId> {
         self.client_handler
-            .on_send_request(&mut self.req_resp, request, respond_to);
+            .on_send_request(&mut self.req_resp, request, respond_to)
     }
 
     pub(crate) fn stop(&mut self) {
@@ -15,12 +15,14 @@ use libp2p::PeerId;
 use tokio::sync::oneshot;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, instrument, trace};
+use web_time::Instant;
 
 use crate::executor::yield_now;
+use crate::metrics::Metrics;
 use crate::p2p::header_ex::utils::{HeaderRequestExt, HeaderResponseExt};
 use crate::p2p::header_ex::{HeaderExError, ReqRespBehaviour};
 use crate::p2p::P2pError;
-use crate::peer_tracker::PeerTracker;
+use crate::peer_tracker::{PeerReport, PeerTracker};
 use crate::utils::{OneshotResultSender, OneshotResultSenderExt};
 
 const MAX_PEERS: usize = 10;
@@ -31,6 +33,7 @@ where
 {
     reqs: HashMap<S::RequestId, State>,
     peer_tracker: Arc<PeerTracker>,
+    metrics: Metrics,
     cancellation_token: CancellationToken,
     tasks: FuturesUnordered<BoxFuture<'static, ()>>,
 }
@@ -38,6 +41,7 @@ where
 struct State {
     request: HeaderRequest,
     respond_to: OneshotSender,
+    sent_at: Instant,
 }
 
 /// Oneshot sender that responds with `RequestCancelled` if not used.
@@ -91,39 +95,46 @@ impl<S> HeaderExClientHandler<S>
 where
     S: RequestSender,
 {
-    pub(super) fn new(peer_tracker: Arc<PeerTracker>) -> Self {
+    pub(super) fn new(peer_tracker: Arc<PeerTracker>, metrics: Metrics) -> Self {
         HeaderExClientHandler {
             reqs: HashMap::new(),
             peer_tracker,
+            metrics,
             cancellation_token: CancellationToken::new(),
             tasks: FuturesUnordered::new(),
         }
     }
 
+    /// Sends the request.
+    ///
+    /// Returns the peer the request was sent to, unless it was sent to multiple peers.
     #[instrument(level = "trace", skip(self, sender, respond_to))]
     pub(super) fn on_send_request(
         &mut self,
         sender: &mut S,
         request: HeaderRequest,
         respond_to: OneshotResultSender<Vec<ExtendedHeader>, P2pError>,
-    ) {
+    ) -> Option<PeerId> {
         if self.cancellation_token.is_cancelled() {
             respond_to.maybe_send_err(HeaderExError::RequestCancelled);
-            return;
+            return None;
         }
 
         if !request.is_valid() {
             respond_to.maybe_send_err(HeaderExError::InvalidRequest);
-            return;
+            return None;
         }
 
-        if request.is_head_request() {
+        let peer = if request.is_head_request() {
             self.send_head_request(sender, request, respond_to);
+            None
         } else {
-            self.send_request(sender, request, respond_to);
-        }
+            self.send_request(sender, request, respond_to)
+        };
 
         trace!("Request initiated");
+
+        peer
     }
 
     fn send_request(
@@ -131,25 +142,28 @@ where
         sender: &mut S,
         request: HeaderRequest,
         respond_to: OneshotResultSender<Vec<ExtendedHeader>, P2pError>,
-    ) {
+    ) -> Option<PeerId> {
         // Validate amount
         if usize::try_from(request.amount).is_err() {
             respond_to.maybe_send_err(HeaderExError::InvalidRequest);
-            return;
+            return None;
         };
 
         let Some(peer) = self.peer_tracker.best_peer() else {
             respond_to.maybe_send_err(P2pError::NoConnectedPeers);
-            return;
+            return None;
         };
 
         let req_id = sender.send_request(&peer, request.clone());
         let state = State {
             request,
             respond_to: OneshotSender::new(respond_to),
+            sent_at: Instant::now(),
         };
 
         self.reqs.insert(req_id, state);
+
+        Some(peer)
     }
 
     fn send_head_request(
@@ -178,6 +192,7 @@ where
             let state = State {
                 request: request.clone(),
                 respond_to: OneshotSender::new(tx),
+                sent_at: Instant::now(),
             };
 
             self.reqs.insert(req_id, state);
@@ -262,12 +277,27 @@ where
             return;
         };
 
+        let latency = state.sent_at.elapsed();
+        self.metrics.observe_header_ex_request(&peer, latency);
+
+        let peer_tracker = self.peer_tracker.clone();
+
         self.tasks.push(
             async move {
-                let res = decode_and_verify_responses(&state.request, &responses)
-                    .await
-                    .map_err(P2pError::from);
-                state.respond_to.maybe_send(res);
+                let res = decode_and_verify_responses(&state.request, &responses).await;
+
+                match res {
+                    Ok(_) => {
+                        peer_tracker.report(peer, PeerReport::Success { latency });
+                    }
+                    Err(HeaderExError::InvalidResponse) => {
+                        peer_tracker.report(peer, PeerReport::InvalidResponse);
+                    }
+                    // Peer may not have the requested headers yet
+                    Err(_) => {}
+                }
+
+                state.respond_to.maybe_send(res.map_err(P2pError::from));
             }
             .boxed(),
         );
@@ -283,6 +313,10 @@ where
         debug!("Outbound failure");
 
         if let Some(mut state) = self.reqs.remove(&request_id) {
+            if let Some(report) = PeerReport::from_outbound_failure(&error) {
+                self.peer_tracker.report(peer, report);
+            }
+
             state
                 .respond_to
                 .maybe_send_err(HeaderExError::OutboundFailure(error));
@@ -393,7 +427,7 @@ mod tests {
     async fn request_height() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -414,7 +448,7 @@ mod tests {
     async fn request_hash() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -439,7 +473,7 @@ mod tests {
     async fn request_range() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -463,7 +497,7 @@ mod tests {
     async fn request_range_responds_with_unsorted_headers() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -492,7 +526,7 @@ mod tests {
     async fn request_range_responds_with_invalid_headaer_in_the_middle() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -520,7 +554,7 @@ mod tests {
     async fn request_range_responds_with_not_found() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -543,7 +577,7 @@ mod tests {
     async fn respond_with_another_height() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -564,7 +598,7 @@ mod tests {
     async fn respond_with_bad_range() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -591,11 +625,73 @@ mod tests {
         ));
     }
 
+    #[async_test]
+    async fn responses_affect_peer_score() {
+        let peer_tracker = peer_tracker_with_n_peers(1);
+        let mut mock_req = MockReq::new();
+        let mut handler =
+            HeaderExClientHandler::<MockReq>::new(peer_tracker.clone(), Metrics::default());
+
+        let mut gen = ExtendedHeaderGenerator::new_from_height(5);
+        let header5 = gen.next();
+
+        let (tx, rx) = oneshot::channel();
+        let peer = handler.on_send_request(&mut mock_req, HeaderRequest::with_origin(5, 1), tx);
+        assert_eq!(peer, peer_tracker.best_peer());
+
+        mock_req.send_n_responses(&mut handler, 1, vec![header5.to_header_response()]);
+        poll_client_and_receiver(&mut handler, rx).await.unwrap();
+        peer_tracker.update_scores_info();
+        assert!(peer_tracker.info().average_peer_score > 0.0);
+
+        let (tx, rx) = oneshot::channel();
+        handler.on_send_request(&mut mock_req, HeaderRequest::with_origin(6, 1), tx);
+
+        mock_req.send_n_responses(&mut handler, 1, vec![header5.to_header_response()]);
+        poll_client_and_receiver(&mut handler, rx)
+            .await
+            .unwrap_err();
+        peer_tracker.update_scores_info();
+        assert!(peer_tracker.info().average_peer_score < 0.0);
+
+        // Head requests are sent to multiple peers
+        let (tx, _rx) = oneshot::channel();
+        let peer = handler.on_send_request(&mut mock_req, HeaderRequest::with_origin(0, 1), tx);
+        assert_eq!(peer, None);
+        mock_req.clear_pending_requests();
+    }
+
+    #[async_test]
+    async fn local_failures_dont_affect_peer_score() {
+        let peer_tracker = peer_tracker_with_n_peers(1);
+        let mut mock_req = MockReq::new();
+        let mut handler =
+            HeaderExClientHandler::<MockReq>::new(peer_tracker.clone(), Metrics::default());
+
+        let (tx, rx) = oneshot::channel();
+        handler.on_send_request(&mut mock_req, HeaderRequest::with_origin(5, 1), tx);
+        mock_req.send_n_failures(&mut handler, 1, OutboundFailure::ConnectionClosed);
+        poll_client_and_receiver(&mut handler, rx)
+            .await
+            .unwrap_err();
+        peer_tracker.update_scores_info();
+        assert_eq!(peer_tracker.info().average_peer_score, 0.0);
+
+        let (tx, rx) = oneshot::channel();
+        handler.on_send_request(&mut mock_req, HeaderRequest::with_origin(5, 1), tx);
+        mock_req.send_n_failures(&mut handler, 1, OutboundFailure::Timeout);
+        poll_client_and_receiver(&mut handler, rx)
+            .await
+            .unwrap_err();
+        peer_tracker.update_scores_info();
+        assert!(peer_tracker.info().average_peer_score < 0.0);
+    }
+
     #[async_test]
     async fn respond_with_bad_hash() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -620,7 +716,7 @@ mod tests {
     async fn request_unavailable_heigh() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -643,7 +739,7 @@ mod tests {
     async fn respond_with_invalid_status_code() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -666,7 +762,7 @@ mod tests {
     async fn respond_with_unknown_status_code() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -689,7 +785,7 @@ mod tests {
     async fn request_range_responds_with_smaller_one() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -707,7 +803,7 @@ mod tests {
     async fn request_range_responds_with_bigger_one() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -732,7 +828,7 @@ mod tests {
     async fn respond_with_invalid_header() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -755,7 +851,7 @@ mod tests {
     async fn respond_with_allowed_bad_header() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -784,7 +880,7 @@ mod tests {
     async fn request_height_then_stop() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -807,7 +903,7 @@ mod tests {
     async fn invalid_requests() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         // Zero amount
         let (tx, rx) = oneshot::channel();
@@ -884,7 +980,7 @@ mod tests {
     async fn head_best() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -922,7 +1018,7 @@ mod tests {
     async fn head_highest_peers() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -965,7 +1061,7 @@ mod tests {
     async fn head_highest_height() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -991,7 +1087,7 @@ mod tests {
     async fn head_request_responds_with_multiple_headers() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -1025,7 +1121,7 @@ mod tests {
     async fn head_request_responds_with_invalid_headers() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -1052,7 +1148,7 @@ mod tests {
     async fn head_request_responds_only_with_invalid_headers() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -1074,7 +1170,7 @@ mod tests {
     async fn head_request_responds_with_only_failures() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -1093,7 +1189,7 @@ mod tests {
     async fn head_request_with_one_peer() {
         let peer_tracker = peer_tracker_with_n_peers(1);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -1114,7 +1210,7 @@ mod tests {
     async fn head_request_with_no_peers() {
         let peer_tracker = peer_tracker_with_n_peers(0);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, rx) = oneshot::channel();
 
@@ -1130,7 +1226,7 @@ mod tests {
     async fn head_request_then_stop() {
         let peer_tracker = peer_tracker_with_n_peers(15);
         let mut mock_req = MockReq::new();
-        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker);
+        let mut handler = HeaderExClientHandler::<MockReq>::new(peer_tracker, Metrics::default());
 
         let (tx, mut rx) = oneshot::channel();
 
@@ -1,27 +1,42 @@
+use std::slice;
+
 use celestia_proto::p2p::pb::HeaderRequest;
 use celestia_types::ExtendedHeader;
 use futures::future::BoxFuture;
 use futures::stream::{FuturesUnordered, StreamExt};
 use futures::FutureExt;
+use libp2p::PeerId;
 use tokio::sync::{mpsc, oneshot};
 use tracing::debug;
 
 use crate::block_ranges::{BlockRange, BlockRangeExt};
 use crate::p2p::header_ex::utils::HeaderRequestExt;
-use crate::p2p::{P2pCmd, P2pError};
+use crate::p2p::{HeaderExError, P2pCmd, P2pError};
+use crate::peer_tracker::PeerReport;
 
 pub(crate) const MIN_AMOUNT_PER_REQ: u64 = 8;
 pub(crate) const MAX_AMOUNT_PER_REQ: u64 = 64;
 pub(crate) const MAX_CONCURRENT_REQS: usize = 8;
+/// Maximum number of invalid batches before the session fails.
+const MAX_REJECTED_BATCHES: usize = 8;
 
 type Result<T, E = P2pError> = std::result::Result<T, E>;
-type TaskResult = (u64, u64, Result<Vec<ExtendedHeader>>);
+type TaskResult = (u64, u64, Result<Vec<ExtendedHeader>>, Option<PeerId>);
 
 pub(crate) struct HeaderSession {
     to_fetch: Option<BlockRange>,
     cmd_tx: mpsc::Sender<P2pCmd>,
     tasks: FuturesUnordered<BoxFuture<'static, TaskResult>>,
     batch_size: u64,
+    anchor: Option<ExtendedHeader>,
+    tail_anchor: Option<ExtendedHeader>,
+    rejected_batches: usize,
+}
+
+/// Headers received from a single peer.
+struct Batch {
+    peer: Option<PeerId>,
+    headers: Vec<ExtendedHeader>,
 }
 
 impl HeaderSession {
@@ -44,53 +59,158 @@ impl HeaderSession {
             cmd_tx,
             tasks: FuturesUnordered::new(),
             batch_size,
+            anchor: None,
+            tail_anchor: None,
+            rejected_batches: 0,
+        }
+    }
+
+    /// Verify the fetched headers against the `anchor`, which is the header
+    /// preceding the range.
+    ///
+    /// Batches which don't belong to the chain of the `anchor` are fetched
+    /// again and the peers which sent them are reported.
+    pub(crate) fn with_anchor(self, anchor: ExtendedHeader) -> Self {
+        HeaderSession {
+            anchor: Some(anchor),
+            ..self
+        }
+    }
+
+    /// Verify the fetched headers against the `anchor`, which is the header
+    /// following the range.
+    ///
+    /// It's used only if there is no anchor preceding the range.
+    pub(crate) fn with_tail_anchor(self, anchor: ExtendedHeader) -> Self {
+        HeaderSession {
+            tail_anchor: Some(anchor),
+            ..self
         }
     }
 
     pub(crate) async fn run(&mut self) -> Result<Vec<ExtendedHeader>> {
-        let mut responses = Vec::new();
+        let mut batches = Vec::new();
 
         for _ in 0..MAX_CONCURRENT_REQS {
             self.send_next_request().await;
         }
 
-        while let Some((height, requested_amount, res)) = self.tasks.next().await {
-            match res {
-                Ok(headers) => {
-                    let headers_len = headers.len() as u64;
-
-                    if headers_len > 0 {
-                        responses.push(headers);
+        loop {
+            while let Some((height, requested_amount, res, peer)) = self.tasks.next().await {
+                match res {
+                    Ok(headers) => {
+                        let headers_len = headers.len() as u64;
+
+                        // Headers of a single response must be connected with each other
+                        if let Some(first) = headers.first() {
+                            if first.verify_adjacent_range(&headers[1..]).is_err() {
+                                self.reject_batch(peer, PeerReport::InvalidResponse).await?;
+                                self.send_request(height, requested_amount).await;
+                                continue;
+                            }
+                        }
+
+                        if headers_len > 0 {
+                            batches.push(Batch { peer, headers });
+                        }
+
+                        if headers_len < requested_amount {
+                            // Reschedule the missing sub-range
+                            let height = height + headers_len;
+                            let amount = requested_amount - headers_len;
+                            self.send_request(height, amount).await;
+
+                            debug!("requested {requested_amount}, got {headers_len}: retrying {height} +{amount}");
+                        } else {
+                            // Schedule next request
+                            self.send_next_request().await;
+                        }
                     }
+                    Err(P2pError::HeaderEx(e)) => {
+                        debug!("HeaderEx error: {e}");
+                        self.send_request(height, requested_amount).await;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
 
-                    if headers_len < requested_amount {
-                        // Reschedule the missing sub-range
-                        let height = height + headers_len;
-                        let amount = requested_amount - headers_len;
-                        self.send_request(height, amount).await;
+            batches.sort_unstable_by_key(|batch| {
+                batch
+                    .headers
+                    .first()
+                    .expect("empty batches aren't added in receiving loop")
+                    .height()
+                    .value()
+            });
+
+            let Some(idx) = self.find_disconnected_batch(&batches) else {
+                break;
+            };
+
+            // Batch doesn't belong to the chain, fetch it again
+            let batch = batches.remove(idx);
+            let height = batch.headers[0].height().value();
+            let amount = batch.headers.len() as u64;
+
+            self.reject_batch(batch.peer, PeerReport::WrongChain)
+                .await?;
+            self.send_request(height, amount).await;
+        }
 
-                        debug!("requested {requested_amount}, got {headers_len}: retrying {height} +{amount}");
-                    } else {
-                        // Schedule next request
-                        self.send_next_request().await;
-                    }
+        Ok(batches
+            .into_iter()
+            .flat_map(|batch| batch.headers)
+            .collect())
+    }
+
+    /// Returns the index of the first batch which isn't connected to the anchor
+    /// through the batches between them.
+    fn find_disconnected_batch(&self, batches: &[Batch]) -> Option<usize> {
+        if let Some(mut prev) = self.anchor.as_ref() {
+            for (idx, batch) in batches.iter().enumerate() {
+                if prev.verify_adjacent_range(&batch.headers).is_err() {
+                    return Some(idx);
                 }
-                Err(P2pError::HeaderEx(e)) => {
-                    debug!("HeaderEx error: {e}");
-                    self.send_request(height, requested_amount).await;
+
+                prev = batch.headers.last().expect("empty batches aren't added");
+            }
+        } else if let Some(mut next) = self.tail_anchor.as_ref() {
+            for (idx, batch) in batches.iter().enumerate().rev() {
+                let last = batch.headers.last().expect("empty batches aren't added");
+
+                if last.verify_adjacent_range(slice::from_ref(next)).is_err() {
+                    return Some(idx);
                 }
-                Err(e) => return Err(e),
+
+                next = &batch.headers[0];
             }
         }
 
-        responses.sort_unstable_by_key(|span| {
-            span.first()
-                .expect("empty spans aren't added in receiving loop")
-                .height()
-                .value()
-        });
+        None
+    }
 
-        Ok(responses.into_iter().flatten().collect())
+    /// Report the peer which sent an invalid batch.
+    ///
+    /// Session fails if the peer isn't known or too many batches were rejected.
+    async fn reject_batch(&mut self, peer: Option<PeerId>, report: PeerReport) -> Result<()> {
+        self.rejected_batches += 1;
+
+        let Some(peer) = peer else {
+            return Err(HeaderExError::InvalidResponse.into());
+        };
+
+        debug!("Rejected batch from {peer}: {report:?}");
+
+        self.cmd_tx
+            .send(P2pCmd::ReportPeer { peer, report })
+            .await
+            .map_err(|_| P2pError::WorkerDied)?;
+
+        if self.rejected_batches > MAX_REJECTED_BATCHES {
+            return Err(HeaderExError::InvalidResponse.into());
+        }
+
+        Ok(())
     }
 
     pub(crate) async fn send_next_request(&mut self) {
@@ -107,6 +227,8 @@ impl HeaderSession {
 
         self.tasks.push(
             async move {
+                let (peer_tx, peer_rx) = oneshot::channel();
+
                 let result = async move {
                     let (tx, rx) = oneshot::channel();
 
@@ -114,6 +236,7 @@ impl HeaderSession {
                         .send(P2pCmd::HeaderExRequest {
                             request,
                             respond_to: tx,
+                            peer_tx: Some(peer_tx),
                         })
                         .await
                         .map_err(|_| P2pError::WorkerDied)?;
@@ -122,7 +245,10 @@ impl HeaderSession {
                 }
                 .await;
 
-                (height, amount, result)
+                // Peer is sent when the request is sent, so it's known by now
+                let peer = peer_rx.await.ok();
+
+                (height, amount, result, peer)
             }
             .boxed(),
         );
@@ -148,7 +274,7 @@ fn take_next_batch(range_to_fetch: &mut Option<BlockRange>, limit: u64) -> Optio
 mod tests {
     use super::*;
     use crate::executor::spawn;
-    use crate::p2p::{HeaderExError, P2p};
+    use crate::p2p::P2p;
     use crate::test_utils::async_test;
     use celestia_types::test_utils::ExtendedHeaderGenerator;
 
@@ -317,6 +443,126 @@ mod tests {
         ));
     }
 
+    #[async_test]
+    async fn refetch_batch_from_wrong_chain() {
+        let (_p2p, mut p2p_mock) = P2p::mocked();
+        let mut gen = ExtendedHeaderGenerator::new();
+        let anchor = gen.next();
+        let headers = gen.next_many(8);
+        let forged_headers = ExtendedHeaderGenerator::new_from_height(2).next_many(8);
+        let bad_peer = PeerId::random();
+
+        let mut session = HeaderSession::new(2..=9, p2p_mock.cmd_tx.clone()).with_anchor(anchor);
+        let (result_tx, result_rx) = oneshot::channel();
+        spawn(async move {
+            let res = session.run().await;
+            result_tx.send(res).unwrap();
+        });
+
+        match p2p_mock.expect_cmd().await {
+            P2pCmd::HeaderExRequest {
+                respond_to,
+                peer_tx: Some(peer_tx),
+                ..
+            } => {
+                peer_tx.send(bad_peer).unwrap();
+                respond_to.send(Ok(forged_headers)).unwrap();
+            }
+            cmd => panic!("Expecting HeaderExRequest, but received: {cmd:?}"),
+        }
+
+        match p2p_mock.expect_cmd().await {
+            P2pCmd::ReportPeer { peer, report } => {
+                assert_eq!(peer, bad_peer);
+                assert_eq!(report, PeerReport::WrongChain);
+            }
+            cmd => panic!("Expecting ReportPeer, but received: {cmd:?}"),
+        }
+
+        let (height, amount, respond_to) = p2p_mock.expect_header_request_for_height_cmd().await;
+        assert_eq!(height, 2);
+        assert_eq!(amount, 8);
+        respond_to.send(Ok(headers.clone())).unwrap();
+
+        p2p_mock.expect_no_cmd().await;
+
+        let received_headers = result_rx.await.unwrap().unwrap();
+        assert_eq!(headers, received_headers);
+    }
+
+    #[async_test]
+    async fn refetch_batch_from_wrong_chain_with_tail_anchor() {
+        let (_p2p, mut p2p_mock) = P2p::mocked();
+        let mut gen = ExtendedHeaderGenerator::new();
+        let headers = gen.next_many(8);
+        let tail_anchor = gen.next();
+        let forged_headers = ExtendedHeaderGenerator::new().next_many(8);
+        let bad_peer = PeerId::random();
+
+        let mut session =
+            HeaderSession::new(1..=8, p2p_mock.cmd_tx.clone()).with_tail_anchor(tail_anchor);
+        let (result_tx, result_rx) = oneshot::channel();
+        spawn(async move {
+            let res = session.run().await;
+            result_tx.send(res).unwrap();
+        });
+
+        match p2p_mock.expect_cmd().await {
+            P2pCmd::HeaderExRequest {
+                respond_to,
+                peer_tx: Some(peer_tx),
+                ..
+            } => {
+                peer_tx.send(bad_peer).unwrap();
+                respond_to.send(Ok(forged_headers)).unwrap();
+            }
+            cmd => panic!("Expecting HeaderExRequest, but received: {cmd:?}"),
+        }
+
+        match p2p_mock.expect_cmd().await {
+            P2pCmd::ReportPeer { peer, report } => {
+                assert_eq!(peer, bad_peer);
+                assert_eq!(report, PeerReport::WrongChain);
+            }
+            cmd => panic!("Expecting ReportPeer, but received: {cmd:?}"),
+        }
+
+        let (height, amount, respond_to) = p2p_mock.expect_header_request_for_height_cmd().await;
+        assert_eq!(height, 1);
+        assert_eq!(amount, 8);
+        respond_to.send(Ok(headers.clone())).unwrap();
+
+        p2p_mock.expect_no_cmd().await;
+
+        let received_headers = result_rx.await.unwrap().unwrap();
+        assert_eq!(headers, received_headers);
+    }
+
+    #[async_test]
+    async fn unknown_peer_from_wrong_chain_is_fatal() {
+        let (_p2p, mut p2p_mock) = P2p::mocked();
+        let mut gen = ExtendedHeaderGenerator::new();
+        let anchor = gen.next();
+        let forged_headers = ExtendedHeaderGenerator::new_from_height(2).next_many(8);
+
+        let mut session = HeaderSession::new(2..=9, p2p_mock.cmd_tx.clone()).with_anchor(anchor);
+        let (result_tx, result_rx) = oneshot::channel();
+        spawn(async move {
+            let res = session.run().await;
+            result_tx.send(res).unwrap();
+        });
+
+        let (_, _, respond_to) = p2p_mock.expect_header_request_for_height_cmd().await;
+        respond_to.send(Ok(forged_headers)).unwrap();
+
+        p2p_mock.expect_no_cmd().await;
+
+        assert!(matches!(
+            result_rx.await,
+            Ok(Err(P2pError::HeaderEx(HeaderExError::InvalidResponse)))
+        ));
+    }
+
     #[test]
     fn take_next_batch_full_batch() {
         let mut range_to_fetch = Some(1..=10);
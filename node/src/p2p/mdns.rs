@@ -0,0 +1,93 @@
+//! Local peer discovery with mDNS.
+//!
+//! mDNS is only available on native platforms. In browsers the behaviour is always
+//! disabled and never produces any events.
+
+use libp2p::{Multiaddr, PeerId};
+
+use crate::p2p::Result;
+
+pub(crate) use self::imp::{new_behaviour, Behaviour, Event};
+
+/// Change of the peers announced in the local network.
+#[derive(Debug)]
+pub(crate) enum MdnsChange {
+    /// Peers were discovered on the addresses.
+    Discovered(Vec<(PeerId, Multiaddr)>),
+    /// Peers are no longer announced on any address.
+    Expired(Vec<PeerId>),
+}
+
+/// Converts an mDNS event into the change of the announced peers.
+pub(crate) fn to_change(behaviour: &Behaviour, ev: Event) -> MdnsChange {
+    imp::to_change(behaviour, ev)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod imp {
+    use libp2p::mdns;
+    use libp2p::swarm::behaviour::toggle::Toggle;
+
+    use super::*;
+    use crate::p2p::P2pError;
+
+    pub(crate) type Behaviour = Toggle<mdns::tokio::Behaviour>;
+    pub(crate) type Event = mdns::Event;
+
+    pub(crate) fn new_behaviour(enabled: bool, local_peer_id: PeerId) -> Result<Behaviour> {
+        if !enabled {
+            return Ok(Toggle::from(None));
+        }
+
+        let mdns = mdns::tokio::Behaviour::new(mdns::Config::default(), local_peer_id)
+            .map_err(|e| P2pError::MdnsInit(e.to_string()))?;
+
+        Ok(Toggle::from(Some(mdns)))
+    }
+
+    pub(super) fn to_change(behaviour: &Behaviour, ev: Event) -> MdnsChange {
+        match ev {
+            mdns::Event::Discovered(peers) => MdnsChange::Discovered(peers),
+            mdns::Event::Expired(peers) => {
+                let mut expired = peers
+                    .into_iter()
+                    .map(|(peer_id, _)| peer_id)
+                    .collect::<Vec<_>>();
+                expired.sort_unstable();
+                expired.dedup();
+
+                // Peer may still be announced on other addresses.
+                if let Some(mdns) = behaviour.as_ref() {
+                    expired.retain(|peer_id| !mdns.discovered_nodes().any(|p| p == peer_id));
+                }
+
+                MdnsChange::Expired(expired)
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod imp {
+    use libp2p::swarm::behaviour::toggle::Toggle;
+    use libp2p::swarm::dummy;
+    use tracing::warn;
+    use void::Void;
+
+    use super::*;
+
+    pub(crate) type Behaviour = Toggle<dummy::Behaviour>;
+    pub(crate) type Event = Void;
+
+    pub(crate) fn new_behaviour(enabled: bool, _local_peer_id: PeerId) -> Result<Behaviour> {
+        if enabled {
+            warn!("mDNS is not supported in the browser, ignoring");
+        }
+
+        Ok(Toggle::from(None))
+    }
+
+    pub(super) fn to_change(_behaviour: &Behaviour, ev: Event) -> MdnsChange {
+        match ev {}
+    }
+}
@@ -0,0 +1,364 @@
+//! Client side of the `shrex` protocols.
+//!
+//! `shrex` is a request-response data retrieval protocol served by celestia full and
+//! bridge nodes. Contrary to shwap over bitswap, which fetches data piece by piece,
+//! `shrex` allows fetching whole EDS or all the namespace data of a block in a single
+//! request. This makes it a good fallback when bitswap is slow or unavailable.
+//!
+//! Supported protocols:
+//! - `shrex/nd` - all [`RowNamespaceData`] of a block for a given namespace
+//! - `shrex/eds` - original data square of a block
+//! - `shrex/sub` - gossipsub topic on which bridge nodes announce new EDSes
+//!
+//! [`RowNamespaceData`]: celestia_types::row_namespace_data::RowNamespaceData
+
+use std::io;
+
+use async_trait::async_trait;
+use celestia_proto::{Response as RawShrexResponse, Status as RawShrexStatus};
+use celestia_types::consts::appconsts::SHARE_SIZE;
+use celestia_types::nmt::Namespace;
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use libp2p::request_response::{self, Codec, OutboundFailure, ProtocolSupport};
+use libp2p::StreamProtocol;
+use prost::Message;
+use web_time::Duration;
+
+use crate::executor::timeout;
+use crate::utils::protocol_id;
+
+/// Protocol name of `shrex/nd`.
+const SHREX_ND_PROTOCOL: &str = "/shrex/nd/v0.1.0";
+/// Protocol name of `shrex/eds`.
+const SHREX_EDS_PROTOCOL: &str = "/shrex/eds/v0.1.0";
+/// Gossipsub topic of `shrex/sub`.
+pub(crate) const SHREX_SUB_TOPIC: &str = "/eds-sub/v0.2.0";
+
+/// Maximum size of the length-delimited status message.
+const STATUS_SIZE_LIMIT: usize = 16;
+/// Size limit of a `shrex/nd` response payload in bytes.
+const ND_RESPONSE_SIZE_LIMIT: usize = 64 * 1024 * 1024;
+/// Time limit on reading a `shrex/nd` response.
+const ND_RESPONSE_TIME_LIMIT: Duration = Duration::from_secs(30);
+/// Maximum width of the ODS received over `shrex/eds`.
+///
+/// Bigger than the current upper bound of the square size, so that the limit
+/// holds after the square size is increased.
+const EDS_MAX_ODS_WIDTH: usize = 512;
+/// Size limit of a `shrex/eds` response payload in bytes.
+///
+/// The payload carries only the shares of the ODS, without any proofs, as the square
+/// is verified against the DAH once extended.
+const EDS_RESPONSE_SIZE_LIMIT: usize = EDS_MAX_ODS_WIDTH * EDS_MAX_ODS_WIDTH * SHARE_SIZE;
+/// Time limit on reading a `shrex/eds` response.
+const EDS_RESPONSE_TIME_LIMIT: Duration = Duration::from_secs(60);
+
+pub(crate) type ShrexBehaviour = request_response::Behaviour<ShrexCodec>;
+pub(crate) type ShrexEvent = request_response::Event<ShrexRequest, ShrexResponse>;
+
+/// Representation of all the errors that can occur in `shrex` protocols.
+#[derive(Debug, thiserror::Error)]
+pub enum ShrexError {
+    /// Requested data not found.
+    #[error("Data not found")]
+    NotFound,
+
+    /// Remote peer considered the request invalid.
+    #[error("Invalid request")]
+    InvalidRequest,
+
+    /// The response is invalid.
+    #[error("Invalid response")]
+    InvalidResponse,
+
+    /// Remote peer failed to handle the request.
+    #[error("Internal error on remote peer")]
+    RemoteInternal,
+
+    /// There are no connected peers that serve `shrex`.
+    #[error("No shrex peers available")]
+    NoPeers,
+
+    /// Error when handling connection to the server.
+    #[error("Outbound failure: {0}")]
+    OutboundFailure(OutboundFailure),
+}
+
+/// Which of the `shrex` protocols to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ShrexProtocol {
+    NamespaceData,
+    Eds,
+}
+
+/// Binary encoded shwap identifier of the requested data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ShrexRequest(Vec<u8>);
+
+impl ShrexRequest {
+    /// Request for the original data square of a block.
+    pub(crate) fn eds(height: u64) -> Self {
+        ShrexRequest(height.to_be_bytes().to_vec())
+    }
+
+    /// Request for all the namespace data of a block.
+    pub(crate) fn namespace_data(height: u64, namespace: Namespace) -> Self {
+        let mut bytes = height.to_be_bytes().to_vec();
+        bytes.extend_from_slice(namespace.as_bytes());
+        ShrexRequest(bytes)
+    }
+}
+
+/// Response received from the `shrex` server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ShrexResponse {
+    status: RawShrexStatus,
+    payload: Vec<u8>,
+}
+
+impl ShrexResponse {
+    /// Returns the payload if the request succeeded.
+    pub(crate) fn into_result(self) -> Result<Vec<u8>, ShrexError> {
+        match self.status {
+            RawShrexStatus::Ok => Ok(self.payload),
+            RawShrexStatus::NotFound => Err(ShrexError::NotFound),
+            RawShrexStatus::Internal => Err(ShrexError::RemoteInternal),
+            RawShrexStatus::Invalid => Err(ShrexError::InvalidRequest),
+        }
+    }
+}
+
+/// Create a client-only behaviour for the given `shrex` protocol.
+pub(crate) fn new_shrex_behaviour(network_id: &str, protocol: ShrexProtocol) -> ShrexBehaviour {
+    let (name, codec) = match protocol {
+        ShrexProtocol::NamespaceData => (
+            SHREX_ND_PROTOCOL,
+            ShrexCodec::new(ND_RESPONSE_SIZE_LIMIT, ND_RESPONSE_TIME_LIMIT),
+        ),
+        ShrexProtocol::Eds => (
+            SHREX_EDS_PROTOCOL,
+            ShrexCodec::new(EDS_RESPONSE_SIZE_LIMIT, EDS_RESPONSE_TIME_LIMIT),
+        ),
+    };
+
+    ShrexBehaviour::with_codec(
+        codec,
+        [(protocol_id(network_id, name), ProtocolSupport::Outbound)],
+        request_response::Config::default().with_request_timeout(codec.time_limit),
+    )
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ShrexCodec {
+    size_limit: usize,
+    time_limit: Duration,
+}
+
+impl ShrexCodec {
+    fn new(size_limit: usize, time_limit: Duration) -> Self {
+        ShrexCodec {
+            size_limit,
+            time_limit,
+        }
+    }
+}
+
+#[async_trait]
+impl Codec for ShrexCodec {
+    type Protocol = StreamProtocol;
+    type Request = ShrexRequest;
+    type Response = ShrexResponse;
+
+    async fn read_request<T>(
+        &mut self,
+        _: &Self::Protocol,
+        _io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "shrex server is not supported",
+        ))
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        _: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        match timeout(self.time_limit, read_response(io, self.size_limit)).await {
+            Ok(res) => res,
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "reading shrex response timed out",
+            )),
+        }
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &Self::Protocol,
+        io: &mut T,
+        req: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        io.write_all(&req.0).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _: &Self::Protocol,
+        _io: &mut T,
+        _resp: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "shrex server is not supported",
+        ))
+    }
+}
+
+async fn read_response<T>(io: &mut T, size_limit: usize) -> io::Result<ShrexResponse>
+where
+    T: AsyncRead + Unpin + Send,
+{
+    let status_len = read_uvarint(io).await?;
+
+    if status_len > STATUS_SIZE_LIMIT as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "shrex status message too large",
+        ));
+    }
+
+    let mut buf = vec![0u8; status_len as usize];
+    io.read_exact(&mut buf).await?;
+
+    let status = RawShrexResponse::decode(&buf[..])
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        .status();
+
+    let mut payload = Vec::new();
+
+    if status == RawShrexStatus::Ok {
+        io.take(size_limit as u64 + 1)
+            .read_to_end(&mut payload)
+            .await?;
+
+        if payload.len() > size_limit {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "shrex response too large",
+            ));
+        }
+    }
+
+    Ok(ShrexResponse { status, payload })
+}
+
+async fn read_uvarint<T>(io: &mut T) -> io::Result<u64>
+where
+    T: AsyncRead + Unpin + Send,
+{
+    let mut val = 0u64;
+
+    for i in 0..10 {
+        let mut byte = [0u8; 1];
+        io.read_exact(&mut byte).await?;
+
+        val |= u64::from(byte[0] & 0x7f) << (i * 7);
+
+        if byte[0] & 0x80 == 0 {
+            return Ok(val);
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint overflow",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::async_test;
+    use futures::io::Cursor;
+
+    fn encode_response(status: RawShrexStatus, payload: &[u8]) -> Vec<u8> {
+        let mut buf = RawShrexResponse {
+            status: status.into(),
+        }
+        .encode_length_delimited_to_vec();
+        buf.extend_from_slice(payload);
+        buf
+    }
+
+    #[async_test]
+    async fn request_encoding() {
+        let ns = Namespace::new_v0(&[1, 2, 3]).unwrap();
+
+        assert_eq!(ShrexRequest::eds(0x0102).0, [0, 0, 0, 0, 0, 0, 1, 2]);
+
+        let req = ShrexRequest::namespace_data(3, ns);
+        assert_eq!(req.0.len(), 8 + 29);
+        assert_eq!(&req.0[..8], &3u64.to_be_bytes());
+        assert_eq!(&req.0[8..], ns.as_bytes());
+    }
+
+    #[async_test]
+    async fn decode_ok_response() {
+        let mut codec = ShrexCodec::new(1024, Duration::from_secs(1));
+        let protocol = StreamProtocol::new("/foo/bar/v0.1");
+        let mut reader = Cursor::new(encode_response(RawShrexStatus::Ok, &[7; 100]));
+
+        let resp = codec.read_response(&protocol, &mut reader).await.unwrap();
+        assert_eq!(resp.into_result().unwrap(), vec![7; 100]);
+    }
+
+    #[async_test]
+    async fn decode_not_found_response() {
+        let mut codec = ShrexCodec::new(1024, Duration::from_secs(1));
+        let protocol = StreamProtocol::new("/foo/bar/v0.1");
+        let mut reader = Cursor::new(encode_response(RawShrexStatus::NotFound, &[]));
+
+        let resp = codec.read_response(&protocol, &mut reader).await.unwrap();
+        assert!(matches!(resp.into_result(), Err(ShrexError::NotFound)));
+    }
+
+    #[async_test]
+    async fn decode_response_too_large() {
+        let mut codec = ShrexCodec::new(64, Duration::from_secs(1));
+        let protocol = StreamProtocol::new("/foo/bar/v0.1");
+        let mut reader = Cursor::new(encode_response(RawShrexStatus::Ok, &[7; 65]));
+
+        let err = codec
+            .read_response(&protocol, &mut reader)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[async_test]
+    async fn decode_truncated_status() {
+        let mut codec = ShrexCodec::new(64, Duration::from_secs(1));
+        let protocol = StreamProtocol::new("/foo/bar/v0.1");
+        let mut reader = Cursor::new(vec![2]);
+
+        let err = codec
+            .read_response(&protocol, &mut reader)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
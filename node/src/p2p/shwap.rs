@@ -3,11 +3,13 @@ use std::sync::Arc;
 use beetswap::multihasher::{Multihasher, MultihasherError};
 use blockstore::block::CidError;
 use celestia_proto::bitswap::Block;
+use celestia_types::fraud_proof::BadEncodingFraudProof;
 use celestia_types::row::{Row, RowId, ROW_ID_MULTIHASH_CODE};
 use celestia_types::row_namespace_data::{
     RowNamespaceData, RowNamespaceDataId, ROW_NAMESPACE_DATA_ID_MULTIHASH_CODE,
 };
 use celestia_types::sample::{Sample, SampleId, SAMPLE_ID_MULTIHASH_CODE};
+use celestia_types::{AxisType, DataAvailabilityHeader, ExtendedDataSquare, ExtendedHeader, Share};
 use cid::{Cid, CidGeneric};
 use libp2p::multihash::Multihash;
 use prost::Message;
@@ -106,6 +108,129 @@ pub(crate) fn get_block_container(expected_cid: &Cid, block: &[u8]) -> Result<Ve
     Ok(block.container)
 }
 
+/// Reconstructs [`ExtendedDataSquare`] out of its rows and shares of its columns,
+/// and verifies it against the [`DataAvailabilityHeader`] of the header.
+///
+/// `rows` must contain an entry for each row of the EDS, with `None` for the missing ones.
+/// `column_shares` holds, for each original column, additional shares of the missing rows
+/// together with their row index. It may be empty if half of the rows is present.
+///
+/// If the reconstructed square doesn't match the header because it was incorrectly
+/// encoded, [`P2pError::BadEncoding`] is returned with a proof of that. Only the
+/// complete rows can be used for the proof.
+pub(crate) fn reconstruct_eds(
+    rows: &[Option<Row>],
+    column_shares: &[Vec<(u16, Share)>],
+    header: &ExtendedHeader,
+) -> Result<ExtendedDataSquare> {
+    let dah = &header.dah;
+    let app_version = header.app_version()?;
+    let square_width = usize::from(dah.square_width());
+    let ods_width = square_width / 2;
+
+    if rows.len() != square_width {
+        return Err(P2pError::Shwap(format!(
+            "expected {square_width} rows, got {}",
+            rows.len()
+        )));
+    }
+
+    let mut ods = vec![Vec::new(); ods_width * ods_width];
+
+    if rows[..ods_width].iter().all(Option::is_some) {
+        // All original rows are present, nothing to repair.
+        for (row_idx, row) in rows[..ods_width].iter().flatten().enumerate() {
+            for (col_idx, share) in row.shares[..ods_width].iter().enumerate() {
+                ods[row_idx * ods_width + col_idx] = share.to_vec();
+            }
+        }
+    } else {
+        // Repair each original column out of the rows we have.
+        for col_idx in 0..ods_width {
+            let mut column: Vec<_> = rows
+                .iter()
+                .map(|row| {
+                    row.as_ref()
+                        .map(|row| row.shares[col_idx].to_vec())
+                        .unwrap_or_default()
+                })
+                .collect();
+
+            for (row_idx, share) in column_shares.get(col_idx).into_iter().flatten() {
+                if let Some(slot) = column.get_mut(usize::from(*row_idx)) {
+                    *slot = share.to_vec();
+                }
+            }
+
+            leopard_codec::reconstruct(&mut column, ods_width)
+                .map_err(|e| P2pError::Shwap(format!("failed to repair column {col_idx}: {e}")))?;
+
+            for (row_idx, share) in column.into_iter().take(ods_width).enumerate() {
+                ods[row_idx * ods_width + col_idx] = share;
+            }
+        }
+    }
+
+    let eds = ExtendedDataSquare::from_ods(ods, app_version)?;
+    let eds_dah = DataAvailabilityHeader::from_eds(&eds);
+
+    if eds_dah == *dah {
+        return Ok(eds);
+    }
+
+    if let Some(befp) = find_bad_encoding(rows, header, &eds, &eds_dah) {
+        return Err(P2pError::BadEncoding(Box::new(befp)));
+    }
+
+    Err(P2pError::Shwap(
+        "reconstructed EDS doesn't match data availability header".to_string(),
+    ))
+}
+
+/// Find an axis which was incorrectly encoded and create a proof for it.
+fn find_bad_encoding(
+    rows: &[Option<Row>],
+    header: &ExtendedHeader,
+    eds: &ExtendedDataSquare,
+    eds_dah: &DataAvailabilityHeader,
+) -> Option<BadEncodingFraudProof> {
+    let dah = &header.dah;
+    let square_width = dah.square_width();
+
+    // Received rows are verified against their roots, so columns can be proven with them.
+    let row_shares: Vec<_> = rows
+        .iter()
+        .map(|row| row.as_ref().map(|row| row.shares.clone()))
+        .collect();
+
+    for col_idx in 0..square_width {
+        if eds_dah.column_root(col_idx) == dah.column_root(col_idx) {
+            continue;
+        }
+
+        if let Ok(befp) = BadEncodingFraudProof::new(header, AxisType::Col, col_idx, &row_shares) {
+            return Some(befp);
+        }
+    }
+
+    // If all columns are correct, rows can be proven with them.
+    let col_shares: Vec<_> = (0..square_width)
+        .map(|col_idx| eds.column(col_idx).ok())
+        .collect();
+
+    for row_idx in 0..square_width {
+        if eds_dah.row_root(row_idx) == dah.row_root(row_idx) {
+            continue;
+        }
+
+        if let Ok(befp) = BadEncodingFraudProof::new(header, AxisType::Row, row_idx, &col_shares) {
+            return Some(befp);
+        }
+    }
+
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,8 +238,8 @@ mod tests {
     use crate::test_utils::async_test;
     use bytes::BytesMut;
     use celestia_types::consts::appconsts::AppVersion;
-    use celestia_types::test_utils::{generate_dummy_eds, ExtendedHeaderGenerator};
-    use celestia_types::{AxisType, DataAvailabilityHeader};
+    use celestia_types::test_utils::{corrupt_eds, generate_dummy_eds, ExtendedHeaderGenerator};
+    use celestia_types::FraudProof;
 
     #[async_test]
     async fn hash() {
@@ -148,4 +273,110 @@ mod tests {
 
         assert_eq!(hash, *cid.hash());
     }
+
+    fn header_with_dah(dah: DataAvailabilityHeader) -> ExtendedHeader {
+        ExtendedHeaderGenerator::new().next_with_dah(dah)
+    }
+
+    fn eds_rows(eds: &ExtendedDataSquare, present: impl Fn(u16) -> bool) -> Vec<Option<Row>> {
+        (0..eds.square_width())
+            .map(|idx| present(idx).then(|| Row::new(idx, eds).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn reconstruct_eds_from_original_rows() {
+        let eds = generate_dummy_eds(8, AppVersion::V2);
+        let header = header_with_dah(DataAvailabilityHeader::from_eds(&eds));
+        let rows = eds_rows(&eds, |idx| idx < 4);
+
+        let reconstructed = reconstruct_eds(&rows, &[], &header).unwrap();
+        assert_eq!(reconstructed, eds);
+    }
+
+    #[test]
+    fn reconstruct_eds_from_parity_rows() {
+        let eds = generate_dummy_eds(8, AppVersion::V2);
+        let header = header_with_dah(DataAvailabilityHeader::from_eds(&eds));
+        let rows = eds_rows(&eds, |idx| idx >= 4);
+
+        let reconstructed = reconstruct_eds(&rows, &[], &header).unwrap();
+        assert_eq!(reconstructed, eds);
+    }
+
+    #[test]
+    fn reconstruct_eds_from_mixed_rows() {
+        let eds = generate_dummy_eds(8, AppVersion::V2);
+        let header = header_with_dah(DataAvailabilityHeader::from_eds(&eds));
+        let rows = eds_rows(&eds, |idx| idx % 2 == 0);
+
+        let reconstructed = reconstruct_eds(&rows, &[], &header).unwrap();
+        assert_eq!(reconstructed, eds);
+    }
+
+    #[test]
+    fn reconstruct_eds_from_rows_and_columns() {
+        let eds = generate_dummy_eds(8, AppVersion::V2);
+        let header = header_with_dah(DataAvailabilityHeader::from_eds(&eds));
+        let rows = eds_rows(&eds, |idx| idx == 1 || idx == 6);
+
+        // each column is completed with shares of different rows
+        let column_shares: Vec<_> = (0..4u16)
+            .map(|col_idx| {
+                [0, 2, 3, 4, 5, 7]
+                    .into_iter()
+                    .filter(|row_idx| (row_idx + col_idx) % 3 != 0)
+                    .take(2)
+                    .map(|row_idx| (row_idx, eds.share(row_idx, col_idx).unwrap().clone()))
+                    .collect()
+            })
+            .collect();
+
+        let reconstructed = reconstruct_eds(&rows, &column_shares, &header).unwrap();
+        assert_eq!(reconstructed, eds);
+
+        // not enough shares in one of the columns
+        let mut column_shares = column_shares;
+        column_shares[2].pop();
+        reconstruct_eds(&rows, &column_shares, &header).unwrap_err();
+    }
+
+    #[test]
+    fn reconstruct_eds_not_enough_rows() {
+        let eds = generate_dummy_eds(8, AppVersion::V2);
+        let header = header_with_dah(DataAvailabilityHeader::from_eds(&eds));
+        let rows = eds_rows(&eds, |idx| idx % 4 == 0);
+
+        reconstruct_eds(&rows, &[], &header).unwrap_err();
+    }
+
+    #[test]
+    fn reconstruct_eds_dah_mismatch() {
+        let eds = generate_dummy_eds(8, AppVersion::V2);
+        let other_eds = generate_dummy_eds(8, AppVersion::V2);
+        let header = header_with_dah(DataAvailabilityHeader::from_eds(&other_eds));
+        let rows = eds_rows(&eds, |idx| idx < 4);
+
+        reconstruct_eds(&rows, &[], &header).unwrap_err();
+    }
+
+    #[test]
+    fn reconstruct_eds_bad_encoding() {
+        let mut gen = ExtendedHeaderGenerator::new();
+        let mut eds = generate_dummy_eds(8, AppVersion::V2);
+        let (header, _) = corrupt_eds(&mut gen, &mut eds);
+
+        // Corrupted rows can't be verified against their roots, so only take valid ones.
+        let rows = eds_rows(&eds, |idx| {
+            Row::new(idx, &eds)
+                .unwrap()
+                .verify(RowId::new(idx, 1).unwrap(), &header.dah)
+                .is_ok()
+        });
+
+        match reconstruct_eds(&rows, &[], &header) {
+            Err(P2pError::BadEncoding(befp)) => befp.validate(&header).unwrap(),
+            res => panic!("unexpected result: {res:?}"),
+        }
+    }
 }
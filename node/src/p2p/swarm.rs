@@ -1,16 +1,17 @@
+use std::path::Path;
+
 use libp2p::identity::Keypair;
 use libp2p::swarm::{NetworkBehaviour, Swarm};
 use web_time::Duration;
 
+use crate::p2p::bandwidth::BandwidthMeter;
 use crate::p2p::{P2pError, Result};
 
 pub(crate) use self::imp::new_swarm;
 
 #[cfg(not(target_arch = "wasm32"))]
 mod imp {
-    use std::env;
     use std::io::Cursor;
-    use std::path::Path;
 
     use futures::future::Either;
     use libp2p::core::muxing::StreamMuxerBox;
@@ -21,18 +22,24 @@ mod imp {
 
     use super::*;
 
-    pub(crate) async fn new_swarm<B>(keypair: Keypair, behaviour: B) -> Result<Swarm<B>>
+    pub(crate) async fn new_swarm<B>(
+        keypair: Keypair,
+        behaviour: B,
+        tls_key_file: Option<&Path>,
+        tls_cert_file: Option<&Path>,
+        bandwidth_meter: BandwidthMeter,
+    ) -> Result<Swarm<B>>
     where
         B: NetworkBehaviour,
     {
-        let tls_key = match env::var("LUMINA_TLS_KEY_FILE") {
-            Ok(path) => Some(read_tls_key(path).await?),
-            Err(_) => None,
+        let tls_key = match tls_key_file {
+            Some(path) => Some(read_tls_key(path).await?),
+            None => None,
         };
 
-        let tls_certs = match env::var("LUMINA_TLS_CERT_FILE") {
-            Ok(path) => Some(read_tls_certs(path).await?),
-            Err(_) => None,
+        let tls_certs = match tls_cert_file {
+            Some(path) => Some(read_tls_certs(path).await?),
+            None => None,
         };
 
         // We do not use system's DNS because libp2p caches system DNS
@@ -94,9 +101,16 @@ mod imp {
                 dns_config,
                 dns::ResolverOpts::default(),
             ))
-            .map(|either, _| match either {
-                Either::Left((peer_id, conn)) => (peer_id, StreamMuxerBox::new(conn)),
-                Either::Right((peer_id, conn)) => (peer_id, StreamMuxerBox::new(conn)),
+            .map(move |either, _| {
+                let (peer_id, muxer) = match either {
+                    Either::Left((peer_id, conn)) => (peer_id, StreamMuxerBox::new(conn)),
+                    Either::Right((peer_id, conn)) => (peer_id, StreamMuxerBox::new(conn)),
+                };
+
+                (
+                    peer_id,
+                    StreamMuxerBox::new(bandwidth_meter.wrap(peer_id, muxer)),
+                )
             })
             .boxed();
 
@@ -161,28 +175,47 @@ mod imp {
 #[cfg(target_arch = "wasm32")]
 mod imp {
     use super::*;
+    use libp2p::core::muxing::StreamMuxerBox;
     use libp2p::core::upgrade::Version;
     use libp2p::{noise, websocket_websys, webtransport_websys, yamux, SwarmBuilder, Transport};
 
-    pub(crate) async fn new_swarm<B>(keypair: Keypair, behaviour: B) -> Result<Swarm<B>>
+    pub(crate) async fn new_swarm<B>(
+        keypair: Keypair,
+        behaviour: B,
+        // Browser can't listen for connections, so TLS files are not used.
+        _tls_key_file: Option<&Path>,
+        _tls_cert_file: Option<&Path>,
+        bandwidth_meter: BandwidthMeter,
+    ) -> Result<Swarm<B>>
     where
         B: NetworkBehaviour,
     {
         let noise_config =
             noise::Config::new(&keypair).map_err(|e| P2pError::NoiseInit(e.to_string()))?;
 
+        let ws_meter = bandwidth_meter.clone();
+        let webtransport_meter = bandwidth_meter;
+
         Ok(SwarmBuilder::with_existing_identity(keypair)
             .with_wasm_bindgen()
             .with_other_transport(move |_| {
                 Ok(websocket_websys::Transport::default()
                     .upgrade(Version::V1Lazy)
                     .authenticate(noise_config)
-                    .multiplex(yamux::Config::default()))
+                    .multiplex(yamux::Config::default())
+                    .map(move |(peer_id, muxer), _| {
+                        let muxer = StreamMuxerBox::new(muxer);
+                        (peer_id, ws_meter.wrap(peer_id, muxer))
+                    }))
             })
             .expect("websocket_websys::Transport is infallible")
-            .with_other_transport(|local_keypair| {
+            .with_other_transport(move |local_keypair| {
                 let config = webtransport_websys::Config::new(local_keypair);
-                webtransport_websys::Transport::new(config)
+
+                webtransport_websys::Transport::new(config).map(move |(peer_id, conn), _| {
+                    let muxer = StreamMuxerBox::new(conn);
+                    (peer_id, webtransport_meter.wrap(peer_id, muxer))
+                })
             })
             .expect("webtransport_websys::Transport is infallible")
             .with_behaviour(|_| behaviour)
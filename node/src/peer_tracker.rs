@@ -1,24 +1,51 @@
 //! Primitives related to tracking the state of peers in the network.
 
 use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::time::Duration;
 
 use dashmap::mapref::entry::Entry;
 use dashmap::mapref::one::RefMut;
 use dashmap::DashMap;
-use libp2p::{swarm::ConnectionId, Multiaddr, PeerId};
+use libp2p::request_response::OutboundFailure;
+use libp2p::{identify, swarm::ConnectionId, Multiaddr, PeerId};
 use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 use tokio::sync::watch;
+use tracing::info;
+use web_time::{Instant, SystemTime};
 
 use crate::events::{EventPublisher, NodeEvent};
+use crate::store::KnownPeer;
+
+/// Default time for which misbehaving peers are banned.
+pub const DEFAULT_PEER_BAN_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum score of a peer, so that a long history of good behaviour
+/// doesn't shield it from being banned.
+const MAX_SCORE: f64 = 20.0;
+/// Minimum score of a peer.
+const MIN_SCORE: f64 = -100.0;
+/// Peers are banned when their score drops to this threshold.
+const BAN_THRESHOLD: f64 = -50.0;
+/// Time after which the score decays half way towards zero.
+const SCORE_HALF_LIFE: Duration = Duration::from_secs(10 * 60);
+/// Width of the score ranges within which peers are considered equally good
+/// and ranked by their latency. It matches the penalty of a single failed request.
+const SCORE_BUCKET: f64 = 5.0;
+/// Weight of a new sample in the moving average of the latency.
+const LATENCY_WEIGHT: f64 = 0.2;
+/// Number of the best peers among which `best_peer` chooses, to spread the load.
+const BEST_PEERS_POOL: usize = 4;
 
 /// Keeps track various information about peers.
 #[derive(Debug)]
 pub struct PeerTracker {
-    peers: DashMap<PeerId, PeerInfo>,
+    peers: DashMap<PeerId, TrackedPeer>,
     info_tx: watch::Sender<PeerTrackerInfo>,
     event_pub: EventPublisher,
+    ban_duration: Duration,
 }
 
 /// Statistics of the connected peers
@@ -29,14 +56,83 @@ pub struct PeerTrackerInfo {
     pub num_connected_peers: u64,
     /// Number of the connected trusted peers.
     pub num_connected_trusted_peers: u64,
+    /// Number of the currently banned peers.
+    pub num_banned_peers: u64,
+    /// Average score of the connected peers.
+    ///
+    /// Score is increased by correct responses and decreased by failed requests or
+    /// invalid data, decaying towards zero over time.
+    pub average_peer_score: f64,
+}
+
+/// Information about a peer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerInfo {
+    /// Id of the peer.
+    pub peer_id: PeerId,
+    /// Known addresses of the peer.
+    pub addrs: Vec<Multiaddr>,
+    /// Protocols supported by the peer, as reported by identify.
+    pub protocols: Vec<String>,
+    /// Agent version of the peer, as reported by identify.
+    pub agent_version: Option<String>,
+    /// Whether the peer is currently connected.
+    pub connected: bool,
+    /// Whether the peer is trusted.
+    pub trusted: bool,
+    /// Whether the peer is currently banned for misbehaving.
+    pub banned: bool,
+    /// Whether the peer is blocked.
+    pub blocked: bool,
+    /// Current score of the peer, decaying towards zero over time.
+    pub score: f64,
+}
+
+/// Behaviour of a peer, which affects its score.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PeerReport {
+    /// Peer responded correctly to a request.
+    Success {
+        /// Time it took the peer to respond.
+        latency: Duration,
+    },
+    /// Peer didn't answer a request in time or doesn't support its protocol.
+    ///
+    /// Failures caused by the local transport or connectivity, like failed dials
+    /// or closed connections, are not reported.
+    RequestFailed,
+    /// Peer expected to hold the block data didn't provide a sample in time.
+    ///
+    /// Bitswap doesn't expose which peer failed to send a block, so this is reported
+    /// for all the peers expected to hold the data and the penalty is mild.
+    SamplingFailed,
+    /// Peer responded with malformed or invalid data.
+    InvalidResponse,
+    /// Peer responded with headers of a different chain.
+    WrongChain,
+    /// Peer sent a gossipsub message which failed the validation.
+    InvalidMessage,
 }
 
 #[derive(Debug)]
-struct PeerInfo {
+struct TrackedPeer {
     state: PeerState,
     addrs: SmallVec<[Multiaddr; 4]>,
     connections: SmallVec<[ConnectionId; 1]>,
     trusted: bool,
+    score: PeerScore,
+    banned_until: Option<Instant>,
+    blocked: bool,
+    last_seen: Option<SystemTime>,
+    agent_version: Option<String>,
+    protocols: Vec<String>,
+}
+
+#[derive(Debug)]
+struct PeerScore {
+    value: f64,
+    updated_at: Instant,
+    latency: Option<Duration>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -46,10 +142,98 @@ enum PeerState {
     Connected,
 }
 
-impl PeerInfo {
+impl TrackedPeer {
+    fn new() -> Self {
+        TrackedPeer {
+            state: PeerState::Discovered,
+            addrs: SmallVec::new(),
+            connections: SmallVec::new(),
+            trusted: false,
+            score: PeerScore::new(),
+            banned_until: None,
+            blocked: false,
+            last_seen: None,
+            agent_version: None,
+            protocols: Vec::new(),
+        }
+    }
+
     fn is_connected(&self) -> bool {
         matches!(self.state, PeerState::Connected)
     }
+
+    fn is_banned(&self, now: Instant) -> bool {
+        self.banned_until.is_some_and(|until| until > now)
+    }
+
+    /// Returns `true` if the peer is banned or blocked.
+    fn is_denied(&self, now: Instant) -> bool {
+        self.blocked || self.is_banned(now)
+    }
+}
+
+impl PeerReport {
+    /// Returns the report for a failed outbound request, `None` if the failure
+    /// can't be attributed to the peer.
+    pub(crate) fn from_outbound_failure(error: &OutboundFailure) -> Option<PeerReport> {
+        match error {
+            OutboundFailure::Timeout | OutboundFailure::UnsupportedProtocols => {
+                Some(PeerReport::RequestFailed)
+            }
+            OutboundFailure::DialFailure
+            | OutboundFailure::ConnectionClosed
+            | OutboundFailure::Io(_) => None,
+        }
+    }
+
+    fn score_delta(&self) -> f64 {
+        match self {
+            PeerReport::Success { .. } => 1.0,
+            PeerReport::RequestFailed => -5.0,
+            PeerReport::SamplingFailed => -1.0,
+            PeerReport::InvalidResponse => -20.0,
+            PeerReport::InvalidMessage => -20.0,
+            PeerReport::WrongChain => -50.0,
+        }
+    }
+}
+
+impl PeerScore {
+    fn new() -> Self {
+        PeerScore {
+            value: 0.0,
+            updated_at: Instant::now(),
+            latency: None,
+        }
+    }
+
+    /// Returns the score decayed until `now`.
+    fn value_at(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated_at);
+        let half_lives = elapsed.as_secs_f64() / SCORE_HALF_LIFE.as_secs_f64();
+        self.value * 0.5f64.powf(half_lives)
+    }
+
+    /// Returns the range of the score decayed until `now`.
+    ///
+    /// Decayed scores are almost never equal, so peers are compared by the range
+    /// of their score, to let the latency decide between the peers of similar score.
+    fn bucket_at(&self, now: Instant) -> i64 {
+        (self.value_at(now) / SCORE_BUCKET).floor() as i64
+    }
+
+    fn apply(&mut self, report: PeerReport, now: Instant) {
+        let value = self.value_at(now) + report.score_delta();
+        self.value = value.clamp(MIN_SCORE, MAX_SCORE);
+        self.updated_at = now;
+
+        if let PeerReport::Success { latency } = report {
+            self.latency = Some(match self.latency {
+                Some(avg) => avg.mul_f64(1.0 - LATENCY_WEIGHT) + latency.mul_f64(LATENCY_WEIGHT),
+                None => latency,
+            });
+        }
+    }
 }
 
 impl PeerTracker {
@@ -59,6 +243,15 @@ impl PeerTracker {
             peers: DashMap::new(),
             info_tx: watch::channel(PeerTrackerInfo::default()).0,
             event_pub,
+            ban_duration: DEFAULT_PEER_BAN_DURATION,
+        }
+    }
+
+    /// Sets the time for which misbehaving peers are banned.
+    pub fn with_ban_duration(self, ban_duration: Duration) -> Self {
+        PeerTracker {
+            ban_duration,
+            ..self
         }
     }
 
@@ -78,28 +271,18 @@ impl PeerTracker {
     pub fn set_maybe_discovered(&self, peer: PeerId) -> bool {
         match self.peers.entry(peer) {
             Entry::Vacant(entry) => {
-                entry.insert(PeerInfo {
-                    state: PeerState::Discovered,
-                    addrs: SmallVec::new(),
-                    connections: SmallVec::new(),
-                    trusted: false,
-                });
+                entry.insert(TrackedPeer::new());
                 true
             }
             Entry::Occupied(_) => false,
         }
     }
 
-    /// Get the `PeerInfo` of the peer.
+    /// Get the `TrackedPeer` of the peer.
     ///
     /// If peer is not found it is added as `PeerState::Discovered`.
-    fn get(&self, peer: PeerId) -> RefMut<PeerId, PeerInfo> {
-        self.peers.entry(peer).or_insert_with(|| PeerInfo {
-            state: PeerState::Discovered,
-            addrs: SmallVec::new(),
-            connections: SmallVec::new(),
-            trusted: false,
-        })
+    fn get(&self, peer: PeerId) -> RefMut<PeerId, TrackedPeer> {
+        self.peers.entry(peer).or_insert_with(TrackedPeer::new)
     }
 
     /// Add an address for a peer.
@@ -124,6 +307,24 @@ impl PeerTracker {
         }
     }
 
+    /// Adds a peer remembered from the previous run of the node, restoring
+    /// its addresses and score.
+    ///
+    /// Trust is not restored, so that peers removed from the bootnodes or the trusted
+    /// peers don't stay trusted. It is set again for the currently configured ones.
+    pub fn add_known_peer(&self, known_peer: &KnownPeer) {
+        self.add_addresses(known_peer.peer_id, &known_peer.addrs);
+
+        let mut peer_info = self.get(known_peer.peer_id);
+
+        if peer_info.last_seen.is_none() {
+            peer_info.last_seen = Some(known_peer.last_seen);
+        }
+
+        peer_info.score.value = known_peer.score.clamp(MIN_SCORE, MAX_SCORE);
+        peer_info.score.updated_at = Instant::now();
+    }
+
     /// Sets peer as trusted.
     pub fn set_trusted(&self, peer: PeerId, is_trusted: bool) {
         let mut peer_info = self.get(peer);
@@ -135,6 +336,9 @@ impl PeerTracker {
 
         peer_info.trusted = is_trusted;
 
+        // Trusted peers are never banned
+        let unbanned = is_trusted && peer_info.banned_until.take().is_some();
+
         // If peer was already connected, then `num_connected_trusted_peers`
         // needs to be adjusted based on the new information.
         if peer_info.is_connected() {
@@ -146,6 +350,11 @@ impl PeerTracker {
                 }
             });
         }
+
+        if unbanned {
+            drop(peer_info);
+            self.update_scores_info();
+        }
     }
 
     /// Sets peer as connected.
@@ -164,6 +373,7 @@ impl PeerTracker {
         }
 
         peer_info.connections.push(connection_id);
+        peer_info.last_seen = Some(SystemTime::now());
 
         // If peer was not already connected from before
         if !peer_info.is_connected() {
@@ -188,6 +398,8 @@ impl PeerTracker {
 
         // If this is the last connection from the peer
         if peer_info.connections.is_empty() {
+            peer_info.last_seen = Some(SystemTime::now());
+
             if peer_info.addrs.is_empty() {
                 peer_info.state = PeerState::Discovered;
             } else {
@@ -207,6 +419,143 @@ impl PeerTracker {
         }
     }
 
+    /// Updates the score of the peer based on its behaviour.
+    ///
+    /// Peer is banned if its score drops too low. Returns `true` if the peer got banned.
+    ///
+    /// Scores in [`PeerTrackerInfo`] are updated only if the peer got banned, otherwise
+    /// they are left for the next [`PeerTracker::update_scores_info`].
+    pub fn report(&self, peer: PeerId, report: PeerReport) -> bool {
+        let now = Instant::now();
+        let mut peer_info = self.get(peer);
+
+        peer_info.score.apply(report, now);
+
+        let banned = !peer_info.trusted
+            && !peer_info.is_banned(now)
+            && peer_info.score.value <= BAN_THRESHOLD;
+
+        if banned {
+            info!("Banning peer {peer} for {:?}", self.ban_duration);
+            peer_info.banned_until = Some(now + self.ban_duration);
+            // Peer starts over once the ban expires.
+            peer_info.score = PeerScore::new();
+
+            drop(peer_info);
+            self.update_scores_info();
+        }
+
+        banned
+    }
+
+    /// Returns true if peer is banned.
+    pub fn is_banned(&self, peer: &PeerId) -> bool {
+        self.peers
+            .get(peer)
+            .is_some_and(|peer_info| peer_info.is_banned(Instant::now()))
+    }
+
+    /// Returns true if peer is trusted.
+    pub fn is_trusted(&self, peer: &PeerId) -> bool {
+        self.peers
+            .get(peer)
+            .is_some_and(|peer_info| peer_info.trusted)
+    }
+
+    /// Sets peer as blocked, so that the node doesn't connect to it.
+    ///
+    /// Returns `true` if the blocked state of the peer changed.
+    pub fn set_blocked(&self, peer: PeerId, is_blocked: bool) -> bool {
+        let mut peer_info = self.get(peer);
+
+        if peer_info.blocked == is_blocked {
+            return false;
+        }
+
+        peer_info.blocked = is_blocked;
+
+        true
+    }
+
+    /// Returns true if peer is blocked.
+    pub fn is_blocked(&self, peer: &PeerId) -> bool {
+        self.peers
+            .get(peer)
+            .is_some_and(|peer_info| peer_info.blocked)
+    }
+
+    /// Returns the blocked peers.
+    pub fn blocked_peers(&self) -> Vec<PeerId> {
+        self.peers
+            .iter()
+            .filter(|pair| pair.value().blocked)
+            .map(|pair| pair.key().to_owned())
+            .collect()
+    }
+
+    /// Sets the agent version and the supported protocols reported by the peer.
+    pub fn set_identify_info(&self, peer: PeerId, info: &identify::Info) {
+        let mut peer_info = self.get(peer);
+
+        peer_info.agent_version = Some(info.agent_version.clone());
+        peer_info.protocols = info.protocols.iter().map(|p| p.to_string()).collect();
+    }
+
+    /// Returns the [`PeerInfo`] of the peer, `None` if peer isn't known.
+    pub fn peer_info(&self, peer: &PeerId) -> Option<PeerInfo> {
+        let peer_info = self.peers.get(peer)?;
+        let now = Instant::now();
+
+        Some(PeerInfo {
+            peer_id: peer.to_owned(),
+            addrs: peer_info.addrs.to_vec(),
+            protocols: peer_info.protocols.clone(),
+            agent_version: peer_info.agent_version.clone(),
+            connected: peer_info.is_connected(),
+            trusted: peer_info.trusted,
+            banned: peer_info.is_banned(now),
+            blocked: peer_info.blocked,
+            score: peer_info.score.value_at(now),
+        })
+    }
+
+    /// Updates the scores and the number of banned peers in [`PeerTrackerInfo`].
+    ///
+    /// Scores decay and bans expire over time, so this should be called periodically.
+    pub fn update_scores_info(&self) {
+        let now = Instant::now();
+        let mut num_banned_peers = 0;
+        let mut num_scored_peers = 0;
+        let mut total_score = 0.0;
+
+        for pair in self.peers.iter() {
+            let peer_info = pair.value();
+
+            if peer_info.is_banned(now) {
+                num_banned_peers += 1;
+            } else if peer_info.is_connected() {
+                num_scored_peers += 1;
+                total_score += peer_info.score.value_at(now);
+            }
+        }
+
+        let average_peer_score = if num_scored_peers > 0 {
+            total_score / num_scored_peers as f64
+        } else {
+            0.0
+        };
+
+        self.info_tx.send_if_modified(|tracker_info| {
+            let modified = tracker_info.num_banned_peers != num_banned_peers
+                || tracker_info.average_peer_score != average_peer_score;
+
+            tracker_info.num_banned_peers = num_banned_peers;
+            tracker_info.average_peer_score = average_peer_score;
+
+            modified
+        });
+    }
+
     /// Returns true if peer is connected.
     #[allow(dead_code)]
     pub fn is_connected(&self, peer: PeerId) -> bool {
@@ -242,42 +591,113 @@ impl PeerTracker {
             .collect()
     }
 
+    /// Returns connections of the banned peers which are still connected.
+    pub fn banned_connections(&self) -> Vec<ConnectionId> {
+        let now = Instant::now();
+
+        self.peers
+            .iter()
+            .filter(|pair| pair.value().is_connected() && pair.value().is_banned(now))
+            .flat_map(|pair| pair.value().connections.clone())
+            .collect()
+    }
+
     /// Returns one of the best peers.
+    ///
+    /// Peer is chosen randomly among a few of the best ones, to spread the load.
     pub fn best_peer(&self) -> Option<PeerId> {
-        const MAX_PEER_SAMPLE: usize = 128;
+        self.best_n_peers(BEST_PEERS_POOL)
+            .choose(&mut rand::thread_rng())
+            .copied()
+    }
+
+    /// Returns up to N amount of best peers.
+    ///
+    /// Peers are grouped into ranges of similar score, which are ranked from the
+    /// highest one, and peers within the same range are ranked by their latency.
+    /// Banned and blocked peers are skipped.
+    pub fn best_n_peers(&self, limit: usize) -> Vec<PeerId> {
+        let now = Instant::now();
 
-        // TODO: Implement peer score and return the best.
         let mut peers = self
             .peers
             .iter()
-            .filter(|pair| pair.value().is_connected())
-            .take(MAX_PEER_SAMPLE)
-            .map(|pair| pair.key().to_owned())
-            .collect::<SmallVec<[_; MAX_PEER_SAMPLE]>>();
+            .filter(|pair| pair.value().is_connected() && !pair.value().is_denied(now))
+            .map(|pair| {
+                let score = &pair.value().score;
+                (pair.key().to_owned(), score.bucket_at(now), score.latency)
+            })
+            // collect instead of sorting an iter to not block the dashmap
+            .collect::<Vec<_>>();
 
-        peers.shuffle(&mut rand::thread_rng());
+        peers.sort_unstable_by(|(_, bucket1, latency1), (_, bucket2, latency2)| {
+            bucket2
+                .cmp(bucket1)
+                .then_with(|| cmp_latency(latency1, latency2))
+        });
 
-        peers.first().copied()
+        peers
+            .into_iter()
+            .take(limit)
+            .map(|(peer, _, _)| peer)
+            .collect()
     }
 
-    /// Returns up to N amount of best peers.
-    #[allow(dead_code)]
-    pub fn best_n_peers(&self, limit: usize) -> Vec<PeerId> {
-        // TODO: Implement peer score and return the best N peers.
-        self.peers
+    /// Returns up to N amount of peers worth remembering across restarts.
+    ///
+    /// These are the peers with known addresses which were seen connected, excluding
+    /// the banned and blocked ones and the ones with a negative score. Trusted peers come first,
+    /// followed by the peers with the highest score.
+    pub fn known_peers(&self, limit: usize) -> Vec<KnownPeer> {
+        let now = Instant::now();
+
+        let mut peers = self
+            .peers
             .iter()
-            .filter(|pair| pair.value().is_connected())
-            .take(limit)
-            .map(|pair| pair.key().to_owned())
-            // collect instead of returning an iter to not block the dashmap
-            .collect()
+            .filter_map(|pair| {
+                let peer_info = pair.value();
+                let score = peer_info.score.value_at(now);
+
+                if peer_info.addrs.is_empty() || peer_info.is_denied(now) || score < 0.0 {
+                    return None;
+                }
+
+                let last_seen = if peer_info.is_connected() {
+                    SystemTime::now()
+                } else {
+                    peer_info.last_seen?
+                };
+
+                Some(KnownPeer {
+                    peer_id: pair.key().to_owned(),
+                    addrs: peer_info.addrs.to_vec(),
+                    last_seen,
+                    trusted: peer_info.trusted,
+                    score,
+                })
+            })
+            // collect instead of sorting an iter to not block the dashmap
+            .collect::<Vec<_>>();
+
+        peers.sort_unstable_by(|peer1, peer2| {
+            peer2
+                .trusted
+                .cmp(&peer1.trusted)
+                .then_with(|| peer2.score.total_cmp(&peer1.score))
+        });
+        peers.truncate(limit);
+
+        peers
     }
 
     /// Returns up to N amount of trusted peers.
     pub fn trusted_n_peers(&self, limit: usize) -> Vec<PeerId> {
         self.peers
             .iter()
-            .filter(|pair| pair.value().is_connected() && pair.value().trusted)
+            .filter(|pair| {
+                let peer_info = pair.value();
+                peer_info.is_connected() && peer_info.trusted && !peer_info.blocked
+            })
             .take(limit)
             .map(|pair| pair.key().to_owned())
             // collect instead of returning an iter to not block the dashmap
@@ -285,6 +705,16 @@ impl PeerTracker {
     }
 }
 
+/// Compares latencies, where unknown latency is the worst one.
+fn cmp_latency(latency1: &Option<Duration>, latency2: &Option<Duration>) -> Ordering {
+    match (latency1, latency2) {
+        (Some(latency1), Some(latency2)) => latency1.cmp(latency2),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
 fn increment_connected_peers(info_tx: &watch::Sender<PeerTrackerInfo>, trusted: bool) {
     info_tx.send_modify(|tracker_info| {
         tracker_info.num_connected_peers += 1;
@@ -376,4 +806,265 @@ mod tests {
         assert_eq!(info.num_connected_peers, 1);
         assert_eq!(info.num_connected_trusted_peers, 0);
     }
+
+    #[test]
+    fn ban_repeat_offenders() {
+        let event_channel = EventChannel::new();
+        let tracker = PeerTracker::new(event_channel.publisher());
+        let peer = PeerId::random();
+
+        tracker.set_connected(peer, ConnectionId::new_unchecked(1), None);
+
+        assert!(!tracker.report(peer, PeerReport::InvalidResponse));
+        assert!(!tracker.report(peer, PeerReport::InvalidResponse));
+        assert!(!tracker.is_banned(&peer));
+        assert_eq!(tracker.best_peer(), Some(peer));
+
+        tracker.update_scores_info();
+        assert!(tracker.info().average_peer_score < 0.0);
+
+        assert!(tracker.report(peer, PeerReport::InvalidResponse));
+        assert!(tracker.is_banned(&peer));
+        assert_eq!(tracker.info().num_banned_peers, 1);
+        assert_eq!(tracker.best_peer(), None);
+        assert_eq!(
+            tracker.banned_connections(),
+            vec![ConnectionId::new_unchecked(1)]
+        );
+    }
+
+    #[test]
+    fn reports_update_info_only_on_ban() {
+        let event_channel = EventChannel::new();
+        let tracker = PeerTracker::new(event_channel.publisher());
+        let mut watcher = tracker.info_watcher();
+        let peer = PeerId::random();
+
+        tracker.set_connected(peer, ConnectionId::new_unchecked(1), None);
+        watcher.mark_unchanged();
+
+        assert!(!tracker.report(peer, PeerReport::InvalidResponse));
+        assert!(!watcher.has_changed().unwrap());
+
+        tracker.update_scores_info();
+        assert!(watcher.has_changed().unwrap());
+        assert!(watcher.borrow_and_update().average_peer_score < 0.0);
+
+        tracker.report(peer, PeerReport::InvalidResponse);
+        assert!(tracker.report(peer, PeerReport::InvalidResponse));
+        assert!(watcher.has_changed().unwrap());
+        assert_eq!(watcher.borrow_and_update().num_banned_peers, 1);
+    }
+
+    #[test]
+    fn ban_expires() {
+        let event_channel = EventChannel::new();
+        let tracker = PeerTracker::new(event_channel.publisher())
+            .with_ban_duration(Duration::from_millis(50));
+        let peer = PeerId::random();
+
+        tracker.set_connected(peer, ConnectionId::new_unchecked(1), None);
+
+        assert!(tracker.report(peer, PeerReport::WrongChain));
+        assert!(tracker.is_banned(&peer));
+
+        std::thread::sleep(Duration::from_millis(60));
+
+        assert!(!tracker.is_banned(&peer));
+        assert_eq!(tracker.best_peer(), Some(peer));
+
+        tracker.update_scores_info();
+        assert_eq!(tracker.info().num_banned_peers, 0);
+    }
+
+    #[test]
+    fn trusted_peers_are_not_banned() {
+        let event_channel = EventChannel::new();
+        let tracker = PeerTracker::new(event_channel.publisher());
+        let peer = PeerId::random();
+
+        tracker.set_trusted(peer, true);
+        tracker.set_connected(peer, ConnectionId::new_unchecked(1), None);
+
+        assert!(!tracker.report(peer, PeerReport::WrongChain));
+        assert!(!tracker.report(peer, PeerReport::WrongChain));
+        assert!(!tracker.is_banned(&peer));
+        assert_eq!(tracker.info().num_banned_peers, 0);
+    }
+
+    #[test]
+    fn rank_by_score_and_latency() {
+        let event_channel = EventChannel::new();
+        let tracker = PeerTracker::new(event_channel.publisher());
+        let failing = PeerId::random();
+        let slow = PeerId::random();
+        let fast = PeerId::random();
+        let unknown = PeerId::random();
+
+        for (n, peer) in [failing, slow, fast, unknown].into_iter().enumerate() {
+            tracker.set_connected(peer, ConnectionId::new_unchecked(n), None);
+        }
+
+        tracker.report(failing, PeerReport::RequestFailed);
+        tracker.report(
+            slow,
+            PeerReport::Success {
+                latency: Duration::from_millis(500),
+            },
+        );
+        tracker.report(
+            fast,
+            PeerReport::Success {
+                latency: Duration::from_millis(50),
+            },
+        );
+
+        assert_eq!(tracker.best_n_peers(4), vec![fast, slow, unknown, failing]);
+        assert_eq!(tracker.best_n_peers(1), vec![fast]);
+    }
+
+    #[test]
+    fn rank_clean_peers_by_latency() {
+        let event_channel = EventChannel::new();
+        let tracker = PeerTracker::new(event_channel.publisher());
+        let slow = PeerId::random();
+        let fast = PeerId::random();
+
+        tracker.set_connected(slow, ConnectionId::new_unchecked(0), None);
+        tracker.set_connected(fast, ConnectionId::new_unchecked(1), None);
+
+        // slow peer answered more requests, so its score is higher
+        for _ in 0..3 {
+            tracker.report(
+                slow,
+                PeerReport::Success {
+                    latency: Duration::from_millis(400),
+                },
+            );
+        }
+        tracker.report(
+            fast,
+            PeerReport::Success {
+                latency: Duration::from_millis(40),
+            },
+        );
+
+        assert_eq!(tracker.best_n_peers(2), vec![fast, slow]);
+
+        // a failed request outweighs the latency
+        tracker.report(fast, PeerReport::RequestFailed);
+        assert_eq!(tracker.best_n_peers(2), vec![slow, fast]);
+    }
+
+    #[test]
+    fn export_and_restore_known_peers() {
+        let event_channel = EventChannel::new();
+        let tracker = PeerTracker::new(event_channel.publisher());
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/2121".parse().unwrap();
+        let good = PeerId::random();
+        let trusted = PeerId::random();
+        let failing = PeerId::random();
+        let never_connected = PeerId::random();
+        let without_addrs = PeerId::random();
+
+        for (n, peer) in [good, trusted, failing].into_iter().enumerate() {
+            tracker.set_connected(peer, ConnectionId::new_unchecked(n), addr.clone());
+        }
+        tracker.set_connected(without_addrs, ConnectionId::new_unchecked(3), None);
+        tracker.add_addresses(never_connected, [&addr]);
+        tracker.set_trusted(trusted, true);
+        tracker.report(
+            good,
+            PeerReport::Success {
+                latency: Duration::from_millis(50),
+            },
+        );
+        tracker.report(failing, PeerReport::RequestFailed);
+        tracker.set_maybe_disconnected(good, ConnectionId::new_unchecked(0));
+
+        let known_peers = tracker.known_peers(10);
+        let ids = known_peers
+            .iter()
+            .map(|peer| peer.peer_id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![trusted, good]);
+        assert!(known_peers[0].trusted);
+        assert_eq!(known_peers[1].addrs, vec![addr.clone()]);
+        assert!(known_peers[1].score > 0.0);
+        assert_eq!(tracker.known_peers(1).len(), 1);
+
+        let restored = PeerTracker::new(event_channel.publisher());
+        for peer in &known_peers {
+            restored.add_known_peer(peer);
+        }
+
+        assert_eq!(restored.addresses(good).to_vec(), vec![addr]);
+        let restored_peers = restored.known_peers(10);
+        // trust is not restored, so the peers are ordered by score
+        assert_eq!(restored_peers[0].peer_id, good);
+        assert_eq!(restored_peers[0].last_seen, known_peers[1].last_seen);
+        assert!((restored_peers[0].score - known_peers[1].score).abs() < 0.01);
+        assert!(!restored.is_connected(trusted));
+        assert!(!restored.is_trusted(&trusted));
+        restored.set_connected(trusted, ConnectionId::new_unchecked(0), None);
+        assert_eq!(restored.info().num_connected_trusted_peers, 0);
+    }
+
+    #[test]
+    fn block_peer() {
+        let event_channel = EventChannel::new();
+        let tracker = PeerTracker::new(event_channel.publisher());
+        let peer = PeerId::random();
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/2121".parse().unwrap();
+
+        assert!(tracker.peer_info(&peer).is_none());
+        assert!(!tracker.is_blocked(&peer));
+
+        tracker.set_trusted(peer, true);
+        tracker.set_connected(peer, ConnectionId::new_unchecked(1), addr.clone());
+        tracker.set_identify_info(
+            peer,
+            &identify::Info {
+                public_key: libp2p::identity::Keypair::generate_ed25519().public(),
+                protocol_version: "ipfs/0.1.0".to_string(),
+                agent_version: "lumina/test".to_string(),
+                listen_addrs: Vec::new(),
+                protocols: vec![libp2p::StreamProtocol::new("/ipfs/id/1.0.0")],
+                observed_addr: addr.clone(),
+            },
+        );
+        assert_eq!(tracker.best_n_peers(10), vec![peer]);
+        assert_eq!(tracker.trusted_n_peers(10), vec![peer]);
+
+        assert!(tracker.set_blocked(peer, true));
+        assert!(!tracker.set_blocked(peer, true));
+        assert!(tracker.is_blocked(&peer));
+        assert_eq!(tracker.blocked_peers(), vec![peer]);
+        assert!(tracker.best_n_peers(10).is_empty());
+        assert!(tracker.trusted_n_peers(10).is_empty());
+        assert!(tracker.known_peers(10).is_empty());
+
+        let info = tracker.peer_info(&peer).unwrap();
+        assert_eq!(
+            info,
+            PeerInfo {
+                peer_id: peer,
+                addrs: vec![addr],
+                protocols: vec!["/ipfs/id/1.0.0".to_string()],
+                agent_version: Some("lumina/test".to_string()),
+                connected: true,
+                trusted: true,
+                banned: false,
+                blocked: true,
+                score: 0.0,
+            }
+        );
+
+        assert!(tracker.set_blocked(peer, false));
+        assert!(tracker.blocked_peers().is_empty());
+        assert_eq!(tracker.best_n_peers(10), vec![peer]);
+
+        tracker.report(peer, PeerReport::RequestFailed);
+        assert!(tracker.peer_info(&peer).unwrap().score < 0.0);
+    }
 }
@@ -10,6 +10,7 @@ use tracing::{debug, error, warn};
 
 use crate::events::{EventPublisher, NodeEvent};
 use crate::executor::{sleep, spawn, JoinHandle};
+use crate::metrics::Metrics;
 use crate::p2p::P2pError;
 use crate::store::{Store, StoreError};
 
@@ -59,6 +60,8 @@ where
     pub pruning_interval: Duration,
     /// Size of pruning window
     pub pruning_window: Duration,
+    /// Metrics of the node.
+    pub metrics: Metrics,
 }
 
 impl Pruner {
@@ -117,6 +120,7 @@ where
     blockstore: Arc<B>,
     pruning_interval: Duration,
     pruning_window: Duration,
+    metrics: Metrics,
 }
 
 impl<S, B> Worker<S, B>
@@ -132,6 +136,7 @@ where
             blockstore: args.blockstore,
             pruning_interval: args.pruning_interval,
             pruning_window: args.pruning_window,
+            metrics: args.metrics,
         }
     }
 
@@ -171,6 +176,7 @@ where
                     return Err(PrunerError::WrongHeightRemoved);
                 }
 
+                self.metrics.observe_pruned(height);
                 last_removed = Some(height);
             }
 
@@ -242,6 +248,7 @@ mod test {
             event_pub: events.publisher(),
             pruning_interval: Duration::from_secs(1),
             pruning_window: TEST_PRUNING_WINDOW,
+            metrics: Metrics::default(),
         });
 
         sleep(Duration::from_secs(1)).await;
@@ -270,6 +277,7 @@ mod test {
             event_pub: events.publisher(),
             pruning_interval: Duration::from_secs(1),
             pruning_window: TEST_PRUNING_WINDOW,
+            metrics: Metrics::default(),
         });
 
         sleep(Duration::from_secs(1)).await;
@@ -330,6 +338,7 @@ mod test {
             event_pub: events.publisher(),
             pruning_interval: Duration::from_secs(1),
             pruning_window: TEST_PRUNING_WINDOW,
+            metrics: Metrics::default(),
         });
 
         sleep(Duration::from_secs(1)).await;
@@ -387,6 +396,7 @@ mod test {
             event_pub: events.publisher(),
             pruning_interval: Duration::from_secs(1),
             pruning_window: TEST_PRUNING_WINDOW,
+            metrics: Metrics::default(),
         });
 
         sleep(Duration::from_secs(1)).await;
@@ -9,29 +9,49 @@ use async_trait::async_trait;
 use celestia_types::hash::Hash;
 use celestia_types::ExtendedHeader;
 use cid::Cid;
+use libp2p::PeerId;
 use prost::Message;
 use serde::{Deserialize, Serialize};
+use tendermint::Time;
 use tendermint_proto::Protobuf;
 use thiserror::Error;
 #[cfg(all(feature = "wasm-bindgen", target_arch = "wasm32"))]
 use wasm_bindgen::prelude::*;
 
+use crate::block_ranges::BlockRangeExt;
 pub use crate::block_ranges::{BlockRange, BlockRanges, BlockRangesError};
+pub use crate::store::changes::{StoreChange, StoreChanges};
 pub use crate::store::either_store::EitherStore;
-pub use crate::store::utils::VerifiedExtendedHeaders;
+pub use crate::store::integrity::{IntegrityIssue, IntegrityReport};
+pub use crate::store::migrations::{MigrationMode, MigrationReport, MigrationStep};
+pub use crate::store::peers::KnownPeer;
+pub use crate::store::snapshot::{export_snapshot, import_snapshot, SnapshotError};
+pub use crate::store::stats::{StoreStats, TableStats};
+pub use crate::store::utils::{sampled_cids, VerifiedExtendedHeaders};
 
 pub use in_memory_store::InMemoryStore;
 #[cfg(target_arch = "wasm32")]
 pub use indexed_db_store::IndexedDbStore;
 #[cfg(not(target_arch = "wasm32"))]
 pub use redb_store::RedbStore;
+#[cfg(all(feature = "sqlite", not(target_arch = "wasm32")))]
+pub use sqlite_store::SqliteStore;
 
+mod changes;
+mod codec;
 mod either_store;
 mod in_memory_store;
 #[cfg(target_arch = "wasm32")]
 mod indexed_db_store;
+mod integrity;
+mod migrations;
+mod peers;
 #[cfg(not(target_arch = "wasm32"))]
 mod redb_store;
+mod snapshot;
+#[cfg(all(feature = "sqlite", not(target_arch = "wasm32")))]
+mod sqlite_store;
+mod stats;
 
 pub(crate) mod utils;
 
@@ -89,6 +109,13 @@ pub trait Store: Send + Sync + Debug {
     /// Returns when `height` is available in the `Store`.
     async fn wait_height(&self, height: u64) -> Result<()>;
 
+    /// Returns a stream of the changes made to the stored headers and their sampling
+    /// status from now on.
+    ///
+    /// Unlike [`Store::wait_new_head`], headers inserted below the head, e.g. while
+    /// syncing backwards, and removed headers are reported too.
+    fn subscribe_changes(&self) -> StoreChanges;
+
     /// Returns the headers from the given heights range.
     ///
     /// If start of the range is unbounded, the first returned header will be of height 1.
@@ -122,6 +149,44 @@ pub trait Store: Send + Sync + Debug {
         Ok(headers)
     }
 
+    /// Returns the last stored header with time at or before the given `time`.
+    ///
+    /// # Errors
+    ///
+    /// If there is no stored header at or before the given `time`, [`StoreError::NotFound`]
+    /// is returned.
+    async fn get_by_time(&self, time: Time) -> Result<ExtendedHeader> {
+        let stored_ranges = self.get_stored_header_ranges().await?;
+
+        // Header times are increasing with height, so find the newest range starting
+        // at or before `time` and binary search it.
+        for range in stored_ranges.as_ref().iter().rev() {
+            let mut found = self.get_by_height(*range.start()).await?;
+
+            if found.time() > time {
+                continue;
+            }
+
+            // All the headers from `after` and above are newer than `time`.
+            let mut after = *range.end() + 1;
+
+            while after - found.height().value() > 1 {
+                let middle = found.height().value() + (after - found.height().value()) / 2;
+                let header = self.get_by_height(middle).await?;
+
+                if header.time() <= time {
+                    found = header;
+                } else {
+                    after = middle;
+                }
+            }
+
+            return Ok(found);
+        }
+
+        Err(StoreError::NotFound)
+    }
+
     /// Returns the highest known height.
     async fn head_height(&self) -> Result<u64>;
 
@@ -142,6 +207,13 @@ pub trait Store: Send + Sync + Debug {
         cids: Vec<Cid>,
     ) -> Result<()>;
 
+    /// Appends CIDs onto the sampling metadata of the header, without changing its status.
+    ///
+    /// Metadata with [`SamplingStatus::Unknown`] is created if it is not set yet. Blocks
+    /// retrieved for the header outside of sampling are tracked this way, so that they are
+    /// removed from the blockstore together with the header.
+    async fn add_sampling_cids(&self, height: u64, cids: Vec<Cid>) -> Result<()>;
+
     /// Gets the sampling metadata for the height.
     ///
     /// `Err(StoreError::NotFound)` indicates that both header **and** sampling metadata for the requested
@@ -168,6 +240,60 @@ pub trait Store: Send + Sync + Debug {
     /// Remove header with lowest height from the store.
     async fn remove_last(&self) -> Result<u64>;
 
+    /// Returns the peers saved with [`Store::set_known_peers`].
+    async fn get_known_peers(&self) -> Result<Vec<KnownPeer>>;
+
+    /// Replaces the saved peers, which the node dials on the next start.
+    async fn set_known_peers(&self, peers: Vec<KnownPeer>) -> Result<()>;
+
+    /// Returns the peers saved with [`Store::set_blocked_peers`].
+    async fn get_blocked_peers(&self) -> Result<Vec<PeerId>>;
+
+    /// Replaces the saved list of peers that the node refuses to connect to.
+    async fn set_blocked_peers(&self, peers: Vec<PeerId>) -> Result<()>;
+
+    /// Check consistency of the stored headers and their indexes.
+    ///
+    /// Store should not be written to while it is checked.
+    async fn check_integrity(&self) -> Result<IntegrityReport> {
+        integrity::check_stored_headers(self).await
+    }
+
+    /// Returns storage usage statistics of the store.
+    ///
+    /// By default only the number of stored headers is reported.
+    async fn stats(&self) -> Result<StoreStats> {
+        let headers = self
+            .get_stored_header_ranges()
+            .await?
+            .into_inner()
+            .iter()
+            .map(BlockRangeExt::len)
+            .sum();
+
+        Ok(StoreStats {
+            headers,
+            ..StoreStats::default()
+        })
+    }
+
+    /// Reclaims the space left in the database by the removed data, e.g. after
+    /// pruning many headers.
+    ///
+    /// Returns `false` if the store doesn't support compaction or there was
+    /// nothing to compact. Compaction may need exclusive access to the database,
+    /// so it should be done while no other component uses it.
+    async fn compact(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Returns schema migrations performed when the store was opened.
+    ///
+    /// `None` for stores without a persistent schema.
+    fn migration_report(&self) -> Option<&MigrationReport> {
+        None
+    }
+
     /// Close store.
     async fn close(self) -> Result<()>;
 }
@@ -198,6 +324,14 @@ pub enum StoreError {
     /// Failed to open the store.
     #[error("Error opening store: {0}")]
     OpenFailed(String),
+
+    /// Failed to compact the store.
+    #[error("Error compacting store: {0}")]
+    CompactionFailed(String),
+
+    /// Encryption key can't be rotated in a store which isn't encrypted.
+    #[error("Store is not encrypted")]
+    NotEncrypted,
 }
 
 /// Store insersion non-fatal errors.
@@ -231,7 +365,10 @@ impl StoreError {
             | StoreError::FatalDatabaseError(_)
             | StoreError::ExecutorError(_)
             | StoreError::OpenFailed(_) => true,
-            StoreError::NotFound | StoreError::InsertionFailed(_) => false,
+            StoreError::NotFound
+            | StoreError::InsertionFailed(_)
+            | StoreError::CompactionFailed(_)
+            | StoreError::NotEncrypted => false,
         }
     }
 }
@@ -356,11 +493,13 @@ mod tests {
     use celestia_types::test_utils::ExtendedHeaderGenerator;
     use celestia_types::Height;
     use rstest::rstest;
+    use std::time::Duration;
 
     // rstest only supports attributes which last segment is `test`
     // https://docs.rs/rstest/0.18.2/rstest/attr.rstest.html#inject-test-attribute
     use crate::test_utils::async_test as test;
     use crate::test_utils::new_block_ranges;
+    use futures::StreamExt;
 
     #[test]
     async fn converts_bounded_ranges() {
@@ -467,6 +606,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_contains_height<S: Store>(
@@ -486,6 +629,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_empty_store<S: Store>(
@@ -508,6 +655,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_read_write<S: Store>(
@@ -529,6 +680,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_pregenerated_data<S: Store>(
@@ -554,6 +709,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_duplicate_insert<S: Store>(
@@ -581,6 +740,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_overwrite_height<S: Store>(
@@ -605,6 +768,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_overwrite_hash<S: Store>(
@@ -629,6 +796,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_append_range<S: Store>(
@@ -646,6 +817,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_fill_range_gap<S: Store>(
@@ -668,6 +843,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_fill_range_gap_with_invalid_header<S: Store>(
@@ -697,6 +876,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_appends_with_gaps<S: Store>(
@@ -719,6 +902,267 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
+    #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
+    #[self::test]
+    async fn test_get_by_time<S: Store>(
+        #[case]
+        #[future(awt)]
+        s: S,
+    ) {
+        let block_time = Duration::from_secs(10);
+        let start_time = Time::from_unix_timestamp(1_700_000_000, 0).unwrap();
+        let mut gen = ExtendedHeaderGenerator::new();
+        gen.set_time(start_time, block_time);
+
+        assert!(matches!(
+            s.get_by_time(start_time).await,
+            Err(StoreError::NotFound)
+        ));
+
+        let headers1 = gen.next_many_verified(10);
+        gen.skip(5);
+        let headers2 = gen.next_many_verified(10);
+        s.insert(headers1.clone()).await.unwrap();
+        s.insert(headers2.clone()).await.unwrap();
+
+        for header in headers1.as_ref().iter().chain(headers2.as_ref()) {
+            let between = (header.time() + block_time / 2).unwrap();
+            assert_eq!(&s.get_by_time(header.time()).await.unwrap(), header);
+            assert_eq!(&s.get_by_time(between).await.unwrap(), header);
+        }
+
+        // Time between the stored ranges
+        let header10 = headers1.as_ref().last().unwrap();
+        let header16 = &headers2.as_ref()[0];
+        let gap = header16.time().duration_since(header10.time()).unwrap();
+        let in_gap = (header10.time() + gap / 2).unwrap();
+        assert_eq!(&s.get_by_time(in_gap).await.unwrap(), header10);
+
+        let before_tail = (headers1.as_ref()[0].time() - block_time / 2).unwrap();
+        assert!(matches!(
+            s.get_by_time(before_tail).await,
+            Err(StoreError::NotFound)
+        ));
+
+        // Pruned tail
+        s.remove_last().await.unwrap();
+        let header1 = &headers1.as_ref()[0];
+        assert!(matches!(
+            s.get_by_time(header1.time()).await,
+            Err(StoreError::NotFound)
+        ));
+    }
+
+    #[rstest]
+    #[case::in_memory(new_in_memory_store())]
+    #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
+    #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
+    #[self::test]
+    async fn test_check_integrity<S: Store>(
+        #[case]
+        #[future(awt)]
+        s: S,
+    ) {
+        let report = s.check_integrity().await.unwrap();
+        assert!(report.is_consistent());
+        assert_eq!(report.last_consistent_height, None);
+
+        let mut gen = ExtendedHeaderGenerator::new();
+        s.insert(gen.next_many_verified(10)).await.unwrap();
+        gen.skip(5);
+        s.insert(gen.next_many_verified(10)).await.unwrap();
+
+        let report = s.check_integrity().await.unwrap();
+        assert!(report.is_consistent(), "{:?}", report.issues);
+        assert_eq!(report.stored_ranges, new_block_ranges([1..=10, 16..=25]));
+        assert_eq!(report.last_consistent_height, Some(25));
+    }
+
+    #[rstest]
+    #[case::in_memory(new_in_memory_store())]
+    #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
+    #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
+    #[self::test]
+    async fn test_stats<S: Store>(
+        #[case]
+        #[future(awt)]
+        s: S,
+    ) {
+        let mut store = s;
+        let stats = store.stats().await.unwrap();
+        assert_eq!(stats.headers, 0);
+        assert_eq!(stats.cids, 0);
+
+        fill_store(&mut store, 10).await;
+
+        let cid0 = "zdpuAyvkgEDQm9TenwGkd5eNaosSxjgEYd8QatfPetgB1CdEZ"
+            .parse()
+            .unwrap();
+        let cid1 = "zb2rhe5P4gXftAwvA4eXQ5HJwsER2owDyS9sKaQRRVQPn93bA"
+            .parse()
+            .unwrap();
+
+        store
+            .update_sampling_metadata(1, SamplingStatus::Accepted, vec![cid0, cid1])
+            .await
+            .unwrap();
+        store.add_sampling_cids(2, vec![cid1]).await.unwrap();
+
+        let stats = store.stats().await.unwrap();
+        assert_eq!(stats.headers, 10);
+        assert_eq!(stats.cids, 2);
+        assert!(!stats.tables.is_empty());
+    }
+
+    #[rstest]
+    #[case::in_memory(new_in_memory_store())]
+    #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
+    #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
+    #[self::test]
+    async fn test_known_peers<S: Store>(
+        #[case]
+        #[future(awt)]
+        s: S,
+    ) {
+        let store = s;
+        assert!(store.get_known_peers().await.unwrap().is_empty());
+
+        let peer = KnownPeer {
+            peer_id: libp2p::PeerId::random(),
+            addrs: vec!["/ip4/1.2.3.4/tcp/2121".parse().unwrap()],
+            last_seen: web_time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(100),
+            trusted: true,
+            score: 5.5,
+        };
+        let peers = vec![peer.clone(), peer.clone()];
+
+        store.set_known_peers(peers.clone()).await.unwrap();
+        assert_eq!(store.get_known_peers().await.unwrap(), peers);
+
+        // peers are replaced, not appended
+        store.set_known_peers(vec![peer.clone()]).await.unwrap();
+        assert_eq!(store.get_known_peers().await.unwrap(), vec![peer]);
+
+        store.set_known_peers(Vec::new()).await.unwrap();
+        assert!(store.get_known_peers().await.unwrap().is_empty());
+    }
+
+    #[rstest]
+    #[case::in_memory(new_in_memory_store())]
+    #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
+    #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
+    #[self::test]
+    async fn test_blocked_peers<S: Store>(
+        #[case]
+        #[future(awt)]
+        s: S,
+    ) {
+        let store = s;
+        assert!(store.get_blocked_peers().await.unwrap().is_empty());
+
+        let peers = vec![libp2p::PeerId::random(), libp2p::PeerId::random()];
+        store.set_blocked_peers(peers.clone()).await.unwrap();
+        assert_eq!(store.get_blocked_peers().await.unwrap(), peers);
+
+        // blocked peers are independent of the known peers
+        assert!(store.get_known_peers().await.unwrap().is_empty());
+
+        store.set_blocked_peers(peers[1..].to_vec()).await.unwrap();
+        assert_eq!(store.get_blocked_peers().await.unwrap(), peers[1..]);
+
+        store.set_blocked_peers(Vec::new()).await.unwrap();
+        assert!(store.get_blocked_peers().await.unwrap().is_empty());
+    }
+
+    #[rstest]
+    #[case::in_memory(new_in_memory_store())]
+    #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
+    #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
+    #[self::test]
+    async fn test_subscribe_changes<S: Store>(
+        #[case]
+        #[future(awt)]
+        s: S,
+    ) {
+        let store = s;
+        let headers = ExtendedHeaderGenerator::new().next_many(20);
+        let mut changes = store.subscribe_changes();
+
+        store.insert(&headers[10..20]).await.unwrap();
+        assert_eq!(
+            changes.next().await,
+            Some(StoreChange::HeadersInserted { range: 11..=20 })
+        );
+
+        // backfill
+        store.insert(&headers[0..10]).await.unwrap();
+        assert_eq!(
+            changes.next().await,
+            Some(StoreChange::HeadersInserted { range: 1..=10 })
+        );
+
+        store
+            .update_sampling_metadata(15, SamplingStatus::Accepted, vec![])
+            .await
+            .unwrap();
+        assert_eq!(
+            changes.next().await,
+            Some(StoreChange::SamplingUpdated {
+                height: 15,
+                status: SamplingStatus::Accepted
+            })
+        );
+
+        // only cids are added, status is unchanged
+        store.add_sampling_cids(15, vec![]).await.unwrap();
+        store.remove_last().await.unwrap();
+        assert_eq!(
+            changes.next().await,
+            Some(StoreChange::HeadersRemoved { range: 1..=1 })
+        );
+
+        // failed operations are not reported
+        store.insert(&headers[5..6]).await.unwrap_err();
+        store.insert(&headers[0..0]).await.unwrap();
+
+        let mut late_changes = store.subscribe_changes();
+        store.remove_last().await.unwrap();
+        let removed = Some(StoreChange::HeadersRemoved { range: 2..=2 });
+        assert_eq!(changes.next().await, removed);
+        assert_eq!(late_changes.next().await, removed);
+    }
+
+    #[rstest]
+    #[case::in_memory(new_in_memory_store())]
+    #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_sampling_height_empty_store<S: Store>(
@@ -739,6 +1183,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_sampling_height<S: Store>(
@@ -810,6 +1258,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_sampling_merge<S: Store>(
@@ -866,6 +1318,58 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
+    #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
+    #[self::test]
+    async fn test_add_sampling_cids<S: Store>(
+        #[case]
+        #[future(awt)]
+        s: S,
+    ) {
+        let mut store = s;
+        fill_store(&mut store, 2).await;
+
+        let cid0 = "zdpuAyvkgEDQm9TenwGkd5eNaosSxjgEYd8QatfPetgB1CdEZ"
+            .parse()
+            .unwrap();
+        let cid1 = "zb2rhe5P4gXftAwvA4eXQ5HJwsER2owDyS9sKaQRRVQPn93bA"
+            .parse()
+            .unwrap();
+
+        store.add_sampling_cids(1, vec![cid0]).await.unwrap();
+
+        let sampling_data = store.get_sampling_metadata(1).await.unwrap().unwrap();
+        assert_eq!(sampling_data.status, SamplingStatus::Unknown);
+        assert_eq!(sampling_data.cids, vec![cid0]);
+
+        store
+            .update_sampling_metadata(2, SamplingStatus::Accepted, vec![cid0])
+            .await
+            .unwrap();
+        store.add_sampling_cids(2, vec![cid0, cid1]).await.unwrap();
+
+        let sampling_data = store.get_sampling_metadata(2).await.unwrap().unwrap();
+        assert_eq!(sampling_data.status, SamplingStatus::Accepted);
+        assert_eq!(sampling_data.cids, vec![cid0, cid1]);
+        assert_eq!(
+            store.get_accepted_sampling_ranges().await.unwrap(),
+            new_block_ranges([2..=2])
+        );
+
+        let res = store.add_sampling_cids(3, vec![cid1]).await;
+        assert!(matches!(res, Err(StoreError::NotFound)));
+    }
+
+    #[rstest]
+    #[case::in_memory(new_in_memory_store())]
+    #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_sampled_cids<S: Store>(
@@ -938,6 +1442,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_empty_store_range<S: Store>(
@@ -956,6 +1464,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_single_header_range<S: Store>(
@@ -986,6 +1498,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_ranges_consolidation<S: Store>(
@@ -1027,6 +1543,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn test_neighbour_validation<S: Store>(
@@ -1048,6 +1568,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn tail_removal_partial_range<S: Store>(
@@ -1069,6 +1593,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn tail_removal_full_range<S: Store>(
@@ -1090,6 +1618,10 @@ mod tests {
     #[rstest]
     #[case::in_memory(new_in_memory_store())]
     #[cfg_attr(not(target_arch = "wasm32"), case::redb(new_redb_store()))]
+    #[cfg_attr(
+        all(feature = "sqlite", not(target_arch = "wasm32")),
+        case::sqlite(new_sqlite_store())
+    )]
     #[cfg_attr(target_arch = "wasm32", case::indexed_db(new_indexed_db_store()))]
     #[self::test]
     async fn tail_removal_remove_all<S: Store>(
@@ -1170,6 +1702,11 @@ mod tests {
         RedbStore::in_memory().await.unwrap()
     }
 
+    #[cfg(all(feature = "sqlite", not(target_arch = "wasm32")))]
+    async fn new_sqlite_store() -> SqliteStore {
+        SqliteStore::in_memory().await.unwrap()
+    }
+
     #[cfg(target_arch = "wasm32")]
     async fn new_indexed_db_store() -> IndexedDbStore {
         use std::sync::atomic::{AtomicU32, Ordering};
@@ -0,0 +1,156 @@
+//! Notifications about the changes of the stored headers.
+
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio_util::sync::ReusableBoxFuture;
+
+use crate::block_ranges::BlockRange;
+use crate::store::SamplingStatus;
+
+const STORE_CHANGES_CAPACITY: usize = 1024;
+
+/// A change of the stored headers, emitted by [`StoreChanges`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StoreChange {
+    /// Headers were inserted, either at the head or while backfilling.
+    HeadersInserted {
+        /// Heights of the inserted headers.
+        range: BlockRange,
+    },
+    /// Headers were removed, e.g. pruned from the tail.
+    HeadersRemoved {
+        /// Heights of the removed headers.
+        range: BlockRange,
+    },
+    /// Sampling status of the header was updated.
+    SamplingUpdated {
+        /// Height of the header.
+        height: u64,
+        /// New sampling status.
+        status: SamplingStatus,
+    },
+    /// Subscriber didn't keep up and missed some changes.
+    ///
+    /// Stored ranges should be read again with [`Store::get_stored_header_ranges`].
+    ///
+    /// [`Store::get_stored_header_ranges`]: crate::store::Store::get_stored_header_ranges
+    Lagged {
+        /// Number of missed changes.
+        missed: u64,
+    },
+}
+
+/// Sending side of the [`StoreChanges`] streams of a store.
+#[derive(Debug)]
+pub(crate) struct StoreChangesSender {
+    tx: broadcast::Sender<StoreChange>,
+}
+
+/// A stream of [`StoreChange`]s, returned by [`Store::subscribe_changes`].
+///
+/// Only changes made after the subscription are emitted. Stream ends when the store is dropped.
+///
+/// [`Store::subscribe_changes`]: crate::store::Store::subscribe_changes
+pub struct StoreChanges {
+    fut: ReusableBoxFuture<'static, RecvResult>,
+}
+
+type RecvResult = (
+    Result<StoreChange, broadcast::error::RecvError>,
+    broadcast::Receiver<StoreChange>,
+);
+
+impl StoreChangesSender {
+    pub(crate) fn new() -> Self {
+        let (tx, _) = broadcast::channel(STORE_CHANGES_CAPACITY);
+        StoreChangesSender { tx }
+    }
+
+    pub(crate) fn send(&self, change: StoreChange) {
+        // Error is produced if there aren't any subscribers. Since this is
+        // a valid case, we ignore the error.
+        let _ = self.tx.send(change);
+    }
+
+    pub(crate) fn subscribe(&self) -> StoreChanges {
+        StoreChanges {
+            fut: ReusableBoxFuture::new(recv(self.tx.subscribe())),
+        }
+    }
+}
+
+impl Default for StoreChangesSender {
+    fn default() -> Self {
+        StoreChangesSender::new()
+    }
+}
+
+async fn recv(mut rx: broadcast::Receiver<StoreChange>) -> RecvResult {
+    let res = rx.recv().await;
+    (res, rx)
+}
+
+impl Stream for StoreChanges {
+    type Item = StoreChange;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let (res, rx) = match self.fut.poll(cx) {
+            Poll::Ready(res) => res,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        self.fut.set(recv(rx));
+
+        match res {
+            Ok(change) => Poll::Ready(Some(change)),
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                Poll::Ready(Some(StoreChange::Lagged { missed }))
+            }
+            Err(broadcast::error::RecvError::Closed) => Poll::Ready(None),
+        }
+    }
+}
+
+impl fmt::Debug for StoreChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StoreChanges { .. }")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::async_test;
+    use futures::StreamExt;
+
+    #[async_test]
+    async fn lagged_subscriber() {
+        let sender = StoreChangesSender::new();
+        let mut changes = sender.subscribe();
+
+        for height in 1..=STORE_CHANGES_CAPACITY as u64 + 2 {
+            sender.send(StoreChange::HeadersInserted {
+                range: height..=height,
+            });
+        }
+
+        assert_eq!(
+            changes.next().await,
+            Some(StoreChange::Lagged { missed: 2 })
+        );
+        assert_eq!(
+            changes.next().await,
+            Some(StoreChange::HeadersInserted { range: 3..=3 })
+        );
+
+        drop(sender);
+
+        assert_eq!(changes.by_ref().count().await, STORE_CHANGES_CAPACITY - 1);
+        assert_eq!(changes.next().await, None);
+    }
+}
@@ -0,0 +1,186 @@
+//! Encoding of the values of the persistent stores, optionally encrypted at rest.
+//!
+//! Encryption is a feature of the [`RedbStore`] and the `IndexedDbStore` backends. Values
+//! are encrypted, while heights, hashes and stored ranges are kept in plain, so that they
+//! can still be queried.
+//!
+//! [`RedbStore`]: crate::store::RedbStore
+
+use std::borrow::Cow;
+
+use celestia_types::ExtendedHeader;
+use libp2p::PeerId;
+use tendermint_proto::Protobuf;
+
+use crate::encryption::Cipher;
+use crate::store::peers::{
+    deserialize_blocked_peers, deserialize_known_peers, serialize_blocked_peers,
+    serialize_known_peers,
+};
+use crate::store::utils::{deserialize_extended_header, deserialize_sampling_metadata};
+use crate::store::{KnownPeer, Result, SamplingMetadata, StoreError};
+
+const HEADERS_DOMAIN: &str = "lumina.store.headers";
+const SAMPLING_METADATA_DOMAIN: &str = "lumina.store.sampling_metadata";
+const KNOWN_PEERS_DOMAIN: &str = "lumina.store.known_peers";
+const BLOCKED_PEERS_DOMAIN: &str = "lumina.store.blocked_peers";
+const KEY_CHECK_DOMAIN: &str = "lumina.store.key_check";
+const KEY_CHECK_VALUE: &[u8] = b"lumina";
+
+/// Encoding of the values of the persistent stores, encrypting them if a cipher is set.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ValueCodec {
+    cipher: Option<Cipher>,
+}
+
+impl ValueCodec {
+    pub(crate) fn encrypted(cipher: Cipher) -> Self {
+        ValueCodec {
+            cipher: Some(cipher),
+        }
+    }
+
+    /// Returns whether the values are encrypted.
+    pub(crate) fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Returns the codec encoding the values in the same way, which decodes only
+    /// the values encrypted with the current key.
+    pub(crate) fn current(&self) -> Self {
+        ValueCodec {
+            cipher: self.cipher.as_ref().map(Cipher::current),
+        }
+    }
+
+    pub(crate) fn encode_header(&self, header: ExtendedHeader) -> Vec<u8> {
+        let height = header.height().value();
+        self.encode(HEADERS_DOMAIN, height, header.encode_vec())
+    }
+
+    pub(crate) fn decode_header(&self, height: u64, value: &[u8]) -> Result<ExtendedHeader> {
+        deserialize_extended_header(&self.decode(HEADERS_DOMAIN, height, value)?)
+    }
+
+    pub(crate) fn encode_sampling_metadata(
+        &self,
+        height: u64,
+        metadata: SamplingMetadata,
+    ) -> Vec<u8> {
+        self.encode(SAMPLING_METADATA_DOMAIN, height, metadata.encode_vec())
+    }
+
+    pub(crate) fn decode_sampling_metadata(
+        &self,
+        height: u64,
+        value: &[u8],
+    ) -> Result<SamplingMetadata> {
+        deserialize_sampling_metadata(&self.decode(SAMPLING_METADATA_DOMAIN, height, value)?)
+    }
+
+    pub(crate) fn encode_known_peers(&self, peers: &[KnownPeer]) -> Vec<u8> {
+        self.encode(KNOWN_PEERS_DOMAIN, 0, serialize_known_peers(peers))
+    }
+
+    pub(crate) fn decode_known_peers(&self, value: &[u8]) -> Result<Vec<KnownPeer>> {
+        deserialize_known_peers(&self.decode(KNOWN_PEERS_DOMAIN, 0, value)?)
+    }
+
+    pub(crate) fn encode_blocked_peers(&self, peers: &[PeerId]) -> Vec<u8> {
+        self.encode(BLOCKED_PEERS_DOMAIN, 0, serialize_blocked_peers(peers))
+    }
+
+    pub(crate) fn decode_blocked_peers(&self, value: &[u8]) -> Result<Vec<PeerId>> {
+        deserialize_blocked_peers(&self.decode(BLOCKED_PEERS_DOMAIN, 0, value)?)
+    }
+
+    /// Re-encode a stored header with the `target` codec.
+    pub(crate) fn reencode_header(
+        &self,
+        target: &ValueCodec,
+        height: u64,
+        value: &[u8],
+    ) -> Result<Vec<u8>> {
+        let plain = self.decode(HEADERS_DOMAIN, height, value)?;
+        Ok(target.encode(HEADERS_DOMAIN, height, plain.into_owned()))
+    }
+
+    /// Re-encode stored sampling metadata with the `target` codec.
+    pub(crate) fn reencode_sampling_metadata(
+        &self,
+        target: &ValueCodec,
+        height: u64,
+        value: &[u8],
+    ) -> Result<Vec<u8>> {
+        let plain = self.decode(SAMPLING_METADATA_DOMAIN, height, value)?;
+        Ok(target.encode(SAMPLING_METADATA_DOMAIN, height, plain.into_owned()))
+    }
+
+    /// Re-encode the stored known peers with the `target` codec.
+    pub(crate) fn reencode_known_peers(
+        &self,
+        target: &ValueCodec,
+        value: &[u8],
+    ) -> Result<Vec<u8>> {
+        let plain = self.decode(KNOWN_PEERS_DOMAIN, 0, value)?;
+        Ok(target.encode(KNOWN_PEERS_DOMAIN, 0, plain.into_owned()))
+    }
+
+    /// Re-encode the stored blocked peers with the `target` codec.
+    pub(crate) fn reencode_blocked_peers(
+        &self,
+        target: &ValueCodec,
+        value: &[u8],
+    ) -> Result<Vec<u8>> {
+        let plain = self.decode(BLOCKED_PEERS_DOMAIN, 0, value)?;
+        Ok(target.encode(BLOCKED_PEERS_DOMAIN, 0, plain.into_owned()))
+    }
+
+    /// Returns the value to be stored for checking the key when the store is opened,
+    /// `None` if the values aren't encrypted.
+    pub(crate) fn key_check(&self) -> Option<Vec<u8>> {
+        let cipher = self.cipher.as_ref()?;
+        Some(cipher.encrypt(KEY_CHECK_DOMAIN, &[], KEY_CHECK_VALUE))
+    }
+
+    /// Check the codec against the stored key check value.
+    ///
+    /// Returns the codec which decodes the stored values, if they need to be re-encoded
+    /// with [`ValueCodec::current`] codec, i.e. when a store is being encrypted or it was
+    /// encrypted with a previous key. Store is being encrypted if the returned codec
+    /// isn't encrypted, which is allowed only for an empty store.
+    pub(crate) fn check_key(&self, key_check: Option<&[u8]>) -> Result<Option<ValueCodec>> {
+        match (&self.cipher, key_check) {
+            (None, None) => Ok(None),
+            (None, Some(_)) => Err(StoreError::OpenFailed(
+                "Store is encrypted, it must be opened with an encryption key".into(),
+            )),
+            (Some(_), None) => Ok(Some(ValueCodec::default())),
+            (Some(cipher), Some(key_check)) => {
+                match cipher.decrypt(KEY_CHECK_DOMAIN, &[], key_check) {
+                    Ok((_, 0)) => Ok(None),
+                    Ok(_) => Ok(Some(self.clone())),
+                    Err(_) => Err(StoreError::OpenFailed("Invalid encryption key".into())),
+                }
+            }
+        }
+    }
+
+    fn encode(&self, domain: &str, key: u64, value: Vec<u8>) -> Vec<u8> {
+        match self.cipher {
+            Some(ref cipher) => cipher.encrypt(domain, &key.to_be_bytes(), &value),
+            None => value,
+        }
+    }
+
+    fn decode<'a>(&self, domain: &str, key: u64, value: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        let Some(ref cipher) = self.cipher else {
+            return Ok(Cow::Borrowed(value));
+        };
+
+        cipher
+            .decrypt(domain, &key.to_be_bytes(), value)
+            .map(|(value, _)| Cow::Owned(value))
+            .map_err(|e| StoreError::StoredDataError(format!("{domain} {key}: {e}")))
+    }
+}
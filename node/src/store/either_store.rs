@@ -5,9 +5,11 @@ use async_trait::async_trait;
 use celestia_types::hash::Hash;
 use celestia_types::ExtendedHeader;
 use cid::Cid;
+use libp2p::PeerId;
 
 use crate::store::{
-    BlockRanges, Result, SamplingMetadata, SamplingStatus, Store, VerifiedExtendedHeaders,
+    BlockRanges, IntegrityReport, KnownPeer, MigrationReport, Result, SamplingMetadata,
+    SamplingStatus, Store, StoreChanges, StoreStats, VerifiedExtendedHeaders,
 };
 
 /// Struct that can be used to build combinations of different [`Store`] types.
@@ -185,6 +187,13 @@ where
         call!(self, wait_height(height))
     }
 
+    fn subscribe_changes(&self) -> StoreChanges {
+        match self {
+            EitherStore::Left(store) => store.subscribe_changes(),
+            EitherStore::Right(store) => store.subscribe_changes(),
+        }
+    }
+
     async fn get_range<RB>(&self, range: RB) -> Result<Vec<ExtendedHeader>>
     where
         RB: RangeBounds<u64> + Send,
@@ -213,6 +222,10 @@ where
         call!(self, update_sampling_metadata(height, status, cids))
     }
 
+    async fn add_sampling_cids(&self, height: u64, cids: Vec<Cid>) -> Result<()> {
+        call!(self, add_sampling_cids(height, cids))
+    }
+
     async fn get_sampling_metadata(&self, height: u64) -> Result<Option<SamplingMetadata>> {
         call!(self, get_sampling_metadata(height))
     }
@@ -237,6 +250,41 @@ where
         call!(self, remove_last())
     }
 
+    async fn get_known_peers(&self) -> Result<Vec<KnownPeer>> {
+        call!(self, get_known_peers())
+    }
+
+    async fn set_known_peers(&self, peers: Vec<KnownPeer>) -> Result<()> {
+        call!(self, set_known_peers(peers))
+    }
+
+    async fn get_blocked_peers(&self) -> Result<Vec<PeerId>> {
+        call!(self, get_blocked_peers())
+    }
+
+    async fn set_blocked_peers(&self, peers: Vec<PeerId>) -> Result<()> {
+        call!(self, set_blocked_peers(peers))
+    }
+
+    async fn stats(&self) -> Result<StoreStats> {
+        call!(self, stats())
+    }
+
+    async fn compact(&mut self) -> Result<bool> {
+        call!(self, compact())
+    }
+
+    async fn check_integrity(&self) -> Result<IntegrityReport> {
+        call!(self, check_integrity())
+    }
+
+    fn migration_report(&self) -> Option<&MigrationReport> {
+        match self {
+            EitherStore::Left(store) => store.migration_report(),
+            EitherStore::Right(store) => store.migration_report(),
+        }
+    }
+
     async fn close(self) -> Result<()> {
         call!(self, close())
     }
@@ -1,5 +1,5 @@
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
 use std::pin::pin;
 
@@ -7,13 +7,16 @@ use async_trait::async_trait;
 use celestia_types::hash::Hash;
 use celestia_types::ExtendedHeader;
 use cid::Cid;
+use libp2p::PeerId;
 use tokio::sync::{Notify, RwLock};
 use tracing::debug;
 
 use crate::block_ranges::BlockRanges;
+use crate::store::changes::StoreChangesSender;
 use crate::store::utils::VerifiedExtendedHeaders;
 use crate::store::{
-    Result, SamplingMetadata, SamplingStatus, Store, StoreError, StoreInsertionError,
+    KnownPeer, Result, SamplingMetadata, SamplingStatus, Store, StoreChange, StoreChanges,
+    StoreError, StoreInsertionError, StoreStats, TableStats,
 };
 
 /// A non-persistent in memory [`Store`] implementation.
@@ -23,6 +26,8 @@ pub struct InMemoryStore {
     inner: RwLock<InMemoryStoreInner>,
     /// Notify when a new header is added
     header_added_notifier: Notify,
+    /// Changes sent to the subscribers
+    changes: StoreChangesSender,
 }
 
 #[derive(Debug, Clone)]
@@ -37,6 +42,10 @@ struct InMemoryStoreInner {
     sampling_data: HashMap<u64, SamplingMetadata>,
     /// Source of truth about accepted sampling ranges present in the db.
     accepted_sampling_ranges: BlockRanges,
+    /// Peers to dial on the next start
+    known_peers: Vec<KnownPeer>,
+    /// Peers the node refuses to connect to
+    blocked_peers: Vec<PeerId>,
 }
 
 impl InMemoryStoreInner {
@@ -47,6 +56,8 @@ impl InMemoryStoreInner {
             header_ranges: BlockRanges::default(),
             sampling_data: HashMap::new(),
             accepted_sampling_ranges: BlockRanges::default(),
+            known_peers: Vec::new(),
+            blocked_peers: Vec::new(),
         }
     }
 }
@@ -57,6 +68,7 @@ impl InMemoryStore {
         InMemoryStore {
             inner: RwLock::new(InMemoryStoreInner::new()),
             header_added_notifier: Notify::new(),
+            changes: StoreChangesSender::new(),
         }
     }
 
@@ -95,8 +107,13 @@ impl InMemoryStore {
             .try_into()
             .map_err(|e| StoreInsertionError::HeadersVerificationFailed(e.to_string()))?;
 
+        let Some(range) = headers.height_range() else {
+            return Ok(());
+        };
+
         self.inner.write().await.insert(headers).await?;
         self.header_added_notifier.notify_waiters();
+        self.changes.send(StoreChange::HeadersInserted { range });
 
         Ok(())
     }
@@ -104,14 +121,21 @@ impl InMemoryStore {
     async fn update_sampling_metadata(
         &self,
         height: u64,
-        status: SamplingStatus,
+        status: Option<SamplingStatus>,
         cids: Vec<Cid>,
     ) -> Result<()> {
         self.inner
             .write()
             .await
             .update_sampling_metadata(height, status, cids)
-            .await
+            .await?;
+
+        if let Some(status) = status {
+            self.changes
+                .send(StoreChange::SamplingUpdated { height, status });
+        }
+
+        Ok(())
     }
 
     async fn get_sampling_metadata(&self, height: u64) -> Result<Option<SamplingMetadata>> {
@@ -131,16 +155,66 @@ impl InMemoryStore {
         InMemoryStore {
             inner: RwLock::new(self.inner.read().await.clone()),
             header_added_notifier: Notify::new(),
+            changes: StoreChangesSender::new(),
         }
     }
 
     async fn remove_last(&self) -> Result<u64> {
-        let mut inner = self.inner.write().await;
-        inner.remove_last()
+        let height = self.inner.write().await.remove_last()?;
+
+        self.changes.send(StoreChange::HeadersRemoved {
+            range: height..=height,
+        });
+
+        Ok(height)
+    }
+
+    async fn stats(&self) -> StoreStats {
+        self.inner.read().await.stats()
+    }
+
+    async fn get_known_peers(&self) -> Vec<KnownPeer> {
+        self.inner.read().await.known_peers.clone()
+    }
+
+    async fn set_known_peers(&self, peers: Vec<KnownPeer>) {
+        self.inner.write().await.known_peers = peers;
+    }
+
+    async fn get_blocked_peers(&self) -> Vec<PeerId> {
+        self.inner.read().await.blocked_peers.clone()
+    }
+
+    async fn set_blocked_peers(&self, peers: Vec<PeerId>) {
+        self.inner.write().await.blocked_peers = peers;
     }
 }
 
 impl InMemoryStoreInner {
+    fn stats(&self) -> StoreStats {
+        let table = |name: &str, entries: usize| TableStats {
+            name: name.to_owned(),
+            entries: entries as u64,
+            bytes: None,
+        };
+
+        StoreStats {
+            headers: self.headers.len() as u64,
+            cids: self
+                .sampling_data
+                .values()
+                .flat_map(|metadata| metadata.cids.iter())
+                .collect::<HashSet<_>>()
+                .len() as u64,
+            tables: vec![
+                table("headers", self.headers.len()),
+                table("heights", self.height_to_hash.len()),
+                table("sampling_metadata", self.sampling_data.len()),
+            ],
+            fragmented_bytes: None,
+        }
+    }
+
     fn get_stored_ranges(&self) -> BlockRanges {
         self.header_ranges.clone()
     }
@@ -258,28 +332,35 @@ impl InMemoryStoreInner {
     async fn update_sampling_metadata(
         &mut self,
         height: u64,
-        status: SamplingStatus,
+        status: Option<SamplingStatus>,
         cids: Vec<Cid>,
     ) -> Result<()> {
         if !self.contains_height(height) {
             return Err(StoreError::NotFound);
         }
 
-        match self.sampling_data.entry(height) {
+        let status = match self.sampling_data.entry(height) {
             Entry::Vacant(entry) => {
+                let status = status.unwrap_or_default();
                 entry.insert(SamplingMetadata { status, cids });
+                status
             }
             Entry::Occupied(mut entry) => {
                 let metadata = entry.get_mut();
-                metadata.status = status;
+
+                if let Some(status) = status {
+                    metadata.status = status;
+                }
 
                 for cid in cids {
                     if !metadata.cids.contains(&cid) {
                         metadata.cids.push(cid);
                     }
                 }
+
+                metadata.status
             }
-        }
+        };
 
         match status {
             SamplingStatus::Accepted => self
@@ -386,6 +467,10 @@ impl Store for InMemoryStore {
         }
     }
 
+    fn subscribe_changes(&self) -> StoreChanges {
+        self.changes.subscribe()
+    }
+
     async fn head_height(&self) -> Result<u64> {
         self.get_head_height().await
     }
@@ -412,7 +497,12 @@ impl Store for InMemoryStore {
         status: SamplingStatus,
         cids: Vec<Cid>,
     ) -> Result<()> {
-        self.update_sampling_metadata(height, status, cids).await
+        self.update_sampling_metadata(height, Some(status), cids)
+            .await
+    }
+
+    async fn add_sampling_cids(&self, height: u64, cids: Vec<Cid>) -> Result<()> {
+        self.update_sampling_metadata(height, None, cids).await
     }
 
     async fn get_sampling_metadata(&self, height: u64) -> Result<Option<SamplingMetadata>> {
@@ -431,6 +521,28 @@ impl Store for InMemoryStore {
         self.remove_last().await
     }
 
+    async fn get_known_peers(&self) -> Result<Vec<KnownPeer>> {
+        Ok(self.get_known_peers().await)
+    }
+
+    async fn set_known_peers(&self, peers: Vec<KnownPeer>) -> Result<()> {
+        self.set_known_peers(peers).await;
+        Ok(())
+    }
+
+    async fn get_blocked_peers(&self) -> Result<Vec<PeerId>> {
+        Ok(self.get_blocked_peers().await)
+    }
+
+    async fn set_blocked_peers(&self, peers: Vec<PeerId>) -> Result<()> {
+        self.set_blocked_peers(peers).await;
+        Ok(())
+    }
+
+    async fn stats(&self) -> Result<StoreStats> {
+        Ok(self.stats().await)
+    }
+
     async fn close(self) -> Result<()> {
         Ok(())
     }
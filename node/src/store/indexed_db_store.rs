@@ -1,36 +1,46 @@
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::fmt::Display;
+use std::ops::RangeInclusive;
 use std::pin::pin;
 
 use async_trait::async_trait;
 use celestia_types::hash::Hash;
 use celestia_types::ExtendedHeader;
 use cid::Cid;
+use futures::future::LocalBoxFuture;
 use futures::Future;
+use js_sys::Uint8Array;
+use libp2p::PeerId;
 use rexie::{Direction, Index, KeyRange, ObjectStore, Rexie, Transaction, TransactionMode};
 use send_wrapper::SendWrapper;
 use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen::{from_value, to_value};
-use smallvec::smallvec;
 use tendermint_proto::Protobuf;
-use tokio::sync::Notify;
-use tracing::warn;
-use wasm_bindgen::JsValue;
+use tokio::sync::{Notify, RwLock};
+use tracing::{info, warn};
+use wasm_bindgen::{JsCast, JsValue};
 
 use crate::block_ranges::BlockRanges;
+use crate::encryption::{Cipher, EncryptionKey};
+use crate::store::changes::StoreChangesSender;
+use crate::store::codec::ValueCodec;
+use crate::store::migrations::{pending_migrations, Migration};
 use crate::store::utils::VerifiedExtendedHeaders;
 use crate::store::{
-    Result, SamplingMetadata, SamplingStatus, Store, StoreError, StoreInsertionError,
+    KnownPeer, MigrationMode, MigrationReport, Result, SamplingMetadata, SamplingStatus, Store,
+    StoreChange, StoreChanges, StoreError, StoreInsertionError, StoreStats, TableStats,
 };
 
 /// indexeddb version, needs to be incremented on every schema schange
-const DB_VERSION: u32 = 4;
+const DB_VERSION: u32 = 5;
 
 // Data stores (SQL table analogue) used in IndexedDb
 const HEADER_STORE_NAME: &str = "headers";
 const SAMPLING_STORE_NAME: &str = "sampling";
 const RANGES_STORE_NAME: &str = "ranges";
 const SCHEMA_STORE_NAME: &str = "schema";
+const PEERS_STORE_NAME: &str = "peers";
 
 // Additional indexes set on HEADER_STORE, for querying by height and hash
 const HASH_INDEX_NAME: &str = "hash";
@@ -39,6 +49,45 @@ const HEIGHT_INDEX_NAME: &str = "height";
 const ACCEPTED_SAMPLING_RANGES_KEY: &str = "accepted_sampling_ranges";
 const HEADER_RANGES_KEY: &str = "header_ranges";
 const VERSION_KEY: &str = "version";
+const KEY_CHECK_KEY: &str = "key_check";
+const KNOWN_PEERS_KEY: &str = "known_peers";
+const BLOCKED_PEERS_KEY: &str = "blocked_peers";
+
+const ALL_STORES: [&str; 5] = [
+    HEADER_STORE_NAME,
+    RANGES_STORE_NAME,
+    SAMPLING_STORE_NAME,
+    SCHEMA_STORE_NAME,
+    PEERS_STORE_NAME,
+];
+
+/// Number of entries copied at once when backing up the database.
+const BACKUP_BATCH_SIZE: u32 = 512;
+/// Number of entries re-encoded at once, when the store is (re-)encrypted.
+const REENCODE_BATCH_SIZE: u32 = 512;
+
+type IndexedDbMigration = Migration<for<'a> fn(&'a Transaction) -> LocalBoxFuture<'a, Result<()>>>;
+
+/// Schema migrations, ordered by the version they migrate from.
+///
+/// Databases of schema v1 hold the headers only and are detected as v2.
+const MIGRATIONS: &[IndexedDbMigration] = &[
+    Migration {
+        from_version: 2,
+        description: "Derive header ranges from the stored headers",
+        run: migrate_v2_to_v3,
+    },
+    Migration {
+        from_version: 3,
+        description: "Store header ranges under a single key",
+        run: migrate_v3_to_v4,
+    },
+    Migration {
+        from_version: 4,
+        description: "Add the known peers store",
+        run: migrate_v4_to_v5,
+    },
+];
 
 #[derive(Debug, Serialize, Deserialize)]
 struct ExtendedHeaderEntry {
@@ -55,62 +104,88 @@ pub struct IndexedDbStore {
     // SendWrapper usage is safe in wasm because we're running on a single thread
     head: SendWrapper<RefCell<Option<ExtendedHeader>>>,
     db: SendWrapper<Rexie>,
+    codec: RwLock<ValueCodec>,
     header_added_notifier: Notify,
+    changes: StoreChangesSender,
+    migration_report: MigrationReport,
 }
 
 impl IndexedDbStore {
     /// Create or open a persistent store.
     pub async fn new(name: &str) -> Result<IndexedDbStore> {
-        let rexie = Rexie::builder(name)
-            .version(DB_VERSION)
-            .add_object_store(
-                ObjectStore::new(HEADER_STORE_NAME)
-                    .key_path("id")
-                    .auto_increment(true)
-                    // These need to match names in `ExtendedHeaderEntry`
-                    .add_index(Index::new(HASH_INDEX_NAME, "hash").unique(true))
-                    .add_index(Index::new(HEIGHT_INDEX_NAME, "height").unique(true)),
-            )
-            .add_object_store(ObjectStore::new(RANGES_STORE_NAME))
-            .add_object_store(ObjectStore::new(SAMPLING_STORE_NAME))
-            .add_object_store(ObjectStore::new(SCHEMA_STORE_NAME))
-            .build()
-            .await
-            .map_err(|e| StoreError::OpenFailed(e.to_string()))?;
-
-        // NOTE: Rexie does not expose any migration functionality, so we
-        // write our version in the store in order to handle it properly.
-        match detect_schema_version(&rexie).await? {
-            Some(schema_version) => {
-                if schema_version > DB_VERSION {
-                    let e = format!(
-                        "Incompatible database schema; found {}, expected {}.",
-                        schema_version, DB_VERSION
-                    );
-                    return Err(StoreError::OpenFailed(e));
+        IndexedDbStore::new_with_migrations(name, MigrationMode::Apply).await
+    }
+
+    /// Create or open a persistent store, handling pending schema migrations according
+    /// to `mode`.
+    ///
+    /// In [`MigrationMode::Backup`] the content of the database is copied to a
+    /// `<name>-v<version>-backup` database before it is migrated.
+    pub async fn new_with_migrations(name: &str, mode: MigrationMode) -> Result<IndexedDbStore> {
+        IndexedDbStore::new_with_codec_and_migrations(name, mode, ValueCodec::default()).await
+    }
+
+    /// Create or open a persistent store, keeping the values encrypted at rest with the `key`.
+    ///
+    /// Headers, their sampling metadata and the peers are encrypted, while heights, hashes
+    /// and stored ranges are kept in plain, so that they can still be queried. Encryption
+    /// can be enabled only on an empty store, since the browser could keep the values
+    /// written before in plain in its storage.
+    pub async fn new_encrypted(name: &str, key: &EncryptionKey) -> Result<IndexedDbStore> {
+        let codec = ValueCodec::encrypted(Cipher::new(key));
+        IndexedDbStore::new_with_codec_and_migrations(name, MigrationMode::Apply, codec).await
+    }
+
+    /// Create or open an encrypted persistent store, which can be encrypted with either
+    /// `key` or `previous_key`.
+    ///
+    /// If the store was encrypted with the `previous_key`, it is re-encrypted with the `key`.
+    /// This allows rotating the key from the configuration of the node.
+    pub async fn new_encrypted_with_previous_key(
+        name: &str,
+        key: &EncryptionKey,
+        previous_key: &EncryptionKey,
+    ) -> Result<IndexedDbStore> {
+        let codec = ValueCodec::encrypted(Cipher::new(key).with_previous_key(previous_key));
+        IndexedDbStore::new_with_codec_and_migrations(name, MigrationMode::Apply, codec).await
+    }
+
+    async fn new_with_codec_and_migrations(
+        name: &str,
+        mode: MigrationMode,
+        codec: ValueCodec,
+    ) -> Result<IndexedDbStore> {
+        let rexie = open_rexie(name).await?;
+
+        let migration_report = match mode {
+            MigrationMode::Apply => migrate(&rexie, false).await?,
+            MigrationMode::DryRun => {
+                let report = migrate(&rexie, true).await?;
+
+                if !report.is_empty() {
+                    rexie.close();
+                    return Err(report.dry_run_error());
                 }
 
-                migrate_older_to_v4(&rexie).await?;
+                report
             }
-            None => {
-                // New database
-                let tx = rexie.transaction(&[SCHEMA_STORE_NAME], TransactionMode::ReadWrite)?;
+            MigrationMode::Backup => {
+                let pending = migrate(&rexie, true).await?;
 
-                let schema_store = tx.store(SCHEMA_STORE_NAME)?;
-                set_schema_version(&schema_store, DB_VERSION).await?;
+                let backup = match pending.from_version.filter(|_| !pending.is_empty()) {
+                    Some(from_version) => Some(backup_db(&rexie, from_version).await?),
+                    None => None,
+                };
 
-                tx.commit().await?;
+                let mut report = migrate(&rexie, false).await?;
+                report.backup = backup;
+                report
             }
-        }
+        };
 
-        // Force us to write migrations!
-        debug_assert_eq!(
-            detect_schema_version(&rexie).await?,
-            Some(DB_VERSION),
-            "Some migrations are missing"
-        );
+        let codec = init_codec(&rexie, codec).await?;
 
-        let db_head = match get_head_from_database(&rexie).await {
+        let db_head = match get_head_from_database(&rexie, &codec).await {
             Ok(v) => Some(v),
             Err(StoreError::NotFound) => None,
             Err(e) => return Err(e),
@@ -119,7 +194,10 @@ impl IndexedDbStore {
         Ok(IndexedDbStore {
             head: SendWrapper::new(RefCell::new(db_head)),
             db: SendWrapper::new(rexie),
+            codec: RwLock::new(codec),
             header_added_notifier: Notify::new(),
+            changes: StoreChangesSender::new(),
+            migration_report,
         })
     }
 
@@ -139,6 +217,36 @@ impl IndexedDbStore {
         res
     }
 
+    /// Re-encrypt all the stored values with the new `key`.
+    ///
+    /// Returns [`StoreError::NotEncrypted`] if the store was opened without a key. Other
+    /// operations on the store wait until it's finished.
+    pub async fn rotate_key(&self, key: &EncryptionKey) -> Result<()> {
+        let codec = ValueCodec::encrypted(Cipher::new(key));
+        let mut current = self.codec.write().await;
+
+        if !current.is_encrypted() {
+            return Err(StoreError::NotEncrypted);
+        }
+
+        self.write_tx(
+            &[
+                HEADER_STORE_NAME,
+                SAMPLING_STORE_NAME,
+                SCHEMA_STORE_NAME,
+                PEERS_STORE_NAME,
+            ],
+            reencode_values_tx_op,
+            (current.clone(), codec.clone()),
+        )
+        .await?;
+
+        *current = codec;
+        info!("Store re-encrypted with a new key");
+
+        Ok(())
+    }
+
     /// Delete the persistent store.
     pub async fn delete_db(self) -> rexie::Result<()> {
         let name = self.db.name();
@@ -161,14 +269,16 @@ impl IndexedDbStore {
     }
 
     async fn get_by_height(&self, height: u64) -> Result<ExtendedHeader> {
+        let codec = self.codec.read().await;
         let tx = self
             .db
             .transaction(&[HEADER_STORE_NAME], TransactionMode::ReadOnly)?;
 
-        get_by_height(&tx.store(HEADER_STORE_NAME)?, height).await
+        get_by_height(&tx.store(HEADER_STORE_NAME)?, &codec, height).await
     }
 
     async fn get_by_hash(&self, hash: &Hash) -> Result<ExtendedHeader> {
+        let codec = self.codec.read().await;
         let tx = self
             .db
             .transaction(&[HEADER_STORE_NAME], TransactionMode::ReadOnly)?;
@@ -180,9 +290,7 @@ impl IndexedDbStore {
             return Err(StoreError::NotFound);
         };
 
-        let serialized_header = from_value::<ExtendedHeaderEntry>(header_entry)?.header;
-        ExtendedHeader::decode(serialized_header.as_ref())
-            .map_err(|e| StoreError::StoredDataError(e.to_string()))
+        decode_header_entry(&codec, header_entry)
     }
 
     async fn get_stored_header_ranges(&self) -> Result<BlockRanges> {
@@ -203,17 +311,19 @@ impl IndexedDbStore {
             .try_into()
             .map_err(|e| StoreInsertionError::HeadersVerificationFailed(e.to_string()))?;
 
-        if headers.as_ref().is_empty() {
+        let Some(range) = headers.height_range() else {
             return Ok(());
-        }
+        };
 
+        let codec = self.codec.read().await;
         let tail = self
             .write_tx(
                 &[HEADER_STORE_NAME, RANGES_STORE_NAME],
                 insert_tx_op,
-                headers,
+                (codec.clone(), headers),
             )
             .await?;
+        drop(codec);
 
         if tail.height().value()
             > self
@@ -227,6 +337,7 @@ impl IndexedDbStore {
         }
 
         self.header_added_notifier.notify_waiters();
+        self.changes.send(StoreChange::HeadersInserted { range });
 
         Ok(())
     }
@@ -256,15 +367,22 @@ impl IndexedDbStore {
     async fn update_sampling_metadata(
         &self,
         height: u64,
-        status: SamplingStatus,
+        status: Option<SamplingStatus>,
         cids: Vec<Cid>,
     ) -> Result<()> {
+        let codec = self.codec.read().await;
         self.write_tx(
             &[SAMPLING_STORE_NAME, RANGES_STORE_NAME],
             update_sampling_metadata_tx_op,
-            (height, status, cids),
+            (codec.clone(), height, status, cids),
         )
         .await?;
+        drop(codec);
+
+        if let Some(status) = status {
+            self.changes
+                .send(StoreChange::SamplingUpdated { height, status });
+        }
 
         Ok(())
     }
@@ -274,6 +392,7 @@ impl IndexedDbStore {
             return Err(StoreError::NotFound);
         }
 
+        let codec = self.codec.read().await;
         let tx = self
             .db
             .transaction(&[SAMPLING_STORE_NAME], TransactionMode::ReadOnly)?;
@@ -284,7 +403,7 @@ impl IndexedDbStore {
             return Ok(None);
         };
 
-        Ok(Some(from_value(sampling_entry)?))
+        decode_sampling_metadata(&codec, height, sampling_entry).map(Some)
     }
 
     async fn get_sampling_ranges(&self) -> Result<BlockRanges> {
@@ -297,12 +416,114 @@ impl IndexedDbStore {
     }
 
     async fn remove_last(&self) -> Result<u64> {
-        self.write_tx(
-            &[HEADER_STORE_NAME, RANGES_STORE_NAME],
-            remove_last_tx_op,
-            (),
-        )
-        .await
+        let height = self
+            .write_tx(
+                &[HEADER_STORE_NAME, RANGES_STORE_NAME],
+                remove_last_tx_op,
+                (),
+            )
+            .await?;
+
+        self.changes.send(StoreChange::HeadersRemoved {
+            range: height..=height,
+        });
+
+        Ok(height)
+    }
+
+    async fn get_known_peers(&self) -> Result<Vec<KnownPeer>> {
+        let codec = self.codec.read().await;
+        let tx = self
+            .db
+            .transaction(&[PEERS_STORE_NAME], TransactionMode::ReadOnly)?;
+        let store = tx.store(PEERS_STORE_NAME)?;
+
+        let Some(value) = store.get(JsValue::from_str(KNOWN_PEERS_KEY)).await? else {
+            return Ok(Vec::new());
+        };
+
+        codec.decode_known_peers(&from_value::<Vec<u8>>(value)?)
+    }
+
+    async fn set_known_peers(&self, peers: Vec<KnownPeer>) -> Result<()> {
+        let codec = self.codec.read().await;
+        let tx = self
+            .db
+            .transaction(&[PEERS_STORE_NAME], TransactionMode::ReadWrite)?;
+        let store = tx.store(PEERS_STORE_NAME)?;
+
+        let key = JsValue::from_str(KNOWN_PEERS_KEY);
+        let value = to_value(&codec.encode_known_peers(&peers))?;
+        store.put(&value, Some(&key)).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_blocked_peers(&self) -> Result<Vec<PeerId>> {
+        let codec = self.codec.read().await;
+        let tx = self
+            .db
+            .transaction(&[PEERS_STORE_NAME], TransactionMode::ReadOnly)?;
+        let store = tx.store(PEERS_STORE_NAME)?;
+
+        let Some(value) = store.get(JsValue::from_str(BLOCKED_PEERS_KEY)).await? else {
+            return Ok(Vec::new());
+        };
+
+        codec.decode_blocked_peers(&from_value::<Vec<u8>>(value)?)
+    }
+
+    async fn set_blocked_peers(&self, peers: Vec<PeerId>) -> Result<()> {
+        let codec = self.codec.read().await;
+        let tx = self
+            .db
+            .transaction(&[PEERS_STORE_NAME], TransactionMode::ReadWrite)?;
+        let store = tx.store(PEERS_STORE_NAME)?;
+
+        let key = JsValue::from_str(BLOCKED_PEERS_KEY);
+        let value = to_value(&codec.encode_blocked_peers(&peers))?;
+        store.put(&value, Some(&key)).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn stats(&self) -> Result<StoreStats> {
+        let codec = self.codec.read().await;
+        let tx = self
+            .db
+            .transaction(&ALL_STORES, TransactionMode::ReadOnly)?;
+        let mut tables = Vec::with_capacity(ALL_STORES.len());
+
+        // IndexedDb doesn't report sizes of the object stores, only an estimate
+        // for the whole origin, which is provided by `navigator.storage`.
+        for store_name in ALL_STORES {
+            tables.push(TableStats {
+                name: store_name.to_owned(),
+                entries: tx.store(store_name)?.count(None).await?.into(),
+                bytes: None,
+            });
+        }
+
+        let mut cids = HashSet::new();
+
+        for (height, raw_metadata) in tx
+            .store(SAMPLING_STORE_NAME)?
+            .scan(None, None, None, None)
+            .await?
+        {
+            let height = from_value(height)?;
+            let metadata = decode_sampling_metadata(&codec, height, raw_metadata)?;
+            cids.extend(metadata.cids);
+        }
+
+        Ok(StoreStats {
+            headers: tx.store(HEADER_STORE_NAME)?.count(None).await?.into(),
+            cids: cids.len() as u64,
+            tables,
+            fragmented_bytes: None,
+        })
     }
 }
 
@@ -374,6 +595,10 @@ impl Store for IndexedDbStore {
         }
     }
 
+    fn subscribe_changes(&self) -> StoreChanges {
+        self.changes.subscribe()
+    }
+
     async fn head_height(&self) -> Result<u64> {
         self.get_head_height()
     }
@@ -394,7 +619,12 @@ impl Store for IndexedDbStore {
         status: SamplingStatus,
         cids: Vec<Cid>,
     ) -> Result<()> {
-        let fut = SendWrapper::new(self.update_sampling_metadata(height, status, cids));
+        let fut = SendWrapper::new(self.update_sampling_metadata(height, Some(status), cids));
+        fut.await
+    }
+
+    async fn add_sampling_cids(&self, height: u64, cids: Vec<Cid>) -> Result<()> {
+        let fut = SendWrapper::new(self.update_sampling_metadata(height, None, cids));
         fut.await
     }
 
@@ -427,6 +657,35 @@ impl Store for IndexedDbStore {
         fut.await
     }
 
+    async fn get_known_peers(&self) -> Result<Vec<KnownPeer>> {
+        let fut = SendWrapper::new(self.get_known_peers());
+        fut.await
+    }
+
+    async fn set_known_peers(&self, peers: Vec<KnownPeer>) -> Result<()> {
+        let fut = SendWrapper::new(self.set_known_peers(peers));
+        fut.await
+    }
+
+    async fn get_blocked_peers(&self) -> Result<Vec<PeerId>> {
+        let fut = SendWrapper::new(self.get_blocked_peers());
+        fut.await
+    }
+
+    async fn set_blocked_peers(&self, peers: Vec<PeerId>) -> Result<()> {
+        let fut = SendWrapper::new(self.set_blocked_peers(peers));
+        fut.await
+    }
+
+    async fn stats(&self) -> Result<StoreStats> {
+        let fut = SendWrapper::new(self.stats());
+        fut.await
+    }
+
+    fn migration_report(&self) -> Option<&MigrationReport> {
+        Some(&self.migration_report)
+    }
+
     async fn close(self) -> Result<()> {
         self.db.take().close();
         Ok(())
@@ -464,7 +723,7 @@ async fn set_ranges(store: &rexie::Store, name: &str, ranges: &BlockRanges) -> R
     Ok(())
 }
 
-async fn get_head_from_database(db: &Rexie) -> Result<ExtendedHeader> {
+async fn get_head_from_database(db: &Rexie, codec: &ValueCodec) -> Result<ExtendedHeader> {
     let tx = db.transaction(
         &[HEADER_STORE_NAME, RANGES_STORE_NAME],
         TransactionMode::ReadOnly,
@@ -475,10 +734,14 @@ async fn get_head_from_database(db: &Rexie) -> Result<ExtendedHeader> {
     let ranges = get_ranges(&ranges_store, HEADER_RANGES_KEY).await?;
     let head_height = ranges.head().ok_or(StoreError::NotFound)?;
 
-    get_by_height(&header_store, head_height).await
+    get_by_height(&header_store, codec, head_height).await
 }
 
-async fn get_by_height(header_store: &rexie::Store, height: u64) -> Result<ExtendedHeader> {
+async fn get_by_height(
+    header_store: &rexie::Store,
+    codec: &ValueCodec,
+    height: u64,
+) -> Result<ExtendedHeader> {
     let height_index = header_store.index(HEIGHT_INDEX_NAME)?;
 
     let height_key = to_value(&height)?;
@@ -486,18 +749,48 @@ async fn get_by_height(header_store: &rexie::Store, height: u64) -> Result<Exten
         return Err(StoreError::NotFound);
     };
 
-    let serialized_header = from_value::<ExtendedHeaderEntry>(header_entry)?.header;
-    ExtendedHeader::decode(serialized_header.as_ref())
-        .map_err(|e| StoreError::StoredDataError(e.to_string()))
+    decode_header_entry(codec, header_entry)
+}
+
+fn decode_header_entry(codec: &ValueCodec, header_entry: JsValue) -> Result<ExtendedHeader> {
+    let entry = from_value::<ExtendedHeaderEntry>(header_entry)?;
+    codec.decode_header(entry.height, &entry.header)
+}
+
+/// Encode the sampling metadata, as a serialized object or, if the store is encrypted,
+/// as an array of encrypted bytes.
+fn encode_sampling_metadata(
+    codec: &ValueCodec,
+    height: u64,
+    metadata: SamplingMetadata,
+) -> Result<JsValue> {
+    if codec.is_encrypted() {
+        let bytes = codec.encode_sampling_metadata(height, metadata);
+        Ok(Uint8Array::from(&bytes[..]).into())
+    } else {
+        Ok(to_value(&metadata)?)
+    }
+}
+
+fn decode_sampling_metadata(
+    codec: &ValueCodec,
+    height: u64,
+    value: JsValue,
+) -> Result<SamplingMetadata> {
+    match value.dyn_ref::<Uint8Array>() {
+        Some(bytes) => codec.decode_sampling_metadata(height, &bytes.to_vec()),
+        None => Ok(from_value(value)?),
+    }
 }
 
 async fn verify_against_neighbours(
     header_store: &rexie::Store,
+    codec: &ValueCodec,
     lowest_header: Option<&ExtendedHeader>,
     highest_header: Option<&ExtendedHeader>,
 ) -> Result<()> {
     if let Some(lowest_header) = lowest_header {
-        let prev = get_by_height(header_store, lowest_header.height().value() - 1)
+        let prev = get_by_height(header_store, codec, lowest_header.height().value() - 1)
             .await
             .map_err(|e| {
                 if let StoreError::NotFound = e {
@@ -514,7 +807,7 @@ async fn verify_against_neighbours(
     }
 
     if let Some(highest_header) = highest_header {
-        let next = get_by_height(header_store, highest_header.height().value() + 1)
+        let next = get_by_height(header_store, codec, highest_header.height().value() + 1)
             .await
             .map_err(|e| {
                 if let StoreError::NotFound = e {
@@ -590,7 +883,7 @@ async fn set_schema_version(store: &rexie::Store, version: u32) -> Result<()> {
 
 async fn insert_tx_op(
     tx: &Transaction,
-    headers: VerifiedExtendedHeaders,
+    (codec, headers): (ValueCodec, VerifiedExtendedHeaders),
 ) -> Result<ExtendedHeader> {
     let head = headers.as_ref().first().expect("headers to not be empty");
     let tail = headers
@@ -612,6 +905,7 @@ async fn insert_tx_op(
     // header range is already internally verified against itself in `P2p::get_unverified_header_ranges`
     verify_against_neighbours(
         &header_store,
+        &codec,
         prev_exists.then_some(head),
         next_exists.then_some(&tail),
     )
@@ -632,7 +926,7 @@ async fn insert_tx_op(
         let header_entry = ExtendedHeaderEntry {
             height,
             hash,
-            header: header.encode_vec(),
+            header: codec.encode_header(header),
         };
 
         let jsvalue_header = to_value(&header_entry)?;
@@ -650,7 +944,7 @@ async fn insert_tx_op(
 
 async fn update_sampling_metadata_tx_op(
     tx: &Transaction,
-    (height, status, cids): (u64, SamplingStatus, Vec<Cid>),
+    (codec, height, status, cids): (ValueCodec, u64, Option<SamplingStatus>, Vec<Cid>),
 ) -> Result<()> {
     let sampling_store = tx.store(SAMPLING_STORE_NAME)?;
     let ranges_store = tx.store(RANGES_STORE_NAME)?;
@@ -665,9 +959,11 @@ async fn update_sampling_metadata_tx_op(
     let height_key = to_value(&height)?;
     let new_entry = match sampling_store.get(height_key.clone()).await? {
         Some(previous_entry) => {
-            let mut value: SamplingMetadata = from_value(previous_entry)?;
+            let mut value = decode_sampling_metadata(&codec, height, previous_entry)?;
 
-            value.status = status;
+            if let Some(status) = status {
+                value.status = status;
+            }
 
             for cid in cids {
                 if !value.cids.contains(&cid) {
@@ -677,10 +973,14 @@ async fn update_sampling_metadata_tx_op(
 
             value
         }
-        None => SamplingMetadata { status, cids },
+        None => SamplingMetadata {
+            status: status.unwrap_or_default(),
+            cids,
+        },
     };
 
-    let metadata_jsvalue = to_value(&new_entry)?;
+    let status = new_entry.status;
+    let metadata_jsvalue = encode_sampling_metadata(&codec, height, new_entry)?;
     sampling_store
         .put(&metadata_jsvalue, Some(&height_key))
         .await?;
@@ -731,49 +1031,306 @@ async fn remove_last_tx_op(tx: &Transaction, _: ()) -> Result<u64> {
     Ok(height)
 }
 
-async fn migrate_older_to_v4(db: &Rexie) -> Result<()> {
-    let Some(version) = detect_schema_version(db).await? else {
-        // New database.
-        return Ok(());
+/// Check the `codec` against the key check stored in the database and re-encode
+/// the stored values, if the store is being encrypted or the key was rotated.
+async fn init_codec(db: &Rexie, codec: ValueCodec) -> Result<ValueCodec> {
+    let key_check = {
+        let tx = db.transaction(&[SCHEMA_STORE_NAME], TransactionMode::ReadOnly)?;
+        let schema_store = tx.store(SCHEMA_STORE_NAME)?;
+
+        match schema_store.get(JsValue::from_str(KEY_CHECK_KEY)).await? {
+            Some(value) => Some(from_value::<Vec<u8>>(value)?),
+            None => None,
+        }
+    };
+
+    let target = codec.current();
+    let Some(source) = codec.check_key(key_check.as_deref())? else {
+        return Ok(target);
     };
 
-    if version >= 4 {
-        // Nothing to migrate.
-        return Ok(());
+    // Values written in plain could be left behind by the browser
+    if !source.is_encrypted() && holds_data(db).await? {
+        return Err(StoreError::OpenFailed(
+            "Encryption can be enabled only on an empty store".into(),
+        ));
     }
 
-    warn!("Migrating DB schema from v{version} to v4");
+    warn!("Encrypting the store with a new key");
 
     let tx = db.transaction(
-        &[HEADER_STORE_NAME, RANGES_STORE_NAME, SCHEMA_STORE_NAME],
+        &[
+            HEADER_STORE_NAME,
+            SAMPLING_STORE_NAME,
+            SCHEMA_STORE_NAME,
+            PEERS_STORE_NAME,
+        ],
         TransactionMode::ReadWrite,
     )?;
+
+    match reencode_values_tx_op(&tx, (source, target.clone())).await {
+        Ok(()) => tx.commit().await?,
+        Err(e) => {
+            tx.abort().await?;
+            return Err(e);
+        }
+    }
+
+    Ok(target)
+}
+
+/// Returns whether the store holds any headers, sampling metadata or peers.
+async fn holds_data(db: &Rexie) -> Result<bool> {
+    let stores = [HEADER_STORE_NAME, SAMPLING_STORE_NAME, PEERS_STORE_NAME];
+    let tx = db.transaction(&stores, TransactionMode::ReadOnly)?;
+
+    for store_name in stores {
+        if tx.store(store_name)?.count(None).await? > 0 {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Re-encode all the headers, sampling metadata and known peers from the `source`
+/// to the `target` codec.
+async fn reencode_values_tx_op(
+    tx: &Transaction,
+    (source, target): (ValueCodec, ValueCodec),
+) -> Result<()> {
     let header_store = tx.store(HEADER_STORE_NAME)?;
-    let ranges_store = tx.store(RANGES_STORE_NAME)?;
+    let sampling_store = tx.store(SAMPLING_STORE_NAME)?;
     let schema_store = tx.store(SCHEMA_STORE_NAME)?;
+    let peers_store = tx.store(PEERS_STORE_NAME)?;
+    let header_key = JsValue::from_str("header");
+
+    let mut offset = 0;
+    loop {
+        let entries = header_store
+            .scan(None, Some(REENCODE_BATCH_SIZE), Some(offset), None)
+            .await?;
+
+        if entries.is_empty() {
+            break;
+        }
+
+        for (_, raw_entry) in &entries {
+            let entry = from_value::<ExtendedHeaderEntry>(raw_entry.clone())?;
+            let header = source.reencode_header(&target, entry.height, &entry.header)?;
+
+            // Entry is updated in place, to keep its id
+            js_sys::Reflect::set(raw_entry, &header_key, &to_value(&header)?)
+                .map_err(|_| StoreError::StoredDataError("could not set header's value".into()))?;
+            header_store.put(raw_entry, None).await?;
+        }
+
+        offset += entries.len() as u32;
+    }
+
+    let mut offset = 0;
+    loop {
+        let entries = sampling_store
+            .scan(None, Some(REENCODE_BATCH_SIZE), Some(offset), None)
+            .await?;
+
+        if entries.is_empty() {
+            break;
+        }
+
+        for (key, value) in &entries {
+            let height = from_value(key.clone())?;
+            let metadata = decode_sampling_metadata(&source, height, value.clone())?;
+            let value = encode_sampling_metadata(&target, height, metadata)?;
+            sampling_store.put(&value, Some(key)).await?;
+        }
+
+        offset += entries.len() as u32;
+    }
+
+    let key = JsValue::from_str(KNOWN_PEERS_KEY);
+    if let Some(value) = peers_store.get(key.clone()).await? {
+        let value = source.reencode_known_peers(&target, &from_value::<Vec<u8>>(value)?)?;
+        peers_store.put(&to_value(&value)?, Some(&key)).await?;
+    }
+
+    let key = JsValue::from_str(BLOCKED_PEERS_KEY);
+    if let Some(value) = peers_store.get(key.clone()).await? {
+        let value = source.reencode_blocked_peers(&target, &from_value::<Vec<u8>>(value)?)?;
+        peers_store.put(&to_value(&value)?, Some(&key)).await?;
+    }
+
+    let key = JsValue::from_str(KEY_CHECK_KEY);
+    match target.key_check() {
+        Some(key_check) => {
+            schema_store.put(&to_value(&key_check)?, Some(&key)).await?;
+        }
+        None => schema_store.delete(key).await?,
+    }
+
+    Ok(())
+}
+
+async fn open_rexie(name: &str) -> Result<Rexie> {
+    Rexie::builder(name)
+        .version(DB_VERSION)
+        .add_object_store(
+            ObjectStore::new(HEADER_STORE_NAME)
+                .key_path("id")
+                .auto_increment(true)
+                // These need to match names in `ExtendedHeaderEntry`
+                .add_index(Index::new(HASH_INDEX_NAME, "hash").unique(true))
+                .add_index(Index::new(HEIGHT_INDEX_NAME, "height").unique(true)),
+        )
+        .add_object_store(ObjectStore::new(RANGES_STORE_NAME))
+        .add_object_store(ObjectStore::new(SAMPLING_STORE_NAME))
+        .add_object_store(ObjectStore::new(SCHEMA_STORE_NAME))
+        .add_object_store(ObjectStore::new(PEERS_STORE_NAME))
+        .build()
+        .await
+        .map_err(|e| StoreError::OpenFailed(e.to_string()))
+}
+
+/// Run pending schema migrations in a single transaction.
+///
+/// On `dry_run` any pending migrations are rolled back.
+async fn migrate(db: &Rexie, dry_run: bool) -> Result<MigrationReport> {
+    // NOTE: Rexie does not expose any migration functionality, so we
+    // write our version in the store in order to handle it properly.
+    let schema_version = detect_schema_version(db).await?;
+    let pending = pending_migrations(MIGRATIONS, schema_version.map(u64::from), DB_VERSION.into())?;
+
+    let tx = db.transaction(&ALL_STORES, TransactionMode::ReadWrite)?;
+
+    match run_migrations(&tx, schema_version, pending).await {
+        Ok(report) if dry_run && !report.is_empty() => {
+            tx.abort().await?;
+            Ok(report)
+        }
+        Ok(report) => {
+            tx.commit().await?;
+            Ok(report)
+        }
+        Err(e) => {
+            tx.abort().await?;
+            Err(e)
+        }
+    }
+}
+
+async fn run_migrations(
+    tx: &Transaction,
+    schema_version: Option<u32>,
+    pending: &[IndexedDbMigration],
+) -> Result<MigrationReport> {
+    let schema_store = tx.store(SCHEMA_STORE_NAME)?;
+    let mut steps = Vec::new();
+
+    for migration in pending {
+        let step = migration.step();
+        warn!("Migrating DB schema {step}");
+
+        (migration.run)(tx).await?;
+        set_schema_version(&schema_store, step.to_version as u32).await?;
+        steps.push(step);
+    }
+
+    if schema_version.is_none() {
+        // New database
+        set_schema_version(&schema_store, DB_VERSION).await?;
+    }
+
+    // Force us to write migrations!
+    debug_assert_eq!(
+        get_schema_version(&schema_store).await.ok(),
+        Some(DB_VERSION),
+        "Some migrations are missing"
+    );
+
+    Ok(MigrationReport {
+        from_version: schema_version.map(u64::from),
+        to_version: DB_VERSION.into(),
+        steps,
+        backup: None,
+    })
+}
+
+/// Copy content of all the object stores to a `<name>-v<version>-backup` database.
+///
+/// Entries are copied in batches, each in a separate pair of transactions, because
+/// a transaction commits as soon as it has no pending requests.
+async fn backup_db(db: &Rexie, version: u64) -> Result<String> {
+    let backup_name = format!("{}-v{version}-backup", db.name());
+
+    Rexie::delete(&backup_name).await?;
+    let backup = open_rexie(&backup_name).await?;
+
+    for store_name in ALL_STORES {
+        // Headers have their key included in the value
+        let inline_keys = store_name == HEADER_STORE_NAME;
+        let mut offset = 0;
+
+        loop {
+            let tx = db.transaction(&[store_name], TransactionMode::ReadOnly)?;
+            let entries = tx
+                .store(store_name)?
+                .scan(None, Some(BACKUP_BATCH_SIZE), Some(offset), None)
+                .await?;
+
+            if entries.is_empty() {
+                break;
+            }
+
+            let backup_tx = backup.transaction(&[store_name], TransactionMode::ReadWrite)?;
+            let backup_store = backup_tx.store(store_name)?;
+
+            for (key, value) in &entries {
+                let key = if inline_keys { None } else { Some(key) };
+                backup_store.put(value, key).await?;
+            }
+
+            backup_tx.commit().await?;
+            offset += entries.len() as u32;
+        }
+    }
+
+    backup.close();
+    warn!("Database backed up to {backup_name}");
+
+    Ok(backup_name)
+}
+
+fn migrate_v2_to_v3(tx: &Transaction) -> LocalBoxFuture<'_, Result<()>> {
+    Box::pin(async move {
+        let header_store = tx.store(HEADER_STORE_NAME)?;
+        let ranges_store = tx.store(RANGES_STORE_NAME)?;
+
+        ranges_store.clear().await?;
 
-    let ranges = if version <= 2 {
         match v2::get_head_header(&header_store).await {
             // On v2 there were no gaps between headers.
-            Ok(head) => BlockRanges::from_vec(smallvec![1..=head.height().value()])
-                .map_err(|e| StoreError::StoredDataError(e.to_string()))?,
-            Err(StoreError::NotFound) => BlockRanges::new(),
-            Err(e) => return Err(e),
+            Ok(head) => v3::set_header_range(&ranges_store, 1..=head.height().value()).await,
+            Err(StoreError::NotFound) => Ok(()),
+            Err(e) => Err(e),
         }
-    } else {
-        // On v3 ranges existed but in different format.
-        v3::get_header_ranges(&ranges_store).await?
-    };
+    })
+}
 
-    ranges_store.clear().await?;
-    set_ranges(&ranges_store, HEADER_RANGES_KEY, &ranges).await?;
+fn migrate_v3_to_v4(tx: &Transaction) -> LocalBoxFuture<'_, Result<()>> {
+    Box::pin(async move {
+        let ranges_store = tx.store(RANGES_STORE_NAME)?;
 
-    // Migrated to version 4
-    set_schema_version(&schema_store, 4).await?;
+        // On v3 ranges existed but in different format.
+        let ranges = v3::get_header_ranges(&ranges_store).await?;
 
-    tx.commit().await?;
+        ranges_store.clear().await?;
+        set_ranges(&ranges_store, HEADER_RANGES_KEY, &ranges).await
+    })
+}
 
-    Ok(())
+fn migrate_v4_to_v5(_tx: &Transaction) -> LocalBoxFuture<'_, Result<()>> {
+    // The store is created when the database is opened, there is nothing to move.
+    Box::pin(async { Ok(()) })
 }
 
 mod v2 {
@@ -798,6 +1355,18 @@ mod v2 {
 mod v3 {
     use super::*;
 
+    pub(super) async fn set_header_range(
+        store: &rexie::Store,
+        range: RangeInclusive<u64>,
+    ) -> Result<()> {
+        let key = to_value(range.start())?;
+        let val = to_value(&(*range.start(), *range.end()))?;
+
+        store.put(&val, Some(&key)).await?;
+
+        Ok(())
+    }
+
     pub(super) async fn get_header_ranges(store: &rexie::Store) -> Result<BlockRanges> {
         let mut ranges = BlockRanges::default();
 
@@ -815,6 +1384,7 @@ mod v3 {
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::encryption::EncryptionKey;
     use crate::test_utils::ExtendedHeaderGeneratorExt;
     use celestia_types::test_utils::ExtendedHeaderGenerator;
     use function_name::named;
@@ -837,7 +1407,7 @@ pub mod tests {
             .expect("inserting test data failed");
 
         for h in 1..=expected_height {
-            s.update_sampling_metadata(h, SamplingStatus::Accepted, vec![])
+            s.update_sampling_metadata(h, Some(SamplingStatus::Accepted), vec![])
                 .await
                 .expect("marking sampled failed");
         }
@@ -985,6 +1555,11 @@ pub mod tests {
                 .await
                 .expect("opening migrated store failed");
 
+            let report = store.migration_report().unwrap();
+            assert_eq!(report.from_version, Some(2));
+            assert_eq!(report.to_version, u64::from(DB_VERSION));
+            assert_eq!(report.steps.len(), 3);
+
             for header in headers {
                 let height = header.height().value();
 
@@ -1000,12 +1575,113 @@ pub mod tests {
             }
 
             store
-                .update_sampling_metadata(1, SamplingStatus::Accepted, vec![])
+                .update_sampling_metadata(1, Some(SamplingStatus::Accepted), vec![])
                 .await
                 .unwrap();
             let sampling_data = store.get_sampling_metadata(1).await.unwrap().unwrap();
             assert_eq!(sampling_data.status, SamplingStatus::Accepted);
         }
+
+        #[named]
+        #[wasm_bindgen_test]
+        async fn dry_run_leaves_db_unchanged() {
+            let store_name = function_name!();
+            let mut gen = ExtendedHeaderGenerator::new();
+            init_store(store_name, gen.next_many(5)).await;
+
+            let res = IndexedDbStore::new_with_migrations(store_name, MigrationMode::DryRun).await;
+            assert!(matches!(res, Err(StoreError::OpenFailed(_))));
+
+            let store = IndexedDbStore::new(store_name).await.unwrap();
+            assert_eq!(store.migration_report().unwrap().steps.len(), 3);
+            assert_eq!(store.head_height().await.unwrap(), 5);
+        }
+
+        #[named]
+        #[wasm_bindgen_test]
+        async fn backup_before_migration() {
+            let store_name = function_name!();
+            let backup_name = format!("{store_name}-v2-backup");
+            let mut gen = ExtendedHeaderGenerator::new();
+            let headers = gen.next_many(5);
+            init_store(store_name, headers.clone()).await;
+
+            let store = IndexedDbStore::new_with_migrations(store_name, MigrationMode::Backup)
+                .await
+                .unwrap();
+            let report = store.migration_report().unwrap();
+            assert_eq!(report.backup.as_deref(), Some(backup_name.as_str()));
+            assert_eq!(report.steps.len(), 3);
+
+            // Backup holds the original headers and is migrated when opened as a store
+            let backup = IndexedDbStore::new(&backup_name).await.unwrap();
+            for header in headers {
+                let height = header.height().value();
+                assert_eq!(backup.get_by_height(height).await.unwrap(), header);
+            }
+        }
+    }
+
+    #[named]
+    #[wasm_bindgen_test]
+    async fn test_encrypted_store() {
+        Rexie::delete(function_name!()).await.unwrap();
+        let key = EncryptionKey::generate();
+        let new_key = EncryptionKey::generate();
+        let mut gen = ExtendedHeaderGenerator::new();
+
+        let store = IndexedDbStore::new_encrypted(function_name!(), &key)
+            .await
+            .unwrap();
+        store.insert(gen.next_many_verified(5)).await.unwrap();
+        store
+            .update_sampling_metadata(3, Some(SamplingStatus::Accepted), vec![])
+            .await
+            .unwrap();
+        let header = store.get_by_height(3).await.unwrap();
+
+        store.rotate_key(&new_key).await.unwrap();
+        assert_eq!(store.get_by_height(3).await.unwrap(), header);
+        assert_eq!(
+            store
+                .get_sampling_metadata(3)
+                .await
+                .unwrap()
+                .unwrap()
+                .status,
+            SamplingStatus::Accepted
+        );
+        store.close().await.unwrap();
+
+        IndexedDbStore::new(function_name!()).await.unwrap_err();
+        IndexedDbStore::new_encrypted(function_name!(), &key)
+            .await
+            .unwrap_err();
+
+        let store = IndexedDbStore::new_encrypted(function_name!(), &new_key)
+            .await
+            .unwrap();
+        assert_eq!(store.head_height().await.unwrap(), 5);
+        assert_eq!(store.get_by_hash(&header.hash()).await.unwrap(), header);
+    }
+
+    #[named]
+    #[wasm_bindgen_test]
+    async fn test_encrypt_non_empty_store() {
+        let (store, _) = gen_filled_store(5, function_name!()).await;
+        store.close().await.unwrap();
+
+        let e = IndexedDbStore::new_encrypted(function_name!(), &EncryptionKey::generate())
+            .await
+            .unwrap_err();
+        assert!(matches!(e, StoreError::OpenFailed(_)));
+
+        let store = IndexedDbStore::new(function_name!()).await.unwrap();
+        let e = store
+            .rotate_key(&EncryptionKey::generate())
+            .await
+            .unwrap_err();
+        assert!(matches!(e, StoreError::NotEncrypted));
     }
 
     // open IndexedDB with unique per-test name to avoid interference and make cleanup easier
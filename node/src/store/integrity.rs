@@ -0,0 +1,261 @@
+//! Consistency checks of the stored headers.
+
+use std::fmt;
+
+use celestia_types::ExtendedHeader;
+use cid::Cid;
+
+use crate::block_ranges::BlockRanges;
+use crate::store::{Result, Store, StoreError};
+
+/// Result of [`Store::check_integrity`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegrityReport {
+    /// Header ranges the store claims to hold.
+    pub stored_ranges: BlockRanges,
+    /// Inconsistencies found.
+    pub issues: Vec<IntegrityIssue>,
+    /// Highest height up to which all the stored headers are consistent.
+    ///
+    /// `None` if the store is empty or even the lowest stored header is inconsistent.
+    pub last_consistent_height: Option<u64>,
+}
+
+/// An inconsistency found by [`Store::check_integrity`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityIssue {
+    /// Header of a stored height is missing.
+    MissingHeader {
+        /// Height of the header.
+        height: u64,
+    },
+    /// Header of a stored height cannot be decoded or is not valid.
+    InvalidHeader {
+        /// Height of the header.
+        height: u64,
+        /// A human readable error.
+        error: String,
+    },
+    /// Header stored under a height is of a different height.
+    WrongHeight {
+        /// Height the header is stored under.
+        height: u64,
+        /// Height of the header.
+        found: u64,
+    },
+    /// Header cannot be found by its hash.
+    HashIndexMismatch {
+        /// Height of the header.
+        height: u64,
+    },
+    /// Header doesn't verify against the previous stored header.
+    BrokenLink {
+        /// Height of the header.
+        height: u64,
+        /// A human readable error.
+        error: String,
+    },
+    /// Header is held in the database, but not in the stored ranges.
+    UntrackedHeader {
+        /// Height of the header.
+        height: u64,
+    },
+    /// Hash index entry doesn't point to a stored header with that hash.
+    DanglingHashIndex {
+        /// Height the entry points to.
+        height: u64,
+    },
+    /// Block in the blockstore is referenced only by sampling metadata of a height,
+    /// which isn't stored anymore.
+    OrphanedBlock {
+        /// CID of the block.
+        cid: Cid,
+    },
+}
+
+impl IntegrityReport {
+    pub(crate) fn new(stored_ranges: BlockRanges, issues: Vec<IntegrityIssue>) -> Self {
+        let mut consistent_ranges = stored_ranges.clone();
+
+        if let Some(height) = issues.iter().filter_map(|i| i.broken_height()).min() {
+            truncate_ranges(&mut consistent_ranges, height - 1);
+        }
+
+        IntegrityReport {
+            stored_ranges,
+            issues,
+            last_consistent_height: consistent_ranges.head(),
+        }
+    }
+
+    /// Returns `true` if no inconsistencies were found.
+    pub fn is_consistent(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl IntegrityIssue {
+    /// Height of the header chain broken by this issue.
+    fn broken_height(&self) -> Option<u64> {
+        match self {
+            IntegrityIssue::MissingHeader { height }
+            | IntegrityIssue::InvalidHeader { height, .. }
+            | IntegrityIssue::WrongHeight { height, .. }
+            | IntegrityIssue::HashIndexMismatch { height }
+            | IntegrityIssue::BrokenLink { height, .. } => Some(*height),
+            IntegrityIssue::UntrackedHeader { .. }
+            | IntegrityIssue::DanglingHashIndex { .. }
+            | IntegrityIssue::OrphanedBlock { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for IntegrityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntegrityIssue::MissingHeader { height } => {
+                write!(f, "Header {height} is missing")
+            }
+            IntegrityIssue::InvalidHeader { height, error } => {
+                write!(f, "Header {height} is invalid: {error}")
+            }
+            IntegrityIssue::WrongHeight { height, found } => {
+                write!(f, "Header {found} is stored as header {height}")
+            }
+            IntegrityIssue::HashIndexMismatch { height } => {
+                write!(f, "Header {height} cannot be found by its hash")
+            }
+            IntegrityIssue::BrokenLink { height, error } => {
+                write!(
+                    f,
+                    "Header {height} doesn't verify against the previous header: {error}"
+                )
+            }
+            IntegrityIssue::UntrackedHeader { height } => {
+                write!(f, "Header {height} is not in the stored ranges")
+            }
+            IntegrityIssue::DanglingHashIndex { height } => {
+                write!(f, "Hash index entry doesn't match header {height}")
+            }
+            IntegrityIssue::OrphanedBlock { cid } => {
+                write!(f, "Block {cid} doesn't belong to any stored header")
+            }
+        }
+    }
+}
+
+/// Remove all the heights above `height` from `ranges`.
+pub(crate) fn truncate_ranges(ranges: &mut BlockRanges, height: u64) {
+    ranges
+        .remove_relaxed(height.saturating_add(1)..=u64::MAX)
+        .expect("valid range");
+}
+
+/// Check the stored headers through the [`Store`] API.
+///
+/// Every header of the stored ranges must be found by its height and hash, and
+/// verify against the previous header of the range.
+pub(crate) async fn check_stored_headers<S>(store: &S) -> Result<IntegrityReport>
+where
+    S: Store + ?Sized,
+{
+    let stored_ranges = store.get_stored_header_ranges().await?;
+    let mut issues = Vec::new();
+
+    for range in stored_ranges.as_ref() {
+        let mut prev: Option<ExtendedHeader> = None;
+
+        for height in range.clone() {
+            let header = match store.get_by_height(height).await {
+                Ok(header) => header,
+                Err(StoreError::NotFound) => {
+                    issues.push(IntegrityIssue::MissingHeader { height });
+                    prev = None;
+                    continue;
+                }
+                Err(StoreError::StoredDataError(error)) => {
+                    issues.push(IntegrityIssue::InvalidHeader { height, error });
+                    prev = None;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            if header.height().value() != height {
+                issues.push(IntegrityIssue::WrongHeight {
+                    height,
+                    found: header.height().value(),
+                });
+                prev = None;
+                continue;
+            }
+
+            if let Err(e) = header.validate() {
+                issues.push(IntegrityIssue::InvalidHeader {
+                    height,
+                    error: e.to_string(),
+                });
+                prev = None;
+                continue;
+            }
+
+            match store.get_by_hash(&header.hash()).await {
+                Ok(by_hash) if by_hash == header => {}
+                Ok(_) | Err(StoreError::NotFound) | Err(StoreError::StoredDataError(_)) => {
+                    issues.push(IntegrityIssue::HashIndexMismatch { height });
+                }
+                Err(e) => return Err(e),
+            }
+
+            if let Some(prev) = prev.as_ref() {
+                if let Err(e) = prev.verify(&header) {
+                    issues.push(IntegrityIssue::BrokenLink {
+                        height,
+                        error: e.to_string(),
+                    });
+                }
+            }
+
+            prev = Some(header);
+        }
+    }
+
+    Ok(IntegrityReport::new(stored_ranges, issues))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::new_block_ranges;
+
+    #[test]
+    fn last_consistent_height() {
+        let report = IntegrityReport::new(new_block_ranges([1..=10, 20..=30]), vec![]);
+        assert_eq!(report.last_consistent_height, Some(30));
+
+        let report = IntegrityReport::new(
+            new_block_ranges([1..=10, 20..=30]),
+            vec![
+                IntegrityIssue::UntrackedHeader { height: 15 },
+                IntegrityIssue::HashIndexMismatch { height: 25 },
+                IntegrityIssue::MissingHeader { height: 27 },
+            ],
+        );
+        assert_eq!(report.last_consistent_height, Some(24));
+
+        let report = IntegrityReport::new(
+            new_block_ranges([1..=10, 20..=30]),
+            vec![IntegrityIssue::MissingHeader { height: 20 }],
+        );
+        assert_eq!(report.last_consistent_height, Some(10));
+
+        let report = IntegrityReport::new(
+            new_block_ranges([1..=10]),
+            vec![IntegrityIssue::MissingHeader { height: 1 }],
+        );
+        assert_eq!(report.last_consistent_height, None);
+
+        let report = IntegrityReport::new(BlockRanges::new(), vec![]);
+        assert_eq!(report.last_consistent_height, None);
+    }
+}
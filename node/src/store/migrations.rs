@@ -0,0 +1,277 @@
+//! Schema migrations of the persistent stores.
+//!
+//! Each persistent [`Store`] keeps an ordered registry of [`Migration`]s, one per schema
+//! version bump, which is run when the store is opened. Registry is checked to be
+//! complete, and a database written with a newer schema than the one known by the
+//! store is never opened.
+//!
+//! [`Store`]: crate::store::Store
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::events::{EventPublisher, NodeEvent};
+use crate::store::{Result, StoreError};
+
+/// How pending schema migrations are applied when a store is opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MigrationMode {
+    /// Apply pending migrations in place.
+    #[default]
+    Apply,
+    /// Copy the database aside before applying pending migrations.
+    Backup,
+    /// Run pending migrations and roll them back, leaving the database unchanged.
+    ///
+    /// Opening a store which needs migrations fails in this mode.
+    DryRun,
+}
+
+/// A single schema migration from `from_version` to `from_version + 1`.
+pub(crate) struct Migration<F> {
+    pub(crate) from_version: u64,
+    pub(crate) description: &'static str,
+    pub(crate) run: F,
+}
+
+/// Schema migrations performed when a store was opened.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MigrationReport {
+    /// Schema version found in the database, `None` if the database was new.
+    pub from_version: Option<u64>,
+    /// Schema version of the database after migrations.
+    pub to_version: u64,
+    /// Migrations applied, or checked in [`MigrationMode::DryRun`].
+    pub steps: Vec<MigrationStep>,
+    /// Location of the database backup, if one was taken.
+    pub backup: Option<String>,
+}
+
+/// A migration applied to the database.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MigrationStep {
+    /// Schema version before the migration.
+    pub from_version: u64,
+    /// Schema version after the migration.
+    pub to_version: u64,
+    /// Human readable description of the migration.
+    pub description: String,
+}
+
+impl MigrationReport {
+    /// Returns `true` if no migrations were needed.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Error returned when a store needing migrations is opened in [`MigrationMode::DryRun`].
+    pub(crate) fn dry_run_error(&self) -> StoreError {
+        let steps = self
+            .steps
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        StoreError::OpenFailed(format!(
+            "Dry run of migrations succeeded, database left unchanged: {steps}"
+        ))
+    }
+
+    /// Publish the report as [`NodeEvent`]s.
+    pub(crate) fn publish(&self, event_pub: &EventPublisher) {
+        if let Some(ref location) = self.backup {
+            event_pub.send(NodeEvent::StoreBackedUp {
+                location: location.clone(),
+            });
+        }
+
+        for step in &self.steps {
+            event_pub.send(NodeEvent::StoreMigrated {
+                from_version: step.from_version,
+                to_version: step.to_version,
+                description: step.description.clone(),
+            });
+        }
+    }
+}
+
+impl fmt::Display for MigrationStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "v{} to v{}: {}",
+            self.from_version, self.to_version, self.description
+        )
+    }
+}
+
+impl<F> Migration<F> {
+    pub(crate) fn step(&self) -> MigrationStep {
+        MigrationStep {
+            from_version: self.from_version,
+            to_version: self.from_version + 1,
+            description: self.description.to_owned(),
+        }
+    }
+}
+
+/// Returns the migrations needed to bring the schema `found` in the database to `latest`.
+///
+/// `None` means the database is new and needs no migrations.
+pub(crate) fn pending_migrations<F>(
+    migrations: &[Migration<F>],
+    found: Option<u64>,
+    latest: u64,
+) -> Result<&[Migration<F>]> {
+    debug_assert!(
+        is_registry_complete(migrations, latest),
+        "Some migrations are missing"
+    );
+
+    let Some(found) = found else {
+        return Ok(&[]);
+    };
+
+    if found > latest {
+        let e = format!("Incompatible database schema; found {found}, expected {latest}.");
+        return Err(StoreError::OpenFailed(e));
+    }
+
+    if found == latest {
+        return Ok(&[]);
+    }
+
+    let start = migrations
+        .iter()
+        .position(|m| m.from_version == found)
+        .ok_or_else(|| {
+            StoreError::OpenFailed(format!("No migration from database schema {found}"))
+        })?;
+
+    Ok(&migrations[start..])
+}
+
+/// Check that migrations are ordered, without gaps and lead to the `latest` schema.
+fn is_registry_complete<F>(migrations: &[Migration<F>], latest: u64) -> bool {
+    let ordered = migrations
+        .windows(2)
+        .all(|pair| pair[0].from_version + 1 == pair[1].from_version);
+
+    let leads_to_latest = match migrations.last() {
+        Some(last) => last.from_version + 1 == latest,
+        None => true,
+    };
+
+    ordered && leads_to_latest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventChannel;
+
+    fn registry() -> Vec<Migration<()>> {
+        vec![
+            Migration {
+                from_version: 1,
+                description: "first",
+                run: (),
+            },
+            Migration {
+                from_version: 2,
+                description: "second",
+                run: (),
+            },
+        ]
+    }
+
+    fn from_versions(migrations: &[Migration<()>]) -> Vec<u64> {
+        migrations.iter().map(|m| m.from_version).collect()
+    }
+
+    #[test]
+    fn pending_from_each_version() {
+        let registry = registry();
+
+        let pending = pending_migrations(&registry, Some(1), 3).unwrap();
+        assert_eq!(from_versions(pending), vec![1, 2]);
+
+        let pending = pending_migrations(&registry, Some(2), 3).unwrap();
+        assert_eq!(from_versions(pending), vec![2]);
+
+        assert!(pending_migrations(&registry, Some(3), 3)
+            .unwrap()
+            .is_empty());
+        assert!(pending_migrations(&registry, None, 3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn refuse_newer_schema() {
+        let registry = registry();
+        let res = pending_migrations(&registry, Some(4), 3);
+        assert!(matches!(res, Err(StoreError::OpenFailed(_))));
+    }
+
+    #[test]
+    fn unknown_old_schema() {
+        let registry = registry();
+        let res = pending_migrations(&registry, Some(0), 3);
+        assert!(matches!(res, Err(StoreError::OpenFailed(_))));
+    }
+
+    #[test]
+    fn registry_completeness() {
+        let mut registry = registry();
+        assert!(is_registry_complete(&registry, 3));
+        assert!(!is_registry_complete(&registry, 4));
+
+        registry.remove(0);
+        registry.insert(
+            0,
+            Migration {
+                from_version: 0,
+                description: "gap",
+                run: (),
+            },
+        );
+        assert!(!is_registry_complete(&registry, 3));
+    }
+
+    #[test]
+    fn report_published_as_events() {
+        let channel = EventChannel::new();
+        let mut sub = channel.subscribe();
+
+        let report = MigrationReport {
+            from_version: Some(1),
+            to_version: 3,
+            steps: registry().iter().map(Migration::step).collect(),
+            backup: Some("db.v1.backup".to_string()),
+        };
+        report.publish(&channel.publisher());
+
+        assert!(matches!(
+            sub.try_recv().unwrap().event,
+            NodeEvent::StoreBackedUp { location } if location == "db.v1.backup"
+        ));
+        assert!(matches!(
+            sub.try_recv().unwrap().event,
+            NodeEvent::StoreMigrated {
+                from_version: 1,
+                to_version: 2,
+                ..
+            }
+        ));
+        assert!(matches!(
+            sub.try_recv().unwrap().event,
+            NodeEvent::StoreMigrated {
+                from_version: 2,
+                to_version: 3,
+                ..
+            }
+        ));
+        assert!(sub.try_recv().is_err());
+    }
+}
@@ -0,0 +1,193 @@
+//! Peers remembered by the node across restarts.
+
+use std::time::Duration;
+
+use libp2p::{Multiaddr, PeerId};
+use prost::Message;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::store::{Result, StoreError};
+
+/// A peer known to the node, persisted in the [`Store`], so that it can be dialed
+/// on the next start without relying only on the bootnodes.
+///
+/// [`Store`]: crate::store::Store
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnownPeer {
+    /// Id of the peer.
+    pub peer_id: PeerId,
+    /// Addresses the peer was reachable on.
+    pub addrs: Vec<Multiaddr>,
+    /// When the peer was last seen connected.
+    pub last_seen: SystemTime,
+    /// Whether the peer was trusted when it was stored.
+    ///
+    /// Trust is not restored on the next start, it only affects which peers are kept.
+    pub trusted: bool,
+    /// Score of the peer at the time it was stored.
+    pub score: f64,
+}
+
+impl KnownPeer {
+    /// Returns `true` if the peer wasn't seen for longer than `max_age`.
+    pub fn is_stale(&self, max_age: Duration) -> bool {
+        SystemTime::now()
+            .duration_since(self.last_seen)
+            .is_ok_and(|age| age > max_age)
+    }
+}
+
+#[derive(Message)]
+struct RawKnownPeers {
+    #[prost(message, repeated, tag = "1")]
+    peers: Vec<RawKnownPeer>,
+}
+
+#[derive(Message)]
+struct RawKnownPeer {
+    #[prost(bytes = "vec", tag = "1")]
+    peer_id: Vec<u8>,
+
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    addrs: Vec<Vec<u8>>,
+
+    /// Seconds since the unix epoch.
+    #[prost(uint64, tag = "3")]
+    last_seen: u64,
+
+    #[prost(bool, tag = "4")]
+    trusted: bool,
+
+    #[prost(double, tag = "5")]
+    score: f64,
+}
+
+impl From<&KnownPeer> for RawKnownPeer {
+    fn from(peer: &KnownPeer) -> Self {
+        let last_seen = peer
+            .last_seen
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        RawKnownPeer {
+            peer_id: peer.peer_id.to_bytes(),
+            addrs: peer.addrs.iter().map(|addr| addr.to_vec()).collect(),
+            last_seen,
+            trusted: peer.trusted,
+            score: peer.score,
+        }
+    }
+}
+
+impl TryFrom<RawKnownPeer> for KnownPeer {
+    type Error = StoreError;
+
+    fn try_from(raw: RawKnownPeer) -> Result<Self> {
+        let peer_id = PeerId::from_bytes(&raw.peer_id)
+            .map_err(|e| StoreError::StoredDataError(format!("Invalid peer id: {e}")))?;
+
+        let addrs = raw
+            .addrs
+            .into_iter()
+            .map(Multiaddr::try_from)
+            .collect::<Result<_, _>>()
+            .map_err(|e| {
+                StoreError::StoredDataError(format!("Invalid address of {peer_id}: {e}"))
+            })?;
+
+        Ok(KnownPeer {
+            peer_id,
+            addrs,
+            last_seen: UNIX_EPOCH + Duration::from_secs(raw.last_seen),
+            trusted: raw.trusted,
+            score: raw.score,
+        })
+    }
+}
+
+pub(crate) fn serialize_known_peers(peers: &[KnownPeer]) -> Vec<u8> {
+    RawKnownPeers {
+        peers: peers.iter().map(RawKnownPeer::from).collect(),
+    }
+    .encode_to_vec()
+}
+
+pub(crate) fn deserialize_known_peers(data: &[u8]) -> Result<Vec<KnownPeer>> {
+    RawKnownPeers::decode(data)
+        .map_err(|e| StoreError::StoredDataError(format!("Invalid known peers: {e}")))?
+        .peers
+        .into_iter()
+        .map(KnownPeer::try_from)
+        .collect()
+}
+
+#[derive(Message)]
+struct RawBlockedPeers {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    peer_ids: Vec<Vec<u8>>,
+}
+
+pub(crate) fn serialize_blocked_peers(peers: &[PeerId]) -> Vec<u8> {
+    RawBlockedPeers {
+        peer_ids: peers.iter().map(|peer_id| peer_id.to_bytes()).collect(),
+    }
+    .encode_to_vec()
+}
+
+pub(crate) fn deserialize_blocked_peers(data: &[u8]) -> Result<Vec<PeerId>> {
+    RawBlockedPeers::decode(data)
+        .map_err(|e| StoreError::StoredDataError(format!("Invalid blocked peers: {e}")))?
+        .peer_ids
+        .iter()
+        .map(|bytes| {
+            PeerId::from_bytes(bytes)
+                .map_err(|e| StoreError::StoredDataError(format!("Invalid peer id: {e}")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_peers_roundtrip() {
+        let peers = vec![
+            KnownPeer {
+                peer_id: PeerId::random(),
+                addrs: vec![
+                    "/ip4/1.2.3.4/tcp/2121".parse().unwrap(),
+                    "/dnsaddr/example.com".parse().unwrap(),
+                ],
+                last_seen: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+                trusted: true,
+                score: 12.5,
+            },
+            KnownPeer {
+                peer_id: PeerId::random(),
+                addrs: Vec::new(),
+                last_seen: UNIX_EPOCH,
+                trusted: false,
+                score: -3.0,
+            },
+        ];
+
+        let decoded = deserialize_known_peers(&serialize_known_peers(&peers)).unwrap();
+        assert_eq!(decoded, peers);
+        assert!(decoded[1].is_stale(Duration::from_secs(60)));
+        assert!(deserialize_known_peers(&[0xff, 0xff]).is_err());
+    }
+
+    #[test]
+    fn blocked_peers_roundtrip() {
+        let peers = vec![PeerId::random(), PeerId::random()];
+
+        let decoded = deserialize_blocked_peers(&serialize_blocked_peers(&peers)).unwrap();
+        assert_eq!(decoded, peers);
+        assert!(deserialize_blocked_peers(&serialize_blocked_peers(&[]))
+            .unwrap()
+            .is_empty());
+        assert!(deserialize_blocked_peers(&[0xff, 0xff]).is_err());
+    }
+}
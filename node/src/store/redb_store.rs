@@ -1,32 +1,41 @@
+use std::collections::HashSet;
 use std::fmt::Display;
+use std::fs;
+use std::io;
 use std::ops::RangeInclusive;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::pin::pin;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
 
 use async_trait::async_trait;
+use blockstore::Blockstore;
 use celestia_types::hash::Hash;
 use celestia_types::ExtendedHeader;
 use cid::Cid;
+use libp2p::PeerId;
 use redb::{
-    CommitError, Database, ReadTransaction, ReadableTable, StorageError, Table, TableDefinition,
-    TableError, TransactionError, WriteTransaction,
+    CommitError, Database, ReadTransaction, ReadableTable, ReadableTableMetadata, StorageError,
+    Table, TableDefinition, TableError, TableHandle, TransactionError, WriteTransaction,
 };
-use tendermint_proto::Protobuf;
 use tokio::sync::Notify;
 use tokio::task::spawn_blocking;
 use tracing::warn;
-use tracing::{debug, trace};
+use tracing::{debug, info, trace};
 
 use crate::block_ranges::BlockRanges;
+use crate::encryption::{Cipher, EncryptionKey};
+use crate::store::changes::StoreChangesSender;
+use crate::store::codec::ValueCodec;
+use crate::store::integrity::{check_stored_headers, truncate_ranges};
+use crate::store::migrations::{pending_migrations, Migration};
 use crate::store::utils::VerifiedExtendedHeaders;
 use crate::store::{
-    Result, SamplingMetadata, SamplingStatus, Store, StoreError, StoreInsertionError,
+    IntegrityIssue, IntegrityReport, KnownPeer, MigrationMode, MigrationReport, Result,
+    SamplingMetadata, SamplingStatus, Store, StoreChange, StoreChanges, StoreError,
+    StoreInsertionError, StoreStats, TableStats,
 };
 use crate::utils::Counter;
 
-use super::utils::{deserialize_extended_header, deserialize_sampling_metadata};
-
 const SCHEMA_VERSION: u64 = 2;
 
 const HEIGHTS_TABLE: TableDefinition<'static, &[u8], u64> = TableDefinition::new("STORE.HEIGHTS");
@@ -37,15 +46,35 @@ const SCHEMA_VERSION_TABLE: TableDefinition<'static, (), u64> =
     TableDefinition::new("STORE.SCHEMA_VERSION");
 const RANGES_TABLE: TableDefinition<'static, &str, Vec<(u64, u64)>> =
     TableDefinition::new("STORE.RANGES");
+const ENCRYPTION_TABLE: TableDefinition<'static, &str, &[u8]> =
+    TableDefinition::new("STORE.ENCRYPTION");
+const PEERS_TABLE: TableDefinition<'static, &str, &[u8]> = TableDefinition::new("STORE.PEERS");
+
+/// Header ranges table of schema v1, replaced by [`HEADER_RANGES_KEY`] in v2.
+const V1_HEADER_HEIGHT_RANGES: TableDefinition<'static, u64, (u64, u64)> =
+    TableDefinition::new("STORE.HEIGHT_RANGES");
+
+type RedbMigration = Migration<fn(&WriteTransaction) -> Result<()>>;
+
+/// Schema migrations, ordered by the version they migrate from.
+const MIGRATIONS: &[RedbMigration] = &[Migration {
+    from_version: 1,
+    description: "Move header ranges into the ranges table",
+    run: migrate_v1_to_v2,
+}];
 
 const ACCEPTED_SAMPING_RANGES_KEY: &str = "KEY.ACCEPTED_SAMPING_RANGES";
 const HEADER_RANGES_KEY: &str = "KEY.HEADER_RANGES";
+const KEY_CHECK_KEY: &str = "KEY.KEY_CHECK";
+const KNOWN_PEERS_KEY: &str = "KEY.KNOWN_PEERS";
+const BLOCKED_PEERS_KEY: &str = "KEY.BLOCKED_PEERS";
 
 /// A [`Store`] implementation based on a [`redb`] database.
 #[derive(Debug)]
 pub struct RedbStore {
     inner: Arc<Inner>,
     task_counter: Counter,
+    migration_report: MigrationReport,
 }
 
 #[derive(Debug)]
@@ -54,18 +83,58 @@ struct Inner {
     db: Arc<Database>,
     /// Notify when a new header is added
     header_added_notifier: Notify,
+    /// Sender of the changes to the subscribers
+    changes: StoreChangesSender,
+    /// Encoding of the values, locked for writing while the values are re-encrypted
+    codec: RwLock<ValueCodec>,
 }
 
 impl RedbStore {
     /// Open a persistent [`redb`] store.
     pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        RedbStore::open_with_migrations(path, MigrationMode::Apply).await
+    }
+
+    /// Open a persistent [`redb`] store, handling pending schema migrations according
+    /// to `mode`.
+    ///
+    /// In [`MigrationMode::Backup`] the database file is copied to
+    /// `<path>.v<version>.backup` before it is migrated.
+    pub async fn open_with_migrations(path: impl AsRef<Path>, mode: MigrationMode) -> Result<Self> {
         let path = path.as_ref().to_owned();
+        let db = open_database(path.clone()).await?;
 
-        let db = spawn_blocking(|| Database::create(path))
-            .await?
-            .map_err(|e| StoreError::OpenFailed(e.to_string()))?;
+        if mode != MigrationMode::Backup {
+            return RedbStore::new_with_migrations(db, mode).await;
+        }
 
-        RedbStore::new(Arc::new(db)).await
+        let pending = migrate(db.clone(), true).await?;
+
+        let Some(from_version) = pending.from_version.filter(|_| !pending.is_empty()) else {
+            return RedbStore::new_with_migrations(db, MigrationMode::Apply).await;
+        };
+
+        // Close the database, so that the copy is consistent
+        drop(db);
+
+        let mut backup_path = path.clone().into_os_string();
+        backup_path.push(format!(".v{from_version}.backup"));
+        let backup_path = PathBuf::from(backup_path);
+
+        let (path, backup_path) = spawn_blocking(move || {
+            fs::copy(&path, &backup_path)?;
+            Ok::<_, io::Error>((path, backup_path))
+        })
+        .await?
+        .map_err(|e| StoreError::OpenFailed(format!("Failed to back up database: {e}")))?;
+
+        warn!("Database backed up to {}", backup_path.display());
+
+        let db = open_database(path).await?;
+        let mut store = RedbStore::new_with_migrations(db, MigrationMode::Apply).await?;
+        store.migration_report.backup = Some(backup_path.display().to_string());
+
+        Ok(store)
     }
 
     /// Open an in memory [`redb`] store.
@@ -79,60 +148,113 @@ impl RedbStore {
 
     /// Create new `RedbStore` with an already opened [`redb::Database`].
     pub async fn new(db: Arc<Database>) -> Result<Self> {
-        let store = RedbStore {
+        RedbStore::new_with_migrations(db, MigrationMode::Apply).await
+    }
+
+    /// Create new `RedbStore` with an already opened [`redb::Database`], handling pending
+    /// schema migrations according to `mode`.
+    ///
+    /// [`MigrationMode::Backup`] is not supported, use [`RedbStore::open_with_migrations`]
+    /// instead.
+    pub async fn new_with_migrations(db: Arc<Database>, mode: MigrationMode) -> Result<Self> {
+        RedbStore::new_with_codec_and_migrations(db, mode, ValueCodec::default()).await
+    }
+
+    /// Create new `RedbStore` with an already opened [`redb::Database`], keeping the
+    /// values encrypted at rest with the `key`.
+    ///
+    /// Headers, their sampling metadata and the peers are encrypted, while heights, hashes
+    /// and stored ranges are kept in plain, so that they can still be queried. Encryption
+    /// can be enabled only on an empty database, since the values written before would
+    /// be left in plain in the free pages of the database file.
+    pub async fn new_encrypted(db: Arc<Database>, key: &EncryptionKey) -> Result<Self> {
+        let codec = ValueCodec::encrypted(Cipher::new(key));
+        RedbStore::new_with_codec_and_migrations(db, MigrationMode::Apply, codec).await
+    }
+
+    /// Create new encrypted `RedbStore` with an already opened [`redb::Database`], which
+    /// can be encrypted with either `key` or `previous_key`.
+    ///
+    /// If the store was encrypted with the `previous_key`, it is re-encrypted with the `key`.
+    /// This allows rotating the key from the configuration of the node.
+    pub async fn new_encrypted_with_previous_key(
+        db: Arc<Database>,
+        key: &EncryptionKey,
+        previous_key: &EncryptionKey,
+    ) -> Result<Self> {
+        let codec = ValueCodec::encrypted(Cipher::new(key).with_previous_key(previous_key));
+        RedbStore::new_with_codec_and_migrations(db, MigrationMode::Apply, codec).await
+    }
+
+    async fn new_with_codec_and_migrations(
+        db: Arc<Database>,
+        mode: MigrationMode,
+        codec: ValueCodec,
+    ) -> Result<Self> {
+        let migration_report = match mode {
+            MigrationMode::Apply => migrate(db.clone(), false).await?,
+            MigrationMode::DryRun => {
+                let report = migrate(db.clone(), true).await?;
+
+                if !report.is_empty() {
+                    return Err(report.dry_run_error());
+                }
+
+                report
+            }
+            MigrationMode::Backup => {
+                let e = "Database backup requires opening the store by path".to_string();
+                return Err(StoreError::OpenFailed(e));
+            }
+        };
+
+        let codec = init_codec(db.clone(), codec).await?;
+
+        Ok(RedbStore {
             inner: Arc::new(Inner {
                 db,
                 header_added_notifier: Notify::new(),
+                changes: StoreChangesSender::new(),
+                codec: RwLock::new(codec),
             }),
             task_counter: Counter::new(),
-        };
+            migration_report,
+        })
+    }
 
-        store
-            .write_tx(|tx| {
-                let mut schema_version_table = tx.open_table(SCHEMA_VERSION_TABLE)?;
-                let schema_version = schema_version_table.get(())?.map(|guard| guard.value());
-
-                match schema_version {
-                    Some(schema_version) => {
-                        if schema_version > SCHEMA_VERSION {
-                            let e = format!(
-                                "Incompatible database schema; found {}, expected {}.",
-                                schema_version, SCHEMA_VERSION
-                            );
-                            return Err(StoreError::OpenFailed(e));
-                        }
+    /// Re-encrypt all the stored values with the new `key`.
+    ///
+    /// Returns [`StoreError::NotEncrypted`] if the store was opened without a key. Other
+    /// transactions wait until the values are re-encrypted.
+    pub async fn rotate_key(&self, key: &EncryptionKey) -> Result<()> {
+        let codec = ValueCodec::encrypted(Cipher::new(key));
+        let inner = self.inner.clone();
+        let guard = self.task_counter.guard();
 
-                        // Do migrations
-                        migrate_v1_to_v2(tx, &mut schema_version_table)?;
-                    }
-                    None => {
-                        // New database
-                        schema_version_table.insert((), SCHEMA_VERSION)?;
-                    }
-                }
+        spawn_blocking(move || {
+            let _guard = guard;
 
-                // Force us to write migrations!
-                debug_assert_eq!(
-                    schema_version_table.get(())?.map(|guard| guard.value()),
-                    Some(SCHEMA_VERSION),
-                    "Some migrations are missing"
-                );
+            let mut current = inner.codec.write().map_err(|_| codec_lock_poisoned())?;
 
-                // create tables, so that reads later don't complain
-                let _heights_table = tx.open_table(HEIGHTS_TABLE)?;
-                let _headers_table = tx.open_table(HEADERS_TABLE)?;
-                let _ranges_table = tx.open_table(RANGES_TABLE)?;
-                let _sampling_table = tx.open_table(SAMPLING_METADATA_TABLE)?;
+            if !current.is_encrypted() {
+                return Err(StoreError::NotEncrypted);
+            }
 
-                Ok(())
-            })
-            .await
-            .map_err(|e| match e {
-                e @ StoreError::OpenFailed(_) => e,
-                e => StoreError::OpenFailed(e.to_string()),
-            })?;
+            let tx = inner.db.begin_write()?;
 
-        Ok(store)
+            if let Err(e) = reencode_values(&tx, &current, &codec) {
+                tx.abort()?;
+                return Err(e);
+            }
+
+            tx.commit()?;
+            *current = codec;
+
+            info!("Store re-encrypted with a new key");
+
+            Ok(())
+        })
+        .await?
     }
 
     /// Returns the raw [`redb::Database`].
@@ -146,7 +268,7 @@ impl RedbStore {
     /// Execute a read transaction.
     async fn read_tx<F, T>(&self, f: F) -> Result<T>
     where
-        F: FnOnce(&mut ReadTransaction) -> Result<T> + Send + 'static,
+        F: FnOnce(&mut ReadTransaction, &ValueCodec) -> Result<T> + Send + 'static,
         T: Send + 'static,
     {
         let inner = self.inner.clone();
@@ -156,8 +278,9 @@ impl RedbStore {
             let _guard = guard;
 
             {
+                let codec = read_codec(&inner.codec)?;
                 let mut tx = inner.db.begin_read()?;
-                f(&mut tx)
+                f(&mut tx, &codec)
             }
         })
         .await?
@@ -168,7 +291,7 @@ impl RedbStore {
     /// If closure returns an error the transaction is aborted, otherwise commited.
     async fn write_tx<F, T>(&self, f: F) -> Result<T>
     where
-        F: FnOnce(&mut WriteTransaction) -> Result<T> + Send + 'static,
+        F: FnOnce(&mut WriteTransaction, &ValueCodec) -> Result<T> + Send + 'static,
         T: Send + 'static,
     {
         let inner = self.inner.clone();
@@ -178,8 +301,9 @@ impl RedbStore {
             let _guard = guard;
 
             {
+                let codec = read_codec(&inner.codec)?;
                 let mut tx = inner.db.begin_write()?;
-                let res = f(&mut tx);
+                let res = f(&mut tx, &codec);
 
                 if res.is_ok() {
                     tx.commit()?;
@@ -194,7 +318,7 @@ impl RedbStore {
     }
 
     async fn head_height(&self) -> Result<u64> {
-        self.read_tx(|tx| {
+        self.read_tx(|tx, _| {
             let table = tx.open_table(RANGES_TABLE)?;
             let header_ranges = get_ranges(&table, HEADER_RANGES_KEY)?;
 
@@ -206,33 +330,33 @@ impl RedbStore {
     async fn get_by_hash(&self, hash: &Hash) -> Result<ExtendedHeader> {
         let hash = *hash;
 
-        self.read_tx(move |tx| {
+        self.read_tx(move |tx, codec| {
             let heights_table = tx.open_table(HEIGHTS_TABLE)?;
             let headers_table = tx.open_table(HEADERS_TABLE)?;
 
             let height = get_height(&heights_table, hash.as_bytes())?;
-            get_header(&headers_table, height)
+            get_header(&headers_table, codec, height)
         })
         .await
     }
 
     async fn get_by_height(&self, height: u64) -> Result<ExtendedHeader> {
-        self.read_tx(move |tx| {
+        self.read_tx(move |tx, codec| {
             let table = tx.open_table(HEADERS_TABLE)?;
-            get_header(&table, height)
+            get_header(&table, codec, height)
         })
         .await
     }
 
     async fn get_head(&self) -> Result<ExtendedHeader> {
-        self.read_tx(|tx| {
+        self.read_tx(|tx, codec| {
             let ranges_table = tx.open_table(RANGES_TABLE)?;
             let headers_table = tx.open_table(HEADERS_TABLE)?;
 
             let header_ranges = get_ranges(&ranges_table, HEADER_RANGES_KEY)?;
             let head = header_ranges.head().ok_or(StoreError::NotFound)?;
 
-            get_header(&headers_table, head)
+            get_header(&headers_table, codec, head)
         })
         .await
     }
@@ -240,7 +364,7 @@ impl RedbStore {
     async fn contains_hash(&self, hash: &Hash) -> bool {
         let hash = *hash;
 
-        self.read_tx(move |tx| {
+        self.read_tx(move |tx, _| {
             let heights_table = tx.open_table(HEIGHTS_TABLE)?;
             let headers_table = tx.open_table(HEADERS_TABLE)?;
 
@@ -252,7 +376,7 @@ impl RedbStore {
     }
 
     async fn contains_height(&self, height: u64) -> bool {
-        self.read_tx(move |tx| {
+        self.read_tx(move |tx, _| {
             let headers_table = tx.open_table(HEADERS_TABLE)?;
             Ok(headers_table.get(height)?.is_some())
         })
@@ -268,8 +392,11 @@ impl RedbStore {
         let headers = headers
             .try_into()
             .map_err(|e| StoreInsertionError::HeadersVerificationFailed(e.to_string()))?;
+        let Some(range) = headers.height_range() else {
+            return Ok(());
+        };
 
-        self.write_tx(move |tx| {
+        self.write_tx(move |tx, codec| {
             let (Some(head), Some(tail)) = (headers.as_ref().first(), headers.as_ref().last())
             else {
                 return Ok(());
@@ -288,6 +415,7 @@ impl RedbStore {
 
             verify_against_neighbours(
                 &headers_table,
+                codec,
                 prev_exists.then_some(head),
                 next_exists.then_some(tail),
             )?;
@@ -295,7 +423,7 @@ impl RedbStore {
             for header in headers {
                 let height = header.height().value();
                 let hash = header.hash();
-                let serialized_header = header.encode_vec();
+                let serialized_header = codec.encode_header(header);
 
                 if headers_table
                     .insert(height, &serialized_header[..])?
@@ -327,6 +455,9 @@ impl RedbStore {
         .await?;
 
         self.inner.header_added_notifier.notify_waiters();
+        self.inner
+            .changes
+            .send(StoreChange::HeadersInserted { range });
 
         Ok(())
     }
@@ -334,10 +465,10 @@ impl RedbStore {
     async fn update_sampling_metadata(
         &self,
         height: u64,
-        status: SamplingStatus,
+        status: Option<SamplingStatus>,
         cids: Vec<Cid>,
     ) -> Result<()> {
-        self.write_tx(move |tx| {
+        self.write_tx(move |tx, codec| {
             let mut sampling_metadata_table = tx.open_table(SAMPLING_METADATA_TABLE)?;
             let mut ranges_table = tx.open_table(RANGES_TABLE)?;
 
@@ -348,11 +479,13 @@ impl RedbStore {
                 return Err(StoreError::NotFound);
             }
 
-            let previous = get_sampling_metadata(&sampling_metadata_table, height)?;
+            let previous = get_sampling_metadata(&sampling_metadata_table, codec, height)?;
 
             let entry = match previous {
                 Some(mut previous) => {
-                    previous.status = status;
+                    if let Some(status) = status {
+                        previous.status = status;
+                    }
 
                     for cid in cids {
                         if !previous.cids.contains(&cid) {
@@ -362,11 +495,14 @@ impl RedbStore {
 
                     previous
                 }
-                None => SamplingMetadata { status, cids },
+                None => SamplingMetadata {
+                    status: status.unwrap_or_default(),
+                    cids,
+                },
             };
+            let status = entry.status;
 
-            // make sure Result is Infallible and unwrap it later
-            let serialized = entry.encode_vec();
+            let serialized = codec.encode_sampling_metadata(height, entry);
 
             sampling_metadata_table.insert(height, &serialized[..])?;
 
@@ -387,11 +523,19 @@ impl RedbStore {
 
             Ok(())
         })
-        .await
+        .await?;
+
+        if let Some(status) = status {
+            self.inner
+                .changes
+                .send(StoreChange::SamplingUpdated { height, status });
+        }
+
+        Ok(())
     }
 
     async fn get_sampling_metadata(&self, height: u64) -> Result<Option<SamplingMetadata>> {
-        self.read_tx(move |tx| {
+        self.read_tx(move |tx, codec| {
             let headers_table = tx.open_table(HEADERS_TABLE)?;
             let sampling_metadata_table = tx.open_table(SAMPLING_METADATA_TABLE)?;
 
@@ -399,13 +543,13 @@ impl RedbStore {
                 return Err(StoreError::NotFound);
             }
 
-            get_sampling_metadata(&sampling_metadata_table, height)
+            get_sampling_metadata(&sampling_metadata_table, codec, height)
         })
         .await
     }
 
     async fn get_stored_ranges(&self) -> Result<BlockRanges> {
-        self.read_tx(|tx| {
+        self.read_tx(|tx, _| {
             let table = tx.open_table(RANGES_TABLE)?;
             get_ranges(&table, HEADER_RANGES_KEY)
         })
@@ -413,7 +557,7 @@ impl RedbStore {
     }
 
     async fn get_sampling_ranges(&self) -> Result<BlockRanges> {
-        self.read_tx(|tx| {
+        self.read_tx(|tx, _| {
             let table = tx.open_table(RANGES_TABLE)?;
             get_ranges(&table, ACCEPTED_SAMPING_RANGES_KEY)
         })
@@ -421,35 +565,337 @@ impl RedbStore {
     }
 
     async fn remove_last(&self) -> Result<u64> {
-        self.write_tx(move |tx| {
-            let mut heights_table = tx.open_table(HEIGHTS_TABLE)?;
-            let mut headers_table = tx.open_table(HEADERS_TABLE)?;
-            let mut ranges_table = tx.open_table(RANGES_TABLE)?;
+        let height = self
+            .write_tx(move |tx, codec| {
+                let mut heights_table = tx.open_table(HEIGHTS_TABLE)?;
+                let mut headers_table = tx.open_table(HEADERS_TABLE)?;
+                let mut ranges_table = tx.open_table(RANGES_TABLE)?;
+
+                let mut header_ranges = get_ranges(&ranges_table, HEADER_RANGES_KEY)?;
+
+                let Some(height) = header_ranges.pop_tail() else {
+                    return Err(StoreError::NotFound);
+                };
+                set_ranges(&mut ranges_table, HEADER_RANGES_KEY, &header_ranges)?;
+
+                let Some(header) = headers_table.remove(height)? else {
+                    return Err(StoreError::StoredDataError(format!(
+                        "inconsistency between ranges and height_to_hash tables, height {height}"
+                    )));
+                };
+
+                let hash = codec.decode_header(height, header.value())?.hash();
+
+                if heights_table.remove(hash.as_bytes())?.is_none() {
+                    return Err(StoreError::StoredDataError(format!(
+                        "inconsistency between header and height_to_hash tables, hash {hash}"
+                    )));
+                }
 
-            let mut header_ranges = get_ranges(&ranges_table, HEADER_RANGES_KEY)?;
+                Ok(height)
+            })
+            .await?;
 
-            let Some(height) = header_ranges.pop_tail() else {
-                return Err(StoreError::NotFound);
-            };
-            set_ranges(&mut ranges_table, HEADER_RANGES_KEY, &header_ranges)?;
+        self.inner.changes.send(StoreChange::HeadersRemoved {
+            range: height..=height,
+        });
 
-            let Some(header) = headers_table.remove(height)? else {
-                return Err(StoreError::StoredDataError(format!(
-                    "inconsistency between ranges and height_to_hash tables, height {height}"
-                )));
-            };
+        Ok(height)
+    }
 
-            let hash = ExtendedHeader::decode(header.value())
-                .map_err(|e| StoreError::StoredDataError(e.to_string()))?
-                .hash();
+    async fn get_known_peers(&self) -> Result<Vec<KnownPeer>> {
+        self.read_tx(|tx, codec| {
+            let table = tx.open_table(PEERS_TABLE)?;
 
-            if heights_table.remove(hash.as_bytes())?.is_none() {
-                return Err(StoreError::StoredDataError(format!(
-                    "inconsistency between header and height_to_hash tables, hash {hash}"
-                )));
+            match table.get(KNOWN_PEERS_KEY)? {
+                Some(guard) => codec.decode_known_peers(guard.value()),
+                None => Ok(Vec::new()),
             }
+        })
+        .await
+    }
+
+    async fn set_known_peers(&self, peers: Vec<KnownPeer>) -> Result<()> {
+        self.write_tx(move |tx, codec| {
+            let mut table = tx.open_table(PEERS_TABLE)?;
+            let value = codec.encode_known_peers(&peers);
+
+            table.insert(KNOWN_PEERS_KEY, &value[..])?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_blocked_peers(&self) -> Result<Vec<PeerId>> {
+        self.read_tx(|tx, codec| {
+            let table = tx.open_table(PEERS_TABLE)?;
+
+            match table.get(BLOCKED_PEERS_KEY)? {
+                Some(guard) => codec.decode_blocked_peers(guard.value()),
+                None => Ok(Vec::new()),
+            }
+        })
+        .await
+    }
+
+    async fn set_blocked_peers(&self, peers: Vec<PeerId>) -> Result<()> {
+        self.write_tx(move |tx, codec| {
+            let mut table = tx.open_table(PEERS_TABLE)?;
+            let value = codec.encode_blocked_peers(&peers);
+
+            table.insert(BLOCKED_PEERS_KEY, &value[..])?;
 
-            Ok(height)
+            Ok(())
+        })
+        .await
+    }
+
+    /// Remove headers above `height`.
+    ///
+    /// Headers not in the stored ranges and hash index entries not matching any stored
+    /// header are removed too.
+    ///
+    /// This is used to repair the store after an inconsistency was found with
+    /// [`Store::check_integrity`], by truncating it to
+    /// [`IntegrityReport::last_consistent_height`].
+    ///
+    /// Sampling metadata of the removed headers is kept, so that their blocks can still
+    /// be found with [`RedbStore::check_blockstore`] and removed with
+    /// [`RedbStore::remove_orphaned_blocks`].
+    pub async fn truncate(&self, height: u64) -> Result<()> {
+        let removed = self
+            .write_tx(move |tx, codec| {
+                let mut heights_table = tx.open_table(HEIGHTS_TABLE)?;
+                let mut headers_table = tx.open_table(HEADERS_TABLE)?;
+                let mut ranges_table = tx.open_table(RANGES_TABLE)?;
+
+                let stored_ranges = get_ranges(&ranges_table, HEADER_RANGES_KEY)?;
+                let mut header_ranges = stored_ranges.clone();
+                let mut sampling_ranges = get_ranges(&ranges_table, ACCEPTED_SAMPING_RANGES_KEY)?;
+                truncate_ranges(&mut header_ranges, height);
+                truncate_ranges(&mut sampling_ranges, height);
+
+                let mut removed_heights = Vec::new();
+                for entry in headers_table.iter()? {
+                    let stored_height = entry?.0.value();
+                    if !header_ranges.contains(stored_height) {
+                        removed_heights.push(stored_height);
+                    }
+                }
+                for height in removed_heights {
+                    headers_table.remove(height)?;
+                }
+
+                let mut removed_hashes = Vec::new();
+                for entry in heights_table.iter()? {
+                    let (hash, indexed_height) = entry?;
+                    let hash = hash.value();
+                    if !is_indexed_header(
+                        &headers_table,
+                        codec,
+                        &header_ranges,
+                        hash,
+                        indexed_height.value(),
+                    )? {
+                        removed_hashes.push(hash.to_vec());
+                    }
+                }
+                for hash in removed_hashes {
+                    heights_table.remove(&hash[..])?;
+                }
+
+                set_ranges(&mut ranges_table, HEADER_RANGES_KEY, &header_ranges)?;
+                set_ranges(
+                    &mut ranges_table,
+                    ACCEPTED_SAMPING_RANGES_KEY,
+                    &sampling_ranges,
+                )?;
+
+                Ok(stored_ranges - header_ranges)
+            })
+            .await?;
+
+        for range in removed.as_ref() {
+            self.inner.changes.send(StoreChange::HeadersRemoved {
+                range: range.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Find blocks of `blockstore` which are referenced only by sampling metadata of
+    /// heights that aren't stored anymore.
+    pub async fn check_blockstore<B>(&self, blockstore: &B) -> Result<Vec<IntegrityIssue>>
+    where
+        B: Blockstore,
+    {
+        let mut issues = Vec::new();
+
+        for (_, cids) in self.get_orphaned_metadata().await? {
+            for cid in cids {
+                if blockstore.has(&cid).await.map_err(blockstore_error)? {
+                    issues.push(IntegrityIssue::OrphanedBlock { cid });
+                }
+            }
+        }
+
+        Ok(issues)
+    }
+
+    /// Remove blocks found by [`RedbStore::check_blockstore`] from `blockstore`,
+    /// together with the sampling metadata referencing them.
+    pub async fn remove_orphaned_blocks<B>(&self, blockstore: &B) -> Result<()>
+    where
+        B: Blockstore,
+    {
+        for (height, cids) in self.get_orphaned_metadata().await? {
+            for cid in &cids {
+                blockstore.remove(cid).await.map_err(blockstore_error)?;
+            }
+
+            // Metadata is removed only after its blocks, so that they are not leaked on crash
+            self.write_tx(move |tx, _| {
+                let mut sampling_metadata_table = tx.open_table(SAMPLING_METADATA_TABLE)?;
+                sampling_metadata_table.remove(height)?;
+                Ok(())
+            })
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the CIDs of sampling metadata whose headers aren't stored.
+    async fn get_orphaned_metadata(&self) -> Result<Vec<(u64, Vec<Cid>)>> {
+        self.read_tx(|tx, codec| {
+            let headers_table = tx.open_table(HEADERS_TABLE)?;
+            let sampling_metadata_table = tx.open_table(SAMPLING_METADATA_TABLE)?;
+            let ranges_table = tx.open_table(RANGES_TABLE)?;
+
+            let header_ranges = get_ranges(&ranges_table, HEADER_RANGES_KEY)?;
+            let mut orphaned = Vec::new();
+
+            for entry in sampling_metadata_table.iter()? {
+                let (height, value) = entry?;
+                let height = height.value();
+
+                if header_ranges.contains(height) && headers_table.get(height)?.is_some() {
+                    continue;
+                }
+
+                let cids = match codec.decode_sampling_metadata(height, value.value()) {
+                    Ok(metadata) => metadata.cids,
+                    Err(e) => {
+                        warn!("Invalid sampling metadata {height}: {e}");
+                        Vec::new()
+                    }
+                };
+
+                orphaned.push((height, cids));
+            }
+
+            Ok(orphaned)
+        })
+        .await
+    }
+
+    /// Returns the number of stored headers and CIDs, together with the size of every table.
+    async fn stats(&self) -> Result<StoreStats> {
+        self.read_tx(|tx, codec| {
+            let headers_table = tx.open_table(HEADERS_TABLE)?;
+            let sampling_metadata_table = tx.open_table(SAMPLING_METADATA_TABLE)?;
+
+            let mut tables = Vec::new();
+            let mut fragmented_bytes = 0;
+
+            for handle in tx.list_tables()? {
+                let name = handle.name().to_owned();
+                let table = tx.open_untyped_table(handle)?;
+                let stats = table.stats()?;
+
+                fragmented_bytes += stats.fragmented_bytes();
+                tables.push(TableStats {
+                    name,
+                    entries: table.len()?,
+                    bytes: Some(
+                        stats.stored_bytes() + stats.metadata_bytes() + stats.fragmented_bytes(),
+                    ),
+                });
+            }
+
+            Ok(StoreStats {
+                headers: headers_table.len()?,
+                cids: get_referenced_cids(&sampling_metadata_table, codec)?.len() as u64,
+                tables,
+                fragmented_bytes: Some(fragmented_bytes),
+            })
+        })
+        .await
+    }
+
+    /// Compact the database.
+    ///
+    /// The database must not be used by anything else, e.g. a [`RedbBlockstore`] created
+    /// from [`RedbStore::raw_db`] must be closed first.
+    ///
+    /// [`RedbBlockstore`]: crate::blockstore::RedbBlockstore
+    pub async fn compact(&mut self) -> Result<bool> {
+        // Wait all ongoing `spawn_blocking` tasks to finish.
+        self.task_counter.wait_guards().await;
+
+        let inner = Arc::get_mut(&mut self.inner)
+            .filter(|inner| Arc::strong_count(&inner.db) == 1)
+            .ok_or_else(|| {
+                StoreError::CompactionFailed("Database is used by other components".to_string())
+            })?;
+
+        // Compaction is blocking, so the database is moved to a blocking task and an empty
+        // one takes its place until it's done.
+        let placeholder = Database::builder()
+            .create_with_backend(redb::backends::InMemoryBackend::new())
+            .map_err(|e| StoreError::CompactionFailed(e.to_string()))?;
+        let mut db = std::mem::replace(&mut inner.db, Arc::new(placeholder));
+
+        let (db, res) = spawn_blocking(move || {
+            let res = Arc::get_mut(&mut db)
+                .expect("checked above")
+                .compact()
+                .map_err(|e| StoreError::CompactionFailed(e.to_string()));
+            (db, res)
+        })
+        .await?;
+
+        inner.db = db;
+        res
+    }
+
+    /// Check the tables for entries not reachable from the stored ranges.
+    async fn check_tables(&self, stored_ranges: BlockRanges) -> Result<Vec<IntegrityIssue>> {
+        self.read_tx(move |tx, codec| {
+            let heights_table = tx.open_table(HEIGHTS_TABLE)?;
+            let headers_table = tx.open_table(HEADERS_TABLE)?;
+
+            let mut issues = Vec::new();
+
+            for entry in headers_table.iter()? {
+                let height = entry?.0.value();
+                if !stored_ranges.contains(height) {
+                    issues.push(IntegrityIssue::UntrackedHeader { height });
+                }
+            }
+
+            for entry in heights_table.iter()? {
+                let (hash, height) = entry?;
+                let height = height.value();
+                if !is_indexed_header(&headers_table, codec, &stored_ranges, hash.value(), height)?
+                {
+                    issues.push(IntegrityIssue::DanglingHashIndex { height });
+                }
+            }
+
+            Ok(issues)
         })
         .await
     }
@@ -504,6 +950,10 @@ impl Store for RedbStore {
         }
     }
 
+    fn subscribe_changes(&self) -> StoreChanges {
+        self.inner.changes.subscribe()
+    }
+
     async fn head_height(&self) -> Result<u64> {
         self.head_height().await
     }
@@ -530,7 +980,12 @@ impl Store for RedbStore {
         status: SamplingStatus,
         cids: Vec<Cid>,
     ) -> Result<()> {
-        self.update_sampling_metadata(height, status, cids).await
+        self.update_sampling_metadata(height, Some(status), cids)
+            .await
+    }
+
+    async fn add_sampling_cids(&self, height: u64, cids: Vec<Cid>) -> Result<()> {
+        self.update_sampling_metadata(height, None, cids).await
     }
 
     async fn get_sampling_metadata(&self, height: u64) -> Result<Option<SamplingMetadata>> {
@@ -549,6 +1004,41 @@ impl Store for RedbStore {
         self.remove_last().await
     }
 
+    async fn get_known_peers(&self) -> Result<Vec<KnownPeer>> {
+        self.get_known_peers().await
+    }
+
+    async fn set_known_peers(&self, peers: Vec<KnownPeer>) -> Result<()> {
+        self.set_known_peers(peers).await
+    }
+
+    async fn get_blocked_peers(&self) -> Result<Vec<PeerId>> {
+        self.get_blocked_peers().await
+    }
+
+    async fn set_blocked_peers(&self, peers: Vec<PeerId>) -> Result<()> {
+        self.set_blocked_peers(peers).await
+    }
+
+    async fn check_integrity(&self) -> Result<IntegrityReport> {
+        let mut report = check_stored_headers(self).await?;
+        let issues = self.check_tables(report.stored_ranges.clone()).await?;
+        report.issues.extend(issues);
+        Ok(report)
+    }
+
+    async fn stats(&self) -> Result<StoreStats> {
+        self.stats().await
+    }
+
+    async fn compact(&mut self) -> Result<bool> {
+        self.compact().await
+    }
+
+    fn migration_report(&self) -> Option<&MigrationReport> {
+        Some(&self.migration_report)
+    }
+
     async fn close(mut self) -> Result<()> {
         // Wait all ongoing `spawn_blocking` tasks to finish.
         self.task_counter.wait_guards().await;
@@ -558,6 +1048,7 @@ impl Store for RedbStore {
 
 fn verify_against_neighbours<R>(
     headers_table: &R,
+    codec: &ValueCodec,
     lowest_header: Option<&ExtendedHeader>,
     highest_header: Option<&ExtendedHeader>,
 ) -> Result<()>
@@ -565,26 +1056,32 @@ where
     R: ReadableTable<u64, &'static [u8]>,
 {
     if let Some(lowest_header) = lowest_header {
-        let prev = get_header(headers_table, lowest_header.height().value() - 1).map_err(|e| {
-            if let StoreError::NotFound = e {
-                StoreError::StoredDataError("inconsistency between headers and ranges table".into())
-            } else {
-                e
-            }
-        })?;
+        let prev =
+            get_header(headers_table, codec, lowest_header.height().value() - 1).map_err(|e| {
+                if let StoreError::NotFound = e {
+                    StoreError::StoredDataError(
+                        "inconsistency between headers and ranges table".into(),
+                    )
+                } else {
+                    e
+                }
+            })?;
 
         prev.verify(lowest_header)
             .map_err(|e| StoreInsertionError::NeighborsVerificationFailed(e.to_string()))?;
     }
 
     if let Some(highest_header) = highest_header {
-        let next = get_header(headers_table, highest_header.height().value() + 1).map_err(|e| {
-            if let StoreError::NotFound = e {
-                StoreError::StoredDataError("inconsistency between headers and ranges table".into())
-            } else {
-                e
-            }
-        })?;
+        let next =
+            get_header(headers_table, codec, highest_header.height().value() + 1).map_err(|e| {
+                if let StoreError::NotFound = e {
+                    StoreError::StoredDataError(
+                        "inconsistency between headers and ranges table".into(),
+                    )
+                } else {
+                    e
+                }
+            })?;
 
         highest_header
             .verify(&next)
@@ -594,6 +1091,53 @@ where
     Ok(())
 }
 
+/// Returns `true` if the hash index entry points to a stored header with that hash.
+fn is_indexed_header<R>(
+    headers_table: &R,
+    codec: &ValueCodec,
+    stored_ranges: &BlockRanges,
+    hash: &[u8],
+    height: u64,
+) -> Result<bool>
+where
+    R: ReadableTable<u64, &'static [u8]>,
+{
+    if !stored_ranges.contains(height) {
+        return Ok(false);
+    }
+
+    let Some(raw_header) = headers_table.get(height)? else {
+        return Ok(false);
+    };
+
+    Ok(codec
+        .decode_header(height, raw_header.value())
+        .is_ok_and(|header| header.hash().as_bytes() == hash))
+}
+
+fn blockstore_error(e: blockstore::Error) -> StoreError {
+    StoreError::FatalDatabaseError(format!("Blockstore error: {e}"))
+}
+
+/// Returns CIDs of all the sampling metadata, in their binary form.
+fn get_referenced_cids<R>(
+    sampling_metadata_table: &R,
+    codec: &ValueCodec,
+) -> Result<HashSet<Vec<u8>>>
+where
+    R: ReadableTable<u64, &'static [u8]>,
+{
+    let mut cids = HashSet::new();
+
+    for entry in sampling_metadata_table.iter()? {
+        let (height, value) = entry?;
+        let metadata = codec.decode_sampling_metadata(height.value(), value.value())?;
+        cids.extend(metadata.cids.iter().map(Cid::to_bytes));
+    }
+
+    Ok(cids)
+}
+
 fn get_ranges<R>(ranges_table: &R, name: &str) -> Result<BlockRanges>
 where
     R: ReadableTable<&'static str, Vec<(u64, u64)>>,
@@ -643,17 +1187,18 @@ where
 }
 
 #[inline]
-fn get_header<R>(headers_table: &R, key: u64) -> Result<ExtendedHeader>
+fn get_header<R>(headers_table: &R, codec: &ValueCodec, key: u64) -> Result<ExtendedHeader>
 where
     R: ReadableTable<u64, &'static [u8]>,
 {
     let serialized = headers_table.get(key)?.ok_or(StoreError::NotFound)?;
-    deserialize_extended_header(serialized.value())
+    codec.decode_header(key, serialized.value())
 }
 
 #[inline]
 fn get_sampling_metadata<R>(
     sampling_metadata_table: &R,
+    codec: &ValueCodec,
     key: u64,
 ) -> Result<Option<SamplingMetadata>>
 where
@@ -661,7 +1206,7 @@ where
 {
     sampling_metadata_table
         .get(key)?
-        .map(|guard| deserialize_sampling_metadata(guard.value()))
+        .map(|guard| codec.decode_sampling_metadata(key, guard.value()))
         .transpose()
 }
 
@@ -708,23 +1253,231 @@ impl From<CommitError> for StoreError {
     }
 }
 
-fn migrate_v1_to_v2(
-    tx: &WriteTransaction,
-    schema_version_table: &mut Table<(), u64>,
-) -> Result<()> {
-    const HEADER_HEIGHT_RANGES: TableDefinition<'static, u64, (u64, u64)> =
-        TableDefinition::new("STORE.HEIGHT_RANGES");
+async fn open_database(path: PathBuf) -> Result<Arc<Database>> {
+    let db = spawn_blocking(|| Database::create(path))
+        .await?
+        .map_err(|e| StoreError::OpenFailed(e.to_string()))?;
+
+    Ok(Arc::new(db))
+}
+
+/// Run pending schema migrations and create missing tables.
+///
+/// On `dry_run` any pending migrations are rolled back.
+async fn migrate(db: Arc<Database>, dry_run: bool) -> Result<MigrationReport> {
+    spawn_blocking(move || {
+        let tx = db.begin_write()?;
+
+        match run_migrations(&tx) {
+            Ok(report) if dry_run && !report.is_empty() => {
+                tx.abort()?;
+                Ok(report)
+            }
+            Ok(report) => {
+                tx.commit()?;
+                Ok(report)
+            }
+            Err(e) => {
+                tx.abort()?;
+                Err(e)
+            }
+        }
+    })
+    .await?
+    .map_err(|e| match e {
+        e @ StoreError::OpenFailed(_) => e,
+        e => StoreError::OpenFailed(e.to_string()),
+    })
+}
 
+fn run_migrations(tx: &WriteTransaction) -> Result<MigrationReport> {
+    let mut schema_version_table = tx.open_table(SCHEMA_VERSION_TABLE)?;
     let schema_version = schema_version_table.get(())?.map(|guard| guard.value());
 
-    // We only migrate from v1
-    if schema_version != Some(1) {
-        return Ok(());
+    let mut steps = Vec::new();
+
+    for migration in pending_migrations(MIGRATIONS, schema_version, SCHEMA_VERSION)? {
+        let step = migration.step();
+        warn!("Migrating DB schema {step}");
+
+        (migration.run)(tx)?;
+        schema_version_table.insert((), step.to_version)?;
+        steps.push(step);
+    }
+
+    if schema_version.is_none() {
+        // New database
+        schema_version_table.insert((), SCHEMA_VERSION)?;
+    }
+
+    debug_assert_eq!(
+        schema_version_table.get(())?.map(|guard| guard.value()),
+        Some(SCHEMA_VERSION),
+        "Some migrations are missing"
+    );
+
+    // create tables, so that reads later don't complain
+    let _heights_table = tx.open_table(HEIGHTS_TABLE)?;
+    let _headers_table = tx.open_table(HEADERS_TABLE)?;
+    let _ranges_table = tx.open_table(RANGES_TABLE)?;
+    let _sampling_table = tx.open_table(SAMPLING_METADATA_TABLE)?;
+    let _peers_table = tx.open_table(PEERS_TABLE)?;
+
+    Ok(MigrationReport {
+        from_version: schema_version,
+        to_version: SCHEMA_VERSION,
+        steps,
+        backup: None,
+    })
+}
+
+/// Check the key of the `codec` against the database, re-encoding the stored values
+/// if the store is being encrypted or it was encrypted with a previous key.
+async fn init_codec(db: Arc<Database>, codec: ValueCodec) -> Result<ValueCodec> {
+    spawn_blocking(move || {
+        let key_check = {
+            let tx = db.begin_read()?;
+
+            match tx.open_table(ENCRYPTION_TABLE) {
+                Ok(table) => table
+                    .get(KEY_CHECK_KEY)?
+                    .map(|guard| guard.value().to_vec()),
+                Err(TableError::TableDoesNotExist(_)) => None,
+                Err(e) => return Err(e.into()),
+            }
+        };
+
+        let target = codec.current();
+        let Some(source) = codec.check_key(key_check.as_deref())? else {
+            return Ok(target);
+        };
+
+        // Values written in plain would be left in the free pages of the database
+        if !source.is_encrypted() && holds_data(&db.begin_read()?)? {
+            return Err(StoreError::OpenFailed(
+                "Encryption can be enabled only on an empty store".into(),
+            ));
+        }
+
+        warn!("Encrypting the store with a new key");
+
+        let tx = db.begin_write()?;
+
+        if let Err(e) = reencode_values(&tx, &source, &target) {
+            tx.abort()?;
+            return Err(e);
+        }
+
+        tx.commit()?;
+
+        Ok(target)
+    })
+    .await?
+}
+
+/// Re-encode all the headers, sampling metadata and known peers from the `source`
+/// to the `target` codec.
+fn reencode_values(tx: &WriteTransaction, source: &ValueCodec, target: &ValueCodec) -> Result<()> {
+    let mut headers_table = tx.open_table(HEADERS_TABLE)?;
+    let mut sampling_metadata_table = tx.open_table(SAMPLING_METADATA_TABLE)?;
+    let mut peers_table = tx.open_table(PEERS_TABLE)?;
+    let mut encryption_table = tx.open_table(ENCRYPTION_TABLE)?;
+
+    for height in table_keys(&headers_table)? {
+        let Some(value) = headers_table
+            .get(height)?
+            .map(|guard| guard.value().to_vec())
+        else {
+            continue;
+        };
+        let value = source.reencode_header(target, height, &value)?;
+        headers_table.insert(height, &value[..])?;
+    }
+
+    for height in table_keys(&sampling_metadata_table)? {
+        let Some(value) = sampling_metadata_table
+            .get(height)?
+            .map(|guard| guard.value().to_vec())
+        else {
+            continue;
+        };
+        let value = source.reencode_sampling_metadata(target, height, &value)?;
+        sampling_metadata_table.insert(height, &value[..])?;
+    }
+
+    let known_peers = peers_table
+        .get(KNOWN_PEERS_KEY)?
+        .map(|guard| guard.value().to_vec());
+
+    if let Some(value) = known_peers {
+        let value = source.reencode_known_peers(target, &value)?;
+        peers_table.insert(KNOWN_PEERS_KEY, &value[..])?;
+    }
+
+    let blocked_peers = peers_table
+        .get(BLOCKED_PEERS_KEY)?
+        .map(|guard| guard.value().to_vec());
+
+    if let Some(value) = blocked_peers {
+        let value = source.reencode_blocked_peers(target, &value)?;
+        peers_table.insert(BLOCKED_PEERS_KEY, &value[..])?;
+    }
+
+    match target.key_check() {
+        Some(key_check) => encryption_table.insert(KEY_CHECK_KEY, &key_check[..])?,
+        None => encryption_table.remove(KEY_CHECK_KEY)?,
+    };
+
+    Ok(())
+}
+
+/// Returns whether any table of the database, e.g. of the store or of a blockstore sharing
+/// the database, holds any data.
+fn holds_data(tx: &ReadTransaction) -> Result<bool> {
+    for handle in tx.list_tables()? {
+        let name = handle.name().to_owned();
+
+        // Tables of the schema and encryption metadata, which hold no stored data
+        if name == SCHEMA_VERSION_TABLE.name() || name == ENCRYPTION_TABLE.name() {
+            continue;
+        }
+
+        // Ranges are written even if they're empty
+        if name == RANGES_TABLE.name() {
+            let table = tx.open_table(RANGES_TABLE)?;
+            for entry in table.iter()? {
+                if !entry?.1.value().is_empty() {
+                    return Ok(true);
+                }
+            }
+            continue;
+        }
+
+        if tx.open_untyped_table(handle)?.len()? > 0 {
+            return Ok(true);
+        }
     }
 
-    warn!("Migrating DB schema from v1 to v2");
+    Ok(false)
+}
+
+fn table_keys<R>(table: &R) -> Result<Vec<u64>>
+where
+    R: ReadableTable<u64, &'static [u8]>,
+{
+    table.iter()?.map(|entry| Ok(entry?.0.value())).collect()
+}
+
+fn read_codec(codec: &RwLock<ValueCodec>) -> Result<RwLockReadGuard<'_, ValueCodec>> {
+    codec.read().map_err(|_| codec_lock_poisoned())
+}
+
+fn codec_lock_poisoned() -> StoreError {
+    StoreError::FatalDatabaseError("Value codec lock poisoned".into())
+}
 
-    let header_ranges_table = tx.open_table(HEADER_HEIGHT_RANGES)?;
+fn migrate_v1_to_v2(tx: &WriteTransaction) -> Result<()> {
+    let header_ranges_table = tx.open_table(V1_HEADER_HEIGHT_RANGES)?;
     let mut ranges_table = tx.open_table(RANGES_TABLE)?;
 
     let raw_ranges = header_ranges_table
@@ -738,19 +1491,24 @@ fn migrate_v1_to_v2(
     tx.delete_table(header_ranges_table)?;
     ranges_table.insert(HEADER_RANGES_KEY, raw_ranges)?;
 
-    // Migrated to v2
-    schema_version_table.insert((), 2)?;
-
     Ok(())
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use crate::test_utils::ExtendedHeaderGeneratorExt;
+    use crate::blockstore::RedbBlockstore;
+    use crate::encryption::EncryptionKey;
+    use crate::test_utils::{new_block_ranges, ExtendedHeaderGeneratorExt};
+    use blockstore::Blockstore;
     use celestia_types::test_utils::ExtendedHeaderGenerator;
+    use cid::multihash::Multihash;
+    use futures::StreamExt;
     use std::path::Path;
+    use std::time::Duration;
     use tempfile::TempDir;
+    use tendermint_proto::Protobuf;
+    use web_time::SystemTime;
 
     #[tokio::test]
     async fn test_store_persistence() {
@@ -830,6 +1588,399 @@ pub mod tests {
         assert_eq!(store1.head_height().await.unwrap(), 16);
     }
 
+    #[tokio::test]
+    async fn test_migrate_v1_with_backup() {
+        let db_dir = TempDir::with_prefix("lumina.store.test").unwrap();
+        let db = db_dir.path().join("db");
+
+        let (store, _) = gen_filled_store(10, Some(&db)).await;
+        downgrade_to_v1(&store).await;
+        drop(store);
+
+        let store = RedbStore::open_with_migrations(&db, MigrationMode::Backup)
+            .await
+            .unwrap();
+
+        let report = store.migration_report().unwrap();
+        assert_eq!(report.from_version, Some(1));
+        assert_eq!(report.to_version, SCHEMA_VERSION);
+        assert_eq!(report.steps.len(), 1);
+        assert_eq!(report.steps[0].from_version, 1);
+        assert_eq!(report.steps[0].to_version, 2);
+
+        let backup = db_dir.path().join("db.v1.backup");
+        assert_eq!(report.backup.as_deref(), Some(&*backup.to_string_lossy()));
+        assert!(backup.exists());
+
+        assert_eq!(store.head_height().await.unwrap(), 10);
+        assert_eq!(
+            store.get_stored_header_ranges().await.unwrap(),
+            new_block_ranges([1..=10])
+        );
+        drop(store);
+
+        // Backup is still in the old schema
+        let backup_db = Database::open(&backup).unwrap();
+        assert_eq!(schema_version(&backup_db), Some(1));
+        drop(backup_db);
+
+        // Migrated database needs no migrations
+        let store = RedbStore::open_with_migrations(&db, MigrationMode::Backup)
+            .await
+            .unwrap();
+        let report = store.migration_report().unwrap();
+        assert!(report.is_empty());
+        assert!(report.backup.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_migrations_dry_run() {
+        let (store, _) = gen_filled_store(10, None).await;
+        downgrade_to_v1(&store).await;
+        let db = store.raw_db();
+        drop(store);
+
+        let res = RedbStore::new_with_migrations(db.clone(), MigrationMode::DryRun).await;
+        assert!(matches!(res, Err(StoreError::OpenFailed(_))));
+        assert_eq!(schema_version(&db), Some(1));
+
+        let store = RedbStore::new(db.clone()).await.unwrap();
+        assert_eq!(schema_version(&db), Some(SCHEMA_VERSION));
+        assert_eq!(store.head_height().await.unwrap(), 10);
+        drop(store);
+
+        let store = RedbStore::new_with_migrations(db, MigrationMode::DryRun)
+            .await
+            .unwrap();
+        assert!(store.migration_report().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_refuse_newer_schema() {
+        let store = create_store(None).await;
+        let db = store.raw_db();
+        drop(store);
+
+        let tx = db.begin_write().unwrap();
+        tx.open_table(SCHEMA_VERSION_TABLE)
+            .unwrap()
+            .insert((), SCHEMA_VERSION + 1)
+            .unwrap();
+        tx.commit().unwrap();
+
+        let res = RedbStore::new(db.clone()).await;
+        assert!(matches!(res, Err(StoreError::OpenFailed(_))));
+        assert_eq!(schema_version(&db), Some(SCHEMA_VERSION + 1));
+    }
+
+    #[tokio::test]
+    async fn test_check_integrity_and_truncate() {
+        let (store, _) = gen_filled_store(20, None).await;
+        let blockstore = RedbBlockstore::new(store.raw_db());
+
+        let sampled_cid = test_cid(1);
+        let truncated_cid = test_cid(2);
+
+        for cid in [sampled_cid, truncated_cid] {
+            blockstore.put_keyed(&cid, b"data").await.unwrap();
+        }
+        store
+            .update_sampling_metadata(5, Some(SamplingStatus::Accepted), vec![sampled_cid])
+            .await
+            .unwrap();
+        store
+            .update_sampling_metadata(15, Some(SamplingStatus::Accepted), vec![truncated_cid])
+            .await
+            .unwrap();
+
+        let report = store.check_integrity().await.unwrap();
+        assert_eq!(report.stored_ranges, new_block_ranges([1..=20]));
+        assert!(report.is_consistent(), "{:?}", report.issues);
+        assert_eq!(report.last_consistent_height, Some(20));
+
+        store
+            .write_tx(|tx, _| {
+                let mut headers_table = tx.open_table(HEADERS_TABLE)?;
+                let mut heights_table = tx.open_table(HEIGHTS_TABLE)?;
+
+                let raw_header = headers_table.remove(12)?.unwrap().value().to_vec();
+                headers_table.insert(30, &raw_header[..])?;
+                heights_table.insert(&[0; 32][..], 3)?;
+
+                Ok(())
+            })
+            .await
+            .unwrap();
+
+        let report = store.check_integrity().await.unwrap();
+        assert_eq!(report.last_consistent_height, Some(11));
+        assert!(report
+            .issues
+            .contains(&IntegrityIssue::MissingHeader { height: 12 }));
+        assert!(report
+            .issues
+            .contains(&IntegrityIssue::UntrackedHeader { height: 30 }));
+        assert!(report
+            .issues
+            .contains(&IntegrityIssue::DanglingHashIndex { height: 3 }));
+        assert!(store
+            .check_blockstore(&blockstore)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let mut changes = store.subscribe_changes();
+        store.truncate(11).await.unwrap();
+        assert_eq!(
+            changes.next().await,
+            Some(StoreChange::HeadersRemoved { range: 12..=20 })
+        );
+
+        let report = store.check_integrity().await.unwrap();
+        assert!(report.is_consistent(), "{:?}", report.issues);
+        assert_eq!(report.stored_ranges, new_block_ranges([1..=11]));
+        assert_eq!(
+            store.get_accepted_sampling_ranges().await.unwrap(),
+            new_block_ranges([5..=5])
+        );
+        assert!(matches!(
+            store.get_sampling_metadata(15).await,
+            Err(StoreError::NotFound)
+        ));
+
+        assert_eq!(
+            store.check_blockstore(&blockstore).await.unwrap(),
+            vec![IntegrityIssue::OrphanedBlock { cid: truncated_cid }]
+        );
+
+        store.remove_orphaned_blocks(&blockstore).await.unwrap();
+        assert!(store
+            .check_blockstore(&blockstore)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(blockstore.has(&sampled_cid).await.unwrap());
+        assert!(!blockstore.has(&truncated_cid).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_compact() {
+        let (mut store, _) = gen_filled_store(100, None).await;
+        let db = store.raw_db();
+
+        let res = store.compact().await;
+        assert!(matches!(res, Err(StoreError::CompactionFailed(_))));
+        drop(db);
+
+        for _ in 0..90 {
+            store.remove_last().await.unwrap();
+        }
+
+        let stats = store.stats().await.unwrap();
+        assert_eq!(stats.headers, 10);
+        assert!(stats.fragmented_bytes.unwrap() > 0);
+
+        assert!(store.compact().await.unwrap());
+
+        let stats = store.stats().await.unwrap();
+        assert_eq!(stats.headers, 10);
+        assert_eq!(store.head_height().await.unwrap(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_store() {
+        let key = EncryptionKey::generate();
+        let db = RedbStore::in_memory().await.unwrap().raw_db();
+        let store = RedbStore::new_encrypted(db.clone(), &key).await.unwrap();
+        let headers = ExtendedHeaderGenerator::new().next_many(10);
+        let cid = test_cid(1);
+
+        store.insert(&headers[..]).await.unwrap();
+        store
+            .update_sampling_metadata(5, Some(SamplingStatus::Accepted), vec![cid])
+            .await
+            .unwrap();
+
+        assert_eq!(store.get_by_height(5).await.unwrap(), headers[4]);
+        assert_eq!(
+            store.get_by_hash(&headers[6].hash()).await.unwrap(),
+            headers[6]
+        );
+        assert_eq!(store.get_head().await.unwrap(), headers[9]);
+        assert_eq!(
+            store.get_sampling_metadata(5).await.unwrap().unwrap().cids,
+            vec![cid]
+        );
+        assert_eq!(
+            store.get_stored_header_ranges().await.unwrap(),
+            new_block_ranges([1..=10])
+        );
+        assert!(store.check_integrity().await.unwrap().is_consistent());
+
+        let raw_header = raw_header(&store, 5).await;
+        assert_ne!(raw_header, headers[4].clone().encode_vec());
+        assert!(ExtendedHeader::decode(&raw_header[..]).is_err());
+        drop(store);
+
+        let e = RedbStore::new_encrypted(db.clone(), &EncryptionKey::generate())
+            .await
+            .unwrap_err();
+        assert!(matches!(e, StoreError::OpenFailed(_)));
+        let e = RedbStore::new(db.clone()).await.unwrap_err();
+        assert!(matches!(e, StoreError::OpenFailed(_)));
+
+        let store = RedbStore::new_encrypted(db, &key).await.unwrap();
+        assert_eq!(store.get_by_height(5).await.unwrap(), headers[4]);
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_non_empty_store() {
+        let (store, _) = gen_filled_store(10, None).await;
+        let db = store.raw_db();
+        drop(store);
+
+        let e = RedbStore::new_encrypted(db, &EncryptionKey::generate())
+            .await
+            .unwrap_err();
+        assert!(matches!(e, StoreError::OpenFailed(_)));
+
+        // blocks of a blockstore sharing the database are stored data too
+        let db = RedbStore::in_memory().await.unwrap().raw_db();
+        let blockstore = RedbBlockstore::new(db.clone());
+        blockstore.put_keyed(&test_cid(1), b"block").await.unwrap();
+
+        let e = RedbStore::new_encrypted(db, &EncryptionKey::generate())
+            .await
+            .unwrap_err();
+        assert!(matches!(e, StoreError::OpenFailed(_)));
+
+        // store created before, which is still empty, can be encrypted
+        let db = RedbStore::in_memory().await.unwrap().raw_db();
+        RedbStore::new_encrypted(db, &EncryptionKey::generate())
+            .await
+            .unwrap();
+
+        let e = RedbStore::in_memory()
+            .await
+            .unwrap()
+            .rotate_key(&EncryptionKey::generate())
+            .await
+            .unwrap_err();
+        assert!(matches!(e, StoreError::NotEncrypted));
+    }
+
+    #[tokio::test]
+    async fn test_encryption_key_rotation() {
+        let old_key = EncryptionKey::generate();
+        let new_key = EncryptionKey::generate();
+        let db = RedbStore::in_memory().await.unwrap().raw_db();
+        let store = RedbStore::new_encrypted(db.clone(), &old_key)
+            .await
+            .unwrap();
+        let headers = ExtendedHeaderGenerator::new().next_many(10);
+
+        store.insert(&headers[..]).await.unwrap();
+        store
+            .update_sampling_metadata(2, Some(SamplingStatus::Accepted), vec![])
+            .await
+            .unwrap();
+        let known_peers = vec![KnownPeer {
+            peer_id: PeerId::random(),
+            addrs: vec!["/ip4/1.2.3.4/tcp/2121".parse().unwrap()],
+            last_seen: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            trusted: false,
+            score: 1.0,
+        }];
+        store.set_known_peers(known_peers.clone()).await.unwrap();
+        let blocked_peers = vec![PeerId::random()];
+        store
+            .set_blocked_peers(blocked_peers.clone())
+            .await
+            .unwrap();
+
+        store.rotate_key(&new_key).await.unwrap();
+        assert_eq!(store.get_by_height(2).await.unwrap(), headers[1]);
+        drop(store);
+
+        RedbStore::new_encrypted(db.clone(), &old_key)
+            .await
+            .unwrap_err();
+        let store = RedbStore::new_encrypted(db.clone(), &new_key)
+            .await
+            .unwrap();
+        assert_eq!(store.get_by_height(2).await.unwrap(), headers[1]);
+        drop(store);
+
+        // rotate the key back, as if it was changed in the configuration
+        let store = RedbStore::new_encrypted_with_previous_key(db.clone(), &old_key, &new_key)
+            .await
+            .unwrap();
+        assert_eq!(store.get_by_height(2).await.unwrap(), headers[1]);
+        assert_eq!(
+            store
+                .get_sampling_metadata(2)
+                .await
+                .unwrap()
+                .unwrap()
+                .status,
+            SamplingStatus::Accepted
+        );
+        assert_eq!(store.get_known_peers().await.unwrap(), known_peers);
+        assert_eq!(store.get_blocked_peers().await.unwrap(), blocked_peers);
+        drop(store);
+
+        // only the new key is accepted once the store is re-encrypted
+        RedbStore::new_encrypted(db.clone(), &new_key)
+            .await
+            .unwrap_err();
+        let store = RedbStore::new_encrypted_with_previous_key(db, &old_key, &new_key)
+            .await
+            .unwrap();
+        assert_eq!(store.get_by_height(10).await.unwrap(), headers[9]);
+    }
+
+    async fn raw_header(store: &RedbStore, height: u64) -> Vec<u8> {
+        store
+            .read_tx(move |tx, _| {
+                let headers_table = tx.open_table(HEADERS_TABLE)?;
+                Ok(headers_table.get(height)?.unwrap().value().to_vec())
+            })
+            .await
+            .unwrap()
+    }
+
+    fn test_cid(n: u8) -> Cid {
+        let mh = Multihash::wrap(0x12, &[n; 32]).unwrap();
+        Cid::new_v1(0x55, mh)
+    }
+
+    /// Rewrite header ranges of the store in the v1 schema.
+    async fn downgrade_to_v1(store: &RedbStore) {
+        store
+            .write_tx(|tx, _| {
+                let mut ranges_table = tx.open_table(RANGES_TABLE)?;
+                let ranges = get_ranges(&ranges_table, HEADER_RANGES_KEY)?;
+                ranges_table.remove(HEADER_RANGES_KEY)?;
+
+                let mut v1_ranges_table = tx.open_table(V1_HEADER_HEIGHT_RANGES)?;
+                for (idx, range) in ranges.into_inner().into_iter().enumerate() {
+                    v1_ranges_table.insert(idx as u64, (*range.start(), *range.end()))?;
+                }
+
+                tx.open_table(SCHEMA_VERSION_TABLE)?.insert((), 1)?;
+
+                Ok(())
+            })
+            .await
+            .unwrap();
+    }
+
+    fn schema_version(db: &Database) -> Option<u64> {
+        let tx = db.begin_read().unwrap();
+        let table = tx.open_table(SCHEMA_VERSION_TABLE).unwrap();
+        table.get(()).unwrap().map(|guard| guard.value())
+    }
+
     pub async fn create_store(path: Option<&Path>) -> RedbStore {
         match path {
             Some(path) => RedbStore::open(path).await.unwrap(),
@@ -0,0 +1,576 @@
+//! Portable snapshots of the [`Store`] contents.
+//!
+//! Snapshot holds the stored headers together with their [`SamplingMetadata`], so that
+//! a new node can be pre-seeded from a file instead of syncing all the headers over
+//! `header-ex`. Format is the same for every [`Store`] implementation:
+//!
+//! - magic bytes followed by a big-endian `u32` format version
+//! - info record with the stored and accepted sampling [`BlockRanges`]
+//! - one record per header in ascending height order, with its sampling metadata
+//!
+//! Every record is a protobuf message prefixed with its big-endian `u32` length.
+//!
+//! [`SamplingMetadata`]: crate::store::SamplingMetadata
+
+use std::{io, slice};
+
+use celestia_types::ExtendedHeader;
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use prost::Message;
+use tendermint_proto::Protobuf;
+use thiserror::Error;
+
+use crate::block_ranges::{BlockRange, BlockRanges};
+use crate::store::utils::{validate_headers, VerifiedExtendedHeaders};
+use crate::store::{RawSamplingMetadata, SamplingMetadata, Store, StoreError};
+
+/// Magic bytes at the beginning of every snapshot.
+const SNAPSHOT_MAGIC: &[u8; 8] = b"LUMINASS";
+/// Version of the snapshot format written by [`export_snapshot`].
+const SNAPSHOT_VERSION: u32 = 1;
+/// Maximum size of a single record in bytes.
+const MAX_RECORD_SIZE: usize = 64 * 1024 * 1024;
+/// Number of headers read from the store or verified at once.
+const BATCH_SIZE: u64 = 512;
+
+type Result<T, E = SnapshotError> = std::result::Result<T, E>;
+
+/// Representation of all the errors that can occur when exporting or importing a snapshot.
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    /// Reading or writing the snapshot failed.
+    #[error("Snapshot I/O failed: {0}")]
+    Io(#[from] io::Error),
+
+    /// Snapshot is malformed.
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+
+    /// Snapshot was written with an unknown version of the format.
+    #[error("Unsupported snapshot version: {0}")]
+    UnsupportedVersion(u32),
+
+    /// Store holds headers which are not part of the snapshot.
+    #[error("Store holds headers which are not part of the snapshot")]
+    StoreConflict,
+
+    /// Headers in the snapshot failed validation or verification.
+    #[error("Headers in snapshot failed verification: {0}")]
+    VerificationFailed(#[from] celestia_types::Error),
+
+    /// An error propagated from the [`Store`].
+    #[error(transparent)]
+    Store(#[from] StoreError),
+}
+
+#[derive(Message)]
+struct RawBlockRange {
+    #[prost(uint64, tag = "1")]
+    start: u64,
+
+    #[prost(uint64, tag = "2")]
+    end: u64,
+}
+
+#[derive(Message)]
+struct RawSnapshotInfo {
+    #[prost(message, repeated, tag = "1")]
+    stored_ranges: Vec<RawBlockRange>,
+
+    #[prost(message, repeated, tag = "2")]
+    accepted_ranges: Vec<RawBlockRange>,
+}
+
+#[derive(Message)]
+struct RawSnapshotEntry {
+    #[prost(bytes, tag = "1")]
+    header: Vec<u8>,
+
+    #[prost(message, optional, tag = "2")]
+    sampling_metadata: Option<RawSamplingMetadata>,
+}
+
+/// Write all the headers and sampling metadata held in the store as a snapshot.
+///
+/// Returns the number of exported headers.
+pub async fn export_snapshot<S, W>(store: &S, writer: &mut W) -> Result<u64>
+where
+    S: Store,
+    W: AsyncWrite + Unpin + Send,
+{
+    let stored_ranges = store.get_stored_header_ranges().await?;
+    let accepted_ranges = store.get_accepted_sampling_ranges().await?;
+
+    writer.write_all(SNAPSHOT_MAGIC).await?;
+    writer.write_all(&SNAPSHOT_VERSION.to_be_bytes()).await?;
+
+    let info = RawSnapshotInfo {
+        stored_ranges: to_raw_ranges(&stored_ranges),
+        accepted_ranges: to_raw_ranges(&accepted_ranges),
+    };
+    write_record(writer, &info).await?;
+
+    let mut exported = 0;
+
+    for range in stored_ranges.as_ref() {
+        for batch in batches(range) {
+            for header in store.get_range(batch).await? {
+                let sampling_metadata = store
+                    .get_sampling_metadata(header.height().value())
+                    .await?
+                    .map(RawSamplingMetadata::from);
+
+                let entry = RawSnapshotEntry {
+                    header: header.encode_vec(),
+                    sampling_metadata,
+                };
+                write_record(writer, &entry).await?;
+
+                exported += 1;
+            }
+        }
+    }
+
+    writer.flush().await?;
+
+    Ok(exported)
+}
+
+/// Read a snapshot written by [`export_snapshot`] into the store.
+///
+/// Each header is validated and verified with [`ExtendedHeader::verify_adjacent_range`]
+/// against the previous header of the same range. Headers starting a range can only be
+/// validated, so a snapshot should be imported only from a trusted source.
+///
+/// Headers are inserted in batches, so a failed import can leave part of the snapshot in
+/// the store. Import can be resumed by importing the same snapshot again: headers already
+/// in the store are compared with the snapshot and skipped. Store holding any header not
+/// in the snapshot is rejected with [`SnapshotError::StoreConflict`].
+///
+/// Returns the number of imported headers.
+pub async fn import_snapshot<S, R>(store: &S, reader: &mut R) -> Result<u64>
+where
+    S: Store,
+    R: AsyncRead + Unpin + Send,
+{
+    let mut magic = [0u8; SNAPSHOT_MAGIC.len()];
+    reader.read_exact(&mut magic).await?;
+
+    if &magic != SNAPSHOT_MAGIC {
+        return Err(SnapshotError::InvalidSnapshot(
+            "missing snapshot magic bytes".to_string(),
+        ));
+    }
+
+    let mut version = [0u8; 4];
+    reader.read_exact(&mut version).await?;
+    let version = u32::from_be_bytes(version);
+
+    if version != SNAPSHOT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+
+    let info: RawSnapshotInfo = read_record(reader).await?;
+    let stored_ranges = from_raw_ranges(info.stored_ranges)?;
+    let accepted_ranges = from_raw_ranges(info.accepted_ranges)?;
+
+    if !(accepted_ranges.clone() - &stored_ranges).is_empty() {
+        return Err(SnapshotError::InvalidSnapshot(
+            "accepted ranges are not stored".to_string(),
+        ));
+    }
+
+    // Store can hold only the headers of a previous, partial import of this snapshot
+    let already_stored = store.get_stored_header_ranges().await?;
+
+    if !(already_stored.clone() - &stored_ranges).is_empty() {
+        return Err(SnapshotError::StoreConflict);
+    }
+
+    let mut imported = 0;
+
+    for range in stored_ranges.as_ref() {
+        let mut prev: Option<ExtendedHeader> = None;
+        let mut batch = Vec::with_capacity(BATCH_SIZE as usize);
+
+        for height in range.clone() {
+            let entry: RawSnapshotEntry = read_record(reader).await?;
+            let header = ExtendedHeader::decode(&entry.header[..])
+                .map_err(|e| SnapshotError::InvalidSnapshot(format!("invalid header: {e}")))?;
+
+            if header.height().value() != height {
+                return Err(SnapshotError::InvalidSnapshot(format!(
+                    "expected header of height {height}, found {}",
+                    header.height()
+                )));
+            }
+
+            let metadata = entry
+                .sampling_metadata
+                .map(SamplingMetadata::try_from)
+                .transpose()
+                .map_err(|e| {
+                    SnapshotError::InvalidSnapshot(format!("invalid sampling metadata: {e}"))
+                })?;
+
+            if already_stored.contains(height) {
+                imported += insert_batch(store, &mut prev, &mut batch).await?;
+
+                if store.get_by_height(height).await? != header {
+                    return Err(SnapshotError::StoreConflict);
+                }
+
+                if let Some(ref prev) = prev {
+                    prev.verify_adjacent_range(slice::from_ref(&header))?;
+                }
+
+                prev = Some(header);
+
+                // Metadata may be missing if the previous import failed while writing it
+                if let Some(m) = metadata {
+                    if store.get_sampling_metadata(height).await?.is_none() {
+                        store
+                            .update_sampling_metadata(height, m.status, m.cids)
+                            .await?;
+                    }
+                }
+            } else {
+                batch.push((header, metadata));
+
+                if batch.len() == BATCH_SIZE as usize {
+                    imported += insert_batch(store, &mut prev, &mut batch).await?;
+                }
+            }
+        }
+
+        imported += insert_batch(store, &mut prev, &mut batch).await?;
+    }
+
+    if store.get_accepted_sampling_ranges().await? != accepted_ranges {
+        return Err(SnapshotError::InvalidSnapshot(
+            "accepted ranges do not match sampling metadata".to_string(),
+        ));
+    }
+
+    Ok(imported)
+}
+
+/// Verify the batch of headers against `prev` and insert it with its sampling metadata.
+///
+/// Returns the number of inserted headers and leaves the batch empty.
+async fn insert_batch<S>(
+    store: &S,
+    prev: &mut Option<ExtendedHeader>,
+    batch: &mut Vec<(ExtendedHeader, Option<SamplingMetadata>)>,
+) -> Result<u64>
+where
+    S: Store,
+{
+    if batch.is_empty() {
+        return Ok(0);
+    }
+
+    let (headers, metadata): (Vec<_>, Vec<_>) = batch.drain(..).unzip();
+    let count = headers.len() as u64;
+
+    validate_headers(&headers).await?;
+
+    if let Some(prev) = prev {
+        prev.verify_adjacent_range(&headers)?;
+    }
+
+    *prev = headers.last().cloned();
+
+    let heights: Vec<_> = headers.iter().map(|h| h.height().value()).collect();
+    store
+        .insert(VerifiedExtendedHeaders::try_from(headers)?)
+        .await?;
+
+    for (height, m) in heights.into_iter().zip(metadata) {
+        if let Some(m) = m {
+            store
+                .update_sampling_metadata(height, m.status, m.cids)
+                .await?;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Split the range into ranges of at most [`BATCH_SIZE`] heights.
+fn batches(range: &BlockRange) -> impl Iterator<Item = BlockRange> {
+    let end = *range.end();
+
+    (*range.start()..=end)
+        .step_by(BATCH_SIZE as usize)
+        .map(move |start| start..=end.min(start + BATCH_SIZE - 1))
+}
+
+fn to_raw_ranges(ranges: &BlockRanges) -> Vec<RawBlockRange> {
+    ranges
+        .as_ref()
+        .iter()
+        .map(|range| RawBlockRange {
+            start: *range.start(),
+            end: *range.end(),
+        })
+        .collect()
+}
+
+fn from_raw_ranges(raw: Vec<RawBlockRange>) -> Result<BlockRanges> {
+    let ranges = raw.into_iter().map(|r| r.start..=r.end).collect();
+
+    BlockRanges::from_vec(ranges)
+        .map_err(|e| SnapshotError::InvalidSnapshot(format!("invalid ranges: {e}")))
+}
+
+async fn write_record<W, M>(writer: &mut W, msg: &M) -> Result<()>
+where
+    W: AsyncWrite + Unpin + Send,
+    M: Message,
+{
+    let buf = msg.encode_to_vec();
+    // Records are limited to MAX_RECORD_SIZE, so the length always fits
+    let len = u32::try_from(buf.len()).expect("record too large");
+
+    writer.write_all(&len.to_be_bytes()).await?;
+    writer.write_all(&buf).await?;
+
+    Ok(())
+}
+
+async fn read_record<R, M>(reader: &mut R) -> Result<M>
+where
+    R: AsyncRead + Unpin + Send,
+    M: Message + Default,
+{
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len).await?;
+    let len = u32::from_be_bytes(len) as usize;
+
+    if len > MAX_RECORD_SIZE {
+        return Err(SnapshotError::InvalidSnapshot(format!(
+            "record of {len} bytes exceeds the size limit"
+        )));
+    }
+
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf).await?;
+
+    M::decode(&buf[..]).map_err(|e| SnapshotError::InvalidSnapshot(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{InMemoryStore, SamplingStatus};
+    use crate::test_utils::{
+        async_test, gen_filled_store, new_block_ranges, ExtendedHeaderGeneratorExt,
+    };
+    use celestia_types::test_utils::{invalidate, ExtendedHeaderGenerator};
+    use cid::Cid;
+    use futures::io::Cursor;
+
+    async fn export(store: &InMemoryStore) -> Vec<u8> {
+        let mut snapshot = Vec::new();
+        export_snapshot(store, &mut snapshot).await.unwrap();
+        snapshot
+    }
+
+    #[async_test]
+    async fn export_import_roundtrip() {
+        let store = InMemoryStore::new();
+        let mut gen = ExtendedHeaderGenerator::new();
+
+        store.insert(gen.next_many_verified(600)).await.unwrap();
+        gen.skip(100);
+        store.insert(gen.next_many_verified(20)).await.unwrap();
+
+        let cid = "bafkreibme22gw2h7y2h7tg2fhqotaqjucnbc24deqo72b6mkl2egezxhvy"
+            .parse::<Cid>()
+            .unwrap();
+        store
+            .update_sampling_metadata(3, SamplingStatus::Accepted, vec![cid])
+            .await
+            .unwrap();
+        store
+            .update_sampling_metadata(4, SamplingStatus::Rejected, vec![])
+            .await
+            .unwrap();
+        store
+            .update_sampling_metadata(710, SamplingStatus::Accepted, vec![])
+            .await
+            .unwrap();
+
+        let snapshot = export(&store).await;
+
+        let imported = InMemoryStore::new();
+        let count = import_snapshot(&imported, &mut Cursor::new(snapshot))
+            .await
+            .unwrap();
+
+        assert_eq!(count, 620);
+        assert_eq!(
+            imported.get_stored_header_ranges().await.unwrap(),
+            new_block_ranges([1..=600, 701..=720])
+        );
+        assert_eq!(
+            imported.get_accepted_sampling_ranges().await.unwrap(),
+            new_block_ranges([3..=3, 710..=710])
+        );
+        assert_eq!(
+            imported.get_range(1..=600).await.unwrap(),
+            store.get_range(1..=600).await.unwrap()
+        );
+        assert_eq!(
+            imported.get_by_height(720).await.unwrap(),
+            store.get_by_height(720).await.unwrap()
+        );
+
+        let metadata = imported.get_sampling_metadata(3).await.unwrap().unwrap();
+        assert_eq!(metadata.status, SamplingStatus::Accepted);
+        assert_eq!(metadata.cids, vec![cid]);
+
+        let metadata = imported.get_sampling_metadata(4).await.unwrap().unwrap();
+        assert_eq!(metadata.status, SamplingStatus::Rejected);
+
+        assert!(imported.get_sampling_metadata(5).await.unwrap().is_none());
+    }
+
+    #[async_test]
+    async fn import_into_conflicting_store() {
+        let (store, _gen) = gen_filled_store(10).await;
+        let snapshot = export(&store).await;
+
+        let (other, _gen) = gen_filled_store(10).await;
+        let res = import_snapshot(&other, &mut Cursor::new(snapshot.clone())).await;
+        assert!(matches!(res, Err(SnapshotError::StoreConflict)));
+
+        let (longer, _gen) = gen_filled_store(11).await;
+        let res = import_snapshot(&longer, &mut Cursor::new(snapshot)).await;
+        assert!(matches!(res, Err(SnapshotError::StoreConflict)));
+    }
+
+    #[async_test]
+    async fn resume_partial_import() {
+        let store = InMemoryStore::new();
+        let mut gen = ExtendedHeaderGenerator::new();
+        let headers = gen.next_many(1200);
+
+        store
+            .insert(VerifiedExtendedHeaders::try_from(headers.clone()).unwrap())
+            .await
+            .unwrap();
+        store
+            .update_sampling_metadata(700, SamplingStatus::Accepted, vec![])
+            .await
+            .unwrap();
+        let snapshot = export(&store).await;
+
+        // Import fails on the last header, after the first batches were inserted
+        let imported = InMemoryStore::new();
+        let mut truncated = snapshot.clone();
+        truncated.truncate(truncated.len() - 1);
+        let res = import_snapshot(&imported, &mut Cursor::new(truncated)).await;
+        assert!(matches!(res, Err(SnapshotError::Io(_))));
+        assert_eq!(
+            imported.get_stored_header_ranges().await.unwrap(),
+            new_block_ranges([1..=1024])
+        );
+
+        let count = import_snapshot(&imported, &mut Cursor::new(snapshot.clone()))
+            .await
+            .unwrap();
+        assert_eq!(count, 176);
+        assert_eq!(
+            imported.get_stored_header_ranges().await.unwrap(),
+            new_block_ranges([1..=1200])
+        );
+        assert_eq!(
+            imported.get_accepted_sampling_ranges().await.unwrap(),
+            new_block_ranges([700..=700])
+        );
+
+        // Importing a complete snapshot again is a no-op
+        let count = import_snapshot(&imported, &mut Cursor::new(snapshot))
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[async_test]
+    async fn import_unsupported_version() {
+        let (store, _gen) = gen_filled_store(10).await;
+        let mut snapshot = export(&store).await;
+        snapshot[SNAPSHOT_MAGIC.len() + 3] = 2;
+
+        let res = import_snapshot(&InMemoryStore::new(), &mut Cursor::new(snapshot)).await;
+        assert!(matches!(res, Err(SnapshotError::UnsupportedVersion(2))));
+    }
+
+    #[async_test]
+    async fn import_truncated_snapshot() {
+        let (store, _gen) = gen_filled_store(10).await;
+        let mut snapshot = export(&store).await;
+        snapshot.truncate(snapshot.len() - 1);
+
+        let res = import_snapshot(&InMemoryStore::new(), &mut Cursor::new(snapshot)).await;
+        assert!(matches!(res, Err(SnapshotError::Io(_))));
+    }
+
+    #[async_test]
+    async fn import_unverified_chain() {
+        let mut gen = ExtendedHeaderGenerator::new();
+        let mut headers = gen.next_many(10);
+        // Replace header with one of a different chain
+        headers[5] = ExtendedHeaderGenerator::new_from_height(6).next();
+
+        let snapshot = snapshot_of(&headers).await;
+
+        let imported = InMemoryStore::new();
+        let res = import_snapshot(&imported, &mut Cursor::new(snapshot)).await;
+        assert!(matches!(res, Err(SnapshotError::VerificationFailed(_))));
+        assert!(imported
+            .get_stored_header_ranges()
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[async_test]
+    async fn import_invalid_header() {
+        let mut gen = ExtendedHeaderGenerator::new();
+        let mut headers = gen.next_many(10);
+        invalidate(&mut headers[3]);
+
+        let snapshot = snapshot_of(&headers).await;
+
+        let res = import_snapshot(&InMemoryStore::new(), &mut Cursor::new(snapshot)).await;
+        assert!(matches!(res, Err(SnapshotError::VerificationFailed(_))));
+    }
+
+    /// Write a snapshot of a single range without going through the store.
+    async fn snapshot_of(headers: &[ExtendedHeader]) -> Vec<u8> {
+        let range = headers[0].height().value()..=headers.last().unwrap().height().value();
+        let mut snapshot = Vec::new();
+
+        snapshot.extend_from_slice(SNAPSHOT_MAGIC);
+        snapshot.extend_from_slice(&SNAPSHOT_VERSION.to_be_bytes());
+
+        let info = RawSnapshotInfo {
+            stored_ranges: to_raw_ranges(&new_block_ranges([range])),
+            accepted_ranges: Vec::new(),
+        };
+        write_record(&mut snapshot, &info).await.unwrap();
+
+        for header in headers {
+            let entry = RawSnapshotEntry {
+                header: header.clone().encode_vec(),
+                sampling_metadata: None,
+            };
+            write_record(&mut snapshot, &entry).await.unwrap();
+        }
+
+        snapshot
+    }
+}
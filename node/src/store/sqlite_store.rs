@@ -0,0 +1,1025 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::path::Path;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use celestia_types::hash::Hash;
+use celestia_types::ExtendedHeader;
+use cid::Cid;
+use libp2p::PeerId;
+use rusqlite::{
+    params, Connection, OpenFlags, OptionalExtension, Transaction, TransactionBehavior,
+};
+use tendermint_proto::Protobuf;
+use tokio::sync::Notify;
+use tokio::task::spawn_blocking;
+use tracing::{debug, trace};
+
+use crate::block_ranges::BlockRanges;
+use crate::store::changes::StoreChangesSender;
+use crate::store::peers::{
+    deserialize_blocked_peers, deserialize_known_peers, serialize_blocked_peers,
+    serialize_known_peers,
+};
+use crate::store::utils::VerifiedExtendedHeaders;
+use crate::store::{
+    KnownPeer, Result, SamplingMetadata, SamplingStatus, Store, StoreChange, StoreChanges,
+    StoreError, StoreInsertionError, StoreStats, TableStats,
+};
+use crate::utils::Counter;
+
+use super::utils::{deserialize_extended_header, deserialize_sampling_metadata};
+
+const SCHEMA_VERSION: u64 = 1;
+
+const CREATE_SCHEMA_VERSION_TABLE: &str = "CREATE TABLE IF NOT EXISTS store_schema_version (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    version INTEGER NOT NULL
+)";
+const CREATE_HEIGHTS_TABLE: &str = "CREATE TABLE IF NOT EXISTS store_heights (
+    hash BLOB PRIMARY KEY,
+    height INTEGER NOT NULL
+)";
+const CREATE_HEADERS_TABLE: &str = "CREATE TABLE IF NOT EXISTS store_headers (
+    height INTEGER PRIMARY KEY,
+    header BLOB NOT NULL
+)";
+const CREATE_SAMPLING_METADATA_TABLE: &str = "CREATE TABLE IF NOT EXISTS store_sampling_metadata (
+    height INTEGER PRIMARY KEY,
+    metadata BLOB NOT NULL
+)";
+const CREATE_RANGES_TABLE: &str = "CREATE TABLE IF NOT EXISTS store_ranges (
+    name TEXT NOT NULL,
+    range_start INTEGER NOT NULL,
+    range_end INTEGER NOT NULL,
+    PRIMARY KEY (name, range_start)
+)";
+const CREATE_PEERS_TABLE: &str = "CREATE TABLE IF NOT EXISTS store_peers (
+    name TEXT PRIMARY KEY,
+    peers BLOB NOT NULL
+)";
+
+const ACCEPTED_SAMPING_RANGES_KEY: &str = "KEY.ACCEPTED_SAMPING_RANGES";
+const HEADER_RANGES_KEY: &str = "KEY.HEADER_RANGES";
+const KNOWN_PEERS_KEY: &str = "KEY.KNOWN_PEERS";
+const BLOCKED_PEERS_KEY: &str = "KEY.BLOCKED_PEERS";
+
+/// A [`Store`] implementation based on a [`SQLite`] database.
+///
+/// [`SQLite`]: https://www.sqlite.org
+#[derive(Debug)]
+pub struct SqliteStore {
+    inner: Arc<Inner>,
+    task_counter: Counter,
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// Connection to the database, shared with other stores
+    conn: Arc<Mutex<Connection>>,
+    /// Connection used only for reading, so that reads don't wait for the writes.
+    ///
+    /// Available only for databases opened from a file in WAL mode.
+    read_conn: Option<Mutex<Connection>>,
+    /// Notify when a new header is added
+    header_added_notifier: Notify,
+    /// Sender of the changes to the subscribers
+    changes: StoreChangesSender,
+}
+
+impl SqliteStore {
+    /// Open a persistent [`SQLite`] store.
+    ///
+    /// The database is switched to the [WAL] mode and a separate connection
+    /// is opened for reading, so reads can proceed while writing.
+    ///
+    /// [`SQLite`]: https://www.sqlite.org
+    /// [WAL]: https://www.sqlite.org/wal.html
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+
+        let (conn, read_conn) = spawn_blocking(move || {
+            let conn = Connection::open(&path)?;
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| {
+                row.get::<_, String>(0)
+            })?;
+
+            let read_conn = Connection::open_with_flags(
+                &path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            )?;
+
+            Ok::<_, rusqlite::Error>((conn, read_conn))
+        })
+        .await?
+        .map_err(|e| StoreError::OpenFailed(e.to_string()))?;
+
+        SqliteStore::with_connections(Arc::new(Mutex::new(conn)), Some(read_conn)).await
+    }
+
+    /// Open an in memory [`SQLite`] store.
+    ///
+    /// [`SQLite`]: https://www.sqlite.org
+    pub async fn in_memory() -> Result<Self> {
+        let conn =
+            Connection::open_in_memory().map_err(|e| StoreError::OpenFailed(e.to_string()))?;
+
+        SqliteStore::new(Arc::new(Mutex::new(conn))).await
+    }
+
+    /// Create new `SqliteStore` with an already opened [`rusqlite::Connection`].
+    ///
+    /// All the transactions are executed on this connection.
+    pub async fn new(conn: Arc<Mutex<Connection>>) -> Result<Self> {
+        SqliteStore::with_connections(conn, None).await
+    }
+
+    async fn with_connections(
+        conn: Arc<Mutex<Connection>>,
+        read_conn: Option<Connection>,
+    ) -> Result<Self> {
+        let store = SqliteStore {
+            inner: Arc::new(Inner {
+                conn,
+                read_conn: read_conn.map(Mutex::new),
+                header_added_notifier: Notify::new(),
+                changes: StoreChangesSender::new(),
+            }),
+            task_counter: Counter::new(),
+        };
+
+        store
+            .write_tx(|tx| {
+                tx.execute(CREATE_SCHEMA_VERSION_TABLE, [])?;
+
+                let schema_version: Option<u64> = tx
+                    .query_row(
+                        "SELECT version FROM store_schema_version WHERE id = 0",
+                        [],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+
+                match schema_version {
+                    Some(schema_version) => {
+                        if schema_version > SCHEMA_VERSION {
+                            let e = format!(
+                                "Incompatible database schema; found {}, expected {}.",
+                                schema_version, SCHEMA_VERSION
+                            );
+                            return Err(StoreError::OpenFailed(e));
+                        }
+                    }
+                    None => {
+                        // New database
+                        tx.execute(
+                            "INSERT INTO store_schema_version (id, version) VALUES (0, ?1)",
+                            params![SCHEMA_VERSION],
+                        )?;
+                    }
+                }
+
+                tx.execute(CREATE_HEIGHTS_TABLE, [])?;
+                tx.execute(CREATE_HEADERS_TABLE, [])?;
+                tx.execute(CREATE_SAMPLING_METADATA_TABLE, [])?;
+                tx.execute(CREATE_RANGES_TABLE, [])?;
+                tx.execute(CREATE_PEERS_TABLE, [])?;
+
+                Ok(())
+            })
+            .await
+            .map_err(|e| match e {
+                e @ StoreError::OpenFailed(_) => e,
+                e => StoreError::OpenFailed(e.to_string()),
+            })?;
+
+        Ok(store)
+    }
+
+    /// Returns the raw [`rusqlite::Connection`].
+    ///
+    /// This is useful if you want to pass the database handle to any other
+    /// stores (e.g. [`SqliteBlockstore`]).
+    ///
+    /// [`SqliteBlockstore`]: crate::blockstore::SqliteBlockstore
+    pub fn raw_db(&self) -> Arc<Mutex<Connection>> {
+        self.inner.conn.clone()
+    }
+
+    /// Execute a read transaction.
+    async fn read_tx<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Transaction) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let inner = self.inner.clone();
+        let guard = self.task_counter.guard();
+
+        spawn_blocking(move || {
+            let _guard = guard;
+
+            {
+                let conn = inner.read_conn.as_ref().unwrap_or(&inner.conn);
+                let mut conn = lock_conn(conn)?;
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Deferred)?;
+                f(&tx)
+            }
+        })
+        .await?
+    }
+
+    /// Execute a write transaction.
+    ///
+    /// If closure returns an error the transaction is rolled back, otherwise commited.
+    async fn write_tx<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Transaction) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let inner = self.inner.clone();
+        let guard = self.task_counter.guard();
+
+        spawn_blocking(move || {
+            let _guard = guard;
+
+            {
+                let mut conn = lock_conn(&inner.conn)?;
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                let res = f(&tx);
+
+                if res.is_ok() {
+                    tx.commit()?;
+                } else {
+                    tx.rollback()?;
+                }
+
+                res
+            }
+        })
+        .await?
+    }
+
+    async fn head_height(&self) -> Result<u64> {
+        self.read_tx(|tx| {
+            let header_ranges = get_ranges(tx, HEADER_RANGES_KEY)?;
+            header_ranges.head().ok_or(StoreError::NotFound)
+        })
+        .await
+    }
+
+    async fn get_by_hash(&self, hash: &Hash) -> Result<ExtendedHeader> {
+        let hash = *hash;
+
+        self.read_tx(move |tx| {
+            let height = get_height(tx, hash.as_bytes())?;
+            get_header(tx, height)
+        })
+        .await
+    }
+
+    async fn get_by_height(&self, height: u64) -> Result<ExtendedHeader> {
+        self.read_tx(move |tx| get_header(tx, height)).await
+    }
+
+    async fn get_head(&self) -> Result<ExtendedHeader> {
+        self.read_tx(|tx| {
+            let header_ranges = get_ranges(tx, HEADER_RANGES_KEY)?;
+            let head = header_ranges.head().ok_or(StoreError::NotFound)?;
+
+            get_header(tx, head)
+        })
+        .await
+    }
+
+    async fn contains_hash(&self, hash: &Hash) -> bool {
+        let hash = *hash;
+
+        self.read_tx(move |tx| {
+            let height = get_height(tx, hash.as_bytes())?;
+            contains_header(tx, height)
+        })
+        .await
+        .unwrap_or(false)
+    }
+
+    async fn contains_height(&self, height: u64) -> bool {
+        self.read_tx(move |tx| contains_header(tx, height))
+            .await
+            .unwrap_or(false)
+    }
+
+    async fn insert<R>(&self, headers: R) -> Result<()>
+    where
+        R: TryInto<VerifiedExtendedHeaders> + Send,
+        <R as TryInto<VerifiedExtendedHeaders>>::Error: Display,
+    {
+        let headers = headers
+            .try_into()
+            .map_err(|e| StoreInsertionError::HeadersVerificationFailed(e.to_string()))?;
+        let Some(range) = headers.height_range() else {
+            return Ok(());
+        };
+
+        self.write_tx(move |tx| {
+            let (Some(head), Some(tail)) = (headers.as_ref().first(), headers.as_ref().last())
+            else {
+                return Ok(());
+            };
+
+            let mut header_ranges = get_ranges(tx, HEADER_RANGES_KEY)?;
+            let headers_range = head.height().value()..=tail.height().value();
+
+            let (prev_exists, next_exists) = header_ranges
+                .check_insertion_constraints(&headers_range)
+                .map_err(StoreInsertionError::ContraintsNotMet)?;
+
+            verify_against_neighbours(
+                tx,
+                prev_exists.then_some(head),
+                next_exists.then_some(tail),
+            )?;
+
+            for header in headers {
+                let height = header.height().value();
+                let hash = header.hash();
+                let serialized_header = header.encode_vec();
+
+                if contains_header(tx, height)? {
+                    return Err(StoreError::StoredDataError(
+                        "inconsistency between headers table and ranges table".into(),
+                    ));
+                }
+
+                if get_height(tx, hash.as_bytes()).is_ok() {
+                    // TODO: Replace this with `StoredDataError` when we implement
+                    // type-safe validation on insertion.
+                    return Err(StoreInsertionError::HashExists(hash).into());
+                }
+
+                tx.execute(
+                    "INSERT INTO store_headers (height, header) VALUES (?1, ?2)",
+                    params![height, serialized_header],
+                )?;
+                tx.execute(
+                    "INSERT INTO store_heights (hash, height) VALUES (?1, ?2)",
+                    params![hash.as_bytes(), height],
+                )?;
+
+                trace!("Inserted header {hash} with height {height}");
+            }
+
+            header_ranges
+                .insert_relaxed(&headers_range)
+                .expect("invalid range");
+            set_ranges(tx, HEADER_RANGES_KEY, &header_ranges)?;
+
+            debug!("Inserted header range {headers_range:?}",);
+
+            Ok(())
+        })
+        .await?;
+
+        self.inner.header_added_notifier.notify_waiters();
+        self.inner
+            .changes
+            .send(StoreChange::HeadersInserted { range });
+
+        Ok(())
+    }
+
+    async fn update_sampling_metadata(
+        &self,
+        height: u64,
+        status: Option<SamplingStatus>,
+        cids: Vec<Cid>,
+    ) -> Result<()> {
+        self.write_tx(move |tx| {
+            let header_ranges = get_ranges(tx, HEADER_RANGES_KEY)?;
+            let mut sampling_ranges = get_ranges(tx, ACCEPTED_SAMPING_RANGES_KEY)?;
+
+            if !header_ranges.contains(height) {
+                return Err(StoreError::NotFound);
+            }
+
+            let previous = get_sampling_metadata(tx, height)?;
+
+            let entry = match previous {
+                Some(mut previous) => {
+                    if let Some(status) = status {
+                        previous.status = status;
+                    }
+
+                    for cid in cids {
+                        if !previous.cids.contains(&cid) {
+                            previous.cids.push(cid);
+                        }
+                    }
+
+                    previous
+                }
+                None => SamplingMetadata {
+                    status: status.unwrap_or_default(),
+                    cids,
+                },
+            };
+            let status = entry.status;
+
+            tx.execute(
+                "INSERT OR REPLACE INTO store_sampling_metadata (height, metadata) VALUES (?1, ?2)",
+                params![height, entry.encode_vec()],
+            )?;
+
+            match status {
+                SamplingStatus::Accepted => sampling_ranges
+                    .insert_relaxed(height..=height)
+                    .expect("invalid height"),
+                _ => sampling_ranges
+                    .remove_relaxed(height..=height)
+                    .expect("invalid height"),
+            }
+
+            set_ranges(tx, ACCEPTED_SAMPING_RANGES_KEY, &sampling_ranges)?;
+
+            Ok(())
+        })
+        .await?;
+
+        if let Some(status) = status {
+            self.inner
+                .changes
+                .send(StoreChange::SamplingUpdated { height, status });
+        }
+
+        Ok(())
+    }
+
+    async fn get_sampling_metadata(&self, height: u64) -> Result<Option<SamplingMetadata>> {
+        self.read_tx(move |tx| {
+            if !contains_header(tx, height)? {
+                return Err(StoreError::NotFound);
+            }
+
+            get_sampling_metadata(tx, height)
+        })
+        .await
+    }
+
+    async fn get_stored_ranges(&self) -> Result<BlockRanges> {
+        self.read_tx(|tx| get_ranges(tx, HEADER_RANGES_KEY)).await
+    }
+
+    async fn get_sampling_ranges(&self) -> Result<BlockRanges> {
+        self.read_tx(|tx| get_ranges(tx, ACCEPTED_SAMPING_RANGES_KEY))
+            .await
+    }
+
+    async fn remove_last(&self) -> Result<u64> {
+        let height = self
+            .write_tx(move |tx| {
+                let mut header_ranges = get_ranges(tx, HEADER_RANGES_KEY)?;
+
+                let Some(height) = header_ranges.pop_tail() else {
+                    return Err(StoreError::NotFound);
+                };
+                set_ranges(tx, HEADER_RANGES_KEY, &header_ranges)?;
+
+                let header = get_header(tx, height).map_err(|e| match e {
+                    StoreError::NotFound => StoreError::StoredDataError(format!(
+                        "inconsistency between ranges and headers tables, height {height}"
+                    )),
+                    e => e,
+                })?;
+                let hash = header.hash();
+
+                tx.execute(
+                    "DELETE FROM store_headers WHERE height = ?1",
+                    params![height],
+                )?;
+
+                if tx.execute(
+                    "DELETE FROM store_heights WHERE hash = ?1",
+                    params![hash.as_bytes()],
+                )? == 0
+                {
+                    return Err(StoreError::StoredDataError(format!(
+                        "inconsistency between header and heights tables, hash {hash}"
+                    )));
+                }
+
+                Ok(height)
+            })
+            .await?;
+
+        self.inner.changes.send(StoreChange::HeadersRemoved {
+            range: height..=height,
+        });
+
+        Ok(height)
+    }
+
+    async fn get_known_peers(&self) -> Result<Vec<KnownPeer>> {
+        self.read_tx(|tx| {
+            tx.query_row(
+                "SELECT peers FROM store_peers WHERE name = ?1",
+                params![KNOWN_PEERS_KEY],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .optional()?
+            .map(|serialized| deserialize_known_peers(&serialized))
+            .transpose()
+            .map(Option::unwrap_or_default)
+        })
+        .await
+    }
+
+    async fn set_known_peers(&self, peers: Vec<KnownPeer>) -> Result<()> {
+        self.write_tx(move |tx| {
+            tx.execute(
+                "INSERT OR REPLACE INTO store_peers (name, peers) VALUES (?1, ?2)",
+                params![KNOWN_PEERS_KEY, serialize_known_peers(&peers)],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_blocked_peers(&self) -> Result<Vec<PeerId>> {
+        self.read_tx(|tx| {
+            tx.query_row(
+                "SELECT peers FROM store_peers WHERE name = ?1",
+                params![BLOCKED_PEERS_KEY],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .optional()?
+            .map(|serialized| deserialize_blocked_peers(&serialized))
+            .transpose()
+            .map(Option::unwrap_or_default)
+        })
+        .await
+    }
+
+    async fn set_blocked_peers(&self, peers: Vec<PeerId>) -> Result<()> {
+        self.write_tx(move |tx| {
+            tx.execute(
+                "INSERT OR REPLACE INTO store_peers (name, peers) VALUES (?1, ?2)",
+                params![BLOCKED_PEERS_KEY, serialize_blocked_peers(&peers)],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn stats(&self) -> Result<StoreStats> {
+        self.read_tx(|tx| {
+            // Size of the indexes is accounted to their tables.
+            let mut stmt = tx.prepare(
+                "SELECT m.tbl_name, COALESCE(SUM(d.pgsize), 0) FROM sqlite_master m
+                 LEFT JOIN dbstat d ON d.name = m.name
+                 WHERE m.type IN ('table', 'index') AND m.tbl_name NOT LIKE 'sqlite_%'
+                 GROUP BY m.tbl_name ORDER BY m.tbl_name",
+            )?;
+            let sizes = stmt
+                .query_map([], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut tables = Vec::with_capacity(sizes.len());
+
+            for (name, bytes) in sizes {
+                let entries =
+                    tx.query_row(&format!("SELECT COUNT(*) FROM \"{name}\""), [], |row| {
+                        row.get(0)
+                    })?;
+
+                tables.push(TableStats {
+                    name,
+                    entries,
+                    bytes: Some(bytes),
+                });
+            }
+
+            let mut cids = HashSet::new();
+            let mut stmt = tx.prepare("SELECT metadata FROM store_sampling_metadata")?;
+            let mut rows = stmt.query([])?;
+
+            while let Some(row) = rows.next()? {
+                let metadata = deserialize_sampling_metadata(&row.get::<_, Vec<u8>>(0)?)?;
+                cids.extend(metadata.cids);
+            }
+
+            Ok(StoreStats {
+                headers: tx
+                    .query_row("SELECT COUNT(*) FROM store_headers", [], |row| row.get(0))?,
+                cids: cids.len() as u64,
+                tables,
+                fragmented_bytes: Some(get_free_bytes(tx)?),
+            })
+        })
+        .await
+    }
+
+    /// Compact the database with `VACUUM`, if it has any free pages.
+    pub async fn compact(&mut self) -> Result<bool> {
+        let inner = self.inner.clone();
+        let guard = self.task_counter.guard();
+
+        spawn_blocking(move || {
+            let _guard = guard;
+
+            let mut conn = lock_conn(&inner.conn)?;
+
+            let free_bytes = {
+                let tx = conn.transaction_with_behavior(TransactionBehavior::Deferred)?;
+                get_free_bytes(&tx)?
+            };
+
+            if free_bytes == 0 {
+                return Ok(false);
+            }
+
+            conn.execute_batch("VACUUM")
+                .map_err(|e| StoreError::CompactionFailed(e.to_string()))?;
+
+            Ok(true)
+        })
+        .await?
+    }
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    async fn get_head(&self) -> Result<ExtendedHeader> {
+        self.get_head().await
+    }
+
+    async fn get_by_hash(&self, hash: &Hash) -> Result<ExtendedHeader> {
+        self.get_by_hash(hash).await
+    }
+
+    async fn get_by_height(&self, height: u64) -> Result<ExtendedHeader> {
+        self.get_by_height(height).await
+    }
+
+    async fn wait_new_head(&self) -> u64 {
+        let head = self.head_height().await.unwrap_or(0);
+        let mut notifier = pin!(self.inner.header_added_notifier.notified());
+
+        loop {
+            let new_head = self.head_height().await.unwrap_or(0);
+
+            if head != new_head {
+                return new_head;
+            }
+
+            // Await for a notification
+            notifier.as_mut().await;
+
+            // Reset notifier
+            notifier.set(self.inner.header_added_notifier.notified());
+        }
+    }
+
+    async fn wait_height(&self, height: u64) -> Result<()> {
+        let mut notifier = pin!(self.inner.header_added_notifier.notified());
+
+        loop {
+            if self.contains_height(height).await {
+                return Ok(());
+            }
+
+            // Await for a notification
+            notifier.as_mut().await;
+
+            // Reset notifier
+            notifier.set(self.inner.header_added_notifier.notified());
+        }
+    }
+
+    fn subscribe_changes(&self) -> StoreChanges {
+        self.inner.changes.subscribe()
+    }
+
+    async fn head_height(&self) -> Result<u64> {
+        self.head_height().await
+    }
+
+    async fn has(&self, hash: &Hash) -> bool {
+        self.contains_hash(hash).await
+    }
+
+    async fn has_at(&self, height: u64) -> bool {
+        self.contains_height(height).await
+    }
+
+    async fn insert<R>(&self, headers: R) -> Result<()>
+    where
+        R: TryInto<VerifiedExtendedHeaders> + Send,
+        <R as TryInto<VerifiedExtendedHeaders>>::Error: Display,
+    {
+        self.insert(headers).await
+    }
+
+    async fn update_sampling_metadata(
+        &self,
+        height: u64,
+        status: SamplingStatus,
+        cids: Vec<Cid>,
+    ) -> Result<()> {
+        self.update_sampling_metadata(height, Some(status), cids)
+            .await
+    }
+
+    async fn add_sampling_cids(&self, height: u64, cids: Vec<Cid>) -> Result<()> {
+        self.update_sampling_metadata(height, None, cids).await
+    }
+
+    async fn get_sampling_metadata(&self, height: u64) -> Result<Option<SamplingMetadata>> {
+        self.get_sampling_metadata(height).await
+    }
+
+    async fn get_stored_header_ranges(&self) -> Result<BlockRanges> {
+        self.get_stored_ranges().await
+    }
+
+    async fn get_accepted_sampling_ranges(&self) -> Result<BlockRanges> {
+        self.get_sampling_ranges().await
+    }
+
+    async fn remove_last(&self) -> Result<u64> {
+        self.remove_last().await
+    }
+
+    async fn get_known_peers(&self) -> Result<Vec<KnownPeer>> {
+        self.get_known_peers().await
+    }
+
+    async fn set_known_peers(&self, peers: Vec<KnownPeer>) -> Result<()> {
+        self.set_known_peers(peers).await
+    }
+
+    async fn get_blocked_peers(&self) -> Result<Vec<PeerId>> {
+        self.get_blocked_peers().await
+    }
+
+    async fn set_blocked_peers(&self, peers: Vec<PeerId>) -> Result<()> {
+        self.set_blocked_peers(peers).await
+    }
+
+    async fn stats(&self) -> Result<StoreStats> {
+        self.stats().await
+    }
+
+    async fn compact(&mut self) -> Result<bool> {
+        self.compact().await
+    }
+
+    async fn close(mut self) -> Result<()> {
+        // Wait all ongoing `spawn_blocking` tasks to finish.
+        self.task_counter.wait_guards().await;
+        Ok(())
+    }
+}
+
+fn get_free_bytes(tx: &Transaction) -> Result<u64> {
+    let page_size: u64 = tx.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+    let free_pages: u64 = tx.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+    Ok(page_size * free_pages)
+}
+
+fn lock_conn(conn: &Mutex<Connection>) -> Result<std::sync::MutexGuard<'_, Connection>> {
+    conn.lock()
+        .map_err(|_| StoreError::FatalDatabaseError("SQLite connection lock poisoned".into()))
+}
+
+fn verify_against_neighbours(
+    tx: &Transaction,
+    lowest_header: Option<&ExtendedHeader>,
+    highest_header: Option<&ExtendedHeader>,
+) -> Result<()> {
+    if let Some(lowest_header) = lowest_header {
+        let prev = get_header(tx, lowest_header.height().value() - 1).map_err(|e| {
+            if let StoreError::NotFound = e {
+                StoreError::StoredDataError("inconsistency between headers and ranges table".into())
+            } else {
+                e
+            }
+        })?;
+
+        prev.verify(lowest_header)
+            .map_err(|e| StoreInsertionError::NeighborsVerificationFailed(e.to_string()))?;
+    }
+
+    if let Some(highest_header) = highest_header {
+        let next = get_header(tx, highest_header.height().value() + 1).map_err(|e| {
+            if let StoreError::NotFound = e {
+                StoreError::StoredDataError("inconsistency between headers and ranges table".into())
+            } else {
+                e
+            }
+        })?;
+
+        highest_header
+            .verify(&next)
+            .map_err(|e| StoreInsertionError::NeighborsVerificationFailed(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+fn get_ranges(tx: &Transaction, name: &str) -> Result<BlockRanges> {
+    let mut stmt = tx.prepare_cached(
+        "SELECT range_start, range_end FROM store_ranges WHERE name = ?1 ORDER BY range_start",
+    )?;
+
+    let raw_ranges = stmt
+        .query_map(params![name], |row| {
+            Ok(row.get::<_, u64>(0)?..=row.get::<_, u64>(1)?)
+        })?
+        .collect::<Result<_, _>>()?;
+
+    BlockRanges::from_vec(raw_ranges).map_err(|e| {
+        let s = format!("Stored BlockRanges for {name} are invalid: {e}");
+        StoreError::StoredDataError(s)
+    })
+}
+
+fn set_ranges(tx: &Transaction, name: &str, ranges: &BlockRanges) -> Result<()> {
+    tx.execute("DELETE FROM store_ranges WHERE name = ?1", params![name])?;
+
+    let mut stmt = tx.prepare_cached(
+        "INSERT INTO store_ranges (name, range_start, range_end) VALUES (?1, ?2, ?3)",
+    )?;
+
+    for range in ranges.as_ref() {
+        stmt.execute(params![name, range.start(), range.end()])?;
+    }
+
+    Ok(())
+}
+
+#[inline]
+fn get_height(tx: &Transaction, hash: &[u8]) -> Result<u64> {
+    tx.query_row(
+        "SELECT height FROM store_heights WHERE hash = ?1",
+        params![hash],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or(StoreError::NotFound)
+}
+
+#[inline]
+fn contains_header(tx: &Transaction, height: u64) -> Result<bool> {
+    Ok(tx
+        .query_row(
+            "SELECT 1 FROM store_headers WHERE height = ?1",
+            params![height],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some())
+}
+
+#[inline]
+fn get_header(tx: &Transaction, height: u64) -> Result<ExtendedHeader> {
+    let serialized: Vec<u8> = tx
+        .query_row(
+            "SELECT header FROM store_headers WHERE height = ?1",
+            params![height],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or(StoreError::NotFound)?;
+
+    deserialize_extended_header(&serialized)
+}
+
+#[inline]
+fn get_sampling_metadata(tx: &Transaction, height: u64) -> Result<Option<SamplingMetadata>> {
+    tx.query_row(
+        "SELECT metadata FROM store_sampling_metadata WHERE height = ?1",
+        params![height],
+        |row| row.get::<_, Vec<u8>>(0),
+    )
+    .optional()?
+    .map(|serialized| deserialize_sampling_metadata(&serialized))
+    .transpose()
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::FatalDatabaseError(format!("SqliteError: {e}"))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::test_utils::ExtendedHeaderGeneratorExt;
+    use celestia_types::test_utils::ExtendedHeaderGenerator;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_store_persistence() {
+        let db_dir = TempDir::with_prefix("lumina.store.test").unwrap();
+        let db = db_dir.path().join("db.sqlite");
+
+        let store = SqliteStore::open(&db).await.unwrap();
+        let mut gen = ExtendedHeaderGenerator::new();
+        let headers = gen.next_many(20);
+
+        store.insert(headers.clone()).await.unwrap();
+        store
+            .update_sampling_metadata(5, Some(SamplingStatus::Accepted), vec![])
+            .await
+            .unwrap();
+        store.close().await.unwrap();
+
+        let reopened_store = SqliteStore::open(&db).await.unwrap();
+
+        assert_eq!(reopened_store.head_height().await.unwrap(), 20);
+        assert_eq!(reopened_store.get_range(..).await.unwrap(), headers);
+        assert_eq!(
+            reopened_store.get_accepted_sampling_ranges().await.unwrap(),
+            BlockRanges::from_vec([5..=5].into_iter().collect()).unwrap()
+        );
+
+        reopened_store
+            .insert(gen.next_many_verified(10))
+            .await
+            .unwrap();
+        assert_eq!(reopened_store.head_height().await.unwrap(), 30);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::await_holding_lock)]
+    async fn test_read_while_writing() {
+        let db_dir = TempDir::with_prefix("lumina.store.test").unwrap();
+        let db = db_dir.path().join("db.sqlite");
+
+        let store = SqliteStore::open(&db).await.unwrap();
+        let mut gen = ExtendedHeaderGenerator::new();
+        store.insert(gen.next_many(10)).await.unwrap();
+
+        // hold the write connection, as a long write would
+        let conn = store.raw_db();
+        let _write_lock = conn.lock().unwrap();
+
+        let head_height =
+            tokio::time::timeout(std::time::Duration::from_secs(1), store.head_height())
+                .await
+                .expect("read blocked by the write connection")
+                .unwrap();
+        assert_eq!(head_height, 10);
+    }
+
+    #[tokio::test]
+    async fn test_incompatible_schema() {
+        let conn = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+        SqliteStore::new(conn.clone()).await.unwrap();
+
+        conn.lock()
+            .unwrap()
+            .execute(
+                "UPDATE store_schema_version SET version = ?1",
+                params![SCHEMA_VERSION + 1],
+            )
+            .unwrap();
+
+        let res = SqliteStore::new(conn).await;
+        assert!(matches!(res, Err(StoreError::OpenFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_compact() {
+        let db_dir = TempDir::with_prefix("lumina.store.test").unwrap();
+        let db = db_dir.path().join("db.sqlite");
+
+        let mut store = SqliteStore::open(&db).await.unwrap();
+        assert!(!store.compact().await.unwrap());
+
+        let mut gen = ExtendedHeaderGenerator::new();
+        store.insert(gen.next_many(100)).await.unwrap();
+
+        for _ in 0..90 {
+            store.remove_last().await.unwrap();
+        }
+
+        let stats = store.stats().await.unwrap();
+        assert_eq!(stats.headers, 10);
+        assert!(stats.fragmented_bytes.unwrap() > 0);
+        assert!(stats.total_bytes().unwrap() > 0);
+
+        assert!(store.compact().await.unwrap());
+
+        let stats = store.stats().await.unwrap();
+        assert_eq!(stats.headers, 10);
+        assert_eq!(stats.fragmented_bytes, Some(0));
+        assert_eq!(store.head_height().await.unwrap(), 100);
+    }
+}
@@ -0,0 +1,40 @@
+//! Storage usage statistics of the stores.
+
+use serde::{Deserialize, Serialize};
+
+/// Result of [`Store::stats`].
+///
+/// [`Store::stats`]: crate::store::Store::stats
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoreStats {
+    /// Number of stored headers.
+    pub headers: u64,
+    /// Number of CIDs tracked in the sampling metadata, i.e. blocks kept in the blockstore.
+    pub cids: u64,
+    /// Tables of the database, including the blockstore if it shares the database.
+    pub tables: Vec<TableStats>,
+    /// Bytes of the database which can be reclaimed with [`Store::compact`].
+    ///
+    /// `None` if the store doesn't report it.
+    ///
+    /// [`Store::compact`]: crate::store::Store::compact
+    pub fragmented_bytes: Option<u64>,
+}
+
+/// Storage usage of a single table of the database.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableStats {
+    /// Name of the table.
+    pub name: String,
+    /// Number of entries in the table.
+    pub entries: u64,
+    /// Bytes used by the table, `None` if the database doesn't report it.
+    pub bytes: Option<u64>,
+}
+
+impl StoreStats {
+    /// Bytes used by all the tables, `None` if the database doesn't report it.
+    pub fn total_bytes(&self) -> Option<u64> {
+        self.tables.iter().map(|table| table.bytes).sum()
+    }
+}
@@ -1,11 +1,10 @@
 use celestia_types::ExtendedHeader;
-#[cfg(not(target_arch = "wasm32"))]
+use cid::Cid;
 use tendermint_proto::Protobuf;
 
+use crate::block_ranges::BlockRange;
 use crate::executor::yield_now;
-use crate::store::Result;
-#[cfg(not(target_arch = "wasm32"))]
-use crate::store::{SamplingMetadata, StoreError};
+use crate::store::{Result, SamplingMetadata, Store, StoreError};
 
 pub(crate) const VALIDATIONS_PER_YIELD: usize = 4;
 
@@ -85,6 +84,13 @@ impl VerifiedExtendedHeaders {
     pub unsafe fn new_unchecked(headers: Vec<ExtendedHeader>) -> Self {
         Self(headers)
     }
+
+    /// Heights of the headers, `None` if there are no headers.
+    pub(crate) fn height_range(&self) -> Option<BlockRange> {
+        let head = self.0.first()?;
+        let tail = self.0.last()?;
+        Some(head.height().value()..=tail.height().value())
+    }
 }
 
 #[allow(unused)]
@@ -101,8 +107,25 @@ pub(crate) async fn validate_headers(headers: &[ExtendedHeader]) -> celestia_typ
     Ok(())
 }
 
+/// Returns the CIDs recorded in the [`SamplingMetadata`] of all the stored headers.
+///
+/// These are the blocks which the node keeps in the blockstore for the stored headers.
+pub async fn sampled_cids<S>(store: &S) -> Result<Vec<Cid>>
+where
+    S: Store,
+{
+    let mut cids = Vec::new();
+
+    for height in store.get_stored_header_ranges().await? {
+        if let Some(metadata) = store.get_sampling_metadata(height).await? {
+            cids.extend(metadata.cids);
+        }
+    }
+
+    Ok(cids)
+}
+
 /// Deserializes [`SamplingMetadata`] and returns [`StoreError::StoredDataError`] on failure.
-#[cfg(not(target_arch = "wasm32"))]
 pub(crate) fn deserialize_sampling_metadata(bytes: &[u8]) -> Result<SamplingMetadata> {
     SamplingMetadata::decode(bytes).map_err(|e| {
         let s = format!("Stored SamplingMetadata cannot be deserialized: {e}");
@@ -111,7 +134,6 @@ pub(crate) fn deserialize_sampling_metadata(bytes: &[u8]) -> Result<SamplingMeta
 }
 
 /// Deserializes [`ExtendedHeader`] and returns [`StoreError::StoredDataError`] on failure.
-#[cfg(not(target_arch = "wasm32"))]
 pub(crate) fn deserialize_extended_header(bytes: &[u8]) -> Result<ExtendedHeader> {
     ExtendedHeader::decode(bytes).map_err(|e| {
         let s = format!("Stored ExtendedHeader cannot be deserialized: {e}");
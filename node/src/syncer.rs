@@ -8,6 +8,16 @@
 //! on the `header-ex` p2p protocol. In the meantime, it constantly checks for the latest
 //! headers announced on the `header-sub` p2p protocol to keep the `subjective_head` as close
 //! to the `network_head` as possible.
+//!
+//! Only headers within the syncing window are synchronized, unless the node runs in
+//! archival mode. In archival mode headers are synchronized all the way back to genesis.
+//! Each batch is verified with `verify_adjacent_range` and against the stored headers
+//! adjacent to it, and since the next batch is always calculated from the stored ranges,
+//! backfilling resumes where it stopped after a restart.
+//!
+//! If a [`TrustedCheckpoint`] is configured, the `subjective_head` is accepted only if it
+//! can be verified from the checkpoint. Otherwise the first head returned by the trusted
+//! peers is trusted.
 
 use std::marker::PhantomData;
 use std::pin::pin;
@@ -16,6 +26,7 @@ use std::time::Duration;
 
 use backoff::backoff::Backoff;
 use backoff::ExponentialBackoffBuilder;
+use celestia_types::hash::Hash;
 use celestia_types::ExtendedHeader;
 use serde::{Deserialize, Serialize};
 use tendermint::Time;
@@ -36,6 +47,12 @@ type Result<T, E = SyncerError> = std::result::Result<T, E>;
 
 const TRY_INIT_BACKOFF_MAX_INTERVAL: Duration = Duration::from_secs(60);
 
+/// Maximum age of a trusted checkpoint, relative to the network head.
+///
+/// Two thirds of the 21 days unbonding period, after which the validators
+/// which signed the checkpoint can't be held accountable anymore.
+const TRUSTING_PERIOD: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
 /// Representation of all the errors that can occur in `Syncer` component.
 #[derive(Debug, thiserror::Error)]
 pub enum SyncerError {
@@ -54,6 +71,22 @@ pub enum SyncerError {
     /// Channel closed unexpectedly.
     #[error("Channel closed unexpectedly")]
     ChannelClosedUnexpectedly,
+
+    /// Header received from the network doesn't match the trusted checkpoint.
+    #[error("Header at height {0} doesn't match trusted checkpoint")]
+    CheckpointMismatch(u64),
+
+    /// Header in the store doesn't match the trusted checkpoint.
+    #[error("Stored header at height {0} conflicts with trusted checkpoint")]
+    StoreConflictsWithCheckpoint(u64),
+
+    /// Network head couldn't be verified from the trusted checkpoint.
+    #[error("Network head verification from trusted checkpoint failed: {0}")]
+    CheckpointVerification(celestia_types::Error),
+
+    /// Trusted checkpoint is older than the trusting period.
+    #[error("Trusted checkpoint at height {0} is older than the trusting period")]
+    CheckpointExpired(u64),
 }
 
 impl SyncerError {
@@ -61,7 +94,11 @@ impl SyncerError {
         match self {
             SyncerError::P2p(e) => e.is_fatal(),
             SyncerError::Store(e) => e.is_fatal(),
-            SyncerError::WorkerDied | SyncerError::ChannelClosedUnexpectedly => true,
+            SyncerError::WorkerDied
+            | SyncerError::ChannelClosedUnexpectedly
+            | SyncerError::StoreConflictsWithCheckpoint(_)
+            | SyncerError::CheckpointExpired(_) => true,
+            SyncerError::CheckpointMismatch(_) | SyncerError::CheckpointVerification(_) => false,
         }
     }
 }
@@ -99,6 +136,55 @@ where
     pub(crate) batch_size: u64,
     /// Syncing window
     pub(crate) syncing_window: Duration,
+    /// Synchronize headers back to genesis, ignoring the syncing window.
+    pub(crate) archival: bool,
+    /// Header from which the network head is verified.
+    pub(crate) trusted_checkpoint: Option<TrustedCheckpoint>,
+}
+
+/// Header trusted by the user, from which the chain is verified.
+///
+/// Without a checkpoint, the first network head returned by the trusted peers
+/// is trusted blindly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrustedCheckpoint {
+    /// Height and hash of the trusted header, which is fetched from the network.
+    Hash {
+        /// Height of the header.
+        height: u64,
+        /// Hash of the header.
+        hash: Hash,
+    },
+    /// Trusted header itself.
+    Header(Box<ExtendedHeader>),
+}
+
+impl TrustedCheckpoint {
+    /// Height of the trusted header.
+    pub fn height(&self) -> u64 {
+        match self {
+            TrustedCheckpoint::Hash { height, .. } => *height,
+            TrustedCheckpoint::Header(header) => header.height().value(),
+        }
+    }
+
+    /// Hash of the trusted header.
+    pub fn hash(&self) -> Hash {
+        match self {
+            TrustedCheckpoint::Hash { hash, .. } => *hash,
+            TrustedCheckpoint::Header(header) => header.hash(),
+        }
+    }
+
+    fn matches(&self, header: &ExtendedHeader) -> bool {
+        header.height().value() == self.height() && header.hash() == self.hash()
+    }
+}
+
+impl From<ExtendedHeader> for TrustedCheckpoint {
+    fn from(header: ExtendedHeader) -> Self {
+        TrustedCheckpoint::Header(Box::new(header))
+    }
 }
 
 #[derive(Debug)]
@@ -202,6 +288,8 @@ where
     batch_size: u64,
     ongoing_batch: Ongoing,
     syncing_window: Duration,
+    archival: bool,
+    trusted_checkpoint: Option<TrustedCheckpoint>,
 }
 
 struct Ongoing {
@@ -232,6 +320,8 @@ where
                 task: FusedReusableFuture::terminated(),
             },
             syncing_window: args.syncing_window,
+            archival: args.archival,
+            trusted_checkpoint: args.trusted_checkpoint,
         })
     }
 
@@ -267,7 +357,8 @@ where
         let mut try_init_fut = pin!(try_init_task(
             self.p2p.clone(),
             self.store.clone(),
-            self.event_pub.clone()
+            self.event_pub.clone(),
+            self.trusted_checkpoint.clone(),
         ));
 
         loop {
@@ -472,15 +563,20 @@ where
             return Ok(());
         }
 
+        // Stored headers adjacent to the batch, which its headers are verified against
+        let prev = match next_batch.start() - 1 {
+            0 => None,
+            height => self.get_stored_header(height).await?,
+        };
+        let next = self.get_stored_header(next_batch.end() + 1).await?;
+
         // make sure we're inside the syncing window before we start
-        match self.store.get_by_height(next_batch.end() + 1).await {
-            Ok(known_header) => {
-                if !self.in_syncing_window(&known_header) {
+        if !self.archival {
+            if let Some(known_header) = &next {
+                if !self.in_syncing_window(known_header) {
                     return Ok(());
                 }
             }
-            Err(StoreError::NotFound) => {}
-            Err(e) => return Err(e.into()),
         }
 
         self.event_pub.send(NodeEvent::FetchingHeadersStarted {
@@ -494,7 +590,9 @@ where
 
         self.ongoing_batch.task.set(async move {
             let now = Instant::now();
-            let res = p2p.get_unverified_header_range(next_batch).await;
+            let res = p2p
+                .get_unverified_header_range(next_batch, prev, next)
+                .await;
             (res, now.elapsed())
         });
 
@@ -557,21 +655,29 @@ where
         Ok(())
     }
 
-    fn in_syncing_window(&self, header: &ExtendedHeader) -> bool {
-        let syncing_window_start =
-            Time::now()
-                .checked_sub(self.syncing_window)
-                .unwrap_or_else(|| {
-                    warn!(
-                        "underflow when computing syncing window start, defaulting to unix epoch"
-                    );
-                    Time::unix_epoch()
-                });
+    async fn get_stored_header(&self, height: u64) -> Result<Option<ExtendedHeader>> {
+        match self.store.get_by_height(height).await {
+            Ok(header) => Ok(Some(header)),
+            Err(StoreError::NotFound) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
 
-        header.time().after(syncing_window_start)
+    fn in_syncing_window(&self, header: &ExtendedHeader) -> bool {
+        in_syncing_window(header, self.syncing_window)
     }
 }
 
+/// Returns `true` if the header is recent enough to be synchronized.
+pub(crate) fn in_syncing_window(header: &ExtendedHeader, syncing_window: Duration) -> bool {
+    let syncing_window_start = Time::now().checked_sub(syncing_window).unwrap_or_else(|| {
+        warn!("underflow when computing syncing window start, defaulting to unix epoch");
+        Time::unix_epoch()
+    });
+
+    header.time().after(syncing_window_start)
+}
+
 /// based on the stored headers and current network head height, calculate range of headers that
 /// should be fetched from the network, anchored on already existing header range in store
 fn calculate_range_to_fetch(
@@ -605,6 +711,7 @@ async fn try_init_task<S>(
     p2p: Arc<P2p>,
     store: Arc<S>,
     event_pub: EventPublisher,
+    trusted_checkpoint: Option<TrustedCheckpoint>,
 ) -> Result<(ExtendedHeader, Duration)>
 where
     S: Store + 'static,
@@ -617,7 +724,15 @@ where
         .build();
 
     loop {
-        match try_init(&p2p, &*store, &event_pub, &mut event_reported).await {
+        match try_init(
+            &p2p,
+            &*store,
+            &event_pub,
+            trusted_checkpoint.as_ref(),
+            &mut event_reported,
+        )
+        .await
+        {
             Ok(network_head) => {
                 return Ok((network_head, now.elapsed()));
             }
@@ -642,6 +757,7 @@ async fn try_init<S>(
     p2p: &P2p,
     store: &S,
     event_pub: &EventPublisher,
+    trusted_checkpoint: Option<&TrustedCheckpoint>,
     event_reported: &mut bool,
 ) -> Result<ExtendedHeader>
 where
@@ -656,6 +772,11 @@ where
 
     let network_head = p2p.get_head_header().await?;
 
+    if let Some(checkpoint) = trusted_checkpoint {
+        let trusted = get_checkpoint_header(p2p, store, checkpoint, &network_head).await?;
+        verify_from_checkpoint(&trusted, &network_head)?;
+    }
+
     // If the network head and the store head have the same height,
     // then `insert` will error because of insertion contraints.
     // However, if both headers are the exactly the same, we
@@ -679,6 +800,79 @@ where
     Ok(network_head)
 }
 
+/// Get the header of the checkpoint, from the store if possible, or from the network.
+///
+/// If the store is empty, the header is inserted to it, so that the syncer
+/// links the rest of the chain to it. A checkpoint older than the trusting
+/// period is rejected, unless it was already accepted and stored before.
+async fn get_checkpoint_header<S>(
+    p2p: &P2p,
+    store: &S,
+    checkpoint: &TrustedCheckpoint,
+    network_head: &ExtendedHeader,
+) -> Result<ExtendedHeader>
+where
+    S: Store,
+{
+    let height = checkpoint.height();
+
+    match store.get_by_height(height).await {
+        Ok(header) if checkpoint.matches(&header) => {
+            if is_expired(&header, network_head) {
+                warn!("Stored trusted checkpoint at height {height} is older than the trusting period");
+            }
+
+            return Ok(header);
+        }
+        Ok(_) => return Err(SyncerError::StoreConflictsWithCheckpoint(height)),
+        Err(StoreError::NotFound) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let header = match checkpoint {
+        TrustedCheckpoint::Header(header) => header.as_ref().clone(),
+        TrustedCheckpoint::Hash { .. } => p2p.get_header_by_height(height).await?,
+    };
+
+    if !checkpoint.matches(&header) {
+        return Err(SyncerError::CheckpointMismatch(height));
+    }
+
+    if is_expired(&header, network_head) {
+        return Err(SyncerError::CheckpointExpired(height));
+    }
+
+    match store.head_height().await {
+        Err(StoreError::NotFound) => store.insert(header.clone()).await?,
+        Ok(_) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(header)
+}
+
+fn verify_from_checkpoint(trusted: &ExtendedHeader, network_head: &ExtendedHeader) -> Result<()> {
+    if network_head.height() == trusted.height() {
+        if network_head.hash() != trusted.hash() {
+            return Err(SyncerError::CheckpointMismatch(trusted.height().value()));
+        }
+
+        return Ok(());
+    }
+
+    trusted
+        .verify(network_head)
+        .map_err(SyncerError::CheckpointVerification)
+}
+
+/// Whether the trusted header is older than the trusting period, relative to the network head.
+fn is_expired(trusted: &ExtendedHeader, network_head: &ExtendedHeader) -> bool {
+    network_head
+        .time()
+        .duration_since(trusted.time())
+        .is_ok_and(|age| age > TRUSTING_PERIOD)
+}
+
 async fn header_sub_recv(
     rx: Option<&mut mpsc::Receiver<ExtendedHeader>>,
 ) -> Result<ExtendedHeader> {
@@ -788,6 +982,8 @@ mod tests {
             event_pub: events.publisher(),
             batch_size: 512,
             syncing_window: DEFAULT_SAMPLING_WINDOW,
+            archival: false,
+            trusted_checkpoint: None,
         })
         .unwrap();
 
@@ -822,6 +1018,120 @@ mod tests {
         p2p_mock.expect_no_cmd().await;
     }
 
+    #[async_test]
+    async fn init_with_trusted_checkpoint() {
+        let events = EventChannel::new();
+        let (mock, mut handle) = P2p::mocked();
+        let store = Arc::new(InMemoryStore::new());
+        let mut gen = ExtendedHeaderGenerator::new();
+        let headers = gen.next_many(30);
+        let checkpoint = TrustedCheckpoint::Hash {
+            height: 10,
+            hash: headers[9].hash(),
+        };
+
+        let _syncer = Syncer::start(SyncerArgs {
+            p2p: Arc::new(mock),
+            store: store.clone(),
+            event_pub: events.publisher(),
+            batch_size: 512,
+            syncing_window: DEFAULT_SAMPLING_WINDOW,
+            archival: false,
+            trusted_checkpoint: Some(checkpoint),
+        })
+        .unwrap();
+
+        handle.announce_trusted_peer_connected();
+
+        let (height, amount, respond_to) = handle.expect_header_request_for_height_cmd().await;
+        assert_eq!((height, amount), (0, 1));
+        respond_to.send(Ok(vec![headers[29].clone()])).unwrap();
+
+        // Checkpoint header is fetched, because it's not in the store
+        let (height, amount, respond_to) = handle.expect_header_request_for_height_cmd().await;
+        assert_eq!((height, amount), (10, 1));
+        respond_to.send(Ok(vec![headers[9].clone()])).unwrap();
+
+        let head_from_syncer = handle.expect_init_header_sub().await;
+        assert_eq!(head_from_syncer, headers[29]);
+
+        let store_ranges = store.get_stored_header_ranges().await.unwrap();
+        assert_eq!(store_ranges.as_ref(), [10..=10, 30..=30]);
+    }
+
+    #[async_test]
+    async fn init_with_trusted_checkpoint_rejects_other_chain() {
+        let events = EventChannel::new();
+        let (mock, mut handle) = P2p::mocked();
+        let store = Arc::new(InMemoryStore::new());
+        let checkpoint = ExtendedHeaderGenerator::new().next_many(10).pop().unwrap();
+        let other_head = ExtendedHeaderGenerator::new().next_many(30).pop().unwrap();
+
+        let _syncer = Syncer::start(SyncerArgs {
+            p2p: Arc::new(mock),
+            store: store.clone(),
+            event_pub: events.publisher(),
+            batch_size: 512,
+            syncing_window: DEFAULT_SAMPLING_WINDOW,
+            archival: false,
+            trusted_checkpoint: Some(checkpoint.clone().into()),
+        })
+        .unwrap();
+
+        handle.announce_trusted_peer_connected();
+
+        let (height, amount, respond_to) = handle.expect_header_request_for_height_cmd().await;
+        assert_eq!((height, amount), (0, 1));
+        respond_to.send(Ok(vec![other_head])).unwrap();
+
+        // Head can't be verified from the checkpoint, so HeaderSub is not initialized
+        handle.expect_no_cmd().await;
+
+        // Checkpoint is stored even though head is rejected
+        assert_eq!(store.get_by_height(10).await.unwrap(), checkpoint);
+        assert!(store.get_by_height(30).await.is_err());
+    }
+
+    #[async_test]
+    async fn init_with_expired_trusted_checkpoint() {
+        let events = EventChannel::new();
+        let (mock, mut handle) = P2p::mocked();
+        let store = Arc::new(InMemoryStore::new());
+        let mut gen = ExtendedHeaderGenerator::new();
+
+        let month_ago = Time::now()
+            .checked_sub(Duration::from_secs(30 * 24 * 60 * 60))
+            .unwrap();
+        gen.set_time(month_ago, Duration::from_secs(1));
+        let mut headers = gen.next_many(10);
+        gen.reset_time();
+        headers.extend(gen.next_many(20));
+
+        let syncer = Syncer::start(SyncerArgs {
+            p2p: Arc::new(mock),
+            store: store.clone(),
+            event_pub: events.publisher(),
+            batch_size: 512,
+            syncing_window: DEFAULT_SAMPLING_WINDOW,
+            archival: false,
+            trusted_checkpoint: Some(headers[9].clone().into()),
+        })
+        .unwrap();
+
+        handle.announce_trusted_peer_connected();
+
+        let (height, amount, respond_to) = handle.expect_header_request_for_height_cmd().await;
+        assert_eq!((height, amount), (0, 1));
+        respond_to.send(Ok(vec![headers[29].clone()])).unwrap();
+
+        // Expired checkpoint is neither stored nor used
+        handle.expect_no_cmd().await;
+        assert!(store.get_by_height(10).await.is_err());
+        assert!(store.get_by_height(30).await.is_err());
+
+        syncer.join().await;
+    }
+
     #[async_test]
     async fn syncing() {
         let mut gen = ExtendedHeaderGenerator::new();
@@ -920,6 +1230,57 @@ mod tests {
         p2p_mock.expect_no_cmd().await;
     }
 
+    #[async_test]
+    async fn archival_resumes_to_genesis() {
+        let month_and_day_ago = Duration::from_secs(31 * 24 * 60 * 60);
+        let mut gen = ExtendedHeaderGenerator::new();
+        gen.set_time(
+            (Time::now() - month_and_day_ago).expect("to not underflow"),
+            Duration::from_secs(1),
+        );
+        let mut headers = gen.next_many(1200);
+        gen.reset_time();
+        headers.append(&mut gen.next_many(2049 - 1200));
+        let network_head = gen.next(); // height 2050
+
+        // Previous run stopped at the edge of the syncing window
+        let store = Arc::new(InMemoryStore::new());
+        store.insert(headers[1024..].to_vec()).await.unwrap();
+
+        let events = EventChannel::new();
+        let (p2p, mut p2p_mock) = P2p::mocked();
+        let syncer = Syncer::start(SyncerArgs {
+            p2p: Arc::new(p2p),
+            store: store.clone(),
+            event_pub: events.publisher(),
+            batch_size: 512,
+            syncing_window: DEFAULT_SAMPLING_WINDOW,
+            archival: true,
+            trusted_checkpoint: None,
+        })
+        .unwrap();
+
+        p2p_mock.announce_trusted_peer_connected();
+
+        let (height, amount, respond_to) = p2p_mock.expect_header_request_for_height_cmd().await;
+        assert_eq!((height, amount), (0, 1));
+        respond_to.send(Ok(vec![network_head.clone()])).unwrap();
+
+        let head_from_syncer = p2p_mock.expect_init_header_sub().await;
+        assert_eq!(head_from_syncer, network_head);
+        assert_syncing(&syncer, &store, &[1025..=2050], 2050).await;
+
+        // Syncer continues below the syncing window
+        handle_session_batch(&mut p2p_mock, &headers, 513..=1024, true).await;
+        assert_syncing(&syncer, &store, &[513..=2050], 2050).await;
+
+        handle_session_batch(&mut p2p_mock, &headers, 1..=512, true).await;
+        assert_syncing(&syncer, &store, &[1..=2050], 2050).await;
+
+        // Syncer is fully synced to genesis and awaiting for events
+        p2p_mock.expect_no_cmd().await;
+    }
+
     #[async_test]
     async fn start_with_filled_store() {
         let events = EventChannel::new();
@@ -936,6 +1297,8 @@ mod tests {
             event_pub: events.publisher(),
             batch_size: 512,
             syncing_window: DEFAULT_SAMPLING_WINDOW,
+            archival: false,
+            trusted_checkpoint: None,
         })
         .unwrap();
 
@@ -1176,6 +1539,8 @@ mod tests {
             event_pub: events.publisher(),
             batch_size: 512,
             syncing_window: DEFAULT_SAMPLING_WINDOW,
+            archival: false,
+            trusted_checkpoint: None,
         })
         .unwrap();
 
@@ -148,6 +148,7 @@ impl MockP2pHandle {
             P2pCmd::HeaderExRequest {
                 request,
                 respond_to,
+                ..
             } => (request, respond_to),
             cmd => panic!("Expecting HeaderExRequest, but received: {cmd:?}"),
         }
@@ -207,4 +208,14 @@ impl MockP2pHandle {
             cmd => panic!("Expecting GetShwapCid, but received: {cmd:?}"),
         }
     }
+
+    /// Assert that a sampling failure was reported to the [`P2p`] worker.
+    ///
+    /// [`P2p`]: crate::p2p::P2p
+    pub async fn expect_report_sampling_failure(&mut self) {
+        match self.expect_cmd().await {
+            P2pCmd::ReportSamplingFailure => {}
+            cmd => panic!("Expecting ReportSamplingFailure, but received: {cmd:?}"),
+        }
+    }
 }
@@ -0,0 +1,214 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::time::{Duration, SystemTime};
+
+use libp2p::{Multiaddr, PeerId};
+use lumina_node::{
+    blockstore::InMemoryBlockstore,
+    network::Network,
+    node::{Node, NodeBuilderError, NodeError, P2pError},
+    store::{InMemoryStore, KnownPeer, Store},
+    test_utils::{listening_test_node_builder, test_node_builder},
+};
+use tokio::time::{sleep, timeout};
+
+#[tokio::test]
+async fn connect_block_and_disconnect() {
+    let server = listening_test_node_builder().start().await.unwrap();
+    let server_id = *server.local_peer_id();
+
+    // give server a sec to breathe, otherwise occiasionally client has problems with connecting
+    sleep(Duration::from_millis(100)).await;
+    let server_addr = server.listeners().await.unwrap().remove(0);
+
+    let client = test_node_builder().start().await.unwrap();
+    assert!(client.peer_info(server_id).await.unwrap().is_none());
+
+    client.connect(server_addr.clone()).await.unwrap();
+    assert!(client.connected_peers().await.unwrap().contains(&server_id));
+
+    // wait for the identify exchange
+    let info = timeout(Duration::from_secs(5), async {
+        loop {
+            let info = client.peer_info(server_id).await.unwrap().unwrap();
+            if info.agent_version.is_some() {
+                break info;
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert!(info.connected);
+    assert!(!info.blocked);
+    assert!(info.agent_version.unwrap().starts_with("lumina/"));
+    assert!(info
+        .protocols
+        .iter()
+        .any(|p| p.ends_with("/header-ex/v0.0.3")));
+
+    client.block_peer(server_id).await.unwrap();
+    assert_eq!(client.list_blocked_peers().await.unwrap(), vec![server_id]);
+    wait_disconnected(&client, server_id).await;
+
+    let err = client.connect(server_addr.clone()).await.unwrap_err();
+    assert!(matches!(err, NodeError::P2p(P2pError::Dial(_))));
+    assert!(client.peer_info(server_id).await.unwrap().unwrap().blocked);
+
+    client.unblock_peer(server_id).await.unwrap();
+    assert!(client.list_blocked_peers().await.unwrap().is_empty());
+    client.connect(server_addr).await.unwrap();
+
+    client.disconnect(server_id).await.unwrap();
+    wait_disconnected(&client, server_id).await;
+}
+
+async fn wait_disconnected(node: &Node<InMemoryBlockstore, InMemoryStore>, peer_id: PeerId) {
+    timeout(Duration::from_secs(5), async {
+        while node.connected_peers().await.unwrap().contains(&peer_id) {
+            sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn bandwidth_accounting() {
+    let server = listening_test_node_builder().start().await.unwrap();
+    let server_id = *server.local_peer_id();
+
+    // give server a sec to breathe, otherwise occiasionally client has problems with connecting
+    sleep(Duration::from_millis(100)).await;
+    let server_addr = server.listeners().await.unwrap().remove(0);
+
+    let client = test_node_builder().start().await.unwrap();
+    assert_eq!(client.bandwidth_stats().total_in, 0.0);
+
+    client.connect(server_addr).await.unwrap();
+
+    // wait for the identify exchange
+    let identify = timeout(Duration::from_secs(5), async {
+        loop {
+            let stats = client.bandwidth_for_protocol("/ipfs/id/1.0.0");
+            if stats.total_in > 0.0 {
+                break stats;
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    let total = client.bandwidth_stats();
+    let for_peer = client.bandwidth_for_peer(&server_id);
+    assert!(for_peer.total_in >= identify.total_in);
+    assert!(total.total_in >= for_peer.total_in);
+    assert!(total.total_out > 0.0);
+    assert_eq!(
+        client.bandwidth_for_protocol("/unknown/1.0.0").total_in,
+        0.0
+    );
+}
+
+#[tokio::test]
+async fn mdns_discovery() {
+    let server = listening_test_node_builder()
+        .mdns(true)
+        .start()
+        .await
+        .unwrap();
+    let server_id = *server.local_peer_id();
+
+    let client = test_node_builder().mdns(true).start().await.unwrap();
+    wait_connected(&client, server_id).await;
+
+    // discovery alone doesn't make the peer trusted
+    assert!(!client.peer_info(server_id).await.unwrap().unwrap().trusted);
+
+    let trusting_client = test_node_builder()
+        .mdns(true)
+        .trust_mdns_peers(true)
+        .start()
+        .await
+        .unwrap();
+    wait_connected(&trusting_client, server_id).await;
+
+    let info = trusting_client.peer_info(server_id).await.unwrap().unwrap();
+    assert!(info.trusted);
+}
+
+async fn wait_connected(node: &Node<InMemoryBlockstore, InMemoryStore>, peer_id: PeerId) {
+    timeout(Duration::from_secs(10), async {
+        while !node.connected_peers().await.unwrap().contains(&peer_id) {
+            sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn mdns_rejected_for_public_networks() {
+    let res = test_node_builder()
+        .network(Network::Mocha)
+        .mdns(true)
+        .start()
+        .await;
+
+    assert!(matches!(
+        res,
+        Err(NodeError::NodeBuilder(
+            NodeBuilderError::MdnsOnPublicNetwork
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn known_peers_trust_follows_config() {
+    let peer_id = PeerId::random();
+    let addr: Multiaddr = format!("/ip4/127.0.0.1/tcp/1/p2p/{peer_id}")
+        .parse()
+        .unwrap();
+
+    // peer was a trusted bootnode in the previous run
+    let store = InMemoryStore::new();
+    store
+        .set_known_peers(vec![KnownPeer {
+            peer_id,
+            addrs: vec![addr.clone()],
+            last_seen: SystemTime::now(),
+            trusted: true,
+            score: 0.0,
+        }])
+        .await
+        .unwrap();
+
+    // and it was removed from the bootnodes since then
+    let node = test_node_builder().store(store).start().await.unwrap();
+    let info = node.peer_info(peer_id).await.unwrap().unwrap();
+    assert!(!info.trusted);
+    node.stop().await;
+
+    let store = InMemoryStore::new();
+    store
+        .set_known_peers(vec![KnownPeer {
+            peer_id,
+            addrs: vec![addr.clone()],
+            last_seen: SystemTime::now(),
+            trusted: true,
+            score: 0.0,
+        }])
+        .await
+        .unwrap();
+
+    // peer is still a bootnode
+    let node = test_node_builder()
+        .store(store)
+        .bootnodes([addr])
+        .start()
+        .await
+        .unwrap();
+    let info = node.peer_info(peer_id).await.unwrap().unwrap();
+    assert!(info.trusted);
+}
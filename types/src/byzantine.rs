@@ -9,7 +9,7 @@ use crate::eds::AxisType;
 use crate::fraud_proof::FraudProof;
 use crate::hash::Hash;
 use crate::nmt::{Namespace, NamespaceProof, Nmt, NmtExt, NS_SIZE};
-use crate::{Error, ExtendedHeader, Result};
+use crate::{Error, ExtendedHeader, Result, Share};
 
 /// A proof that the block producer incorrectly encoded [`ExtendedDataSquare`].
 ///
@@ -39,6 +39,87 @@ pub struct BadEncodingFraudProof {
     axis: AxisType,
 }
 
+impl BadEncodingFraudProof {
+    /// Create a proof that an axis of the [`ExtendedDataSquare`] was incorrectly encoded.
+    ///
+    /// Shares of the incorrectly encoded axis are proven with the orthogonal axes, e.g.
+    /// if a column is incorrectly encoded, `orthogonal_axes` must hold the rows of the square.
+    /// Missing axes should be provided as `None`, but at least half of them must be present
+    /// and each present one must match its root in the [`ExtendedHeader`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the dimensions of provided axes doesn't match
+    /// the square, or if the resulting proof doesn't prove the bad encoding.
+    ///
+    /// [`ExtendedDataSquare`]: crate::ExtendedDataSquare
+    pub fn new(
+        header: &ExtendedHeader,
+        axis: AxisType,
+        index: u16,
+        orthogonal_axes: &[Option<Vec<Share>>],
+    ) -> Result<Self> {
+        let square_width = header.dah.square_width();
+
+        if index >= square_width || orthogonal_axes.len() != usize::from(square_width) {
+            return Err(Error::EdsInvalidDimentions);
+        }
+
+        let proof_axis = match axis {
+            AxisType::Row => AxisType::Col,
+            AxisType::Col => AxisType::Row,
+        };
+
+        let shares = orthogonal_axes
+            .iter()
+            .map(|maybe_axis| {
+                let Some(axis_shares) = maybe_axis else {
+                    return Ok(None);
+                };
+
+                if axis_shares.len() != usize::from(square_width) {
+                    return Err(Error::EdsInvalidDimentions);
+                }
+
+                let mut nmt = Nmt::default();
+
+                for share in axis_shares {
+                    nmt.push_leaf(share.as_ref(), *share.namespace())
+                        .map_err(Error::Nmt)?;
+                }
+
+                let (data, proof) = nmt.get_index_with_proof(index.into());
+                let namespace = axis_shares[usize::from(index)].namespace();
+
+                Ok(Some(ShareWithProof {
+                    leaf: NmtLeaf {
+                        namespace,
+                        share: data,
+                    },
+                    proof: nmt_rs::NamespaceProof::PresenceProof {
+                        proof,
+                        ignore_max_ns: true,
+                    }
+                    .into(),
+                    proof_axis,
+                }))
+            })
+            .collect::<Result<_>>()?;
+
+        let befp = BadEncodingFraudProof {
+            header_hash: header.hash(),
+            block_height: header.height(),
+            shares,
+            index,
+            axis,
+        };
+
+        befp.validate(header)?;
+
+        Ok(befp)
+    }
+}
+
 impl FraudProof for BadEncodingFraudProof {
     const TYPE: &'static str = "badencoding";
 
@@ -417,6 +498,39 @@ mod tests {
     use crate::test_utils::{corrupt_eds, generate_dummy_eds, ExtendedHeaderGenerator};
     use crate::DataAvailabilityHeader;
 
+    #[test]
+    fn new_befp_from_orthogonal_axes() {
+        let mut gen = ExtendedHeaderGenerator::new();
+        let mut eds = generate_dummy_eds(8, AppVersion::V2);
+        let (eh, proof) = corrupt_eds(&mut gen, &mut eds);
+
+        let orthogonal_axis = match proof.axis {
+            AxisType::Row => AxisType::Col,
+            AxisType::Col => AxisType::Row,
+        };
+        // provide only half of the orthogonal axes
+        let axes: Vec<_> = (0..eds.square_width())
+            .map(|idx| (idx % 2 == 0).then(|| eds.axis(orthogonal_axis, idx).unwrap()))
+            .collect();
+
+        let befp = BadEncodingFraudProof::new(&eh, proof.axis, proof.index, &axes).unwrap();
+
+        befp.validate(&eh).unwrap();
+    }
+
+    #[test]
+    fn new_befp_over_correct_data() {
+        let mut gen = ExtendedHeaderGenerator::new();
+        let eds = generate_dummy_eds(8, AppVersion::V2);
+        let eh = gen.next_with_dah(DataAvailabilityHeader::from_eds(&eds));
+
+        let axes: Vec<_> = (0..eds.square_width())
+            .map(|idx| Some(eds.column(idx).unwrap()))
+            .collect();
+
+        BadEncodingFraudProof::new(&eh, AxisType::Row, 0, &axes).unwrap_err();
+    }
+
     #[cfg(target_arch = "wasm32")]
     use wasm_bindgen_test::wasm_bindgen_test as test;
 
@@ -2,7 +2,9 @@
 
 use std::cmp::Ordering;
 use std::fmt::Display;
+use std::ops::Range;
 
+use nmt_rs::nmt_proof::NamespaceProof as NmtNamespaceProof;
 use serde::{Deserialize, Serialize};
 
 use crate::consts::appconsts::{AppVersion, SHARE_SIZE};
@@ -11,7 +13,7 @@ use crate::consts::data_availability_header::{
 };
 use crate::nmt::{Namespace, NamespacedSha2Hasher, Nmt, NmtExt, NS_SIZE};
 use crate::row_namespace_data::{RowNamespaceData, RowNamespaceDataId};
-use crate::{bail_validation, DataAvailabilityHeader, Error, InfoByte, Result, Share};
+use crate::{bail_validation, DataAvailabilityHeader, Error, InfoByte, Result, Share, ShareProof};
 
 /// Represents either column or row of the [`ExtendedDataSquare`].
 ///
@@ -443,6 +445,65 @@ impl ExtendedDataSquare {
 
         Ok(rows)
     }
+
+    /// Return the shares in the `range` of the flattened original data square, together
+    /// with the proof of their inclusion in the [`DataAvailabilityHeader`].
+    ///
+    /// All the shares in the range must belong to the same namespace.
+    pub fn get_share_range(
+        &self,
+        range: Range<usize>,
+        dah: &DataAvailabilityHeader,
+    ) -> Result<ShareProof> {
+        let ods_width = usize::from(self.square_width / 2);
+        let ods_size = ods_width * ods_width;
+
+        if range.is_empty() || range.end > ods_size {
+            bail_validation!("Invalid share range {range:?} of {ods_size} shares");
+        }
+
+        let ods_share = |idx: usize| {
+            // Indexes are bounded by the size of ODS, so they fit in u16
+            self.share((idx / ods_width) as u16, (idx % ods_width) as u16)
+        };
+
+        let namespace = ods_share(range.start)?.namespace();
+        let mut data = Vec::with_capacity(range.len());
+
+        for idx in range.clone() {
+            let share = ods_share(idx)?;
+
+            if share.namespace() != namespace {
+                bail_validation!("Shares in range {range:?} belong to different namespaces");
+            }
+
+            data.push(*share.data());
+        }
+
+        let start_row = (range.start / ods_width) as u16;
+        let end_row = ((range.end - 1) / ods_width) as u16;
+        let mut share_proofs = Vec::with_capacity(usize::from(end_row - start_row) + 1);
+
+        for row in start_row..=end_row {
+            let row_start = usize::from(row) * ods_width;
+            let start_col = range.start.saturating_sub(row_start);
+            let end_col = (range.end - row_start).min(ods_width);
+
+            let proof = self.row_nmt(row)?.build_range_proof(start_col..end_col);
+
+            share_proofs.push(
+                NmtNamespaceProof::PresenceProof {
+                    proof,
+                    ignore_max_ns: true,
+                }
+                .into(),
+            );
+        }
+
+        let row_proof = dah.row_proof(start_row..=end_row)?;
+
+        Ok(ShareProof::new(data, namespace, share_proofs, row_proof))
+    }
 }
 
 /// Raw representation of [`ExtendedDataSquare`].
@@ -482,7 +543,8 @@ fn flatten_index(row: u16, col: u16, square_width: u16) -> usize {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{test_utils::generate_eds, Blob, ExtendedHeader};
+    use crate::test_utils::{generate_dummy_eds, generate_eds};
+    use crate::{Blob, ExtendedHeader};
 
     #[test]
     fn axis_type_serialization() {
@@ -531,6 +593,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn get_share_range() {
+        let eds = generate_dummy_eds(16, AppVersion::V3);
+        let dah = DataAvailabilityHeader::from_eds(&eds);
+
+        // Range spanning three rows of ODS
+        let proof = eds.get_share_range(5..21, &dah).unwrap();
+        proof.verify(dah.hash()).unwrap();
+        assert_eq!(proof.shares().len(), 16);
+        assert_eq!(&proof.shares()[0], eds.share(0, 5).unwrap().data());
+        assert_eq!(&proof.shares()[15], eds.share(2, 4).unwrap().data());
+
+        let proof = eds.get_share_range(63..64, &dah).unwrap();
+        proof.verify(dah.hash()).unwrap();
+
+        eds.get_share_range(5..5, &dah).unwrap_err();
+        eds.get_share_range(0..65, &dah).unwrap_err();
+
+        // Shares of different namespaces
+        let eds = generate_eds(16, AppVersion::V3);
+        let dah = DataAvailabilityHeader::from_eds(&eds);
+        eds.get_share_range(0..64, &dah).unwrap_err();
+    }
+
     #[test]
     fn nmt_roots() {
         let eds_json = include_str!("../test_data/shwap_samples/eds.json");
@@ -25,6 +25,20 @@ pub struct ShareProof {
 }
 
 impl ShareProof {
+    pub(crate) fn new(
+        data: Vec<[u8; SHARE_SIZE]>,
+        namespace_id: Namespace,
+        share_proofs: Vec<NamespaceProof>,
+        row_proof: RowProof,
+    ) -> Self {
+        ShareProof {
+            data,
+            namespace_id,
+            share_proofs,
+            row_proof,
+        }
+    }
+
     /// Get the shares proven by this proof.
     pub fn shares(&self) -> &[[u8; SHARE_SIZE]] {
         &self.data